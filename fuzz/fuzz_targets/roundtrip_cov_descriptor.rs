@@ -0,0 +1,50 @@
+extern crate elements_miniscript as miniscript;
+
+use std::str::FromStr;
+
+use miniscript::bitcoin::PublicKey;
+use miniscript::descriptor::LegacyCSFSCov;
+use miniscript::extensions::CovExtArgs;
+use miniscript::{CovenantExt, Descriptor};
+
+fn do_test(data: &[u8]) {
+    // `Descriptor<DummyKey>` cannot express covenants, as the covenant key
+    // must be convertible to a public key. Parse real keys instead, and
+    // check that the script encoding parses back to the same string.
+    let s = String::from_utf8_lossy(data);
+    if let Ok(desc) = Descriptor::<PublicKey>::from_str(&s) {
+        if let Ok(cov) = desc.as_cov() {
+            let script = cov.encode();
+            let cov2 =
+                LegacyCSFSCov::<PublicKey, CovenantExt<CovExtArgs>>::parse_insane(&script).unwrap();
+            assert_eq!(cov.to_string(), cov2.to_string());
+        }
+    }
+}
+
+fn main() {
+    loop {
+        honggfuzz::fuzz!(|data| {
+            do_test(data);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use miniscript::elements::hex::FromHex;
+
+    #[test]
+    fn duplicate_crash() {
+        let hex = Vec::<u8>::from_hex("00").unwrap();
+        super::do_test(&hex);
+    }
+
+    #[test]
+    fn cov_roundtrip() {
+        let desc = "elcovwsh(\
+            02e6642fd69bd211f93f7f1f36ca51a26a5290eb2dd1b0d8279a87bb0d480c8443,\
+            pk(0324be1f3f64fbadf5ed74d8eda3a62b2a25cb6df4aec3436c5cf52e1d2edc6d8c))";
+        super::do_test(desc.as_bytes());
+    }
+}
@@ -9,18 +9,21 @@
 //!
 
 use core::fmt;
+use std::ops::Range;
 
+use elements::hashes::{hash160, Hash};
 use elements::{self, script, secp256k1_zkp, Script};
 
 use super::ELMTS_STR;
 use crate::descriptor::checksum::{self, verify_checksum};
+use crate::descriptor::{DescriptorPublicKey, IndexDerivator, KeyDerivator};
 use crate::expression::{self, FromTree};
 use crate::miniscript::context::ScriptContext;
 use crate::policy::{semantic, Liftable};
 use crate::util::{varint_len, witness_to_scriptsig};
 use crate::{
     elementssig_to_rawsig, BareCtx, Error, ForEachKey, Miniscript, MiniscriptKey, Satisfier,
-    ToPublicKey, TranslatePk, Translator,
+    SigType, ToPublicKey, TranslatePk, Translator,
 };
 
 /// Create a Bare Descriptor. That is descriptor that is
@@ -132,6 +135,56 @@ impl<Pk: MiniscriptKey + ToPublicKey> Bare<Pk> {
         let witness = vec![];
         Ok((witness, script_sig))
     }
+
+    /// Returns the satisfying witness and scriptSig of the non-malleable
+    /// and the malleable satisfaction using the `satisfier`, whichever has
+    /// the smaller scriptSig. The non-malleable one is preferred on ties.
+    ///
+    /// This does not enumerate all the malleable satisfactions. Each of
+    /// the two satisfiers minimizes the witness serialization size, which
+    /// is not always the smallest scriptSig: pushes of a single byte up to
+    /// 16 are encoded as `OP_N` and pushes of 76 bytes or more need
+    /// `OP_PUSHDATA1`. It runs the satisfier twice and is slower than
+    /// [`Bare::get_satisfaction_mall`].
+    pub fn get_satisfaction_smaller_of_mall<S>(
+        &self,
+        satisfier: S,
+    ) -> Result<(Vec<Vec<u8>>, Script), Error>
+    where
+        S: Satisfier<Pk>,
+    {
+        let mall = self.get_satisfaction_mall(&satisfier);
+        match self.get_satisfaction(&satisfier) {
+            Ok(non_mall) => match mall {
+                Ok(mall) if mall.1.len() < non_mall.1.len() => Ok(mall),
+                _ => Ok(non_mall),
+            },
+            Err(_) => mall,
+        }
+    }
+}
+
+impl Bare<DescriptorPublicKey> {
+    /// Derives the descriptor at each index in `range` and returns its
+    /// script pubkey, e.g. to scan for the scripts of a wallet up to its
+    /// gap limit. A descriptor without wildcards yields a single script.
+    pub fn script_pubkeys<'a, C: secp256k1_zkp::Verification>(
+        &'a self,
+        secp: &'a secp256k1_zkp::Secp256k1<C>,
+        range: Range<u32>,
+    ) -> impl Iterator<Item = Result<Script, Error>> + 'a {
+        let range = if self.for_any_key(|key| key.has_wildcard()) {
+            range
+        } else {
+            0..1
+        };
+        range.map(move |i| {
+            let bare = self
+                .translate_pk(&mut IndexDerivator(i))?
+                .translate_pk(&mut KeyDerivator(secp))?;
+            Ok(bare.script_pubkey())
+        })
+    }
 }
 
 impl<Pk: MiniscriptKey> fmt::Debug for Bare<Pk> {
@@ -140,6 +193,8 @@ impl<Pk: MiniscriptKey> fmt::Debug for Bare<Pk> {
     }
 }
 
+/// Appends the `#checksum` to the descriptor string. Alternate formatting
+/// (`{:#}`) leaves it out, and the result still parses.
 impl<Pk: MiniscriptKey> fmt::Display for Bare<Pk> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use fmt::Write;
@@ -178,7 +233,7 @@ impl_from_str!(
     type Err = Error;,
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let desc_str = verify_checksum(s)?;
-        let top = expression::Tree::from_str(&desc_str[2..])?;
+        let top = expression::Tree::from_str(desc_str)?;
         Self::from_tree(&top)
     }
 );
@@ -254,6 +309,7 @@ impl<Pk: MiniscriptKey> Pkh<Pk> {
     /// Assumes all ec-signatures are 73 bytes, including push opcode and
     /// sighash suffix. Includes the weight of the VarInts encoding the
     /// scriptSig and witness stack length.
+    #[deprecated(note = "use max_weight_to_satisfy instead")]
     pub fn max_satisfaction_weight(&self) -> usize {
         4 * (1 + 73 + BareCtx::pk_len(&self.pk))
     }
@@ -262,14 +318,8 @@ impl<Pk: MiniscriptKey> Pkh<Pk> {
 impl<Pk: MiniscriptKey + ToPublicKey> Pkh<Pk> {
     /// Obtains the corresponding script pubkey for this descriptor.
     pub fn script_pubkey(&self) -> Script {
-        // Fine to hard code the `Network` here because we immediately call
-        // `script_pubkey` which does not use the `network` field of `Address`.
-        let addr = elements::Address::p2pkh(
-            &self.pk.to_public_key(),
-            None,
-            &elements::AddressParams::ELEMENTS,
-        );
-        addr.script_pubkey()
+        let hash = self.pk.to_pubkeyhash(SigType::Ecdsa);
+        Script::new_p2pkh(&hash.into())
     }
 
     /// Obtains the corresponding script pubkey for this descriptor.
@@ -322,12 +372,114 @@ impl<Pk: MiniscriptKey + ToPublicKey> Pkh<Pk> {
     }
 }
 
+/// A [`Pkh`] descriptor of which only the pubkey hash is known, e.g. when
+/// it was recovered from a scriptPubKey. It has the same scriptPubKey and
+/// address as the [`Pkh`], but can only be satisfied by a satisfier that
+/// knows the public key for the hash.
+#[derive(Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct PkhHash {
+    /// hash160 of the public key
+    hash: hash160::Hash,
+}
+
+impl PkhHash {
+    /// Create a new pubkey hash descriptor
+    pub fn new(hash: hash160::Hash) -> Self {
+        Self { hash }
+    }
+
+    /// Recovers the pubkey hash of a p2pkh scriptPubKey. Returns `None`
+    /// for other scripts.
+    pub fn from_script_pubkey(spk: &Script) -> Option<Self> {
+        if spk.is_p2pkh() {
+            // OP_DUP OP_HASH160 OP_PUSHBYTES_20 <hash> OP_EQUALVERIFY OP_CHECKSIG
+            let hash = hash160::Hash::from_slice(&spk[3..23]).expect("20 bytes");
+            Some(Self::new(hash))
+        } else {
+            None
+        }
+    }
+
+    /// Get a reference to the pubkey hash
+    pub fn as_inner(&self) -> &hash160::Hash {
+        &self.hash
+    }
+
+    /// Obtains the corresponding script pubkey for this descriptor.
+    pub fn script_pubkey(&self) -> Script {
+        Script::new_p2pkh(&self.hash.into())
+    }
+
+    /// Obtains the corresponding address for this descriptor.
+    pub fn address(
+        &self,
+        blinder: Option<secp256k1_zkp::PublicKey>,
+        params: &'static elements::address::AddressParams,
+    ) -> elements::Address {
+        elements::Address {
+            params,
+            payload: elements::address::Payload::PubkeyHash(self.hash.into()),
+            blinding_pubkey: blinder,
+        }
+    }
+
+    /// Returns the witness and scriptSig to spend an output controlled by
+    /// this descriptor, with the public key and signature looked up by
+    /// hash from the `satisfier`.
+    ///
+    /// # Errors
+    /// [`Error::MissingPubkey`] if the satisfier does not know the public
+    /// key for the hash, and [`Error::MissingSig`] if it knows the key but
+    /// has no signature.
+    pub fn get_satisfaction<Pk, S>(&self, satisfier: S) -> Result<(Vec<Vec<u8>>, Script), Error>
+    where
+        Pk: MiniscriptKey + ToPublicKey,
+        S: Satisfier<Pk>,
+    {
+        if let Some((pk, sig)) = satisfier.lookup_raw_pkh_ecdsa_sig(&self.hash) {
+            let sig_vec = elementssig_to_rawsig(&sig);
+            let script_sig = script::Builder::new()
+                .push_slice(&sig_vec[..])
+                .push_key(&pk)
+                .into_script();
+            Ok((vec![], script_sig))
+        } else if let Some(pk) = satisfier.lookup_raw_pkh_pk(&self.hash) {
+            Err(Error::MissingSig(pk))
+        } else {
+            Err(Error::MissingPubkey(self.hash))
+        }
+    }
+}
+
+impl Pkh<DescriptorPublicKey> {
+    /// Derives the descriptor at each index in `range` and returns its
+    /// address, e.g. to scan for the addresses of a wallet up to its gap
+    /// limit. A key without wildcards yields a single address.
+    pub fn addresses<'a, C: secp256k1_zkp::Verification>(
+        &'a self,
+        secp: &'a secp256k1_zkp::Secp256k1<C>,
+        range: Range<u32>,
+        blinder: Option<secp256k1_zkp::PublicKey>,
+        params: &'static elements::address::AddressParams,
+    ) -> impl Iterator<Item = Result<elements::Address, Error>> + 'a {
+        let range = if self.pk.has_wildcard() { range } else { 0..1 };
+        range.map(move |i| {
+            let pkh = self
+                .translate_pk(&mut IndexDerivator(i))?
+                .translate_pk(&mut KeyDerivator(secp))?;
+            Ok(pkh.address(blinder, params))
+        })
+    }
+}
+
 impl<Pk: MiniscriptKey> fmt::Debug for Pkh<Pk> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}pkh({:?})", ELMTS_STR, self.pk)
     }
 }
 
+/// Appends the `#checksum` to the descriptor string. Alternate formatting
+/// (`{:#}`) leaves it out, and the result still parses.
 impl<Pk: MiniscriptKey> fmt::Display for Pkh<Pk> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use fmt::Write;
@@ -389,3 +541,267 @@ impl<P: MiniscriptKey, Q: MiniscriptKey> TranslatePk<P, Q> for Pkh<P> {
         Ok(Pkh::new(t.pk(&self.pk)?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::miniscript::context::ScriptContextError;
+    use crate::ElementsSig;
+
+    #[test]
+    fn display_no_checksum() {
+        let pk = "020000000000000000000000000000000000000000000000000000000000000002";
+
+        let bare = Bare::<bitcoin::PublicKey>::from_str(&format!("elpk({})", pk)).unwrap();
+        let no_checksum = format!("{:#}", bare);
+        assert_eq!(no_checksum, format!("elpk({})", pk));
+        assert!(bare.to_string().starts_with(&format!("{}#", no_checksum)));
+        assert_eq!(Bare::from_str(&no_checksum).unwrap(), bare);
+        assert_eq!(Bare::from_str(&bare.to_string()).unwrap(), bare);
+
+        let pkh = Pkh::<bitcoin::PublicKey>::from_str(&format!("elpkh({})", pk)).unwrap();
+        let no_checksum = format!("{:#}", pkh);
+        assert_eq!(no_checksum, format!("elpkh({})", pk));
+        assert!(pkh.to_string().starts_with(&format!("{}#", no_checksum)));
+        assert_eq!(Pkh::from_str(&no_checksum).unwrap(), pkh);
+        assert_eq!(Pkh::from_str(&pkh.to_string()).unwrap(), pkh);
+    }
+
+    #[test]
+    fn pkh_hash() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = bitcoin::PublicKey::new(secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk));
+        let pkh = Pkh::new(pk);
+        let params = &elements::AddressParams::ELEMENTS;
+
+        let pkh_hash = PkhHash::from_script_pubkey(&pkh.script_pubkey()).unwrap();
+        assert_eq!(pkh_hash, PkhHash::new(pk.to_pubkeyhash(SigType::Ecdsa)));
+        assert_eq!(pkh_hash.script_pubkey(), pkh.script_pubkey());
+        assert_eq!(pkh_hash.address(None, params), pkh.address(None, params));
+        let blinder = pk.inner;
+        assert_eq!(
+            pkh_hash.address(Some(blinder), params),
+            pkh.address(Some(blinder), params)
+        );
+        assert_eq!(
+            PkhHash::from_script_pubkey(&crate::descriptor::Wpkh::new(pk).unwrap().script_pubkey()),
+            None
+        );
+
+        // The key is needed to satisfy
+        let msg = secp256k1_zkp::Message::from_digest_slice(&[1; 32]).unwrap();
+        let sig = (secp.sign_ecdsa(&msg, &sk), elements::EcdsaSighashType::All);
+        match pkh_hash.get_satisfaction::<bitcoin::PublicKey, _>(()) {
+            Err(Error::MissingPubkey(hash)) => assert_eq!(&hash, pkh_hash.as_inner()),
+            res => panic!("Unexpected result {:?}", res),
+        }
+        let mut sigs = HashMap::new();
+        sigs.insert(*pkh_hash.as_inner(), (pk, sig));
+        let satisfaction = pkh_hash.get_satisfaction(&sigs).unwrap();
+        let mut key_sigs = HashMap::new();
+        key_sigs.insert(pk, sig);
+        assert_eq!(satisfaction, pkh.get_satisfaction(&key_sigs).unwrap());
+    }
+
+    #[test]
+    fn derived_range() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let params = &elements::AddressParams::ELEMENTS;
+        let xpub = "tpubDBrgjcxBxnXyL575sHdkpKohWu5qHKoQ7TJXKNrYznh5fVEGBv89hA8ENW7A8MFVpFUSvgLqc4Nj1WZcpePX6rrxviVtPowvMuGF5rdT2Vi";
+        let new_bare = |pk: &str| {
+            Bare::new(
+                Miniscript::<DescriptorPublicKey, BareCtx>::from_str(&format!("pk({})", pk))
+                    .unwrap(),
+            )
+            .unwrap()
+        };
+        let derived = |i: u32| {
+            DescriptorPublicKey::from_str(&format!("{}/{}", xpub, i))
+                .unwrap()
+                .at_derivation_index(0)
+                .unwrap()
+                .derive_public_key(&secp)
+                .unwrap()
+        };
+
+        let pkh = Pkh::new(DescriptorPublicKey::from_str(&format!("{}/*", xpub)).unwrap());
+        let addrs: Vec<_> = pkh
+            .addresses(&secp, 2..5, None, params)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let expected: Vec<_> = (2..5)
+            .map(|i| Pkh::new(derived(i)).address(None, params))
+            .collect();
+        assert_eq!(addrs, expected);
+
+        let bare = new_bare(&format!("{}/*", xpub));
+        let spks: Vec<_> = bare
+            .script_pubkeys(&secp, 2..5)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let expected: Vec<_> = (2..5)
+            .map(|i| {
+                Bare::new(
+                    Miniscript::<bitcoin::PublicKey, BareCtx>::from_str(&format!(
+                        "pk({})",
+                        derived(i)
+                    ))
+                    .unwrap(),
+                )
+                .unwrap()
+                .script_pubkey()
+            })
+            .collect();
+        assert_eq!(spks, expected);
+
+        // Keys without wildcards yield a single item
+        let pkh = Pkh::new(DescriptorPublicKey::from_str(&format!("{}/7", xpub)).unwrap());
+        let addrs: Vec<_> = pkh.addresses(&secp, 0..10, None, params).collect();
+        assert_eq!(addrs.len(), 1);
+        assert_eq!(
+            addrs[0].as_ref().unwrap(),
+            &Pkh::new(derived(7)).address(None, params)
+        );
+        let bare = new_bare(&format!("{}/7", xpub));
+        assert_eq!(bare.script_pubkeys(&secp, 0..10).count(), 1);
+
+        // Hardened wildcards cannot be derived from an xpub
+        let pkh = Pkh::new(DescriptorPublicKey::from_str(&format!("{}/*h", xpub)).unwrap());
+        pkh.addresses(&secp, 0..1, None, params)
+            .next()
+            .unwrap()
+            .unwrap_err();
+    }
+
+    #[test]
+    fn bare_satisfaction_exceeds_script_sig() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let msg = secp256k1_zkp::Message::from_digest_slice(&[1; 32]).unwrap();
+        let pks: Vec<bitcoin::PublicKey> = (1..=25u8)
+            .map(|i| {
+                let sk = secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap();
+                bitcoin::PublicKey::new(secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk))
+            })
+            .collect();
+        let sig = secp.sign_ecdsa(
+            &msg,
+            &secp256k1_zkp::SecretKey::from_slice(&[1; 32]).unwrap(),
+        );
+        let sigs: HashMap<bitcoin::PublicKey, ElementsSig> = pks
+            .iter()
+            .map(|pk| (*pk, (sig, elements::EcdsaSighashType::All)))
+            .collect();
+
+        let thresh = |n: usize| {
+            let subs: Vec<String> = pks[..n]
+                .iter()
+                .enumerate()
+                .map(|(i, pk)| {
+                    if i == 0 {
+                        format!("pk({})", pk)
+                    } else {
+                        format!("s:pk({})", pk)
+                    }
+                })
+                .collect();
+            let ms = Miniscript::<bitcoin::PublicKey, BareCtx>::from_str_insane(&format!(
+                "thresh({},{})",
+                n,
+                subs.join(",")
+            ))
+            .unwrap();
+            // Not a standard bare script, bypass the top level checks
+            Bare { ms }
+        };
+
+        let (witness, script_sig) = thresh(2).get_satisfaction(&sigs).unwrap();
+        assert!(witness.is_empty());
+        assert!(!script_sig.is_empty());
+
+        // 25 signatures do not fit into a standard scriptSig
+        match thresh(25).get_satisfaction(&sigs) {
+            Err(Error::ContextError(ScriptContextError::MaxScriptSigSizeExceeded)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+        match thresh(25).get_satisfaction_mall(&sigs) {
+            Err(Error::ContextError(ScriptContextError::MaxScriptSigSizeExceeded)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn bare_satisfaction_smaller_of_mall() {
+        use elements::hashes::{sha256, Hash};
+
+        struct Sat(bitcoin::PublicKey, ElementsSig, [u8; 32]);
+        impl Satisfier<bitcoin::PublicKey> for Sat {
+            fn lookup_ecdsa_sig(&self, pk: &bitcoin::PublicKey) -> Option<ElementsSig> {
+                if *pk == self.0 {
+                    Some(self.1)
+                } else {
+                    None
+                }
+            }
+
+            fn lookup_sha256(&self, h: &sha256::Hash) -> Option<[u8; 32]> {
+                if *h == sha256::Hash::hash(&self.2) {
+                    Some(self.2)
+                } else {
+                    None
+                }
+            }
+        }
+
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = bitcoin::PublicKey::new(secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk));
+        let msg = secp256k1_zkp::Message::from_digest_slice(&[1; 32]).unwrap();
+        let sig = (secp.sign_ecdsa(&msg, &sk), elements::EcdsaSighashType::All);
+        let preimage = [0xab; 32];
+        let hash = sha256::Hash::hash(&preimage);
+        let satisfier = Sat(pk, sig, preimage);
+        let bare = |ms: String| Bare {
+            ms: Miniscript::<bitcoin::PublicKey, BareCtx>::from_str_insane(&ms).unwrap(),
+        };
+
+        // Same as the non-malleable satisfaction when it is the smallest
+        let desc = bare(format!("pk({})", pk));
+        assert_eq!(
+            desc.get_satisfaction_smaller_of_mall(&satisfier).unwrap(),
+            desc.get_satisfaction(&satisfier).unwrap()
+        );
+
+        // The non-malleable satisfaction must reveal the preimages, as
+        // anyone could replace the signature with them
+        let desc = bare(format!(
+            "or_d(pk({}),and_v(v:sha256({}),and_v(v:sha256({}),sha256({}))))",
+            pk, hash, hash, hash
+        ));
+        let (_, non_mall) = desc.get_satisfaction(&satisfier).unwrap();
+        let (_, script_sig) = desc.get_satisfaction_smaller_of_mall(&satisfier).unwrap();
+        assert_eq!(
+            script_sig,
+            desc.get_satisfaction_mall(&satisfier).unwrap().1
+        );
+        assert_eq!(non_mall.len(), 100);
+        assert_eq!(script_sig.len(), 73);
+
+        // Either preimage branch can be swapped for the other, so there is
+        // no non-malleable satisfaction without giving up the signature
+        let desc = bare(format!(
+            "or_d(pk({}),or_i(sha256({}),sha256({})))",
+            pk, hash, hash
+        ));
+        assert!(desc.get_satisfaction(&satisfier).is_err());
+        let (witness, script_sig) = desc.get_satisfaction_smaller_of_mall(&satisfier).unwrap();
+        assert!(witness.is_empty());
+        assert_eq!(
+            script_sig,
+            desc.get_satisfaction_mall(&satisfier).unwrap().1
+        );
+    }
+}
@@ -19,6 +19,10 @@
 //! sighash onto the top of the stack in the required order
 //!
 //! ** WORKS only for Segwit sighash
+//! The covenant signatures are ECDSA, as CHECKSIGFROMSTACK in segwit v0
+//! scripts only verifies ECDSA signatures. For BIP340 Schnorr covenant
+//! signatures use the tapscript [`super::TapCSFSCov`] (`eltapcov()`)
+//! descriptor instead.
 //! A new transaction digest algorithm is defined, but only applicable to sigops in version 0 witness program:
 //! Text from BIP 143:
 //!  Double SHA256 of the serialization of:
@@ -28,7 +32,8 @@
 //! 3. ELEMENTS EXTRA hashIssuances (32-byte hash)
 //! 4. outpoint (32-byte hash + 4-byte little endian)
 //! 5. scriptCode of the input (serialized as scripts inside CTxOuts)
-//! 6. value of the output spent by this input (8-byte little endian)
+//! 6. value of the output spent by this input (ELEMENTS: 9-byte explicit or
+//!    33-byte confidential value)
 //! 7. nSequence of the input (4-byte little endian)
 //! 8. hashOutputs (32-byte hash)
 //! 9. nLocktime of the transaction (4-byte little endian)
@@ -41,15 +46,23 @@
 //! all the items using OP_CAT to obtain a Sighash on which we
 //! which we verify using CHECKSIGFROMSTACK
 use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
 
 use bitcoin;
-use elements::encode::{serialize, Encodable};
-use elements::hashes::{sha256d, Hash};
-use elements::{self, script, secp256k1_zkp, Script};
+use elements::encode::serialize;
+use elements::hashes::{sha256d, Hash, HashEngine};
+use elements::{self, script, secp256k1_zkp, LockTime, Script, Sequence};
 
 use super::super::ELMTS_STR;
-use super::{CovError, CovOperations};
+use super::{
+    CovError, CovOperations, CovSighashItem, CovSighashItems, CovSighashType, CovWitness,
+    CovWrapperError, COV_CSFS_SIG_DEPTH, COV_SIGHASH_ITEM_COUNT, COV_SIGHASH_TYPE_LEN,
+};
 use crate::descriptor::checksum::{self, verify_checksum};
+use crate::descriptor::{
+    ConversionError, DefiniteDescriptorKey, DescriptorPublicKey, IndexDerivator, KeyDerivator,
+};
 use crate::expression::{self, FromTree};
 use crate::extensions::ParseableExt;
 use crate::miniscript::lex::{lex, Token as Tk, TokenIter};
@@ -59,37 +72,200 @@ use crate::miniscript::limits::{
 use crate::miniscript::{decode, types};
 use crate::util::varint_len;
 use crate::{
-    Error, ExtTranslator, Extension, ForEachKey, Miniscript, MiniscriptKey, Satisfier,
-    ScriptContext, Segwitv0, ToPublicKey, TranslateExt, TranslatePk, Translator,
+    errstr, script_num_size, ElementsSig, Error, ExtTranslator, Extension, ForEachKey, Miniscript,
+    MiniscriptKey, Satisfier, ScriptContext, Segwitv0, Terminal, ToPublicKey, TranslateExt,
+    TranslatePk, Translator,
 };
 
-// A simple utility function to serialize an array
-// of elements and compute double sha2 on it
-fn hash256_arr<T: Encodable>(sl: &[T]) -> sha256d::Hash {
-    let mut enc = sha256d::Hash::engine();
-    for elem in sl {
-        elem.consensus_encode(&mut enc).unwrap();
-    }
-    sha256d::Hash::from_engine(enc)
-}
 pub(crate) const COV_SCRIPT_SIZE: usize = 120;
 pub(crate) const COV_SCRIPT_OPCODE_COST: usize = 74;
+// Multi key covenant script parts. The opcode costs count the pushes
+// of the per key stack depths and the threshold, but the sizes do not.
+// see test_multi_sanity_check_limits test for the test assert
+pub(crate) const COV_MULTI_PREFIX_SIZE: usize = 82;
+pub(crate) const COV_MULTI_PREFIX_OPCODE_COST: usize = 66;
+pub(crate) const COV_MULTI_KEY_SIZE: usize = 88;
+pub(crate) const COV_MULTI_KEY_OPCODE_COST: usize = 22;
+pub(crate) const COV_MULTI_SUFFIX_SIZE: usize = 2;
+pub(crate) const COV_MULTI_SUFFIX_OPCODE_COST: usize = 3;
+// The script code of multi key covenants is part of the sighash
+// message, which must fit in a single 520 byte stack element.
+pub(crate) const COV_MULTI_MAX_KEYS: usize = 3;
+
 /// The covenant descriptor
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+///
+/// The inner miniscript is always in segwit v0 context. Covenants with a
+/// tapscript miniscript, e.g. one using `multi_a`, are
+/// [`TapCSFSCov`](super::TapCSFSCov)s.
+///
+/// Equality, ordering and hashing are structural over the keys, the
+/// threshold and the miniscript AST. Like the descriptor string, they
+/// ignore the [`LegacyCSFSCov::nop_padding`] of parsed scripts. Use
+/// [`LegacyCSFSCov::canonical_bytes`] to compare covenants by the script
+/// they encode to instead.
+#[derive(Clone)]
 pub struct LegacyCSFSCov<Pk: MiniscriptKey, Ext: Extension> {
-    /// the pk constraining the Covenant
-    /// The key over which we want CHECKSIGFROMSTACK
-    pub(crate) pk: Pk,
+    /// the pks constraining the Covenant
+    /// The keys over which we want CHECKSIGFROMSTACK
+    pub(crate) pks: Vec<Pk>,
+    /// The number of `pks` that must sign the sighash
+    pub(crate) k: usize,
     /// the underlying Miniscript
     /// Must be under segwit context
     // All known extensions are enabled in covenant descriptor
     pub(crate) ms: Miniscript<Pk, Segwitv0, Ext>,
+    /// Number of `OP_NOP`s in front of the miniscript. Only set
+    /// by [`LegacyCSFSCov::parse_insane_padded`]
+    pub(crate) nop_padding: usize,
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> PartialEq for LegacyCSFSCov<Pk, Ext> {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.pks, self.k, &self.ms) == (&other.pks, other.k, &other.ms)
+    }
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> Eq for LegacyCSFSCov<Pk, Ext> {}
+
+impl<Pk: MiniscriptKey, Ext: Extension> PartialOrd for LegacyCSFSCov<Pk, Ext> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> Ord for LegacyCSFSCov<Pk, Ext> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (&self.pks, self.k, &self.ms).cmp(&(&other.pks, other.k, &other.ms))
+    }
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> std::hash::Hash for LegacyCSFSCov<Pk, Ext> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.pks.hash(state);
+        self.k.hash(state);
+        self.ms.hash(state);
+    }
 }
 
 impl<Pk: MiniscriptKey, Ext: Extension> LegacyCSFSCov<Pk, Ext> {
+    /// Number of opcodes in the single key covenant wrapper script around
+    /// the miniscript. One less when the miniscript ends with a free verify.
+    pub const WRAPPER_OPCODE_COST: usize = COV_SCRIPT_OPCODE_COST;
+
+    /// Size in bytes of the single key covenant wrapper script around the
+    /// miniscript. One less when the miniscript ends with a free verify.
+    pub const WRAPPER_SCRIPT_SIZE: usize = COV_SCRIPT_SIZE;
+
+    /// Number of sighash items the wrapper expects on the stack: the ten
+    /// segwit v0 sighash items and the elements specific hashissuances.
+    pub const SIGHASH_ITEM_COUNT: usize = COV_SIGHASH_ITEM_COUNT;
+
+    /// Stack depth at which the single key wrapper picks the covenant
+    /// signature, right below the sighash items.
+    pub const CSFS_SIG_DEPTH: usize = COV_CSFS_SIG_DEPTH;
+
     /// Get the pk from covenant
+    /// For multi key covenants, this is the first key
     pub fn pk(&self) -> &Pk {
-        &self.pk
+        &self.pks[0]
+    }
+
+    /// Get all the pks from covenant
+    pub fn pks(&self) -> &[Pk] {
+        &self.pks
+    }
+
+    /// All keys of the covenant in [`ForEachKey`] order: the covenant keys
+    /// followed by the keys of the inner miniscript. Keys are not
+    /// deduplicated, a covenant key used in the miniscript appears twice.
+    pub fn keys(&self) -> Vec<&Pk> {
+        let mut keys = vec![];
+        self.for_each_key(|pk| {
+            keys.push(pk);
+            true
+        });
+        keys
+    }
+
+    /// Get the number of pks that must sign the covenant sighash
+    pub fn threshold(&self) -> usize {
+        self.k
+    }
+
+    /// Whether this is a single key covenant
+    fn is_single_key(&self) -> bool {
+        self.pks.len() == 1 && self.k == 1
+    }
+
+    // Write the covenant keys as `pk` or `multi(k,pk1,..,pkn)`
+    fn fmt_keys<W: fmt::Write>(&self, f: &mut W) -> fmt::Result {
+        if self.is_single_key() {
+            return write!(f, "{}", self.pk());
+        }
+        write!(f, "multi({}", self.k)?;
+        for pk in &self.pks {
+            write!(f, ",{}", pk)?;
+        }
+        write!(f, ")")
+    }
+
+    // Write the descriptor string without the checksum. This is the
+    // canonical `elcovwsh(keys,ms)` form accepted by `from_tree`.
+    fn fmt_no_checksum<W: fmt::Write>(&self, f: &mut W) -> fmt::Result {
+        write!(f, "{}covwsh(", ELMTS_STR)?;
+        self.fmt_keys(f)?;
+        write!(f, ",{})", self.ms)
+    }
+
+    // Opcodes and size of the covenant wrapper for the given keys
+    fn wrapper_cost(n_keys: usize, k: usize) -> (usize, usize) {
+        if n_keys == 1 && k == 1 {
+            return (Self::WRAPPER_OPCODE_COST, Self::WRAPPER_SCRIPT_SIZE);
+        }
+        let ops = COV_MULTI_PREFIX_OPCODE_COST
+            + n_keys * COV_MULTI_KEY_OPCODE_COST
+            + COV_MULTI_SUFFIX_OPCODE_COST;
+        let size = COV_MULTI_PREFIX_SIZE
+            + (0..n_keys)
+                .map(|i| COV_MULTI_KEY_SIZE + 2 * script_num_size(i + 2))
+                .sum::<usize>()
+            + COV_MULTI_SUFFIX_SIZE
+            + script_num_size(k);
+        (ops, size)
+    }
+
+    // Size of the covenant script code, the part of the wrapper after
+    // the OP_CODESEPARATOR
+    fn cov_script_code_len(&self) -> usize {
+        Self::script_code_len(self.pks.len(), self.k)
+    }
+
+    fn script_code_len(n_keys: usize, k: usize) -> usize {
+        if n_keys == 1 && k == 1 {
+            return 2;
+        }
+        let (_, cov_script_size) = Self::wrapper_cost(n_keys, k);
+        cov_script_size - COV_MULTI_PREFIX_SIZE
+    }
+
+    // Check that the threshold is valid for the given number of keys
+    fn check_threshold(n_keys: usize, k: usize) -> Result<(), Error> {
+        if n_keys == 0 || n_keys > COV_MULTI_MAX_KEYS {
+            return Err(errstr("covenant must have between 1 and 3 keys"));
+        }
+        if k == 0 || k > n_keys {
+            return Err(errstr(
+                "covenant threshold must be between 1 and the number of keys",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Get the number of `OP_NOP`s padding the covenant script. The
+    /// padding is not part of the descriptor string, and covenants that
+    /// only differ in their padding compare equal.
+    pub fn nop_padding(&self) -> usize {
+        self.nop_padding
     }
 
     /// Get a reference to Miniscript inside covenant
@@ -103,27 +279,88 @@ impl<Pk: MiniscriptKey, Ext: Extension> LegacyCSFSCov<Pk, Ext> {
     }
 
     /// Create a new Self from components
+    ///
+    /// Returns [`CovError::OpCountLimitExceeded`] or
+    /// [`CovError::ScriptSizeLimitExceeded`] with the computed totals if
+    /// the covenant script exceeds the consensus limits.
     pub fn new(pk: Pk, ms: Miniscript<Pk, Segwitv0, Ext>) -> Result<Self, Error> {
+        Self::new_multi(vec![pk], 1, ms)
+    }
+
+    /// Create a new Self where `threshold` out of `keys` must sign the
+    /// covenant sighash. A 1-of-1 covenant is the same as [`Self::new`].
+    /// At most 3 keys are supported because the script code must fit in
+    /// the sighash message on the stack.
+    ///
+    /// Returns the same errors as [`Self::new`].
+    pub fn new_multi(
+        keys: Vec<Pk>,
+        threshold: usize,
+        ms: Miniscript<Pk, Segwitv0, Ext>,
+    ) -> Result<Self, Error> {
+        Self::check_threshold(keys.len(), threshold)?;
+        Self::check_limits(keys.len(), threshold, &ms, 0)?;
+        Ok(Self {
+            pks: keys,
+            k: threshold,
+            ms,
+            nop_padding: 0,
+        })
+    }
+
+    /// Replace the inner miniscript with the result of applying `f` to it,
+    /// keeping the covenant keys, threshold and padding.
+    ///
+    /// The consensus limits are checked again on the new miniscript, so
+    /// this returns the same errors as [`Self::new`] in addition to any
+    /// error returned by `f`.
+    pub fn try_map_inner<F>(self, f: F) -> Result<Self, Error>
+    where
+        F: FnOnce(Miniscript<Pk, Segwitv0, Ext>) -> Result<Miniscript<Pk, Segwitv0, Ext>, Error>,
+    {
+        let ms = f(self.ms)?;
+        Self::check_limits(self.pks.len(), self.k, &ms, self.nop_padding)?;
+        Ok(Self { ms, ..self })
+    }
+
+    // Check the consensus limits of the covenant script with
+    // `nop_padding` OP_NOPs in front of the miniscript
+    fn check_limits(
+        n_keys: usize,
+        k: usize,
+        ms: &Miniscript<Pk, Segwitv0, Ext>,
+        nop_padding: usize,
+    ) -> Result<(), Error> {
         // // 1) Check the 201 opcode count here
         let ms_op_count = ms.ext.ops.op_count();
         // statically computed
         // see cov_test_limits test for the test assert
-        let cov_script_ops = COV_SCRIPT_OPCODE_COST;
-        let total_ops = ms_op_count.ok_or(Error::ImpossibleSatisfaction)? + cov_script_ops
-            - if ms.ext.has_free_verify { 1 } else { 0 };
+        let (cov_script_ops, cov_script_size) = Self::wrapper_cost(n_keys, k);
+        let total_ops =
+            ms_op_count.ok_or(Error::ImpossibleSatisfaction)? + cov_script_ops + nop_padding
+                - if ms.ext.has_free_verify { 1 } else { 0 };
         if total_ops > MAX_OPS_PER_SCRIPT {
-            return Err(Error::ImpossibleSatisfaction);
+            return Err(CovError::OpCountLimitExceeded {
+                ops: total_ops,
+                limit: MAX_OPS_PER_SCRIPT,
+            })?;
         }
-        // 2) TODO: Sighash never exceeds 520 bytes, but we check the
-        // witness script before the codesep is still under 520
-        // bytes if the covenant relies on introspection of script
-        let ss = COV_SCRIPT_SIZE - if ms.ext.has_free_verify { 1 } else { 0 };
+        // 2) The sighash message, including the script code after the
+        // codesep, is pushed on the stack and must be under 520 bytes.
+        // This always holds for at most COV_MULTI_MAX_KEYS keys, see the
+        // cov_sighash_message_size test. The miniscript before the
+        // codesep is never pushed on the stack.
+        let ss = cov_script_size - if ms.ext.has_free_verify { 1 } else { 0 };
         // 3) Check that the script size does not exceed 10_000 bytes
         // global consensus rule
-        if ms.script_size() + ss > MAX_SCRIPT_SIZE {
-            Err(Error::ScriptSizeTooLarge)
+        let script_size = ms.script_size() + ss + nop_padding;
+        if script_size > MAX_SCRIPT_SIZE {
+            Err(CovError::ScriptSizeLimitExceeded {
+                size: script_size,
+                limit: MAX_SCRIPT_SIZE,
+            })?
         } else {
-            Ok(Self { pk, ms })
+            Ok(())
         }
     }
     /// Encode
@@ -132,63 +369,385 @@ impl<Pk: MiniscriptKey, Ext: Extension> LegacyCSFSCov<Pk, Ext> {
         Pk: ToPublicKey,
         Ext: ParseableExt,
     {
-        let builder = self.ms.node.encode(script::Builder::new());
-        builder.verify_cov(&self.pk.to_public_key()).into_script()
+        let single_key = self.is_single_key();
+        self.encode_with_wrapper(|builder, keys, k| {
+            if single_key {
+                builder.verify_cov(&keys[0])
+            } else {
+                builder.verify_cov_multi(keys, k)
+            }
+        })
+    }
+
+    /// Encode the padding and the miniscript, then let `wrapper` append
+    /// the covenant check for the keys and threshold. [`Self::encode`]
+    /// uses the correct wrapper; tests pass a tampered one to produce
+    /// scripts that are almost covenants.
+    pub(crate) fn encode_with_wrapper<F>(&self, wrapper: F) -> Script
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+        F: FnOnce(script::Builder, &[bitcoin::PublicKey], usize) -> script::Builder,
+    {
+        let mut builder = script::Builder::new();
+        for _ in 0..self.nop_padding {
+            builder = builder.push_opcode(elements::opcodes::all::OP_NOP);
+        }
+        let builder = self.ms.node.encode(builder);
+        let keys: Vec<_> = self.pks.iter().map(|pk| pk.to_public_key()).collect();
+        wrapper(builder, &keys, self.k).into_script()
+    }
+
+    /// The bytes of the encoded covenant script, see [`Self::encode`].
+    /// Covenants with the same canonical bytes have the same script
+    /// pubkey, so this can be used as the key for deduplicating them.
+    pub fn canonical_bytes(&self) -> Vec<u8>
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        self.encode().into_bytes()
+    }
+
+    /// The single key covenant wrapper script for `pk`, without the
+    /// miniscript in front of it. [`Self::encode`] appends this to the
+    /// miniscript; its size and opcode count are
+    /// [`Self::WRAPPER_SCRIPT_SIZE`] and [`Self::WRAPPER_OPCODE_COST`].
+    pub fn cov_wrapper_script(pk: &Pk) -> Script
+    where
+        Pk: ToPublicKey,
+    {
+        script::Builder::new()
+            .verify_cov(&pk.to_public_key())
+            .into_script()
+    }
+
+    // Lookup the sighash items from the satisfier and serialize them in
+    // the order of the sighash message.
+    fn sighash_items<S: Satisfier<Pk>>(
+        s: &S,
+        sighash_type: CovSighashType,
+    ) -> Result<[Vec<u8>; COV_SIGHASH_ITEM_COUNT], CovError>
+    where
+        Pk: ToPublicKey,
+    {
+        use crate::descriptor::CovError::MissingSighashItem;
+        use crate::descriptor::CovSighashItem as Item;
+        let n_version = s
+            .lookup_nversion()
+            .ok_or(MissingSighashItem(Item::NVersion))?;
+        let hash_prevouts = s
+            .lookup_hashprevouts()
+            .ok_or(MissingSighashItem(Item::HashPrevouts))?;
+        // SIGHASH_NONE and SIGHASH_SINGLE do not commit to the sequences
+        let hash_sequence = match sighash_type {
+            CovSighashType::All => s
+                .lookup_hashsequence()
+                .ok_or(MissingSighashItem(Item::HashSequence))?,
+            CovSighashType::None | CovSighashType::Single(_) => sha256d::Hash::all_zeros(),
+        };
+        let hash_issuances = s
+            .lookup_hashissuances()
+            .ok_or(MissingSighashItem(Item::HashIssuances))?;
+        let outpoint = s
+            .lookup_outpoint()
+            .ok_or(MissingSighashItem(Item::Outpoint))?;
+        let script_code = s
+            .lookup_scriptcode()
+            .ok_or(MissingSighashItem(Item::ScriptCode))?;
+        let value = s.lookup_value().ok_or(MissingSighashItem(Item::Value))?;
+        let n_sequence = s
+            .lookup_nsequence()
+            .ok_or(MissingSighashItem(Item::NSequence))?;
+        let outputs = s
+            .lookup_outputs()
+            .ok_or(MissingSighashItem(Item::Outputs))?;
+        let hash_outputs = sighash_type.hash_outputs(outputs)?;
+        let n_locktime = s
+            .lookup_nlocktime()
+            .ok_or(MissingSighashItem(Item::NLocktime))?;
+        let sighash_ty = sighash_type.as_u32();
+
+        let items = [
+            serialize(&n_version),      // item 1
+            serialize(&hash_prevouts),  // item 2
+            serialize(&hash_sequence),  // item 3
+            serialize(&hash_issuances), // ELEMENTS EXTRA: item 3b
+            serialize(&outpoint),       // item 4
+            serialize(script_code),     // item 5
+            serialize(&value),          // item 6
+            serialize(&n_sequence),     // item 7
+            serialize(&hash_outputs),   // item 8
+            serialize(&n_locktime),     // item 9
+            serialize(&sighash_ty),     // item 10
+        ];
+        Ok(items)
+    }
+
+    /// The sighash items that the covenant commits to. The covenant
+    /// rebuilds the full segwit v0 sighash message from the witness, so
+    /// this is currently always the set of all the items. Which outputs
+    /// are committed to is still up to the [`CovSighashType`] of the
+    /// signature.
+    pub fn committed_fields(&self) -> CovSighashItems {
+        CovSighashItem::ALL.iter().copied().collect()
+    }
+
+    /// The message verified by the covenant CHECKSIGFROMSTACK. This is the
+    /// double SHA256 of the sighash items 1-10 provided by the satisfier
+    /// and is the same as the segwit sighash of the spending transaction.
+    ///
+    /// Signers can use this to create the covenant signature without a
+    /// [`Satisfier`] that provides the signature. The outputs committed
+    /// to follow the sighash type looked up from `s`, see
+    /// [`Self::sighash_message_with_type`] to choose it instead.
+    pub fn sighash_message<S: Satisfier<Pk>>(&self, s: &S) -> Result<sha256d::Hash, CovError>
+    where
+        Pk: ToPublicKey,
+    {
+        let sighash_type = CovSighashType::from_satisfier(s)?;
+        self.sighash_message_with_type(s, sighash_type)
     }
 
-    /// Create a satisfaction for the Covenant Descriptor
+    /// Same as [`Self::sighash_message`], but only commits to the outputs
+    /// selected by `sighash_type`.
+    pub fn sighash_message_with_type<S: Satisfier<Pk>>(
+        &self,
+        s: &S,
+        sighash_type: CovSighashType,
+    ) -> Result<sha256d::Hash, CovError>
+    where
+        Pk: ToPublicKey,
+    {
+        let items = Self::sighash_items(s, sighash_type)?;
+        let mut enc = sha256d::Hash::engine();
+        for item in items {
+            enc.input(&item);
+        }
+        Ok(sha256d::Hash::from_engine(enc))
+    }
+
+    /// Create a satisfaction for the Covenant Descriptor committing
+    /// to the outputs selected by the sighash type looked up from `s`.
+    /// SIGHASH_SINGLE commits to the output at the index of the input
+    /// being spent.
     pub fn satisfy<S: Satisfier<Pk>>(&self, s: S, allow_mall: bool) -> Result<Vec<Vec<u8>>, Error>
     where
         Pk: ToPublicKey,
         Ext: ParseableExt,
     {
-        let mut wit = {
-            use crate::descriptor::CovError::MissingSighashItem;
-            let n_version = s.lookup_nversion().ok_or(MissingSighashItem(1))?;
-            let hash_prevouts = s.lookup_hashprevouts().ok_or(MissingSighashItem(1))?;
-            let hash_sequence = s.lookup_hashsequence().ok_or(MissingSighashItem(3))?;
-            // note the 3 again, for elements
-            let hash_issuances = s.lookup_hashissuances().ok_or(MissingSighashItem(3))?;
-            let outpoint = s.lookup_outpoint().ok_or(MissingSighashItem(4))?;
-            let script_code = s.lookup_scriptcode().ok_or(MissingSighashItem(5))?;
-            let value = s.lookup_value().ok_or(MissingSighashItem(6))?;
-            let n_sequence = s.lookup_nsequence().ok_or(MissingSighashItem(7))?;
-            let outputs = s.lookup_outputs().ok_or(MissingSighashItem(8))?;
-            let hash_outputs = hash256_arr(outputs);
-            let n_locktime = s.lookup_nlocktime().ok_or(MissingSighashItem(9))?;
-            let sighash_ty = s.lookup_sighashu32().ok_or(MissingSighashItem(10))?;
-
-            let (sig, hash_ty) = s
-                .lookup_ecdsa_sig(&self.pk)
-                .ok_or(CovError::MissingCovSignature)?;
-            // Hashtype must be the same
-            if sighash_ty != hash_ty.as_u32() {
-                return Err(CovError::CovenantSighashTypeMismatch)?;
+        let sighash_type = CovSighashType::from_satisfier(&s)?;
+        self.satisfy_with_sighash_type(s, allow_mall, sighash_type)
+    }
+
+    /// Create a satisfaction for the Covenant Descriptor where the
+    /// covenant sighash only commits to the outputs selected by
+    /// `sighash_type`. The covenant signatures must use the same
+    /// sighash type.
+    pub fn satisfy_with_sighash_type<S: Satisfier<Pk>>(
+        &self,
+        s: S,
+        allow_mall: bool,
+        sighash_type: CovSighashType,
+    ) -> Result<Vec<Vec<u8>>, Error>
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        Ok(self.satisfy_parts(s, allow_mall, sighash_type)?.into())
+    }
+
+    /// Same as [`Self::satisfy_with_sighash_type`], but returns the
+    /// witness split into its parts instead of the final witness stack
+    ///
+    /// Fails with [`CovError::LockTimeMismatch`] if `s` reports an `after`
+    /// timelock of the miniscript as satisfied that the committed
+    /// nLockTime does not satisfy, and likewise with
+    /// [`CovError::SequenceMismatch`] for `older` and the committed
+    /// nSequence.
+    pub fn satisfy_parts<S: Satisfier<Pk>>(
+        &self,
+        s: S,
+        allow_mall: bool,
+        sighash_type: CovSighashType,
+    ) -> Result<CovWitness, Error>
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        let (sigs, items) = {
+            let items = Self::sighash_items(&s, sighash_type)?;
+            self.check_committed_timelocks(&s)?;
+            let sighash_ty = sighash_type.as_u32();
+
+            // Use the signatures of the first k keys that have one
+            let mut sigs = Vec::with_capacity(self.pks.len());
+            let mut n_sigs = 0;
+            let mut missing = None;
+            for (i, pk) in self.pks.iter().enumerate() {
+                if n_sigs == self.k {
+                    sigs.push(None);
+                    continue;
+                }
+                match s.lookup_ecdsa_sig(pk) {
+                    Some((sig, hash_ty)) => {
+                        // Hashtype must be the same
+                        if sighash_ty != hash_ty.as_u32() {
+                            return Err(CovError::CovenantSighashTypeMismatch)?;
+                        }
+                        sigs.push(Some(sig));
+                        n_sigs += 1;
+                    }
+                    None => {
+                        missing = missing.or(Some(i));
+                        sigs.push(None);
+                    }
+                }
+            }
+            if let Some(i) = missing.filter(|_| n_sigs < self.k) {
+                return Err(CovError::MissingCovSignature(i))?;
             }
 
-            vec![
-                Vec::from(sig.serialize_der().as_ref()), // The covenant sig
-                serialize(&sighash_ty),                  // item 10(11)
-                serialize(&n_locktime),                  // item 9(10)
-                serialize(&hash_outputs),                // item 8(9)
-                serialize(&n_sequence),                  // item 7(8)
-                serialize(&value),                       // item 6(7)
-                serialize(script_code),                  // item 5(6)
-                serialize(&outpoint),                    // item 4(5)
-                serialize(&hash_issuances),              // ELEMENTS EXTRA: item 3b(4)
-                serialize(&hash_sequence),               // item 3
-                serialize(&hash_prevouts),               // item 2
-                serialize(&n_version),                   // item 1
-            ]
+            // One signature slot per key. Keys that don't sign get an
+            // empty slot.
+            let sigs: Vec<Vec<u8>> = sigs
+                .iter()
+                .map(|sig| match sig {
+                    Some(sig) => Vec::from(sig.serialize_der().as_ref()),
+                    None => vec![],
+                })
+                .collect();
+            (sigs, items)
         };
 
-        let ms_wit = if !allow_mall {
-            self.ms.satisfy(s)?
+        let ms_witness = if !allow_mall {
+            self.satisfy_inner(s)?
         } else {
             self.ms.satisfy_malleable(s)?
         };
-        wit.extend(ms_wit);
-        Ok(wit)
+        Ok(Self::cov_witness(sigs, items, ms_witness))
+    }
+
+    /// Assemble the witness from a covenant signature created elsewhere,
+    /// e.g. by an air-gapped device that signed [`Self::sighash_message_with_type`].
+    /// The sighash items are looked up from `s`, and `ms_witness` is the
+    /// witness of the inner miniscript as returned by [`Self::satisfy_inner`].
+    /// As with [`Self::satisfy`], the witness script is not included.
+    ///
+    /// The signature `sig` of the covenant key `pk` goes in the slot of
+    /// that key. Multi key covenants that need more than one signature
+    /// must use [`Self::satisfy_parts`] instead.
+    pub fn finalize_with_sig<S: Satisfier<Pk>>(
+        &self,
+        pk: &Pk,
+        sig: ElementsSig,
+        sighash_type: CovSighashType,
+        s: &S,
+        ms_witness: Vec<Vec<u8>>,
+    ) -> Result<Vec<Vec<u8>>, CovError>
+    where
+        Pk: ToPublicKey,
+    {
+        // Hashtype must be the same
+        if sig.1.as_u32() != sighash_type.as_u32() {
+            return Err(CovError::CovenantSighashTypeMismatch);
+        }
+        if self.k > 1 {
+            return Err(CovError::NotEnoughCovSignatures(self.k));
+        }
+        let idx = self
+            .pks
+            .iter()
+            .position(|cov_pk| cov_pk == pk)
+            .ok_or(CovError::UnknownCovKey)?;
+        let items = Self::sighash_items(s, sighash_type)?;
+        let mut sigs = vec![vec![]; self.pks.len()];
+        sigs[idx] = Vec::from(sig.0.serialize_der().as_ref());
+        Ok(Self::cov_witness(sigs, items, ms_witness).into())
+    }
+
+    // The inner miniscript is satisfied with the satisfier's view of the
+    // timelocks, but the covenant commits to the nLockTime and nSequence it
+    // looks up. Reject a satisfier that claims an `after` or `older`
+    // timelock the committed values do not meet.
+    fn check_committed_timelocks<S: Satisfier<Pk>>(&self, s: &S) -> Result<(), CovError>
+    where
+        Pk: ToPublicKey,
+    {
+        let n_locktime = s.lookup_nlocktime();
+        let n_sequence = s.lookup_nsequence();
+        for ms in self.ms.iter() {
+            match ms.node {
+                Terminal::After(n) => {
+                    let committed = match n_locktime {
+                        Some(committed) => committed,
+                        None => continue,
+                    };
+                    let n = LockTime::from(n);
+                    if s.check_after(n)
+                        && !Satisfier::<Pk>::check_after(&LockTime::from_consensus(committed), n)
+                    {
+                        return Err(CovError::LockTimeMismatch {
+                            committed,
+                            required: n.to_consensus_u32(),
+                        });
+                    }
+                }
+                Terminal::Older(n) => {
+                    let committed = match n_sequence {
+                        Some(committed) => committed,
+                        None => continue,
+                    };
+                    if s.check_older(n)
+                        && !Satisfier::<Pk>::check_older(&Sequence::from_consensus(committed), n)
+                    {
+                        return Err(CovError::SequenceMismatch {
+                            committed,
+                            required: n.to_consensus_u32(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Put the serialized signatures, sighash items and miniscript
+    // witness together
+    fn cov_witness(
+        sigs: Vec<Vec<u8>>,
+        items: [Vec<u8>; COV_SIGHASH_ITEM_COUNT],
+        ms_witness: Vec<Vec<u8>>,
+    ) -> CovWitness {
+        let [i1, i2, i3, i3b, i4, i5, i6, i7, i8, i9, i10] = items;
+        CovWitness {
+            sigs,
+            n_version: i1,
+            hash_prevouts: i2,
+            hash_sequence: i3,
+            hash_issuances: i3b,
+            outpoint: i4,
+            script_code: i5,
+            value: i6,
+            n_sequence: i7,
+            hash_outputs: i8,
+            n_locktime: i9,
+            sighash_u32: i10,
+            ms_witness,
+        }
+    }
+
+    /// Satisfies only the miniscript inside the covenant and returns its
+    /// witness, without the covenant signatures and sighash items. This is
+    /// the `ms_witness` of [`Self::satisfy_parts`], and is useful to check
+    /// that the spending condition can be met independently of the covenant.
+    pub fn satisfy_inner<S: Satisfier<Pk>>(&self, s: S) -> Result<Vec<Vec<u8>>, Error>
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        self.ms.satisfy(s)
     }
 
     /// Script code for signing with covenant publickey.
@@ -198,7 +757,14 @@ impl<Pk: MiniscriptKey, Ext: Extension> LegacyCSFSCov<Pk, Ext> {
     where
         Pk: ToPublicKey,
     {
-        script::Builder::new().post_codesep_script().into_script()
+        if self.is_single_key() {
+            script::Builder::new().post_codesep_script().into_script()
+        } else {
+            let keys: Vec<_> = self.pks.iter().map(|pk| pk.to_public_key()).collect();
+            script::Builder::new()
+                .post_codesep_multi_script(&keys, self.k)
+                .into_script()
+        }
     }
 }
 
@@ -206,9 +772,36 @@ impl<Ext: ParseableExt> LegacyCSFSCov<bitcoin::PublicKey, Ext> {
     /// Check if the given script is a covenant descriptor
     /// Consumes the iterator so that only remaining miniscript
     /// needs to be parsed from the iterator
+    /// Returns the threshold and the covenant keys
+    ///
+    /// Single key covenants end in `OP_CHECKSIGFROMSTACK`, whose result is
+    /// the result of the script. Multi key covenants use
+    /// `OP_CHECKSIGFROMSTACKVERIFY` for every key and end by comparing the
+    /// counter to the threshold. The post codesep part of the wrapper is the
+    /// script code signed by the covenant keys, so other forms are rejected.
+    ///
+    /// A wrapper that only partially matches is reported as
+    /// [`CovError::BadCovDescriptor`], like one that does not match at all.
+    fn check_cov_script(
+        tokens: &mut TokenIter<'_>,
+    ) -> Result<(usize, Vec<bitcoin::PublicKey>), Error> {
+        let res = match tokens.peek() {
+            Some(Tk::CheckSigFromStack) => {
+                Self::check_single_cov_script(tokens).map(|pk| (1, vec![pk]))
+            }
+            _ => Self::check_multi_cov_script(tokens),
+        };
+        res.map_err(|e| match e {
+            Error::Unexpected(_) | Error::UnexpectedStart => {
+                Error::CovError(CovError::BadCovDescriptor)
+            }
+            e => e,
+        })
+    }
+
     #[allow(unreachable_patterns)]
-    fn check_cov_script(tokens: &mut TokenIter<'_>) -> Result<bitcoin::PublicKey, Error> {
-        match_token!(tokens,
+    fn check_single_cov_script(tokens: &mut TokenIter<'_>) -> Result<bitcoin::PublicKey, Error> {
+        let (pk, type_len, type_depth, sig_depth) = match_token!(tokens,
             Tk::CheckSigFromStack, Tk::Verify, Tk::CheckSig, Tk::CodeSep, Tk::Swap,
             Tk::FromAltStack, Tk::Dup, Tk::Bytes33(pk), Tk::Sha256,
             Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(4), Tk::Size,   // item 10
@@ -225,12 +818,86 @@ impl<Ext: ParseableExt> LegacyCSFSCov<bitcoin::PublicKey, Ext> {
             Tk::Swap, Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(32), Tk::Size, // item 3
             Tk::Swap, Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(32), Tk::Size, // item 2
             Tk::Swap, Tk::Verify, Tk::Equal, Tk::Num(4), Tk::Size,  // item 1
-            Tk::ToAltStack, Tk::Cat, Tk::Left, Tk::Num(1),
-            Tk::Pick, Tk::Num(11), Tk::Pick, Tk::Num(11), Tk::Verify => {
-                Ok(bitcoin::PublicKey::from_slice(pk)?)
+            Tk::ToAltStack, Tk::Cat, Tk::Left, Tk::Num(type_len),
+            Tk::Pick, Tk::Num(type_depth), Tk::Pick, Tk::Num(sig_depth), Tk::Verify => {
+                (pk, type_len, type_depth, sig_depth)
             },
-            _ => Err(Error::CovError(CovError::BadCovDescriptor)),
-        )
+            _ => return Err(Error::CovError(CovError::BadCovDescriptor)),
+        );
+        if type_len as usize != COV_SIGHASH_TYPE_LEN
+            || type_depth as usize != COV_SIGHASH_ITEM_COUNT
+            || sig_depth as usize != COV_CSFS_SIG_DEPTH
+        {
+            return Err(Error::CovError(CovError::BadCovDescriptor));
+        }
+        Ok(bitcoin::PublicKey::from_slice(pk)?)
+    }
+
+    #[allow(unreachable_patterns)]
+    fn check_multi_cov_script(
+        tokens: &mut TokenIter<'_>,
+    ) -> Result<(usize, Vec<bitcoin::PublicKey>), Error> {
+        let k = match_token!(tokens,
+            Tk::Equal, Tk::Num(k) => k as usize,
+            _ => return Err(Error::CovError(CovError::BadCovDescriptor)),
+        );
+        // The number of keys is the number of NIPs
+        let mut n_keys = 0;
+        while tokens.peek() == Some(&Tk::Nip) {
+            tokens.next();
+            n_keys += 1;
+        }
+        let n_keys_ok = n_keys > 1 && n_keys <= COV_MULTI_MAX_KEYS;
+        if tokens.next() != Some(Tk::Drop) || !n_keys_ok || k == 0 || k > n_keys {
+            return Err(Error::CovError(CovError::BadCovDescriptor));
+        }
+        let mut keys = Vec::with_capacity(n_keys);
+        for i in (0..n_keys).rev() {
+            let (pk, pk2, d1, d2) = match_token!(tokens,
+                Tk::EndIf, Tk::Drop, Tk::Else,
+                Tk::Swap, Tk::Add, Tk::Num(1), Tk::Swap,
+                Tk::Verify, Tk::CheckSig, Tk::Bytes33(pk), Tk::Cat,
+                Tk::ToAltStack, Tk::Dup, Tk::FromAltStack, Tk::Pick, Tk::Num(d1),
+                Tk::CheckSigFromStackVerify, Tk::Bytes33(pk2), Tk::Over,
+                Tk::If, Tk::ZeroNotEqual, Tk::Size, Tk::Pick, Tk::Num(d2) => (pk, pk2, d1, d2),
+                _ => return Err(Error::CovError(CovError::BadCovDescriptor)),
+            );
+            if pk != pk2 || d1 != d2 || d1 as usize != i + 2 {
+                return Err(Error::CovError(CovError::BadCovDescriptor));
+            }
+            keys.push(bitcoin::PublicKey::from_slice(pk)?);
+        }
+        keys.reverse();
+        let script_code = script::Builder::new()
+            .post_codesep_multi_script(&keys, k)
+            .into_script();
+        let (script_code_len, type_len, type_depth) = match_token!(tokens,
+            Tk::CodeSep, Tk::Swap, Tk::Num(0), Tk::Sha256,
+            Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(4), Tk::Size,   // item 10
+            Tk::Swap, Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(4), Tk::Size,  // item 9
+            Tk::Swap, Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(32), Tk::Size, // item 8
+            Tk::Swap, Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(4), Tk::Size,  // item 7
+            Tk::Swap, Tk::Cat, Tk::EndIf,
+                Tk::Verify, Tk::Equal, Tk::Num(33), Tk::Size, Tk::Else,// item 6
+                Tk::Verify, Tk::Equal, Tk::Num(9), Tk::Size, Tk::If,   // item 6
+                Tk::Equal, Tk::Num(1), Tk::Left, Tk::Num(1), Tk::Dup,  // item 6
+            Tk::Swap, Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(len), Tk::Size,  // item 5
+            Tk::Swap, Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(36), Tk::Size, // item 4
+            Tk::Swap, Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(32), Tk::Size, // item 3b
+            Tk::Swap, Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(32), Tk::Size, // item 3
+            Tk::Swap, Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(32), Tk::Size, // item 2
+            Tk::Swap, Tk::Verify, Tk::Equal, Tk::Num(4), Tk::Size,  // item 1
+            Tk::ToAltStack, Tk::Left, Tk::Num(type_len), Tk::Pick, Tk::Num(type_depth),
+            Tk::Verify => (len as usize, type_len as usize, type_depth as usize),
+            _ => return Err(Error::CovError(CovError::BadCovDescriptor)),
+        );
+        if script_code_len != serialize(&script_code).len()
+            || type_len != COV_SIGHASH_TYPE_LEN
+            || type_depth != COV_SIGHASH_ITEM_COUNT - 1
+        {
+            return Err(Error::CovError(CovError::BadCovDescriptor));
+        }
+        Ok((k, keys))
     }
 
     /// Parse a descriptor from script. While parsing
@@ -241,8 +908,134 @@ impl<Ext: ParseableExt> LegacyCSFSCov<bitcoin::PublicKey, Ext> {
     // All code for covenants can thus be separated in a module
     // This parsing is parse_insane
     pub fn parse_insane(script: &script::Script) -> Result<Self, Error> {
-        let (pk, ms) = Self::parse_cov_components(script)?;
-        Self::new(pk, ms)
+        let (k, pks, ms) = Self::parse_cov_components(script)?;
+        Self::new_multi(pks, k, ms)
+    }
+
+    /// Same as [`Self::parse_insane`], but tolerates a run of `OP_NOP`s
+    /// in front of the miniscript, as used by some signing devices to pad
+    /// scripts to a fixed length. The number of `OP_NOP`s is kept so that
+    /// [`Self::encode`] reproduces the parsed script.
+    ///
+    /// Padded covenants are non-standard: the padding is not part of the
+    /// descriptor string, so parsing the string back gives an unpadded
+    /// covenant with a different script pubkey.
+    pub fn parse_insane_padded(script: &script::Script) -> Result<Self, Error> {
+        // OP_NOP carries no data, so every leading NOP byte is an opcode
+        let nop_padding = script
+            .as_bytes()
+            .iter()
+            .take_while(|b| **b == elements::opcodes::all::OP_NOP.into_u8())
+            .count();
+        let script = Script::from(script.as_bytes()[nop_padding..].to_vec());
+        let (k, pks, ms) = Self::parse_cov_components(&script)?;
+        Self::check_threshold(pks.len(), k)?;
+        Self::check_limits(pks.len(), k, &ms, nop_padding)?;
+        Ok(LegacyCSFSCov {
+            pks,
+            k,
+            ms,
+            nop_padding,
+        })
+    }
+
+    /// Same as [`Self::parse_insane`], but when the script does not end in
+    /// a covenant wrapper, the first token that differs from the expected
+    /// wrapper is reported as [`CovWrapperError::BadWrapperAt`]. Other
+    /// parse errors are reported as [`CovWrapperError::Parse`].
+    ///
+    /// This is meant for debugging hand written covenant scripts. The
+    /// script is lexed a second time to locate the token, so use
+    /// [`Self::parse_insane`] when the position is not needed.
+    pub fn parse_insane_with_diagnostics(script: &script::Script) -> Result<Self, CovWrapperError> {
+        match Self::parse_insane(script) {
+            Err(Error::CovError(CovError::BadCovDescriptor)) => {
+                match Self::diagnose_cov_script(script)? {
+                    Some(err) => Err(err),
+                    None => Err(Error::CovError(CovError::BadCovDescriptor).into()),
+                }
+            }
+            res => Ok(res?),
+        }
+    }
+
+    // Compare the end of the script to the wrapper of a covenant with the
+    // threshold and number of keys the script appears to have, reading it
+    // backwards like `check_cov_script`. The keys are not known in advance,
+    // so any 33 byte push is accepted for a key, as long as both pushes of
+    // a key in a multi key wrapper agree.
+    fn diagnose_cov_script(script: &script::Script) -> Result<Option<CovWrapperError>, Error> {
+        let tokens = lex(script)?;
+        let rev: Vec<_> = tokens.iter().rev().collect();
+        let mismatch = |i: usize, expected: String| {
+            Some(CovWrapperError::BadWrapperAt {
+                position: tokens.len().saturating_sub(i + 1),
+                expected,
+                got: rev
+                    .get(i)
+                    .map(|tk| tk.to_string())
+                    .unwrap_or_else(|| String::from("the start of the script")),
+            })
+        };
+
+        let (k, n_keys) = match rev.first() {
+            Some(Tk::CheckSigFromStack) => (1, 1),
+            Some(Tk::Equal) => {
+                let k = match rev.get(1) {
+                    Some(Tk::Num(k)) => *k as usize,
+                    _ => return Ok(mismatch(1, String::from("a threshold"))),
+                };
+                let n_keys = rev[2..].iter().take_while(|tk| ***tk == Tk::Nip).count();
+                if n_keys < 2 {
+                    return Ok(mismatch(2 + n_keys, Tk::Nip.to_string()));
+                } else if n_keys > COV_MULTI_MAX_KEYS {
+                    return Ok(mismatch(2 + COV_MULTI_MAX_KEYS, Tk::Drop.to_string()));
+                } else if k == 0 || k > n_keys {
+                    let expected = format!("a threshold between 1 and {}", n_keys);
+                    return Ok(mismatch(1, expected));
+                }
+                (k, n_keys)
+            }
+            _ => {
+                let expected = format!("{} or {}", Tk::CheckSigFromStack, Tk::Equal);
+                return Ok(mismatch(0, expected));
+            }
+        };
+
+        // Any valid key will do, only the positions of the keys are used
+        let dummy = bitcoin::PublicKey::from_str(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .expect("valid key");
+        let wrapper = if n_keys == 1 {
+            script::Builder::new().verify_cov(&dummy).into_script()
+        } else {
+            script::Builder::new()
+                .verify_cov_multi(&vec![dummy; n_keys], k)
+                .into_script()
+        };
+        let expected_tokens = lex(&wrapper)?;
+
+        let mut keys: Vec<Option<&[u8]>> = vec![None; n_keys];
+        let mut n_pushes = 0;
+        for (i, expected) in expected_tokens.iter().rev().enumerate() {
+            if let Tk::Bytes33(_) = expected {
+                // Single key wrappers push their key once, multi key
+                // wrappers twice in a row
+                let key = &mut keys[n_pushes / 2];
+                n_pushes += 1;
+                match (rev.get(i), *key) {
+                    (Some(Tk::Bytes33(pk)), Some(prev)) if *pk != prev => {
+                        return Ok(mismatch(i, Tk::Bytes33(prev).to_string()));
+                    }
+                    (Some(Tk::Bytes33(pk)), _) => *key = Some(pk),
+                    _ => return Ok(mismatch(i, String::from("a 33 byte key"))),
+                }
+            } else if rev.get(i).copied() != Some(expected) {
+                return Ok(mismatch(i, expected.to_string()));
+            }
+        }
+        Ok(None)
     }
 
     // Utility function to parse the components of cov
@@ -253,7 +1046,8 @@ impl<Ext: ParseableExt> LegacyCSFSCov<bitcoin::PublicKey, Ext> {
         script: &script::Script,
     ) -> Result<
         (
-            bitcoin::PublicKey,
+            usize,
+            Vec<bitcoin::PublicKey>,
             Miniscript<bitcoin::PublicKey, Segwitv0, Ext>,
         ),
         Error,
@@ -261,19 +1055,40 @@ impl<Ext: ParseableExt> LegacyCSFSCov<bitcoin::PublicKey, Ext> {
     where
         Ext: ParseableExt,
     {
+        Self::parse_with_context::<Segwitv0>(script)
+    }
+
+    /// Parses the components of a covenant script, the threshold, the
+    /// covenant keys and the inner miniscript, with the inner miniscript
+    /// checked against the rules of `Ctx` instead of [`Segwitv0`].
+    ///
+    /// This allows recovering covenants from raw scripts whose context is
+    /// not known in advance: try [`Segwitv0`] first and fall back to
+    /// another context. Only [`Segwitv0`] components can be turned into a
+    /// [`LegacyCSFSCov`] with a valid address.
+    pub fn parse_with_context<Ctx: ScriptContext>(
+        script: &script::Script,
+    ) -> Result<
+        (
+            usize,
+            Vec<bitcoin::PublicKey>,
+            Miniscript<Ctx::Key, Ctx, Ext>,
+        ),
+        Error,
+    > {
         let tokens = lex(script)?;
         let mut iter = TokenIter::new(tokens);
 
-        let pk = LegacyCSFSCov::<bitcoin::PublicKey, Ext>::check_cov_script(&mut iter)?;
-        let ms = decode::parse(&mut iter)?;
-        Segwitv0::check_global_validity(&ms)?;
+        let (k, pks) = LegacyCSFSCov::<bitcoin::PublicKey, Ext>::check_cov_script(&mut iter)?;
+        let ms = decode::parse::<Ctx, Ext>(&mut iter)?;
+        Ctx::check_global_validity(&ms)?;
         if ms.ty.corr.base != types::Base::B {
             return Err(Error::NonTopLevel(format!("{:?}", ms)));
         };
         if let Some(leading) = iter.next() {
             Err(Error::Trailing(leading.to_string()))
         } else {
-            Ok((pk, ms))
+            Ok((k, pks, ms))
         }
     }
 
@@ -292,11 +1107,27 @@ impl_from_tree!(
     => Ext; Extension,
     fn from_tree(top: &expression::Tree<'_>) -> Result<Self, Error> {
         if top.name == "elcovwsh" && top.args.len() == 2 {
-            let pk = expression::terminal(&top.args[0], |pk| Pk::from_str(pk))?;
+            let keys = &top.args[0];
+            let (k, pks) = if keys.name == "multi" && !keys.args.is_empty() {
+                let k = expression::parse_num::<u32>(keys.args[0].name)? as usize;
+                let pks = keys.args[1..]
+                    .iter()
+                    .map(|sub| expression::terminal(sub, Pk::from_str))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Self::check_threshold(pks.len(), k)?;
+                (k, pks)
+            } else {
+                (1, vec![expression::terminal(keys, Pk::from_str)?])
+            };
             let top = &top.args[1];
             let sub = Miniscript::from_tree(top)?;
             Segwitv0::top_level_checks(&sub)?;
-            Ok(LegacyCSFSCov { pk, ms: sub })
+            Ok(LegacyCSFSCov {
+                pks,
+                k,
+                ms: sub,
+                nop_padding: 0,
+            })
         } else {
             Err(Error::Unexpected(format!(
                 "{}({} args) while parsing elcovwsh descriptor",
@@ -312,7 +1143,7 @@ where
     Ext: Extension,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}covwsh({},{})", ELMTS_STR, self.pk, self.ms)
+        self.fmt_no_checksum(f)
     }
 }
 
@@ -322,9 +1153,8 @@ where
     Ext: Extension,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use fmt::Write;
         let mut wrapped_f = checksum::Formatter::new(f);
-        write!(wrapped_f, "{}covwsh({},{})", ELMTS_STR, self.pk, self.ms)?;
+        self.fmt_no_checksum(&mut wrapped_f)?;
         wrapped_f.write_checksum_if_not_alt()
     }
 }
@@ -341,6 +1171,8 @@ impl_from_str!(
     }
 );
 
+serde_string_impl_pk!(LegacyCSFSCov, "an elements covenant descriptor" => Ext ; Extension);
+
 impl<Pk, Ext> LegacyCSFSCov<Pk, Ext>
 where
     Pk: MiniscriptKey,
@@ -350,8 +1182,9 @@ where
     pub fn sanity_check(&self) -> Result<(), Error> {
         self.ms.sanity_check()?;
         // Additional local check for p2wsh script size
-        let ss = COV_SCRIPT_SIZE - if self.ms.ext.has_free_verify { 1 } else { 0 };
-        if self.ms.script_size() + ss > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+        let (_, cov_script_size) = Self::wrapper_cost(self.pks.len(), self.k);
+        let ss = cov_script_size - if self.ms.ext.has_free_verify { 1 } else { 0 };
+        if self.ms.script_size() + ss + self.nop_padding > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
             Err(Error::ScriptSizeTooLarge)
         } else {
             Ok(())
@@ -371,6 +1204,30 @@ where
         elements::Address::p2wsh(&self.encode(), blinder, params)
     }
 
+    /// Obtains the confidential address for this descriptor, blinded
+    /// with `blinder`. Same as [`Self::address`] with `Some(blinder)`.
+    pub fn confidential_address(
+        &self,
+        blinder: secp256k1_zkp::PublicKey,
+        params: &'static elements::AddressParams,
+    ) -> elements::Address
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        self.address(Some(blinder), params)
+    }
+
+    /// Obtains the unblinded address for this descriptor. Same as
+    /// [`Self::address`] with `None`.
+    pub fn explicit_address(&self, params: &'static elements::AddressParams) -> elements::Address
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        self.address(None, params)
+    }
+
     /// Obtains the script pubkey for this descriptor.
     pub fn script_pubkey(&self) -> Script
     where
@@ -398,6 +1255,17 @@ where
         self.encode()
     }
 
+    /// The witness script of the covenant. Unlike
+    /// [`Descriptor::explicit_script`](crate::Descriptor::explicit_script),
+    /// this cannot fail because covenants are never taproot descriptors.
+    pub fn explicit_script(&self) -> Script
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        self.encode()
+    }
+
     /// Returns satisfying non-malleable witness and scriptSig to spend an
     /// output controlled by the given descriptor if it possible to
     /// construct one using the satisfier S.
@@ -413,13 +1281,76 @@ where
         Ok((witness, script_sig))
     }
 
+    /// Same as [`Self::get_satisfaction`], but also returns the fee paid for
+    /// the witness at `feerate`, in satoshis per 1000 weight units, rounded up.
+    ///
+    /// This does no fee-aware selection of its own: the witness is exactly
+    /// the one returned by [`Self::get_satisfaction`]. The covenant
+    /// signatures, sighash items and witness script have the same size
+    /// whichever way the inner miniscript is satisfied, so the smallest
+    /// miniscript witness picked by the non-malleable satisfier is also the
+    /// cheapest spend at any feerate.
+    ///
+    /// Returns [`CovError::FeeOverflow`] if the fee does not fit in a u64.
+    pub fn get_satisfaction_with_fee<S>(
+        &self,
+        satisfier: S,
+        feerate: u64,
+    ) -> Result<(Vec<Vec<u8>>, Script, u64), Error>
+    where
+        Pk: ToPublicKey,
+        S: Satisfier<Pk>,
+        Ext: ParseableExt,
+    {
+        let (witness, script_sig) = self.get_satisfaction(satisfier)?;
+        let weight = varint_len(witness.len())
+            + witness
+                .iter()
+                .map(|item| varint_len(item.len()) + item.len())
+                .sum::<usize>();
+        let fee = (weight as u64)
+            .checked_mul(feerate)
+            .and_then(|fee| fee.checked_add(999))
+            .ok_or(CovError::FeeOverflow { weight, feerate })?
+            / 1000;
+        Ok((witness, script_sig, fee))
+    }
+
+    /// Computes an upper bound on the difference between a non-satisfied
+    /// `TxIn`'s `segwit_weight` and a satisfied `TxIn`'s `segwit_weight`.
+    /// This is the weight of the witness stack: its item count varint
+    /// (minus the one byte of an empty witness), the items with their
+    /// length prefixes and the witness script. The scriptSig and the rest
+    /// of the `TxIn` are not included.
+    ///
+    /// The covenant part of the witness is estimated assuming
+    /// - 72 byte DER signatures for the `k` signing keys (the sighash type
+    ///   is not part of the signature, it is sighash item 10) and an empty
+    ///   slot for every other covenant key,
+    /// - a confidential 33 byte value for sighash item 6,
+    /// - the covenant script code for sighash item 5.
+    ///
+    /// The outputs are only committed to by their 32 byte hash, so the
+    /// number of outputs does not change the estimate. The witness of
+    /// fragments such as `outputs_pref` is part of the miniscript estimate.
+    pub fn max_weight_to_satisfy(&self) -> Result<usize, Error> {
+        let (script_size, max_sat_elems, max_sat_size) = self.max_witness_sizes()?;
+        // stack size varint difference between non-satisfied (0) and satisfied
+        let stack_varint_diff = varint_len(max_sat_elems) - varint_len(0);
+
+        Ok(stack_varint_diff + varint_len(script_size) + script_size + max_sat_size)
+    }
+
     /// Computes an upper bound on the weight of a satisfying witness to the
-    /// transaction.
+    /// transaction. Unlike [`Self::max_weight_to_satisfy`], this includes
+    /// 4 weight units for the empty scriptSig length byte and the full
+    /// item count varint of the witness stack.
+    ///
+    /// The estimate makes the same assumptions as
+    /// [`Self::max_weight_to_satisfy`].
+    #[deprecated(note = "use max_weight_to_satisfy instead")]
     pub fn max_satisfaction_weight(&self) -> Result<usize, Error> {
-        let script_size =
-            self.ms.script_size() + 58 - if self.ms.ext.has_free_verify { 1 } else { 0 };
-        let max_sat_elems = self.ms.max_satisfaction_witness_elements()? + 12;
-        let max_sat_size = self.ms.max_satisfaction_size()? + 275;
+        let (script_size, max_sat_elems, max_sat_size) = self.max_witness_sizes()?;
 
         Ok(4 +  // scriptSig length byte
             varint_len(script_size) +
@@ -428,6 +1359,30 @@ where
             max_sat_size)
     }
 
+    // The witness script size, and the maximum number of witness elements
+    // (including the witness script) and their total serialized size
+    // (excluding the witness script)
+    fn max_witness_sizes(&self) -> Result<(usize, usize, usize), Error> {
+        let n = self.pks.len();
+        let (_, cov_script_size) = Self::wrapper_cost(n, self.k);
+        let script_size = self.ms.script_size() + cov_script_size + self.nop_padding
+            - if self.ms.ext.has_free_verify { 1 } else { 0 };
+
+        // Sighash items 1 to 10, each with its length prefix
+        let script_code_len = self.cov_script_code_len();
+        let script_code_len = varint_len(script_code_len) + script_code_len;
+        let cov_sat_size = [4, 32, 32, 32, 36, script_code_len, 33, 4, 32, 4, 4]
+            .iter()
+            .map(|len| varint_len(*len) + len)
+            .sum::<usize>()
+            // k signatures and an empty slot for each other key
+            + self.k * (1 + 72)
+            + (n - self.k);
+        let max_sat_elems = self.ms.max_satisfaction_witness_elements()? + 11 + n;
+        let max_sat_size = self.ms.max_satisfaction_size()? + cov_sat_size;
+        Ok((script_size, max_sat_elems, max_sat_size))
+    }
+
     /// This returns the entire explicit script as the script code.
     /// You will need this script code when singing with pks that
     /// inside Miniscript. Use the [`Self::cov_script_code`] method to
@@ -440,6 +1395,34 @@ where
         self.inner_script()
     }
 
+    /// Returns the script code to use when signing with `key`.
+    /// This is [`Self::cov_script_code`] for the covenant keys and
+    /// [`Self::ecdsa_sighash_script_code`] for keys inside the Miniscript.
+    /// A key used in both places signs as a covenant key.
+    /// Returns `None` if `key` is not part of this descriptor.
+    pub fn script_code_for(&self, key: &Pk) -> Option<Script>
+    where
+        Pk: ToPublicKey,
+        Ext: ParseableExt,
+    {
+        match self.signs_as_cov_key(key)? {
+            true => Some(self.cov_script_code()),
+            false => Some(self.ecdsa_sighash_script_code()),
+        }
+    }
+
+    // Whether `key` signs as a covenant key or as a key inside the
+    // miniscript, `None` if `key` is not part of this descriptor
+    fn signs_as_cov_key(&self, key: &Pk) -> Option<bool> {
+        if self.pks.contains(key) {
+            Some(true)
+        } else if self.ms.for_any_key(|pk| pk == key) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
     /// Returns a possilbly mallable satisfying non-malleable witness and scriptSig to spend an
     /// output controlled by the given descriptor if it possible to
     /// construct one using the satisfier S.
@@ -456,12 +1439,14 @@ where
     }
 }
 
+/// The covenant keys are visited first, in the order of [`LegacyCSFSCov::pks`],
+/// followed by the keys of the inner miniscript in script order.
 impl<Pk: MiniscriptKey, Ext: Extension> ForEachKey<Pk> for LegacyCSFSCov<Pk, Ext> {
     fn for_each_key<'a, F: FnMut(&'a Pk) -> bool>(&'a self, mut pred: F) -> bool
     where
         Pk: 'a,
     {
-        pred(&self.pk) && self.ms.for_any_key(pred)
+        self.pks.iter().all(&mut pred) && self.ms.for_each_key(pred)
     }
 }
 
@@ -478,12 +1463,79 @@ where
         T: Translator<P, Q, E>,
     {
         Ok(LegacyCSFSCov {
-            pk: t.pk(&self.pk)?,
+            pks: self
+                .pks
+                .iter()
+                .map(|pk| t.pk(pk))
+                .collect::<Result<Vec<_>, _>>()?,
+            k: self.k,
             ms: self.ms.translate_pk(t)?,
+            nop_padding: self.nop_padding,
         })
     }
 }
 
+impl<Ext: Extension> LegacyCSFSCov<DescriptorPublicKey, Ext> {
+    /// Whether or not the covenant keys or the inner miniscript keys have
+    /// any wildcards i.e. `/*`.
+    pub fn has_wildcard(&self) -> bool {
+        self.for_any_key(|key| key.has_wildcard())
+    }
+
+    /// Replaces all wildcards (i.e. `/*`) in the covenant keys and in the
+    /// inner miniscript with the same derivation index, turning it into a
+    /// *definite* covenant descriptor.
+    ///
+    /// # Errors
+    /// - If index ≥ 2^31
+    pub fn at_derivation_index(
+        &self,
+        index: u32,
+    ) -> Result<LegacyCSFSCov<DefiniteDescriptorKey, Ext>, ConversionError> {
+        self.translate_pk(&mut IndexDerivator(index))
+    }
+
+    /// Derives the covenant descriptor at `index` and converts all keys
+    /// to [`bitcoin::PublicKey`]. See [`crate::Descriptor::derived_descriptor`].
+    pub fn derived_descriptor<C: secp256k1_zkp::Verification>(
+        &self,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+        index: u32,
+    ) -> Result<LegacyCSFSCov<bitcoin::PublicKey, Ext>, ConversionError> {
+        self.at_derivation_index(index)?
+            .translate_pk(&mut KeyDerivator(secp))
+    }
+
+    /// Derives the covenant descriptor at each index in `range` to find the
+    /// one with the given `script_pubkey`. See
+    /// [`crate::Descriptor::find_derivation_index_for_spk`].
+    ///
+    /// Only the indexes in `range` are searched, each of them requires
+    /// deriving all the keys of the descriptor, so keep the range to the
+    /// gap limit of the wallet. A non-ranged descriptor is only checked
+    /// once and the returned index is meaningless.
+    pub fn find_derivation_index_for_spk<C: secp256k1_zkp::Verification>(
+        &self,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+        script_pubkey: &Script,
+        range: Range<u32>,
+    ) -> Result<Option<(u32, LegacyCSFSCov<bitcoin::PublicKey, Ext>)>, ConversionError>
+    where
+        Ext: ParseableExt,
+    {
+        let range = if self.has_wildcard() { range } else { 0..1 };
+
+        for i in range {
+            let concrete = self.derived_descriptor(secp, i)?;
+            if &concrete.script_pubkey() == script_pubkey {
+                return Ok(Some((i, concrete)));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
 impl<Pk, Ext, ExtQ> TranslateExt<Ext, ExtQ> for LegacyCSFSCov<Pk, Ext>
 where
     Pk: MiniscriptKey,
@@ -498,8 +1550,110 @@ where
         T: ExtTranslator<Ext, ExtQ, E>,
     {
         Ok(LegacyCSFSCov {
-            pk: self.pk.clone(),
+            pks: self.pks.clone(),
+            k: self.k,
             ms: self.ms.translate_ext(translator)?,
+            nop_padding: self.nop_padding,
         })
     }
 }
+
+/// A [`LegacyCSFSCov`] together with its encoded script
+///
+/// [`LegacyCSFSCov::encode`] walks the whole miniscript, and every script
+/// getter of [`LegacyCSFSCov`] calls it again. This wrapper encodes the
+/// covenant once and serves the getters from the cached script, which
+/// helps when computing the script pubkeys of many covenants, e.g. over a
+/// derivation range. The covenant cannot be changed while it is wrapped;
+/// use [`CompiledLegacyCSFSCov::into_inner`] to translate it and wrap the
+/// result again.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CompiledLegacyCSFSCov<Pk: MiniscriptKey, Ext: Extension> {
+    cov: LegacyCSFSCov<Pk, Ext>,
+    script: Script,
+    cov_script_code: Script,
+}
+
+impl<Pk, Ext> CompiledLegacyCSFSCov<Pk, Ext>
+where
+    Pk: MiniscriptKey + ToPublicKey,
+    Ext: ParseableExt,
+{
+    /// Encode the covenant and cache its script and covenant script code
+    pub fn new(cov: LegacyCSFSCov<Pk, Ext>) -> Self {
+        let script = cov.encode();
+        let cov_script_code = cov.cov_script_code();
+        Self {
+            cov,
+            script,
+            cov_script_code,
+        }
+    }
+
+    /// Get a reference to the inner covenant descriptor
+    pub fn as_inner(&self) -> &LegacyCSFSCov<Pk, Ext> {
+        &self.cov
+    }
+
+    /// Consume self and return the inner covenant descriptor
+    pub fn into_inner(self) -> LegacyCSFSCov<Pk, Ext> {
+        self.cov
+    }
+
+    /// The cached script, see [`LegacyCSFSCov::encode`]
+    pub fn encoded(&self) -> &Script {
+        &self.script
+    }
+
+    /// See [`LegacyCSFSCov::address`]
+    pub fn address(
+        &self,
+        blinder: Option<secp256k1_zkp::PublicKey>,
+        params: &'static elements::AddressParams,
+    ) -> elements::Address {
+        elements::Address::p2wsh(&self.script, blinder, params)
+    }
+
+    /// See [`LegacyCSFSCov::script_pubkey`]
+    pub fn script_pubkey(&self) -> Script {
+        self.script.to_v0_p2wsh()
+    }
+
+    /// See [`LegacyCSFSCov::inner_script`]
+    pub fn inner_script(&self) -> Script {
+        self.script.clone()
+    }
+
+    /// See [`LegacyCSFSCov::explicit_script`]
+    pub fn explicit_script(&self) -> Script {
+        self.script.clone()
+    }
+
+    /// See [`LegacyCSFSCov::ecdsa_sighash_script_code`]
+    pub fn ecdsa_sighash_script_code(&self) -> Script {
+        self.script.clone()
+    }
+
+    /// See [`LegacyCSFSCov::cov_script_code`]
+    pub fn cov_script_code(&self) -> Script {
+        self.cov_script_code.clone()
+    }
+
+    /// See [`LegacyCSFSCov::script_code_for`]
+    pub fn script_code_for(&self, key: &Pk) -> Option<Script> {
+        match self.cov.signs_as_cov_key(key)? {
+            true => Some(self.cov_script_code()),
+            false => Some(self.ecdsa_sighash_script_code()),
+        }
+    }
+}
+
+impl<Pk, Ext> From<LegacyCSFSCov<Pk, Ext>> for CompiledLegacyCSFSCov<Pk, Ext>
+where
+    Pk: MiniscriptKey + ToPublicKey,
+    Ext: ParseableExt,
+{
+    fn from(cov: LegacyCSFSCov<Pk, Ext>) -> Self {
+        Self::new(cov)
+    }
+}
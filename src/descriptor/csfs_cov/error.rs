@@ -16,6 +16,7 @@
 
 use std::{error, fmt};
 
+use super::{CovSighashItem, CovSighashItems};
 use crate::Error;
 /// Covenant related Errors
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -24,12 +25,20 @@ pub enum CovError {
     MissingScriptCode,
     /// Missing value (segwit sighash)
     MissingValue,
-    /// Missing a sighash Item in satisfier,
-    MissingSighashItem(u8),
-    /// Missing Sighash Signature
+    /// Missing a sighash item in satisfier
+    MissingSighashItem(CovSighashItem),
+    /// Missing sighash items in a [`super::CovSatisfaction`]
+    MissingSighashItems(CovSighashItems),
+    /// Missing Sighash Signature for the covenant pk
+    /// at the given index.
     /// This must be a secp signature serialized
     /// in DER format *with* the sighash byte
-    MissingCovSignature,
+    MissingCovSignature(usize),
+    /// The key that signed the covenant is not one of the covenant keys
+    UnknownCovKey,
+    /// The covenant needs signatures from this many covenant keys, but
+    /// only one signature was provided
+    NotEnoughCovSignatures(usize),
     /// Bad(Malformed) Covenant Descriptor
     BadCovDescriptor,
     /// Cannot lift a Covenant Descriptor
@@ -40,6 +49,83 @@ pub enum CovError {
     /// The Covenant Sighash type and the satisfier sighash
     /// type must be the same
     CovenantSighashTypeMismatch,
+    /// Missing transaction, spent utxos or input index (taproot sighash)
+    MissingTxEnv,
+    /// Missing genesis hash (taproot sighash)
+    MissingGenesisHash,
+    /// The input index is not an input of the transaction
+    /// (taproot sighash)
+    InputIndexOutOfBounds {
+        /// The index of the input being spent
+        index: usize,
+        /// The number of inputs in the transaction
+        n_inputs: usize,
+    },
+    /// The number of spent utxos differs from the number of transaction
+    /// inputs (taproot sighash)
+    SpentUtxosMismatch {
+        /// The number of spent utxos
+        n_utxos: usize,
+        /// The number of inputs in the transaction
+        n_inputs: usize,
+    },
+    /// The covenant script(miniscript + covenant wrapper) exceeds
+    /// the consensus opcode limit
+    OpCountLimitExceeded {
+        /// The number of opcodes in the covenant script
+        ops: usize,
+        /// The consensus limit
+        limit: usize,
+    },
+    /// The covenant script(miniscript + covenant wrapper) exceeds
+    /// the consensus script size limit
+    ScriptSizeLimitExceeded {
+        /// The size of the covenant script
+        size: usize,
+        /// The consensus limit
+        limit: usize,
+    },
+    /// The output committed to by a SIGHASH_SINGLE covenant
+    /// does not exist
+    SingleOutputOutOfRange {
+        /// The index of the committed output
+        index: usize,
+        /// The number of outputs in the transaction
+        n_outputs: usize,
+    },
+    /// The sighash type looked up from the satisfier can not be used
+    /// for the covenant sighash. Only SIGHASH_ALL, SIGHASH_NONE and
+    /// SIGHASH_SINGLE without ANYONECANPAY are supported.
+    UnsupportedSighashType(u32),
+    /// A SIGHASH_SINGLE covenant sighash needs the index of the input
+    /// being spent, but the satisfier does not provide it
+    MissingInputIndex,
+    /// The satisfier reports an `after` timelock of the inner miniscript
+    /// as satisfied, but the nLockTime committed to by the covenant does
+    /// not satisfy it
+    LockTimeMismatch {
+        /// The nLockTime committed to by the covenant
+        committed: u32,
+        /// The `after` timelock of the miniscript
+        required: u32,
+    },
+    /// The satisfier reports an `older` timelock of the inner miniscript
+    /// as satisfied, but the nSequence committed to by the covenant does
+    /// not satisfy it
+    SequenceMismatch {
+        /// The nSequence committed to by the covenant
+        committed: u32,
+        /// The `older` timelock of the miniscript
+        required: u32,
+    },
+    /// The fee for the witness weight at the requested feerate does not
+    /// fit in a u64
+    FeeOverflow {
+        /// The weight of the witness
+        weight: usize,
+        /// The feerate, in satoshis per 1000 weight units
+        feerate: u64,
+    },
 }
 
 impl fmt::Display for CovError {
@@ -49,15 +135,84 @@ impl fmt::Display for CovError {
             CovError::MissingValue => write!(f, "Missing value"),
             CovError::BadCovDescriptor => write!(f, "Bad or Malformed covenant descriptor"),
             CovError::CovenantLift => write!(f, "Cannot lift a covenant descriptor"),
-            CovError::MissingSighashItem(i) => {
-                write!(f, "Missing sighash item # : {} in satisfier", i)
+            CovError::MissingSighashItem(item) => {
+                write!(f, "Missing covenant sighash field: {}", item)
             }
-            CovError::MissingCovSignature => write!(f, "Missing signature over the covenant pk"),
+            CovError::MissingSighashItems(items) => {
+                write!(f, "Missing sighash items: {}", items)
+            }
+            CovError::MissingCovSignature(i) => {
+                write!(f, "Missing signature over the covenant pk #{}", i)
+            }
+            CovError::UnknownCovKey => write!(f, "The signing key is not a covenant key"),
+            CovError::NotEnoughCovSignatures(k) => write!(
+                f,
+                "The covenant needs {} signatures, but only one was provided",
+                k
+            ),
             CovError::CovenantSighashTypeMismatch => write!(
                 f,
                 "The sighash type provided in the witness must the same \
                 as the one used in signature"
             ),
+            CovError::MissingTxEnv => write!(f, "Missing transaction environment"),
+            CovError::MissingGenesisHash => write!(f, "Missing genesis hash"),
+            CovError::InputIndexOutOfBounds { index, n_inputs } => write!(
+                f,
+                "Input {} is out of bounds, the transaction has {} inputs",
+                index, n_inputs
+            ),
+            CovError::SpentUtxosMismatch { n_utxos, n_inputs } => write!(
+                f,
+                "{} spent utxos provided for a transaction with {} inputs",
+                n_utxos, n_inputs
+            ),
+            CovError::OpCountLimitExceeded { ops, limit } => write!(
+                f,
+                "Covenant script has {} opcodes, {} over the limit of {}",
+                ops,
+                ops.saturating_sub(limit),
+                limit
+            ),
+            CovError::ScriptSizeLimitExceeded { size, limit } => write!(
+                f,
+                "Covenant script is {} bytes, {} over the limit of {}",
+                size,
+                size.saturating_sub(limit),
+                limit
+            ),
+            CovError::SingleOutputOutOfRange { index, n_outputs } => write!(
+                f,
+                "Covenant commits to output {}, but the transaction has {} outputs",
+                index, n_outputs
+            ),
+            CovError::UnsupportedSighashType(ty) => {
+                write!(f, "Unsupported covenant sighash type {:#x}", ty)
+            }
+            CovError::MissingInputIndex => {
+                f.write_str("SIGHASH_SINGLE covenant sighash without the spent input index")
+            }
+            CovError::LockTimeMismatch {
+                committed,
+                required,
+            } => write!(
+                f,
+                "Covenant commits to nLockTime {}, which does not satisfy after({})",
+                committed, required
+            ),
+            CovError::SequenceMismatch {
+                committed,
+                required,
+            } => write!(
+                f,
+                "Covenant commits to nSequence {}, which does not satisfy older({})",
+                committed, required
+            ),
+            CovError::FeeOverflow { weight, feerate } => write!(
+                f,
+                "Fee for {} weight units at {} sat/kWU overflows",
+                weight, feerate
+            ),
         }
     }
 }
@@ -70,3 +225,54 @@ impl From<CovError> for Error {
         Error::CovError(e)
     }
 }
+
+/// Error of [`super::LegacyCSFSCov::parse_insane_with_diagnostics`]
+#[derive(Debug, PartialEq)]
+pub enum CovWrapperError {
+    /// The script could not be parsed for a reason other than a
+    /// mismatched covenant wrapper
+    Parse(Error),
+    /// The covenant wrapper of the script differs from the expected wrapper
+    BadWrapperAt {
+        /// Index of the first differing token, counted from the start of
+        /// the script
+        position: usize,
+        /// The expected token
+        expected: String,
+        /// The token found in the script
+        got: String,
+    },
+}
+
+impl fmt::Display for CovWrapperError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            CovWrapperError::Parse(ref e) => fmt::Display::fmt(e, f),
+            CovWrapperError::BadWrapperAt {
+                position,
+                ref expected,
+                ref got,
+            } => write!(
+                f,
+                "Bad covenant wrapper at token {}: expected {}, got {}",
+                position, expected, got
+            ),
+        }
+    }
+}
+
+impl error::Error for CovWrapperError {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match self {
+            CovWrapperError::Parse(e) => Some(e),
+            CovWrapperError::BadWrapperAt { .. } => None,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<Error> for CovWrapperError {
+    fn from(e: Error) -> CovWrapperError {
+        CovWrapperError::Parse(e)
+    }
+}
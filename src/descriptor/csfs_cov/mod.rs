@@ -46,21 +46,35 @@ mod cov;
 mod error;
 mod satisfy;
 mod script_internals;
-pub use self::cov::LegacyCSFSCov;
-pub use self::error::CovError;
-pub use self::satisfy::LegacyCovSatisfier;
+mod tap_cov;
+pub use self::cov::{CompiledLegacyCSFSCov, LegacyCSFSCov};
+pub use self::error::{CovError, CovWrapperError};
+pub use self::satisfy::{
+    hash256_arr, hash_issuances, CovSatisfaction, CovSighashItem, CovSighashItems, CovSighashType,
+    CovWitness, Hash256Engine, LazyCovSatisfier, LegacyCovSatisfier, TapCovSatisfier,
+};
 pub use self::script_internals::CovOperations;
+pub(crate) use self::script_internals::{
+    COV_CSFS_SIG_DEPTH, COV_SIGHASH_ITEM_COUNT, COV_SIGHASH_TYPE_LEN, TAP_COV_ITEM_SIZES,
+    TAP_COV_MSG_SUFFIX, TAP_COV_SPEND_TYPE_POS,
+};
+pub use self::tap_cov::TapCSFSCov;
+pub(crate) use self::tap_cov::{TAP_COV_SPEND_TYPE, UNSPENDABLE_INTERNAL_KEY};
 
 #[cfg(test)]
 #[allow(unused_imports)]
 mod tests {
 
+    use std::collections::HashMap;
     use std::str::FromStr;
 
     use bitcoin;
     use elements::encode::serialize;
     use elements::hex::ToHex;
-    use elements::opcodes::all::OP_PUSHNUM_1;
+    use elements::opcodes::all::{
+        OP_CHECKSIG, OP_CHECKSIGFROMSTACK, OP_CHECKSIGFROMSTACKVERIFY, OP_DROP, OP_NIP,
+        OP_PUSHNUM_1, OP_PUSHNUM_10, OP_PUSHNUM_3,
+    };
     use elements::secp256k1_zkp::ZERO_TWEAK;
     use elements::{
         self, confidential, opcodes, script, secp256k1_zkp, AssetId, AssetIssuance,
@@ -70,11 +84,14 @@ mod tests {
 
     use super::cov::*;
     use super::*;
-    use crate::descriptor::DescriptorType;
-    use crate::extensions::{CovExtArgs, CovenantExt, NoExtParam};
+    use crate::descriptor::{DescriptorPublicKey, DescriptorType};
+    use crate::extensions::{CovExtArgs, CovenantExt, NoExtParam, TxEnv};
     use crate::interpreter::SatisfiedConstraint;
     use crate::util::{count_non_push_opcodes, witness_size};
-    use crate::{interpreter, Descriptor, ElementsSig, Error, Interpreter, Satisfier};
+    use crate::{
+        interpreter, Descriptor, ElementsSig, Error, ForEachKey, Interpreter, Legacy, Miniscript,
+        Satisfier, Segwitv0,
+    };
 
     const BTC_ASSET: [u8; 32] = [
         0x23, 0x0f, 0x4f, 0x5d, 0x4b, 0x7c, 0x6f, 0xa8, 0x45, 0x80, 0x6e, 0xe4, 0xf6, 0x77, 0x13,
@@ -96,6 +113,14 @@ mod tests {
         string_rtt("elcovwsh(A,and_v(v:pk(B),pk(C)))");
         string_rtt("elcovwsh(A,thresh(2,l:ver_eq(1),s:pk(C),s:pk(B)))");
         string_rtt("elcovwsh(A,outputs_pref(01020304))");
+        string_rtt("elcovwsh(multi(2,A,B,C),pk(D))");
+
+        // 1-of-1 covenants are the same as single key ones
+        let desc = Descriptor::<String>::from_str("elcovwsh(multi(1,A),pk(B))").unwrap();
+        assert_eq!(desc, Descriptor::from_str("elcovwsh(A,pk(B))").unwrap());
+        Descriptor::<String>::from_str("elcovwsh(multi(3,A,B),pk(D))").unwrap_err();
+        Descriptor::<String>::from_str("elcovwsh(multi(0,A,B),pk(D))").unwrap_err();
+        Descriptor::<String>::from_str("elcovwsh(multi(2,A,B,C,D),pk(E))").unwrap_err();
     }
 
     fn script_rtt(desc_str: &str) {
@@ -134,6 +159,49 @@ mod tests {
             "elcovwsh({},and_v(v:outputs_pref(f2f233),pk({})))",
             pks[0], pks[1]
         ));
+        script_rtt(&format!(
+            "elcovwsh(multi(2,{},{},{}),pk({}))",
+            pks[0], pks[1], pks[2], pks[3]
+        ));
+        script_rtt(&format!(
+            "elcovwsh(multi(1,{},{}),and_v(v:ver_eq(2),pk({})))",
+            pks[0], pks[1], pks[2]
+        ));
+    }
+
+    #[test]
+    fn parse_nop_padded() {
+        type Cov = LegacyCSFSCov<bitcoin::PublicKey, CovenantExt<CovExtArgs>>;
+        let (pks, _sks) = setup_keys(2);
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elcovwsh({},pk({}))",
+            pks[0], pks[1]
+        ))
+        .unwrap();
+        let script = desc.as_cov().unwrap().encode();
+        let mut padded = script::Builder::new();
+        for _ in 0..5 {
+            padded = padded.push_opcode(opcodes::all::OP_NOP);
+        }
+        let padded = Script::from([padded.into_script().into_bytes(), script.to_bytes()].concat());
+
+        // Padding is rejected unless explicitly allowed
+        Cov::parse_insane(&padded).unwrap_err();
+        let cov = Cov::parse_insane_padded(&padded).unwrap();
+        assert_eq!(cov.nop_padding(), 5);
+        assert_eq!(cov.encode(), padded);
+        assert_eq!(cov.to_string(), desc.to_string());
+        // Like the string form, equality ignores the padding
+        assert_eq!(&cov, desc.as_cov().unwrap());
+        assert_ne!(
+            cov.canonical_bytes(),
+            desc.as_cov().unwrap().canonical_bytes()
+        );
+
+        // Unpadded scripts parse the same as with parse_insane
+        let cov = Cov::parse_insane_padded(&script).unwrap();
+        assert_eq!(cov.nop_padding(), 0);
+        assert_eq!(&cov, desc.as_cov().unwrap());
     }
 
     // Some deterministic keys for ease of testing
@@ -161,13 +229,22 @@ mod tests {
     #[test]
     fn test_sanity_check_limits() {
         let (pks, _sks) = setup_keys(1);
+        type Cov = LegacyCSFSCov<bitcoin::PublicKey, CovenantExt<CovExtArgs>>;
         // Count of the opcodes without the
-        let cov_script = script::Builder::new().verify_cov(&pks[0]).into_script();
+        let cov_script = Cov::cov_wrapper_script(&pks[0]);
         assert_eq!(
             count_non_push_opcodes(&cov_script),
-            Ok(cov::COV_SCRIPT_OPCODE_COST)
+            Ok(Cov::WRAPPER_OPCODE_COST)
         );
-        assert_eq!(cov_script.len(), cov::COV_SCRIPT_SIZE);
+        assert_eq!(cov_script.len(), Cov::WRAPPER_SCRIPT_SIZE);
+
+        // The wrapper is the suffix of the encoded covenant when the
+        // miniscript does not end with a free verify
+        let ms = Miniscript::<_, Segwitv0, _>::from_str_insane("older(10)").unwrap();
+        let desc = Cov::new(pks[0], ms.clone()).unwrap();
+        let mut encoded = ms.encode().to_bytes();
+        encoded.extend(cov_script.as_bytes());
+        assert_eq!(desc.encode().to_bytes(), encoded);
 
         let sighash_size = 4
         + 32
@@ -182,6 +259,411 @@ mod tests {
         assert_eq!(sighash_size, 185);
     }
 
+    #[test]
+    fn test_multi_sanity_check_limits() {
+        let (pks, _sks) = setup_keys(cov::COV_MULTI_MAX_KEYS);
+        for n in 2..=cov::COV_MULTI_MAX_KEYS {
+            let cov_script = script::Builder::new()
+                .verify_cov_multi(&pks[..n], 1)
+                .into_script();
+            assert_eq!(
+                count_non_push_opcodes(&cov_script),
+                Ok(cov::COV_MULTI_PREFIX_OPCODE_COST
+                    + n * cov::COV_MULTI_KEY_OPCODE_COST
+                    + cov::COV_MULTI_SUFFIX_OPCODE_COST)
+            );
+            let depth_pushes: usize = (0..n).map(|i| 2 * crate::script_num_size(i + 2)).sum();
+            assert_eq!(
+                cov_script.len(),
+                cov::COV_MULTI_PREFIX_SIZE
+                    + n * cov::COV_MULTI_KEY_SIZE
+                    + depth_pushes
+                    + cov::COV_MULTI_SUFFIX_SIZE
+                    + 1
+            );
+        }
+    }
+
+    #[test]
+    fn cov_csfs_forms() {
+        let (pks, _sks) = setup_keys(4);
+        type Cov = LegacyCSFSCov<bitcoin::PublicKey, CovenantExt<CovExtArgs>>;
+        let ends_with = |script: &elements::Script, op: elements::opcodes::All| {
+            script.as_bytes().last() == Some(&op.into_u8())
+        };
+
+        // Single key covenants end with OP_CHECKSIGFROMSTACK, with and
+        // without the OP_VERIFY folded into the end of the miniscript
+        for ms in &["older(10)", "sha256(H)"] {
+            let ms = ms.replace('H', &"11".repeat(32));
+            let desc_str = format!("elcovwsh({},{})", pks[0], ms);
+            let desc = Descriptor::<bitcoin::PublicKey>::from_str(&desc_str).unwrap();
+            let script = desc.as_cov().unwrap().encode();
+            assert!(ends_with(&script, OP_CHECKSIGFROMSTACK));
+            assert_eq!(&Cov::parse_insane(&script).unwrap(), desc.as_cov().unwrap());
+        }
+
+        // Multi key covenants check every key with OP_CHECKSIGFROMSTACKVERIFY
+        let desc_str = format!(
+            "elcovwsh(multi(2,{},{},{}),pk({}))",
+            pks[0], pks[1], pks[2], pks[3]
+        );
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&desc_str).unwrap();
+        let cov = desc.as_cov().unwrap();
+        let script = cov.encode();
+        let csfsv = OP_CHECKSIGFROMSTACKVERIFY.into_u8();
+        assert_eq!(
+            cov.cov_script_code()
+                .as_bytes()
+                .iter()
+                .filter(|op| **op == csfsv)
+                .count(),
+            3
+        );
+        assert_eq!(&Cov::parse_insane(&script).unwrap(), cov);
+
+        // The single key script code is signed, so swapping in the
+        // VERIFY form changes the covenant and is rejected
+        let desc_str = format!("elcovwsh({},older(10))", pks[0]);
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&desc_str).unwrap();
+        let mut bytes = desc.as_cov().unwrap().encode().into_bytes();
+        *bytes.last_mut().unwrap() = csfsv;
+        bytes.push(OP_PUSHNUM_1.into_u8());
+        Cov::parse_insane(&elements::Script::from(bytes)).unwrap_err();
+    }
+
+    #[test]
+    fn parse_tampered_cov_wrapper() {
+        let (pks, _sks) = setup_keys(3);
+        type Cov = LegacyCSFSCov<bitcoin::PublicKey, CovenantExt<CovExtArgs>>;
+        let bad_cov = Err(Error::CovError(CovError::BadCovDescriptor));
+        let bad_cov_at = |position: usize, expected: &str, got: &str| {
+            Err(CovWrapperError::BadWrapperAt {
+                position,
+                expected: String::from(expected),
+                got: String::from(got),
+            })
+        };
+
+        // Appends the correct wrapper with `tamper` applied to the bytes
+        // after its leading OP_VERIFY, which may be folded into the
+        // last opcode of the miniscript
+        let tampered = |tamper: fn(&mut [u8])| {
+            move |builder: script::Builder, keys: &[bitcoin::PublicKey], k: usize| {
+                let wrap = |b: script::Builder| {
+                    if keys.len() == 1 {
+                        b.verify_cov(&keys[0])
+                    } else {
+                        b.verify_cov_multi(keys, k)
+                    }
+                };
+                let tail_len = wrap(script::Builder::new()).into_script().len() - 1;
+                let mut bytes = wrap(builder).into_script().into_bytes();
+                let start = bytes.len() - tail_len;
+                tamper(&mut bytes[start..]);
+                script::Builder::from(bytes)
+            }
+        };
+
+        let single = Cov::from_str(&format!("elcovwsh({},pk({}))", pks[0], pks[1])).unwrap();
+        assert_eq!(
+            single.encode_with_wrapper(tampered(|_| ())),
+            single.encode()
+        );
+        // Pick the wrong stack item as the signature
+        let script = single.encode_with_wrapper(tampered(|w| w[0] = OP_PUSHNUM_10.into_u8()));
+        assert_eq!(Cov::parse_insane(&script), bad_cov);
+        assert_eq!(
+            Cov::parse_insane_with_diagnostics(&script),
+            bad_cov_at(3, "#11", "#10")
+        );
+        // OP_CHECKSIG instead of OP_CHECKSIGVERIFY in the script code
+        let script = single.encode_with_wrapper(tampered(|w| {
+            let i = w.len() - 2;
+            w[i] = OP_CHECKSIG.into_u8();
+        }));
+        assert_eq!(Cov::parse_insane(&script), bad_cov);
+        // OP_CHECKSIGVERIFY is lexed as OP_CHECKSIG OP_VERIFY
+        assert_eq!(
+            Cov::parse_insane_with_diagnostics(&script),
+            bad_cov_at(93, "Verify", "CheckSig")
+        );
+
+        let multi = Cov::from_str(&format!(
+            "elcovwsh(multi(1,{},{}),pk({}))",
+            pks[0], pks[1], pks[2]
+        ))
+        .unwrap();
+        assert_eq!(multi.encode_with_wrapper(tampered(|_| ())), multi.encode());
+        // One OP_NIP less than the number of keys
+        let script = multi.encode_with_wrapper(tampered(|w| {
+            let i = w.len() - 3;
+            assert_eq!(w[i], OP_NIP.into_u8());
+            w[i] = OP_DROP.into_u8();
+        }));
+        assert_eq!(Cov::parse_insane(&script), bad_cov);
+        // The wrapper ends in OP_NIP OP_NIP OP_1 OP_EQUAL
+        assert_eq!(
+            Cov::parse_insane_with_diagnostics(&script),
+            bad_cov_at(138, "Nip", "Drop")
+        );
+        // A threshold larger than the number of keys
+        let script = multi.encode_with_wrapper(tampered(|w| {
+            let i = w.len() - 2;
+            w[i] = OP_PUSHNUM_3.into_u8();
+        }));
+        assert_eq!(Cov::parse_insane(&script), bad_cov);
+        assert_eq!(
+            Cov::parse_insane_with_diagnostics(&script),
+            bad_cov_at(139, "a threshold between 1 and 2", "#3")
+        );
+        // The two pushes of the last key differ
+        let script = multi.encode_with_wrapper(tampered(|w| {
+            let i = w.len() - 14;
+            w[i] ^= 1;
+        }));
+        assert_eq!(Cov::parse_insane(&script), bad_cov);
+        match Cov::parse_insane_with_diagnostics(&script) {
+            Err(CovWrapperError::BadWrapperAt {
+                position: 118,
+                expected,
+                got,
+            }) => {
+                assert_eq!(got, pks[1].to_string());
+                assert_ne!(expected, got);
+            }
+            res => panic!("Unexpected result {:?}", res),
+        }
+        // Not a covenant at all
+        let script = multi.to_ms().encode();
+        assert_eq!(Cov::parse_insane(&script), bad_cov);
+        assert_eq!(
+            Cov::parse_insane_with_diagnostics(&script),
+            bad_cov_at(1, "CheckSigFromStack or Equal", "CheckSig")
+        );
+        // Scripts that don't lex are not diagnosed
+        let script = elements::Script::from(vec![opcodes::all::OP_PUSHDATA1.into_u8()]);
+        match Cov::parse_insane_with_diagnostics(&script) {
+            Err(CovWrapperError::Parse(Error::Script(_))) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+        // Valid covenants parse as usual
+        assert_eq!(
+            Cov::parse_insane_with_diagnostics(&multi.encode()),
+            Ok(multi)
+        );
+    }
+
+    #[test]
+    fn cov_layout_constants() {
+        let (pks, _sks) = setup_keys(3);
+        type Cov = LegacyCSFSCov<bitcoin::PublicKey, CovenantExt<CovExtArgs>>;
+        assert_eq!(Cov::SIGHASH_ITEM_COUNT, CovSighashItem::ALL.len());
+        assert_eq!(Cov::CSFS_SIG_DEPTH, Cov::SIGHASH_ITEM_COUNT);
+
+        // The wrapper picks right after its leading OP_VERIFY
+        let wrapper_head = |cov: &Cov| {
+            let keys = cov.pks();
+            let b = script::Builder::new();
+            let wrapper = if keys.len() == 1 {
+                b.verify_cov(&keys[0])
+            } else {
+                b.verify_cov_multi(keys, cov.threshold())
+            };
+            wrapper.into_script().into_bytes()[1..].to_vec()
+        };
+        let single = Cov::from_str(&format!("elcovwsh({},pk({}))", pks[0], pks[1])).unwrap();
+        let expected = script::Builder::new()
+            .push_int(Cov::CSFS_SIG_DEPTH as i64)
+            .push_opcode(opcodes::all::OP_PICK)
+            .push_int(Cov::SIGHASH_ITEM_COUNT as i64)
+            .push_opcode(opcodes::all::OP_PICK)
+            .push_int(1)
+            .push_opcode(opcodes::all::OP_LEFT)
+            .into_script();
+        assert!(wrapper_head(&single).starts_with(expected.as_bytes()));
+        assert_eq!(Cov::parse_insane(&single.encode()).unwrap(), single);
+
+        // Multi key covenants only pick the sighash type, item 10
+        let multi = Cov::from_str(&format!(
+            "elcovwsh(multi(1,{},{}),pk({}))",
+            pks[0], pks[1], pks[2]
+        ))
+        .unwrap();
+        let expected = script::Builder::new()
+            .push_int(Cov::SIGHASH_ITEM_COUNT as i64 - 1)
+            .push_opcode(opcodes::all::OP_PICK)
+            .push_int(1)
+            .push_opcode(opcodes::all::OP_LEFT)
+            .into_script();
+        let head = wrapper_head(&multi);
+        assert!(head.starts_with(expected.as_bytes()));
+        assert_eq!(Cov::parse_insane(&multi.encode()).unwrap(), multi);
+
+        // Picking item 9 instead is rejected by the parser
+        let mut bytes = multi.encode().into_bytes();
+        let start = bytes.len() - head.len();
+        bytes[start] = opcodes::all::OP_PUSHNUM_9.into_u8();
+        assert_eq!(
+            Cov::parse_insane(&elements::Script::from(bytes)),
+            Err(Error::CovError(CovError::BadCovDescriptor))
+        );
+    }
+
+    #[test]
+    fn compiled_cov_getters() {
+        let (pks, _sks) = setup_keys(3);
+        let desc_str = format!("elcovwsh({},and_v(v:pk({}),older(10)))", pks[0], pks[1]);
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&desc_str).unwrap();
+        let cov = desc.as_cov().unwrap();
+        let compiled = CompiledLegacyCSFSCov::new(cov.clone());
+        let params = &elements::AddressParams::ELEMENTS;
+
+        assert_eq!(compiled.as_inner(), cov);
+        assert_eq!(compiled.encoded(), &cov.encode());
+        assert_eq!(compiled.inner_script(), cov.inner_script());
+        assert_eq!(compiled.explicit_script(), cov.explicit_script());
+        assert_eq!(cov.explicit_script(), desc.explicit_script().unwrap());
+        assert_eq!(cov.explicit_script().to_v0_p2wsh(), desc.script_pubkey());
+        assert_eq!(compiled.script_pubkey(), cov.script_pubkey());
+        assert_eq!(compiled.address(None, params), cov.address(None, params));
+        assert_eq!(
+            compiled.ecdsa_sighash_script_code(),
+            cov.ecdsa_sighash_script_code()
+        );
+        assert_eq!(compiled.cov_script_code(), cov.cov_script_code());
+        for pk in &pks {
+            assert_eq!(compiled.script_code_for(pk), cov.script_code_for(pk));
+        }
+        assert_eq!(compiled.into_inner(), *cov);
+    }
+
+    #[test]
+    fn cov_keys_order() {
+        let (pks, _sks) = setup_keys(4);
+        type Cov = LegacyCSFSCov<bitcoin::PublicKey, CovenantExt<CovExtArgs>>;
+        // The covenant key is also used in the miniscript
+        let cov = Cov::from_str(&format!(
+            "elcovwsh({},or_d(pk({}),and_v(v:pk({}),pk({}))))",
+            pks[1], pks[2], pks[3], pks[1]
+        ))
+        .unwrap();
+        assert_eq!(cov.keys(), vec![&pks[1], &pks[2], &pks[3], &pks[1]]);
+
+        let cov = Cov::from_str(&format!(
+            "elcovwsh(multi(2,{},{},{}),multi(1,{},{}))",
+            pks[2], pks[0], pks[1], pks[3], pks[0]
+        ))
+        .unwrap();
+        assert_eq!(
+            cov.keys(),
+            vec![&pks[2], &pks[0], &pks[1], &pks[3], &pks[0]]
+        );
+        // Every key is visited, not only up to the first match
+        assert!(cov.for_each_key(|pk| pks.contains(pk)));
+        assert!(!cov.for_each_key(|pk| *pk != pks[3]));
+    }
+
+    #[test]
+    fn cov_limits_overage() {
+        type Ms = crate::Miniscript<String, crate::Segwitv0, CovenantExt<CovExtArgs>>;
+        // Each `a:sha256(H)` costs seven opcodes
+        let ms_str = format!("thresh(1,pk(A){})", ",a:sha256(H)".repeat(20));
+        let ms = Ms::from_str_insane(&ms_str).unwrap();
+        // `thresh` ends in `OP_EQUAL`, which absorbs the wrapper's `OP_VERIFY`
+        assert!(ms.ext.has_free_verify);
+        let ops = ms.ext.ops.op_count().unwrap()
+            + LegacyCSFSCov::<String, CovenantExt<CovExtArgs>>::WRAPPER_OPCODE_COST
+            - 1;
+        match LegacyCSFSCov::new(String::from("A"), ms) {
+            Err(Error::CovError(CovError::OpCountLimitExceeded { ops: got, limit })) => {
+                assert_eq!(got, ops);
+                assert_eq!(limit, 201);
+            }
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn cov_limit_error_display() {
+        let err = CovError::OpCountLimitExceeded {
+            ops: 205,
+            limit: 201,
+        };
+        assert_eq!(
+            err.to_string(),
+            "Covenant script has 205 opcodes, 4 over the limit of 201"
+        );
+        // The fields are public, so a value under the limit must not underflow
+        let err = CovError::ScriptSizeLimitExceeded {
+            size: 10,
+            limit: 10_000,
+        };
+        assert_eq!(
+            err.to_string(),
+            "Covenant script is 10 bytes, 0 over the limit of 10000"
+        );
+    }
+
+    #[test]
+    fn cov_try_map_inner() {
+        type Cov = LegacyCSFSCov<String, CovenantExt<CovExtArgs>>;
+        type Ms = crate::Miniscript<String, crate::Segwitv0, CovenantExt<CovExtArgs>>;
+        let cov = Cov::from_str("elcovwsh(A,pk(B))").unwrap();
+
+        let mapped = cov
+            .clone()
+            .try_map_inner(|_| Ms::from_str_insane("and_v(v:pk(B),older(10))"))
+            .unwrap();
+        assert_eq!(
+            mapped,
+            Cov::from_str("elcovwsh(A,and_v(v:pk(B),older(10)))").unwrap()
+        );
+
+        // Limits are checked again on the new miniscript
+        let big = format!("thresh(1,pk(B){})", ",a:sha256(H)".repeat(20));
+        match cov.clone().try_map_inner(|_| Ms::from_str_insane(&big)) {
+            Err(Error::CovError(CovError::OpCountLimitExceeded { limit, .. })) => {
+                assert_eq!(limit, 201);
+            }
+            res => panic!("Unexpected result {:?}", res),
+        }
+        // Errors from the closure are forwarded
+        match cov.try_map_inner(|_| Err(Error::ImpossibleSatisfaction)) {
+            Err(Error::ImpossibleSatisfaction) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn cov_sighash_message_size() {
+        type Ms = crate::Miniscript<String, crate::Segwitv0, CovenantExt<CovExtArgs>>;
+        // The miniscript comes before the codesep, so a script well over
+        // 520 bytes is fine as long as the sighash message fits
+        let keys: Vec<String> = (0..20).map(|i| format!("K{}", i)).collect();
+        let ms = Ms::from_str_insane(&format!("multi(1,{})", keys.join(","))).unwrap();
+        assert!(ms.script_size() > 520);
+        let cov_keys = vec![String::from("A"), String::from("B"), String::from("C")];
+        assert!(LegacyCSFSCov::new_multi(cov_keys, 3, ms).is_ok());
+
+        // The sighash message contains the script code after the codesep.
+        // The largest supported covenant is just under the limit, one
+        // more key would push the sighash message over it
+        fn sighash_message_size(n_keys: usize, k: usize) -> usize {
+            // Size of the other sighash items, with a confidential value
+            const FIXED_SIZE: usize = 213;
+            let (keys, _) = setup_keys(n_keys);
+            let script_code = script::Builder::new()
+                .post_codesep_multi_script(&keys, k)
+                .into_script();
+            FIXED_SIZE + serialize(&script_code).len()
+        }
+        for k in 1..=cov::COV_MULTI_MAX_KEYS {
+            assert!(sighash_message_size(cov::COV_MULTI_MAX_KEYS, k) <= 520);
+        }
+        assert!(sighash_message_size(cov::COV_MULTI_MAX_KEYS + 1, 1) > 520);
+    }
+
     fn _satisfy_and_interpret(
         desc: Descriptor<bitcoin::PublicKey, CovenantExt<CovExtArgs>>,
         cov_sk: secp256k1_zkp::SecretKey,
@@ -189,105 +671,1509 @@ mod tests {
         assert_eq!(desc.desc_type(), DescriptorType::Cov);
         let desc = desc.as_cov().unwrap();
         // Now create a transaction spending this.
-        let mut spend_tx = Transaction {
-            version: 2,
-            lock_time: LockTime::ZERO,
-            input: vec![txin_from_txid_vout(
-                "141f79c7c254ee3a9a9bc76b4f60564385b784bdfc1882b25154617801fe2237",
-                1,
-            )],
-            output: vec![],
-        };
-
-        spend_tx.output.push(TxOut::default());
+        let mut spend_tx = cov_spend_tx();
         spend_tx.output[0].script_pubkey = script::Builder::new()
             .push_opcode(opcodes::all::OP_PUSHNUM_1)
             .into_script()
             .to_v0_p2wsh();
-        spend_tx.output[0].value = confidential::Value::Explicit(99_000);
-        spend_tx.output[0].asset =
+
+        // same second output
+        let second_out = spend_tx.output[0].clone();
+        spend_tx.output.push(second_out);
+
+        // Add a fee output
+        spend_tx.output.push(TxOut::default());
+        spend_tx.output[2].asset =
             confidential::Asset::Explicit(AssetId::from_slice(&BTC_ASSET).unwrap());
+        spend_tx.output[2].value = confidential::Value::Explicit(2_000);
+
+        // Try to satisfy the covenant part
+        let script_code = desc.cov_script_code();
+        let cov_sat = LegacyCovSatisfier::new_segwitv0(
+            &spend_tx,
+            0,
+            confidential::Value::Explicit(200_000),
+            &script_code,
+            EcdsaSighashType::All,
+        );
+
+        // Create a signature to sign the input
+
+        let sighash_u256 = cov_sat.segwit_sighash().unwrap();
+        assert_eq!(
+            desc.sighash_message(&cov_sat).unwrap()[..],
+            sighash_u256[..]
+        );
+        assert_eq!(
+            desc.sighash_message(&()),
+            Err(CovError::MissingSighashItem(CovSighashItem::SighashU32))
+        );
+        let el_sig = sign_sighash(&cov_sat, &[cov_sk])[0];
+
+        // For satisfying the Pk part of the covenant
+        struct SimpleSat {
+            sig: ElementsSig,
+            pk: bitcoin::PublicKey,
+        }
+
+        impl Satisfier<bitcoin::PublicKey> for SimpleSat {
+            fn lookup_ecdsa_sig(&self, pk: &bitcoin::PublicKey) -> Option<ElementsSig> {
+                if *pk == self.pk {
+                    Some(self.sig)
+                } else {
+                    None
+                }
+            }
+        }
+
+        let pk_sat = SimpleSat {
+            sig: el_sig,
+            pk: *desc.pk(),
+        };
+
+        // A pair of satisfiers is also a satisfier
+        let (wit, ss) = desc.get_satisfaction((cov_sat, pk_sat))?;
+        let interpreter = Interpreter::from_txdata(
+            &desc.script_pubkey(),
+            &ss,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+
+        assert!(wit[0].len() <= 73);
+        assert!(wit[1].len() == 4); // version
+
+        // Check that everything is executed correctly with correct sigs inside
+        // miniscript
+        let constraints = interpreter
+            .iter_assume_sigs()
+            .collect::<Result<Vec<_>, _>>()
+            .expect("If satisfy succeeds, interpret must succeed");
+
+        // The last constraint satisfied must be the covenant pk
+        assert_eq!(
+            constraints.last().unwrap(),
+            &SatisfiedConstraint::CovenantSig {
+                key_sig: interpreter::KeySigPair::Ecdsa(*desc.pk(), el_sig),
+                message: secp256k1_zkp::Message::from_digest_slice(&sighash_u256[..]).unwrap(),
+            }
+        );
+        // Signatures in the miniscript are reported as public keys
+        let n_ms_sigs = constraints
+            .iter()
+            .filter(|c| matches!(c, SatisfiedConstraint::PublicKey { .. }))
+            .count();
+        assert_eq!(n_ms_sigs, desc.to_ms().iter_pk().count());
+        Ok(())
+    }
+
+    #[test]
+    fn cov_canonical_bytes() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        fn hash<T: Hash>(t: &T) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            t.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let (pks, _sks) = setup_keys(2);
+        let desc = LegacyCSFSCov::<bitcoin::PublicKey, CovenantExt<CovExtArgs>>::from_str(
+            &format!("elcovwsh({},pk({}))", pks[0], pks[1]),
+        )
+        .unwrap();
+        // `pk` is an alias for `c:pk_k`
+        let alias = LegacyCSFSCov::<bitcoin::PublicKey, CovenantExt<CovExtArgs>>::from_str(
+            &format!("elcovwsh({},c:pk_k({}))", pks[0], pks[1]),
+        )
+        .unwrap();
+        assert_eq!(desc, alias);
+        assert_eq!(hash(&desc), hash(&alias));
+        assert_eq!(desc.canonical_bytes(), alias.canonical_bytes());
+
+        // Encoding is deterministic
+        assert_eq!(desc.encode(), desc.clone().encode());
+        assert_eq!(desc.canonical_bytes(), desc.encode().to_bytes());
+        let other = LegacyCSFSCov::<bitcoin::PublicKey, CovenantExt<CovExtArgs>>::from_str(
+            &format!("elcovwsh({},pk({}))", pks[1], pks[0]),
+        )
+        .unwrap();
+        assert_ne!(desc.canonical_bytes(), other.canonical_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn cov_serde() {
+        use serde_test::{assert_tokens, Token};
+
+        let desc = LegacyCSFSCov::<String, CovenantExt<CovExtArgs>>::from_str(
+            "elcovwsh(multi(2,A,B,C),and_v(v:pk(D),after(10)))",
+        )
+        .unwrap();
+        assert_tokens(
+            &desc,
+            &[Token::String(
+                "elcovwsh(multi(2,A,B,C),and_v(v:pk(D),after(10)))#evg8cnds",
+            )],
+        );
+    }
+
+    #[test]
+    fn cov_addresses() {
+        let (pks, _sks) = setup_keys(3);
+        let cov = LegacyCSFSCov::<bitcoin::PublicKey, CovenantExt<CovExtArgs>>::from_str(&format!(
+            "elcovwsh({},pk({}))",
+            pks[0], pks[1]
+        ))
+        .unwrap();
+        let params = &elements::AddressParams::ELEMENTS;
+
+        let confidential = cov.confidential_address(pks[2].inner, params);
+        let explicit = cov.explicit_address(params);
+        assert!(confidential.is_blinded());
+        assert!(!explicit.is_blinded());
+        assert_eq!(confidential.script_pubkey(), cov.script_pubkey());
+        assert_eq!(explicit.script_pubkey(), cov.script_pubkey());
+        assert_eq!(confidential, cov.address(Some(pks[2].inner), params));
+        assert_eq!(explicit, cov.address(None, params));
+    }
+
+    #[test]
+    fn cov_string_roundtrip() {
+        type Cov = LegacyCSFSCov<String, CovenantExt<CovExtArgs>>;
+        for s in &[
+            "elcovwsh(A,pk(B))",
+            "elcovwsh(multi(2,A,B,C),and_v(v:pk(D),after(10)))",
+        ] {
+            let desc = Cov::from_str(s).unwrap();
+            assert_eq!(Cov::from_str(&desc.to_string()).unwrap(), desc);
+            // Debug is the canonical string without the checksum
+            assert_eq!(format!("{:?}", desc), *s);
+            assert_eq!(format!("{:#}", desc), *s);
+            assert_eq!(Cov::from_str(&format!("{:?}", desc)).unwrap(), desc);
+        }
+    }
+
+    #[test]
+    fn parse_cov_with_context() {
+        type Cov = LegacyCSFSCov<bitcoin::PublicKey, CovenantExt<CovExtArgs>>;
+        let (pks, _sks) = setup_keys(2);
+        let desc = Cov::from_str(&format!("elcovwsh({},1)", pks[0])).unwrap();
+        // The miniscript is encoded in front of the covenant wrapper
+        let one_len = desc.to_ms().script_size();
+        let cov_script = desc.encode();
+        let wrapper = &cov_script.as_bytes()[one_len..];
+
+        // Uncompressed keys are allowed in Legacy, but not in Segwitv0
+        let uncompressed = bitcoin::PublicKey {
+            compressed: false,
+            inner: pks[1].inner,
+        };
+        let ms = Miniscript::<bitcoin::PublicKey, Legacy, CovenantExt<CovExtArgs>>::from_str(
+            &format!("and_v(v:pk({}),older(10))", uncompressed),
+        )
+        .unwrap();
+        let mut script = ms.encode().into_bytes();
+        script.extend(wrapper);
+        let script = Script::from(script);
+
+        Cov::parse_with_context::<Segwitv0>(&script).unwrap_err();
+        Cov::parse_insane(&script).unwrap_err();
+        let (k, cov_pks, parsed_ms) = Cov::parse_with_context::<Legacy>(&script).unwrap();
+        assert_eq!(k, 1);
+        assert_eq!(cov_pks, vec![pks[0]]);
+        assert_eq!(parsed_ms, ms);
+    }
+
+    #[test]
+    fn derive_ranged_cov() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpub = "tpubDBrgjcxBxnXyL575sHdkpKohWu5qHKoQ7TJXKNrYznh5fVEGBv89hA8ENW7A8MFVpFUSvgLqc4Nj1WZcpePX6rrxviVtPowvMuGF5rdT2Vi";
+        let desc_str = format!("elcovwsh({}/0/*,pk({}/1/*))", xpub, xpub);
+        let desc =
+            LegacyCSFSCov::<DescriptorPublicKey, CovenantExt<CovExtArgs>>::from_str(&desc_str)
+                .unwrap();
+        assert!(desc.has_wildcard());
+
+        let derived_0 = desc.derived_descriptor(&secp, 0).unwrap();
+        let derived_1 = desc.derived_descriptor(&secp, 1).unwrap();
+        let params = &elements::AddressParams::ELEMENTS;
+        assert_ne!(
+            derived_0.address(None, params),
+            derived_1.address(None, params)
+        );
+
+        // Both the covenant key and the inner key derive at the same index
+        for (i, derived) in [derived_0, derived_1].iter().enumerate() {
+            let key = |path: &str| {
+                DescriptorPublicKey::from_str(&format!("{}/{}/{}", xpub, path, i))
+                    .unwrap()
+                    .at_derivation_index(0)
+                    .unwrap()
+                    .derive_public_key(&secp)
+                    .unwrap()
+            };
+            assert_eq!(*derived.pk(), key("0"));
+            assert_eq!(
+                derived.to_ms().iter_pk().collect::<Vec<_>>(),
+                vec![key("1")]
+            );
+
+            // Same result as deriving the generic descriptor
+            let full = Descriptor::<DescriptorPublicKey>::from_str(&desc_str)
+                .unwrap()
+                .derived_descriptor(&secp, i as u32)
+                .unwrap();
+            assert_eq!(full.as_cov().unwrap(), derived);
+        }
+    }
+
+    #[test]
+    fn cov_key_origins_roundtrip() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpub = "tpubDBrgjcxBxnXyL575sHdkpKohWu5qHKoQ7TJXKNrYznh5fVEGBv89hA8ENW7A8MFVpFUSvgLqc4Nj1WZcpePX6rrxviVtPowvMuGF5rdT2Vi";
+        let single = format!(
+            "elcovwsh([d34db33f/0'/0']{}/0/*,pk([d34db33f/0'/1']{}/1/*))",
+            xpub, xpub
+        );
+        let multi = format!(
+            "elcovwsh(multi(1,[d34db33f/0'/0']{}/0/*,[cafebabe/1]{}/2/*),pk([d34db33f/0'/1']{}/1/*))",
+            xpub, xpub, xpub
+        );
+        for desc_str in [single, multi].iter() {
+            let desc =
+                LegacyCSFSCov::<DescriptorPublicKey, CovenantExt<CovExtArgs>>::from_str(desc_str)
+                    .unwrap();
+            assert_eq!(format!("{:#}", desc), *desc_str);
+            let reparsed = LegacyCSFSCov::<DescriptorPublicKey, CovenantExt<CovExtArgs>>::from_str(
+                &desc.to_string(),
+            )
+            .unwrap();
+            assert_eq!(reparsed, desc);
+
+            // Origins are kept through the generic descriptor and derivation
+            let full = Descriptor::<DescriptorPublicKey>::from_str(desc_str).unwrap();
+            assert_eq!(format!("{:#}", full), *desc_str);
+            let derived = desc.at_derivation_index(3).unwrap();
+            assert!(derived.to_string().contains("[d34db33f/0'/0']"));
+            assert!(derived.to_string().contains("[d34db33f/0'/1']"));
+            assert!(desc.derived_descriptor(&secp, 3).is_ok());
+        }
+    }
+
+    #[test]
+    fn find_derivation_index_for_cov_spk() {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let xpub = "tpubDBrgjcxBxnXyL575sHdkpKohWu5qHKoQ7TJXKNrYznh5fVEGBv89hA8ENW7A8MFVpFUSvgLqc4Nj1WZcpePX6rrxviVtPowvMuGF5rdT2Vi";
+        let desc = LegacyCSFSCov::<DescriptorPublicKey, CovenantExt<CovExtArgs>>::from_str(
+            &format!("elcovwsh({}/0/*,pk({}/1/*))", xpub, xpub),
+        )
+        .unwrap();
+
+        let derived = desc.derived_descriptor(&secp, 5).unwrap();
+        let spk = derived.script_pubkey();
+        assert_eq!(
+            desc.find_derivation_index_for_spk(&secp, &spk, 0..10)
+                .unwrap(),
+            Some((5, derived))
+        );
+        // Indexes outside of the range are not searched
+        assert_eq!(
+            desc.find_derivation_index_for_spk(&secp, &spk, 0..5)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn script_code_for_key() {
+        let (pks, _sks) = setup_keys(5);
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elcovwsh({},pk({}))",
+            pks[0], pks[1]
+        ))
+        .unwrap();
+        let desc = desc.as_cov().unwrap();
+        assert_eq!(desc.script_code_for(&pks[0]), Some(desc.cov_script_code()));
+        assert_eq!(
+            desc.script_code_for(&pks[1]),
+            Some(desc.ecdsa_sighash_script_code())
+        );
+        assert_eq!(desc.script_code_for(&pks[2]), None);
+
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elcovwsh(multi(2,{},{},{}),pk({}))",
+            pks[0], pks[1], pks[2], pks[3]
+        ))
+        .unwrap();
+        let desc = desc.as_cov().unwrap();
+        for pk in &pks[..3] {
+            assert_eq!(desc.script_code_for(pk), Some(desc.cov_script_code()));
+        }
+        assert_eq!(
+            desc.script_code_for(&pks[3]),
+            Some(desc.ecdsa_sighash_script_code())
+        );
+        assert_eq!(desc.script_code_for(&pks[4]), None);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn multi_cov_satisfy() {
+        let (pks, sks) = setup_keys(3);
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elcovwsh(multi(2,{},{},{}),1)",
+            pks[0], pks[1], pks[2]
+        ))
+        .unwrap();
+        let desc = desc.as_cov().unwrap();
+        assert_eq!(desc.pks(), &pks[..]);
+        assert_eq!(desc.threshold(), 2);
+
+        let spend_tx = cov_spend_tx();
+        let script_code = desc.cov_script_code();
+        let cov_sat = cov_satisfier(&spend_tx, &script_code);
+        let sigs = sign_sighash(&cov_sat, &sks);
+
+        // Only the key at index 2 signed
+        let mut sig_map = HashMap::new();
+        sig_map.insert(pks[2], sigs[2]);
+        match desc.get_satisfaction((&cov_sat, &sig_map)) {
+            Err(Error::CovError(CovError::MissingCovSignature(0))) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+
+        // Keys at index 0 and 2 signed, the key at index 1 gets an empty slot
+        sig_map.insert(pks[0], sigs[0]);
+        let (wit, _) = desc.get_satisfaction((&cov_sat, &sig_map)).unwrap();
+        assert_eq!(wit[0], sigs[2].0.serialize_der().to_vec());
+        assert!(wit[1].is_empty());
+        assert_eq!(wit[2], sigs[0].0.serialize_der().to_vec());
+        assert_eq!(wit[3], serialize(&EcdsaSighashType::All.as_u32()));
+        assert_eq!(wit.last().unwrap(), &desc.encode().into_bytes());
+
+        // Only the first two signatures are used
+        sig_map.insert(pks[1], sigs[1]);
+        let (wit, ss) = desc.get_satisfaction((&cov_sat, &sig_map)).unwrap();
+        assert!(4 + serialize(&wit).len() <= desc.max_satisfaction_weight().unwrap());
+        // An empty witness serializes to a single zero byte
+        assert!(serialize(&wit).len() - 1 <= desc.max_weight_to_satisfy().unwrap());
+        assert!(wit[0].is_empty());
+        assert_eq!(wit[1], sigs[1].0.serialize_der().to_vec());
+        assert_eq!(wit[2], sigs[0].0.serialize_der().to_vec());
+
+        // The interpreter checks the covenant signature of every signer
+        let interpreter = Interpreter::from_txdata(
+            &desc.script_pubkey(),
+            &ss,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert_eq!(
+            interpreter.inferred_descriptor_string(),
+            format!("elcovwsh(multi(2,{},{},{}),1)", pks[0], pks[1], pks[2])
+        );
+        let msg = secp256k1_zkp::Message::from_digest_slice(&cov_sat.segwit_sighash().unwrap()[..])
+            .unwrap();
+        let constraints = interpreter
+            .iter_assume_sigs()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            constraints,
+            vec![
+                SatisfiedConstraint::CovenantSig {
+                    key_sig: interpreter::KeySigPair::Ecdsa(pks[0], sigs[0]),
+                    message: msg,
+                },
+                SatisfiedConstraint::CovenantSig {
+                    key_sig: interpreter::KeySigPair::Ecdsa(pks[1], sigs[1]),
+                    message: msg,
+                },
+            ]
+        );
+
+        // Fewer than 2 signatures do not satisfy the covenant
+        let mut wit = wit;
+        wit[1] = vec![];
+        let interpreter = Interpreter::from_txdata(
+            &desc.script_pubkey(),
+            &ss,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        match interpreter
+            .iter_assume_sigs()
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Err(interpreter::Error::IncorrectCovenantWitness) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn cov_after_committed_locktime() {
+        let (pks, sks) = setup_keys(1);
+        let desc =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elcovwsh({},after(100))", pks[0]))
+                .unwrap();
+        let desc = desc.as_cov().unwrap();
+        let script_code = desc.cov_script_code();
+
+        let satisfy = |tx_locktime: u32, after_locktime: u32| {
+            let mut spend_tx = cov_spend_tx();
+            spend_tx.lock_time = LockTime::from_height(tx_locktime).unwrap();
+            spend_tx.input[0].sequence = Sequence::ENABLE_LOCKTIME_NO_RBF;
+            let cov_sat = cov_satisfier(&spend_tx, &script_code);
+            let mut sig_map = HashMap::new();
+            sig_map.insert(pks[0], sign_sighash(&cov_sat, &sks[..1])[0]);
+            let after = LockTime::from_height(after_locktime).unwrap();
+            desc.satisfy((&cov_sat, &sig_map, after), false)
+        };
+
+        // The locktime used for after(100) is the committed nLockTime
+        satisfy(100, 100).unwrap();
+        satisfy(150, 100).unwrap();
+        // The satisfier claims a locktime the covenant does not commit to
+        match satisfy(50, 100) {
+            Err(Error::CovError(CovError::LockTimeMismatch {
+                committed: 50,
+                required: 100,
+            })) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+        // No satisfier claims after(100) at all
+        match satisfy(100, 50) {
+            Err(Error::CouldNotSatisfy) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn cov_older_committed_sequence() {
+        let (pks, sks) = setup_keys(1);
+        let desc =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elcovwsh({},older(144))", pks[0]))
+                .unwrap();
+        let desc = desc.as_cov().unwrap();
+        let script_code = desc.cov_script_code();
+
+        let satisfy = |tx_sequence: u32, older_sequence: u32| {
+            let mut spend_tx = cov_spend_tx();
+            spend_tx.input[0].sequence = Sequence::from_consensus(tx_sequence);
+            let cov_sat = cov_satisfier(&spend_tx, &script_code);
+            let mut sig_map = HashMap::new();
+            sig_map.insert(pks[0], sign_sighash(&cov_sat, &sks[..1])[0]);
+            let older = Sequence::from_consensus(older_sequence);
+            desc.satisfy((&cov_sat, &sig_map, older), false)
+        };
+
+        // The sequence used for older(144) is the committed nSequence
+        satisfy(144, 144).unwrap();
+        satisfy(200, 144).unwrap();
+        // The satisfier claims a sequence the covenant does not commit to
+        match satisfy(100, 144) {
+            Err(Error::CovError(CovError::SequenceMismatch {
+                committed: 100,
+                required: 144,
+            })) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+        // A final nSequence disables relative timelocks
+        match satisfy(0xffff_ffff, 144) {
+            Err(Error::CovError(CovError::SequenceMismatch {
+                committed: 0xffff_ffff,
+                required: 144,
+            })) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+        // No satisfier claims older(144) at all
+        match satisfy(144, 100) {
+            Err(Error::CouldNotSatisfy) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn cov_get_satisfaction_with_fee() {
+        let (pks, sks) = setup_keys(4);
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elcovwsh({},or_i(pk({}),and_v(v:pk({}),pk({}))))",
+            pks[0], pks[1], pks[2], pks[3]
+        ))
+        .unwrap();
+        let desc = desc.as_cov().unwrap();
+        let script_code = desc.cov_script_code();
+
+        let spend_tx = cov_spend_tx();
+        let cov_sat = cov_satisfier(&spend_tx, &script_code);
+        let sighash_u256 = cov_sat.segwit_sighash().unwrap();
+        let msg = secp256k1_zkp::Message::from_digest_slice(&sighash_u256[..]).unwrap();
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let sig_map = |signers: &[usize]| {
+            let mut sig_map = HashMap::new();
+            for &i in signers {
+                sig_map.insert(
+                    pks[i],
+                    (secp.sign_ecdsa(&msg, &sks[i]), EcdsaSighashType::All),
+                );
+            }
+            sig_map
+        };
+
+        // Only the longer branch can be satisfied
+        let (long_wit, _, long_fee) = desc
+            .get_satisfaction_with_fee((&cov_sat, &sig_map(&[0, 2, 3])), 1000)
+            .unwrap();
+        assert_eq!(long_wit.len(), 11 + 1 + 3 + 1);
+
+        // With both branches available, the single key branch is cheaper
+        // as the covenant overhead is the same for both
+        let sigs = sig_map(&[0, 1, 2, 3]);
+        let (wit, script_sig, fee) = desc
+            .get_satisfaction_with_fee((&cov_sat, &sigs), 1000)
+            .unwrap();
+        assert_eq!(
+            (wit.clone(), script_sig),
+            desc.get_satisfaction((&cov_sat, &sigs)).unwrap()
+        );
+        assert_eq!(wit.len(), 11 + 1 + 2 + 1);
+        assert!(fee < long_fee);
+        // One satoshi per weight unit of the serialized witness
+        let weight = wit.iter().map(|item| item.len() + 1).sum::<usize>() + 1;
+        assert_eq!(fee, weight as u64);
+        assert!(weight <= desc.max_weight_to_satisfy().unwrap() + 1);
+
+        // Fees are rounded up
+        let (_, _, fee) = desc
+            .get_satisfaction_with_fee((&cov_sat, &sigs), 1)
+            .unwrap();
+        assert_eq!(fee, 1);
+        let (_, _, fee) = desc
+            .get_satisfaction_with_fee((&cov_sat, &sigs), 0)
+            .unwrap();
+        assert_eq!(fee, 0);
+        match desc.get_satisfaction_with_fee((&cov_sat, &sigs), u64::MAX) {
+            Err(Error::CovError(CovError::FeeOverflow { weight: w, feerate }))
+                if w == weight && feerate == u64::MAX => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn cov_satisfier_from_prevout() {
+        let (pks, sks) = setup_keys(2);
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elcovwsh({},pk({}))",
+            pks[0], pks[1]
+        ))
+        .unwrap();
+        let desc = desc.as_cov().unwrap();
+
+        let prevout = TxOut {
+            script_pubkey: desc.script_pubkey(),
+            value: confidential::Value::Explicit(100_000),
+            asset: confidential::Asset::Explicit(AssetId::from_slice(&BTC_ASSET).unwrap()),
+            ..Default::default()
+        };
+        let spend_tx = cov_spend_tx();
+
+        let script_code = desc.cov_script_code();
+        let cov_sat = LegacyCovSatisfier::from_prevout(
+            &spend_tx,
+            0,
+            &prevout,
+            &script_code,
+            EcdsaSighashType::All,
+        )
+        .unwrap();
+        match LegacyCovSatisfier::from_prevout(
+            &spend_tx,
+            1,
+            &prevout,
+            &script_code,
+            EcdsaSighashType::All,
+        ) {
+            Err(CovError::InputIndexOutOfBounds {
+                index: 1,
+                n_inputs: 1,
+            }) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+        let sighash_u256 = cov_sat.segwit_sighash().unwrap();
+        let msg = secp256k1_zkp::Message::from_digest_slice(&sighash_u256[..]).unwrap();
+        assert_eq!(
+            desc.sighash_message(&cov_sat).unwrap()[..],
+            sighash_u256[..]
+        );
+
+        // Only the signatures are supplied by the caller
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let mut sig_map = HashMap::new();
+        for (pk, sk) in pks.iter().zip(&sks) {
+            sig_map.insert(*pk, (secp.sign_ecdsa(&msg, sk), EcdsaSighashType::All));
+        }
+        let (wit, ss) = desc.get_satisfaction((&cov_sat, &sig_map)).unwrap();
+
+        let interpreter = Interpreter::from_txdata(
+            &prevout.script_pubkey,
+            &ss,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        let constraints = interpreter
+            .iter_assume_sigs()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            constraints.last().unwrap(),
+            &SatisfiedConstraint::CovenantSig {
+                key_sig: interpreter::KeySigPair::Ecdsa(pks[0], sig_map[&pks[0]]),
+                message: msg,
+            }
+        );
+    }
+
+    #[test]
+    fn cov_finalize_with_sig() {
+        let (pks, sks) = setup_keys(3);
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elcovwsh({},pk({}))",
+            pks[0], pks[1]
+        ))
+        .unwrap();
+        let desc = desc.as_cov().unwrap();
+
+        let spend_tx = cov_spend_tx();
+        let script_code = desc.cov_script_code();
+        let cov_sat = cov_satisfier(&spend_tx, &script_code);
+
+        // The covenant signature is created without the satisfier
+        let msg = desc
+            .sighash_message_with_type(&cov_sat, CovSighashType::All)
+            .unwrap();
+        let msg = secp256k1_zkp::Message::from_digest_slice(&msg[..]).unwrap();
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let sigs: Vec<_> = sks
+            .iter()
+            .map(|sk| (secp.sign_ecdsa(&msg, sk), EcdsaSighashType::All))
+            .collect();
+
+        let mut ms_sigs = HashMap::new();
+        ms_sigs.insert(pks[1], sigs[1]);
+        let ms_witness = desc.satisfy_inner(&ms_sigs).unwrap();
+        let wit = desc
+            .finalize_with_sig(
+                &pks[0],
+                sigs[0],
+                CovSighashType::All,
+                &cov_sat,
+                ms_witness.clone(),
+            )
+            .unwrap();
+
+        // Same witness as satisfying with all the signatures
+        let mut sig_map = ms_sigs.clone();
+        sig_map.insert(pks[0], sigs[0]);
+        let expected = desc.satisfy((&cov_sat, &sig_map), false).unwrap();
+        assert_eq!(wit, expected);
+
+        let none_sig = (sigs[0].0, EcdsaSighashType::None);
+        assert_eq!(
+            desc.finalize_with_sig(
+                &pks[0],
+                none_sig,
+                CovSighashType::All,
+                &cov_sat,
+                ms_witness.clone()
+            ),
+            Err(CovError::CovenantSighashTypeMismatch)
+        );
+        assert_eq!(
+            desc.finalize_with_sig(
+                &pks[1],
+                sigs[1],
+                CovSighashType::All,
+                &cov_sat,
+                ms_witness.clone()
+            ),
+            Err(CovError::UnknownCovKey)
+        );
+        assert_eq!(
+            desc.finalize_with_sig(&pks[0], sigs[0], CovSighashType::All, &(), ms_witness),
+            Err(CovError::MissingSighashItem(CovSighashItem::NVersion))
+        );
+
+        // The signature goes in the slot of the signing key
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elcovwsh(multi(1,{},{}),1)",
+            pks[0], pks[2]
+        ))
+        .unwrap();
+        let desc = desc.as_cov().unwrap();
+        let msg = desc
+            .sighash_message_with_type(&cov_sat, CovSighashType::All)
+            .unwrap();
+        let msg = secp256k1_zkp::Message::from_digest_slice(&msg[..]).unwrap();
+        let sig = (secp.sign_ecdsa(&msg, &sks[2]), EcdsaSighashType::All);
+        let wit = desc
+            .finalize_with_sig(&pks[2], sig, CovSighashType::All, &cov_sat, vec![])
+            .unwrap();
+        let mut sig_map = HashMap::new();
+        sig_map.insert(pks[2], sig);
+        let expected = desc.satisfy((&cov_sat, &sig_map), false).unwrap();
+        assert_eq!(wit, expected);
+
+        // A single signature can't satisfy a 2 of 3 covenant
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elcovwsh(multi(2,{},{},{}),1)",
+            pks[0], pks[1], pks[2]
+        ))
+        .unwrap();
+        let desc = desc.as_cov().unwrap();
+        assert_eq!(
+            desc.finalize_with_sig(&pks[0], sigs[0], CovSighashType::All, &cov_sat, vec![]),
+            Err(CovError::NotEnoughCovSignatures(2))
+        );
+    }
+
+    #[test]
+    fn cov_confidential_value() {
+        let (pks, sks) = setup_keys(1);
+        let desc =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elcovwsh({},1)", pks[0])).unwrap();
+        let desc = desc.as_cov().unwrap();
+        let blinded = confidential::Value::new_confidential_from_assetid(
+            &secp256k1_zkp::Secp256k1::signing_only(),
+            100_000,
+            AssetId::from_slice(&BTC_ASSET).unwrap(),
+            confidential::ValueBlindingFactor::from_slice(&[1; 32]).unwrap(),
+            confidential::AssetBlindingFactor::from_slice(&[2; 32]).unwrap(),
+        );
+        let explicit = confidential::Value::Explicit(100_000);
+
+        let spend_tx = cov_spend_tx();
+        let script_code = desc.cov_script_code();
+        for (value, value_len) in [(explicit, 9), (blinded, 33)] {
+            let cov_sat = LegacyCovSatisfier::new_segwitv0(
+                &spend_tx,
+                0,
+                value,
+                &script_code,
+                EcdsaSighashType::All,
+            );
+            // The covenant message must hash to the consensus sighash
+            let sighash_u256 = cov_sat.segwit_sighash().unwrap();
+            assert_eq!(
+                desc.sighash_message(&cov_sat).unwrap()[..],
+                sighash_u256[..]
+            );
+            let msg = secp256k1_zkp::Message::from_digest_slice(&sighash_u256[..]).unwrap();
+
+            let mut sig_map = HashMap::new();
+            sig_map.insert(pks[0], sign_sighash(&cov_sat, &sks[..1])[0]);
+            let (wit, ss) = desc.get_satisfaction((&cov_sat, &sig_map)).unwrap();
+            // signature, then sighash items 10 down to 7, then the value
+            assert_eq!(wit[5].len(), value_len);
+            assert_eq!(wit[5], serialize(&value));
+
+            let interpreter = Interpreter::from_txdata(
+                &desc.script_pubkey(),
+                &ss,
+                &wit,
+                Sequence::ZERO,
+                LockTime::ZERO,
+            )
+            .unwrap();
+            let constraints = interpreter
+                .iter_assume_sigs()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(
+                constraints.last().unwrap(),
+                &SatisfiedConstraint::CovenantSig {
+                    key_sig: interpreter::KeySigPair::Ecdsa(pks[0], sig_map[&pks[0]]),
+                    message: msg,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn cov_sighash_types() {
+        let (pks, sks) = setup_keys(1);
+        let desc =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elcovwsh({},1)", pks[0])).unwrap();
+        let desc = desc.as_cov().unwrap();
+
+        let mut spend_tx = cov_spend_tx();
+        spend_tx.output.push(TxOut::default());
+        spend_tx.output[1].value = confidential::Value::Explicit(1_000);
+
+        let script_code = desc.cov_script_code();
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        for ty in [
+            CovSighashType::All,
+            CovSighashType::None,
+            CovSighashType::Single(0),
+        ] {
+            let cov_sat = LegacyCovSatisfier::new_segwitv0(
+                &spend_tx,
+                0,
+                confidential::Value::Explicit(100_000),
+                &script_code,
+                ty.ecdsa_sighash_type(),
+            );
+            let sighash = cov_sat.segwit_sighash().unwrap();
+            assert_eq!(
+                desc.sighash_message_with_type(&cov_sat, ty).unwrap()[..],
+                sighash[..]
+            );
+
+            let msg = secp256k1_zkp::Message::from_digest_slice(&sighash[..]).unwrap();
+            let sig = (secp.sign_ecdsa(&msg, &sks[0]), ty.ecdsa_sighash_type());
+            let mut sig_map = HashMap::new();
+            sig_map.insert(pks[0], sig);
+            let wit = desc
+                .satisfy_with_sighash_type((&cov_sat, &sig_map), false, ty)
+                .unwrap();
+            assert_eq!(wit[1], serialize(&ty.as_u32()));
+            assert_eq!(wit[1][0], ty.ecdsa_sighash_type() as u8);
+
+            // The sighash type defaults to the one of the satisfier
+            assert_eq!(desc.sighash_message(&cov_sat).unwrap()[..], sighash[..]);
+            assert_eq!(desc.satisfy((&cov_sat, &sig_map), false).unwrap(), wit);
+
+            // The signature must use the same sighash type
+            if ty != CovSighashType::All {
+                match desc.satisfy_with_sighash_type(
+                    (&cov_sat, &sig_map),
+                    false,
+                    CovSighashType::All,
+                ) {
+                    Err(Error::CovError(CovError::CovenantSighashTypeMismatch)) => {}
+                    res => panic!("Unexpected result {:?}", res),
+                }
+            }
+        }
+
+        // Only the standard sighash types without ANYONECANPAY are supported
+        let acp = CovSatisfaction::new().sighash_u32(0x81);
+        assert_eq!(
+            desc.sighash_message(&acp),
+            Err(CovError::UnsupportedSighashType(0x81))
+        );
+        // SIGHASH_SINGLE needs the index of the spent input
+        let single = CovSatisfaction::new().sighash_u32(3);
+        assert_eq!(
+            desc.sighash_message(&single),
+            Err(CovError::MissingInputIndex)
+        );
+
+        // SIGHASH_SINGLE commits to the output at the input index
+        let mut single_tx = spend_tx.clone();
+        single_tx.input.push(single_tx.input[0].clone());
+        single_tx.output.truncate(1);
+        let cov_sat = LegacyCovSatisfier::new_segwitv0(
+            &single_tx,
+            1,
+            confidential::Value::Explicit(100_000),
+            &script_code,
+            EcdsaSighashType::Single,
+        );
+        assert_eq!(
+            desc.sighash_message(&cov_sat),
+            Err(CovError::SingleOutputOutOfRange {
+                index: 1,
+                n_outputs: 1
+            })
+        );
+
+        let cov_sat = LegacyCovSatisfier::new_segwitv0(
+            &spend_tx,
+            0,
+            confidential::Value::Explicit(100_000),
+            &script_code,
+            EcdsaSighashType::Single,
+        );
+        assert_eq!(
+            desc.sighash_message_with_type(&cov_sat, CovSighashType::Single(2)),
+            Err(CovError::SingleOutputOutOfRange {
+                index: 2,
+                n_outputs: 2
+            })
+        );
+    }
+
+    #[test]
+    fn cov_satisfaction_validate() {
+        let (pks, _sks) = setup_keys(1);
+        let desc =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elcovwsh({},1)", pks[0])).unwrap();
+        let desc = desc.as_cov().unwrap();
+
+        let spend_tx = cov_spend_tx();
+        let script_code = desc.cov_script_code();
+        let value = confidential::Value::Explicit(100_000);
+        let tx_sat = LegacyCovSatisfier::new_segwitv0(
+            &spend_tx,
+            0,
+            value,
+            &script_code,
+            EcdsaSighashType::All,
+        );
+
+        // All the missing items are reported at once
+        let cov_sat = CovSatisfaction::new()
+            .n_version(spend_tx.version)
+            .hash_prevouts(Satisfier::<bitcoin::PublicKey>::lookup_hashprevouts(&tx_sat).unwrap())
+            .outpoint(spend_tx.input[0].previous_output)
+            .value(value)
+            .outputs(spend_tx.output.clone());
+        let err = cov_sat.validate().unwrap_err();
+        let missing: Vec<_> = match err {
+            CovError::MissingSighashItems(items) => items.iter().collect(),
+            e => panic!("Unexpected error {}", e),
+        };
+        assert_eq!(
+            missing,
+            vec![
+                CovSighashItem::HashSequence,
+                CovSighashItem::HashIssuances,
+                CovSighashItem::ScriptCode,
+                CovSighashItem::NSequence,
+                CovSighashItem::NLocktime,
+                CovSighashItem::SighashU32,
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "Missing sighash items: hashSequence, hashIssuances, scriptCode, \
+            nSequence, nLocktime, sighash type"
+        );
+
+        // A complete set gives the same sighash as the transaction
+        let cov_sat = cov_sat
+            .hash_sequence(Satisfier::<bitcoin::PublicKey>::lookup_hashsequence(&tx_sat).unwrap())
+            .hash_issuances(Satisfier::<bitcoin::PublicKey>::lookup_hashissuances(&tx_sat).unwrap())
+            .script_code(script_code.clone())
+            .n_sequence(spend_tx.input[0].sequence.to_consensus_u32())
+            .n_locktime(spend_tx.lock_time.to_consensus_u32())
+            .sighash_u32(EcdsaSighashType::All.as_u32());
+        cov_sat.validate().unwrap();
+        assert_eq!(
+            desc.sighash_message(&cov_sat).unwrap()[..],
+            tx_sat.segwit_sighash().unwrap()[..]
+        );
+    }
+
+    #[test]
+    fn cov_committed_fields() {
+        let (pks, _sks) = setup_keys(2);
+        for desc_str in [
+            format!("elcovwsh({},1)", pks[0]),
+            format!("elcovwsh(multi(1,{},{}),1)", pks[0], pks[1]),
+        ] {
+            let desc = Descriptor::<bitcoin::PublicKey>::from_str(&desc_str).unwrap();
+            let committed = desc.as_cov().unwrap().committed_fields();
+            assert_eq!(
+                committed.iter().collect::<Vec<_>>(),
+                CovSighashItem::ALL.to_vec()
+            );
+        }
+    }
 
-        // same second output
-        let second_out = spend_tx.output[0].clone();
-        spend_tx.output.push(second_out);
+    #[test]
+    fn lazy_cov_satisfier() {
+        use std::cell::Cell;
 
-        // Add a fee output
-        spend_tx.output.push(TxOut::default());
-        spend_tx.output[2].asset =
-            confidential::Asset::Explicit(AssetId::from_slice(&BTC_ASSET).unwrap());
-        spend_tx.output[2].value = confidential::Value::Explicit(2_000);
+        let (pks, _sks) = setup_keys(1);
+        let desc =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elcovwsh({},1)", pks[0])).unwrap();
+        let desc = desc.as_cov().unwrap();
 
-        // Try to satisfy the covenant part
+        let spend_tx = cov_spend_tx();
         let script_code = desc.cov_script_code();
-        let cov_sat = LegacyCovSatisfier::new_segwitv0(
+        let value = confidential::Value::Explicit(100_000);
+        let tx_sat = LegacyCovSatisfier::new_segwitv0(
             &spend_tx,
             0,
-            confidential::Value::Explicit(200_000),
+            value,
             &script_code,
             EcdsaSighashType::All,
         );
 
-        // Create a signature to sign the input
+        // Counts the calls of each closure
+        let calls = Cell::new(0u32);
+        let lazy_sat = |hash_sequence: Option<elements::hashes::sha256d::Hash>| {
+            let (calls, tx_sat) = (&calls, &tx_sat);
+            let (spend_tx, script_code) = (&spend_tx, &script_code);
+            LazyCovSatisfier::new()
+                .n_version(move || {
+                    calls.set(calls.get() + 1);
+                    Some(spend_tx.version)
+                })
+                .hash_prevouts(move || {
+                    calls.set(calls.get() + 1);
+                    Satisfier::<bitcoin::PublicKey>::lookup_hashprevouts(tx_sat)
+                })
+                .hash_sequence(move || {
+                    calls.set(calls.get() + 1);
+                    hash_sequence
+                })
+                .hash_issuances(move || {
+                    calls.set(calls.get() + 1);
+                    Satisfier::<bitcoin::PublicKey>::lookup_hashissuances(tx_sat)
+                })
+                .outpoint(move || {
+                    calls.set(calls.get() + 1);
+                    Satisfier::<bitcoin::PublicKey>::lookup_outpoint(tx_sat)
+                })
+                .script_code(move || {
+                    calls.set(calls.get() + 1);
+                    Some(script_code)
+                })
+                .value(move || {
+                    calls.set(calls.get() + 1);
+                    Some(value)
+                })
+                .n_sequence(move || {
+                    calls.set(calls.get() + 1);
+                    Satisfier::<bitcoin::PublicKey>::lookup_nsequence(tx_sat)
+                })
+                .outputs(move || {
+                    calls.set(calls.get() + 1);
+                    Some(&spend_tx.output)
+                })
+                .n_locktime(move || {
+                    calls.set(calls.get() + 1);
+                    Satisfier::<bitcoin::PublicKey>::lookup_nlocktime(tx_sat)
+                })
+                .sighash_u32(move || {
+                    calls.set(calls.get() + 1);
+                    Satisfier::<bitcoin::PublicKey>::lookup_sighashu32(tx_sat)
+                })
+        };
 
-        let sighash_u256 = cov_sat.segwit_sighash().unwrap();
-        let secp = secp256k1_zkp::Secp256k1::signing_only();
-        let sig = secp.sign_ecdsa(
-            &secp256k1_zkp::Message::from_digest_slice(&sighash_u256[..]).unwrap(),
-            &cov_sk,
+        // The sighash type is looked up first. The closures after the
+        // first missing item are not called
+        let sat = lazy_sat(None);
+        match desc.sighash_message(&sat) {
+            Err(CovError::MissingSighashItem(CovSighashItem::HashSequence)) => {}
+            x => panic!("Unexpected result {:?}", x),
+        }
+        assert_eq!(calls.get(), 4);
+
+        // Every closure is called once, the results are kept
+        calls.set(0);
+        let hash_sequence = Satisfier::<bitcoin::PublicKey>::lookup_hashsequence(&tx_sat);
+        let sat = lazy_sat(hash_sequence);
+        let sighash = desc.sighash_message(&sat).unwrap();
+        assert_eq!(sighash[..], tx_sat.segwit_sighash().unwrap()[..]);
+        assert_eq!(calls.get(), 11);
+        assert_eq!(desc.sighash_message(&sat).unwrap(), sighash);
+        assert_eq!(calls.get(), 11);
+    }
+
+    #[test]
+    fn missing_sighash_item() {
+        use elements::hashes::{sha256d, Hash};
+
+        let (pks, _sks) = setup_keys(2);
+        let desc = LegacyCSFSCov::<bitcoin::PublicKey, CovenantExt<CovExtArgs>>::from_str(
+            &format!("elcovwsh({},pk({}))", pks[0], pks[1]),
+        )
+        .unwrap();
+        // hashSequence and hashIssuances are reported separately
+        let partial = CovSatisfaction::new()
+            .sighash_u32(EcdsaSighashType::All.as_u32())
+            .n_version(2)
+            .hash_prevouts(sha256d::Hash::all_zeros());
+        assert_eq!(
+            desc.sighash_message(&partial),
+            Err(CovError::MissingSighashItem(CovSighashItem::HashSequence))
+        );
+        let partial = partial.hash_sequence(sha256d::Hash::all_zeros());
+        let err = desc.sighash_message(&partial).unwrap_err();
+        assert_eq!(
+            err,
+            CovError::MissingSighashItem(CovSighashItem::HashIssuances)
         );
-        let el_sig = (sig, EcdsaSighashType::All);
+        assert_eq!(
+            err.to_string(),
+            "Missing covenant sighash field: hashIssuances"
+        );
+    }
 
-        // For satisfying the Pk part of the covenant
-        struct SimpleSat {
-            sig: ElementsSig,
-            pk: bitcoin::PublicKey,
-        }
+    #[test]
+    fn hash256_arr_engine() {
+        use elements::hashes::{sha256d, Hash};
 
-        impl Satisfier<bitcoin::PublicKey> for SimpleSat {
-            fn lookup_ecdsa_sig(&self, pk: &bitcoin::PublicKey) -> Option<ElementsSig> {
-                if *pk == self.pk {
-                    Some(self.sig)
-                } else {
-                    None
-                }
-            }
+        let (pks, _sks) = setup_keys(3);
+        let outputs: Vec<_> = pks
+            .iter()
+            .enumerate()
+            .map(|(i, pk)| TxOut {
+                asset: confidential::Asset::Explicit(AssetId::from_slice(&BTC_ASSET).unwrap()),
+                value: confidential::Value::Explicit(1000 * (i as u64 + 1)),
+                nonce: confidential::Nonce::Null,
+                script_pubkey: Script::from(pk.to_bytes()),
+                witness: elements::TxOutWitness::default(),
+            })
+            .collect();
+
+        let mut engine = Hash256Engine::new();
+        for out in &outputs {
+            engine.input(out);
         }
+        let hash = engine.finish();
+        assert_eq!(hash, hash256_arr(&outputs));
+        assert_eq!(hash, sha256d::Hash::hash(&serialize(&outputs)[1..]));
+        assert_eq!(hash, CovSighashType::All.hash_outputs(&outputs).unwrap());
+        assert_eq!(hash256_arr::<TxOut>(&[]), sha256d::Hash::hash(&[]),);
+    }
 
-        let pk_sat = SimpleSat {
-            sig: el_sig,
-            pk: desc.pk,
+    #[test]
+    fn hash_issuances_vectors() {
+        use elements::encode::deserialize;
+        use elements::hashes::{sha256, sha256d, Hash, HashEngine};
+        use elements::hex::FromHex;
+        use elements::sighash::SighashCache;
+
+        // The segwit sighash of the first input. The covenant message does not
+        // support spending an input with an issuance, so the issuance of the
+        // spent input (after nSequence) is added here by hand.
+        let sighash_for = |tx: &Transaction, script_code: &Script, value| {
+            let cov_sat =
+                CovSatisfaction::new().issuances(tx.input.iter().map(|txin| &txin.asset_issuance));
+            let hash_issuances =
+                Satisfier::<bitcoin::PublicKey>::lookup_hashissuances(&cov_sat).unwrap();
+            let txin = &tx.input[0];
+            let mut prevouts = Hash256Engine::new();
+            for txin in &tx.input {
+                prevouts.input(&txin.previous_output);
+            }
+            let sequences: Vec<_> = tx.input.iter().map(|txin| txin.sequence).collect();
+            let mut enc = sha256d::Hash::engine();
+            enc.input(&serialize(&tx.version));
+            enc.input(&prevouts.finish()[..]);
+            enc.input(&hash256_arr(&sequences)[..]);
+            enc.input(&hash_issuances[..]);
+            enc.input(&serialize(&txin.previous_output));
+            enc.input(&serialize(script_code));
+            enc.input(&serialize(&value));
+            enc.input(&serialize(&txin.sequence));
+            enc.input(&serialize(&txin.asset_issuance));
+            let outputs = CovSighashType::All.hash_outputs(&tx.output).unwrap();
+            enc.input(&outputs[..]);
+            enc.input(&serialize(&tx.lock_time));
+            enc.input(&serialize(&EcdsaSighashType::All.as_u32()));
+            sha256d::Hash::from_engine(enc)
         };
 
-        // A pair of satisfiers is also a satisfier
-        let (wit, ss) = desc.get_satisfaction((cov_sat, pk_sat))?;
-        let interpreter = Interpreter::from_txdata(
-            &desc.script_pubkey(),
-            &ss,
-            &wit,
-            Sequence::ZERO,
-            LockTime::ZERO,
+        // Issuance sighash vector generated by Elements Core
+        let tx: Transaction = deserialize(
+            &Vec::<u8>::from_hex(
+                "010000000001715df5ccebaf02ff18d6fae7263fa69fed5de59c900f4749556eba41bc7bf2af0000\
+                 00800000000000000000000000000000000000000000000000000000000000000000000000000000\
+                 000000000000000000000000000000000000000000000000000000000000000100000000000003e8\
+                 01000000000000000a0201230f4f5d4b7c6fa845806ee4f67713459e1b69e8e60fcee2e4940c7a0d\
+                 5de1b2010000000124101100001f5175517551755175517551755175517551755175517551755175\
+                 517551755101230f4f5d4b7c6fa845806ee4f67713459e1b69e8e60fcee2e4940c7a0d5de1b20100\
+                 00000005f5e100000000000000",
+            )
+            .unwrap(),
         )
         .unwrap();
+        assert!(tx.input[0].has_issuance());
+        let script_code = Script::from(
+            Vec::<u8>::from_hex("76a914f54a5851e9372b87810a8e60cdd2e7cfd80b6e3188ac").unwrap(),
+        );
+        let value: confidential::Value = deserialize(
+            &Vec::<u8>::from_hex(
+                "0850863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        // sighashes are displayed in serialization order here
+        let expected = sha256::Hash::from_str(
+            "ea946ee417d5a16a1038b2c3b54d1b7b12a9f98c0dcb4684bf005eb1c27d0c92",
+        )
+        .unwrap();
+        assert_eq!(sighash_for(&tx, &script_code, value)[..], expected[..]);
 
-        assert!(wit[0].len() <= 73);
-        assert!(wit[1].len() == 4); // version
-
-        // Check that everything is executed correctly with correct sigs inside
-        // miniscript
-        let constraints = interpreter
-            .iter_assume_sigs()
-            .collect::<Result<Vec<_>, _>>()
-            .expect("If satisfy succeeds, interpret must succeed");
+        // Liquid issuance transaction eda1d7c0f47fe209c3b5e98ec4bf48fc03f78ce8dcb9742683751fac42f7e4ed
+        let tx: Transaction = deserialize(
+            &Vec::<u8>::from_hex(
+                "02000000010173828cbc65fd68ab78dc86992b76ae50ae2bf8ceedbe8de0483172f0886219f70000\
+                 00806b483045022100a21a578a7f2f98ca65115488facb62d7c196d2df14213aed986cfdbdfd0564\
+                 7402204197c1fd1d9e94a14535e0918cd3ca5932f6c086ac49136f255fd72ba7651d4801210211dd\
+                 65ff387faf9bd658527cb79d2b91cc7b691b0b5273b1a50d43104ea50f0dfeffffff000000000000\
+                 00000000000000000000000000000000000000000000000000000000000000000000000000000000\
+                 0000000000000000000000000000000000000981654eb5ccd9927b8bea94997dce4ae85b3d95a207\
+                 00384f0b8c1fe99518063800030a1f491e09f485e018ec05dfa75239207546d19339b23e074dad18\
+                 3d788f81e7a708ce96a4dbcf09acde1fee82af501c9846ee59c51e77813feb3dff8d199195ee6303\
+                 ed14decec9a849df2449da03ab2d9ee717e03878226220612d790e7dec08f1e81976a914d7f8ae92\
+                 f7a073586c318b10db200db2ce831a1e88ac0a6c90cc7e0028e021801e68d3a6db206598985e4cb9\
+                 54d09bcdbb64312a4024d308c2740f3fe59958d51bcf8ccc863a2212c07313effa4a887814fb217b\
+                 511ea27802759443a36a675dc6671ed02fefecc127a8dafb9894d2ff44a74c5e8aae3dc2e91976a9\
+                 14904d0f751d7aa301c724899be86224034758cc7988ac01230f4f5d4b7c6fa845806ee4f6771345\
+                 9e1b69e8e60fcee2e4940c7a0d5de1b201000000000000dc50000097010000fd450c40264c7003da\
+                 ed6754f258faa19bf81c8d94532cd8ec023420ed4e7e10225993508c96f319d07b8cd89b4d1818c4\
+                 46e3599347482a767c7dd3b2fb5030b7eaf62bc439875f020a6b400a29deedc363f9daed32a081a5\
+                 ef6295b7c4e2a9db31a6ed1a6d290d5a354f71aae49db8a34fbff22ca1e897a73984805705a0787c\
+                 23d5dc8973aeaf4f431febe5e1feec2c4eb31a1ad74ff01a0536e2fffe081b554f8b4488e152fa98\
+                 b2cb06a2ed6e793352e76b81896db6911416b458d42f2e6cdc6a67d7e522cd51af9a1004ad7329ed\
+                 86811adafde53caa97d4100ef5e2dcd63e9569992716691eab34e7adc87bcb94c9fd983d265079bb\
+                 0f9179cd089de335cf6da676e822f81e46cfcb2271390efa383944b99682927fec879380c23f6484\
+                 939d7320ead9690d90855ea2f27c8dbac965e64ab5e004c96a4a09e590cb012a2297231fc52f7720\
+                 cce1dba52e0448e05fa86cfd94561f09cd702aefae25bdb15c8903512577565019bf7372ba40f61e\
+                 fd922bf5f34878ee2501de84935512b5908cac697e0e0cb917c24315290735210a47e861a7340e54\
+                 0540f4b2fb92f54f2540dd98b2512280eb2db08f52f6ab4544fb7377b9424c050afa126412eb80e2\
+                 0611432528ed3860c2e27eab18a0bb8568c76df5c9a3de777c0ae69dd8a71e81df3fac8eeb31da8a\
+                 ea467eca0f2b32bd416c5adf106b7d18b6de9fbd4348d45c517f2bc369e0504ae267f16d5e34225c\
+                 b0100090c75c27f67eda8dd1986a6bf29b0488a115abd33f0c19e75dc8622302b50e1b67b1e60748\
+                 93b411251b0f40327b9a135f93b926ecc7a91da9dea77fccb234d90735235d7196dbdcc3cc542f11\
+                 faafd54341c380f7bcf6faf9757460951ccaa9533a9c4bafbd61cb8260f916bdcf25c3d12885fbc4\
+                 8b74d865888823240f780ed8c924f9f60a1dd9125391a43e66dc65d7b5fa82b8eb5ea2a200fbcf37\
+                 cba4c3fb31015c0cbd17b16a50ca8416e312254b87ce083abccdadcbde4b4cfa99b01b989819c6fd\
+                 40d140493e0795015457befbc52654070dfba56877987502dda25b5eba2a6b1374dc4dd19c607811\
+                 f3a1deb8de67cd17b1d6fe2ec3ffac884325d61d95f881194ee5577ebc338d9809be55c12bfac4d6\
+                 9e596bc416186f2d76a1dc1dd8fe787cd0012639f7da04b3c193403d6a55c1ee8c05a3b1a0fa65d3\
+                 a5b86b9398999a9a448d8c34dba35731d542d5e99a21ae63bd30f0ed9f1ec12cbb6e812766689981\
+                 fa5d6ecd6d9972e0423cb6cabfcc0fb333ae21dd61327ebd511528df5d758e2a73b7ccb839ac5bc6\
+                 aa51b58c8f6e09be27e51e8071c17ad899d2b08ba6b7443fff03ad6fe943efebef8e35f3873cd549\
+                 41d90b44529b115af8367af9bdb605193ec4353d5fd65bc148f3f76afe7815ace18ac6c2c70c8a91\
+                 27ace4ef0f0344ae9367db15a5a1d87b8719bbce43c37581488202051838cbaa139bbb7f6c85b9a7\
+                 dc5090bdec1168c6defb6eb5e9c2f3841c1e53c4a5b40d51c340e309c8db21f2ff4667e928e71ead\
+                 c5a462d715116001d6822dcb012d6bf4e94e07d625786e290f2c32e55a8fd0b13aa2776130c842db\
+                 34eac5358733f54b8efef2803c98d0d20467ae116d983bc2e75b2ec83e6a8a9562b29b77f1c49742\
+                 2ccebd7df4f05d4c2fe403d57ed37239b974339d0a7b536ecea53e391a70c18160381418b3d7de2d\
+                 a0abd5743186c7361d7f398d719e7c4945f06f4e74c95ae07023a153c1f5dfe152f914af504e8067\
+                 f87bee54d92493162b8e09d4fa18e8722863b084c9a19b5a3893dbbb1d456efaf17581538becf64e\
+                 a8d63c6bc87fa87f6a71c0650e307a080dc9b335f4baffc350725583367021fb4b77c2e1e05d339e\
+                 1ae7832eb2d46ac036bf951798bb7b236d9ac73df5c59639b8159a07890d5bc6134d08c10f3f5b27\
+                 1d63a735700ae3690939ac74912c4b6c3f197fc0ef9a41961b4adcf0c0520288f2073be175a15db0\
+                 f2f6a79ba2db0ccd3e2486129827f90e9b9f8e814ce23b151d93b35854bdfaa0ec0e2f3d992ffa95\
+                 61648aa831edfd7f356602366ae9d1252fb3196bf5844b376fa7c6f8cdda0210b60c97ac89663868\
+                 8632b8f33554ad271140c29e4a1f43e5c8f266b88eefdb01d583c27c98c8084edc8034ae51dd4a6d\
+                 ca1b9e311662685e53c509fe61c9583acb5693c598edfc33c2bd039a7121cef3a49ac135089ace29\
+                 485b2cc4ff9402935e4dd42753955a69d981debe35eddb9eebffbe6f6027e39cb74bd7e88ef384c7\
+                 e7cdf8d2e3224cc9e9f49b72b1978db7c30bdf1824edb67cab8b7c6e8633e7d14d261fba0fbc6811\
+                 209f73adfe8a2c485970000526536395f7312347582aa2a49f3935acc1db33c555002653a46a9635\
+                 a4bf67caa700f685f2291eebd7bd59c72e98ea0cd24a14aab5b288f8f40f87cac409affb5a7e43a3\
+                 06df831a1106c54ee330f04df833dab8f5171e5f4f3087b5b82bc02d91c71363db7eebec29bd4ec6\
+                 f32e6afc1a07635f1c07f8aa15643f06108326e9e29df1c93f6321868144dc84719c1031b7f81e5e\
+                 d49eb9f8b4b6f863b21084a57bede3a7ee9640a91f6091ef584eebb4e3fef49c03ed703c8d869f18\
+                 f90777e65c6270f33e371b01efacee44a15346ba29fc7d6bd7c2217cc399de615d41e4006df808c6\
+                 3e3cb986845cc52c2fda7072d88aff90b54a29c787f5aef0a4b96ea6f9717f3bc8df1e5bef6ec73f\
+                 51705b5133621460d1fab14a420fd4dcd8019c0588d7d85104b88ea04d9139ce2e31d5756928af8b\
+                 6a3202e4ecf00686dc292ad752ec0da61b3308ac9e8f7ca159962f54f284116511a7b53519dde7d7\
+                 b5f92c41a9e31d6a006117c91fc078c8bf437f5e735c8698094674c6795df6479fad8f352602a148\
+                 27d57afef36d059dd185fd141f498306a44806120ae1966aaa4c64b99c25154595460d179f4042cf\
+                 0abc6a0b277757b3fc5368366df8ea3fcde280961e5663290f2e2af37a6e9963c58ba7b68f9b63c1\
+                 6bd7d22a9303cb69ff529544d4a8254050363a074e3f6703dbe403406dca15086d3312a86d3f3f11\
+                 9628b214fda41fe28416e0d7670e0ad567f2a66d2331788037c1748e00e110d15bf105b0002eff2d\
+                 8aa4e2902addda34c82af7149978f1c924fc5fa6189068d09dde69f2b1a91fe96587eb7929cb9e30\
+                 c7a6e9ac8ab353509cdfa7d7f1f7bb2cd8bef944532a3d085fd609f96d35d5eb41627dacba7b8ae6\
+                 c30b9e8ae40684d1c0890a59e98c4b6de684e15559be97f382161aff01b28aa21786e71edf458d85\
+                 2b6482d1ae20a25f130a110fe8812d79e19dbec32be5a7bf495f5a264bd64f62a2646b8ea37e6fd3\
+                 6c280197e4e141c29ec7a7aa25581e08673dd713bfa81d77e0c9855964b33dae6dbf0953cdb1d423\
+                 07ea5f7eb49bdb341b6be3f9f2a029e5a656dc614a6c6d69681167b607b90bde3f5cbdce734bfa7f\
+                 76b0297c2462b47caae4e371cf258b7ce897ccaaa3f9915de94a150764791ef9cea89716b6bfe7fc\
+                 8ca502a947e211432794c9d415a32ba7e822d071d3fd61a53c26cf1beb6fa8811249e0f2482aca9d\
+                 1c7365d99e655e722c8aa38d8f14b101f18b047263f1ab95f6bbeb2c03ab6b64cce35687ab76e0c1\
+                 79997516b947b2b9f1857a354a33e763a0ff5fb90b55cbbfb1b56d5114fe3329555550ec7fc8de5f\
+                 6797bb53129a1887a1c3e47b7c53c2f224fd1cb5df02cc6ba4ce7dea93379f6cdd06475e7ab51453\
+                 781bb2605debe86519497609ee4644224ab84ce896fabec46b81dfdab035942de9f160829c1f8e7b\
+                 eccc02d659c7e09d28074bb1fe24100cf61799ecf8306c2685ca24a22a17de2dc1a78599a524afb7\
+                 6fb961a093b8f35da2c04cbae86ba793320312f63d479d36c9b5984364b508ae8d8120ae14e3c864\
+                 1591b320e314b36db6bc9aa5f548b91953fac433f0efd94bcbf660eb560db5fa4af31320cf254d67\
+                 5d97b7fde1b8551b85087b6e7818deb66a75125a3d2040a03e1c2bc4add8f2f6cb636237136fe9ae\
+                 879fa18e12282cd1cc5d0d84aecd8ad56da49ac0150fe7dd4710fadd580f39a49e427b42f980ca34\
+                 3b68e73b1b34b838e125bc0b3a6eaa038bbacab6f06558f6ace4c416cd1ecf6818cffd8690a319fc\
+                 2ae2c4a8eb10e602ce3f0a464f7b920d68846113d8389c292601ec112338fa2de3a5a0bd1847f54a\
+                 04bfac1a93bc5e7c1a5ac2a76fbf5fd0d2ccf1934a1e1438ec9e3e1fe6b943d1a72037c70a4dcec9\
+                 83bf828b11be1488cf7b1ff84725d1e7aac6ca2807bd520936db6730ca23fd298e93c88c15647eba\
+                 71bf39bfd4515b4df1cd8c2bc0b4db11d9912295af18a8521862952cc1f6f08da8cca0d967ca7b7e\
+                 e0690a6558e328ad08a056e3d65adcde079404f03d18f540615a322cab285ea156790305066fb26f\
+                 c1ab57a09279f5ffff4edffc6137a900000063020003e6847a3cd9b5eba65bd81056ba707f19961a\
+                 c3d0a26cc63ed8e433d44b1c9c4c82be6857f9f85ecbe7db17fd4693c31ee1f8b9247eb43ad3f4c6\
+                 ad81e5829723de029baf6b65c9201bd065ad569d4eba4736145c4d005922968ebedf9deba2f5fd0c\
+                 0a601f00000000000000013566ebac06d73056cf8d5f565ac1aa83889dafc5dd36797810823b909e\
+                 f66724e21827d0670561dfbe1982788483b9be05b7729275a44c43977df9cbab97c553475d222837\
+                 9892be237d42b252057daf29b72fd7111b67283069d410b68d54f8e0da92bf0b3598ef7ce1cfd313\
+                 c9a9c06b0ea313935929e1cd1287d412814c0232caa165c467ab0eb3ade399ab2abb7f92a8abf970\
+                 adddf989a8fbaaa360fcad2990dc845b9d83a1d536669ace9a498d83043238df074d7030bf2caf65\
+                 48d439ba7533285fb25679b44fa4ded2f0a157d59d95c8821a36c6c801680cfb1c74db08aee75ead\
+                 a227f0e45ebcf0d7640471d45fe56d108360d3a7f640d662f19a1c96de625db64ed1bdb4afd0a1d6\
+                 241538e7ababcb7ecc27c8873fc9e63b029af9ea142e80d2dda2c45214ef21800fea9dfaed833d35\
+                 f17eff9ccca4648c961c4e1d2cd97962fe6b69935036aba9c89ba13f84aa6630b580aabd3efdb0d0\
+                 3db8bda63b0a69157d00c7834582722a3bd696f174175bac95e74a1b3cc5a09831e2be6409412544\
+                 d35833afeff6b88f76954ed9c0019c75701622fabcd6c8b4accbd1f3551c7f39a51ed58c773449cb\
+                 a2afef7b7fbf55bcba1f4f845cbeca662b2fb95ab84e275bf9d0dedd96ee32eb2ea71e9b133e6060\
+                 d19707a4b63734fdfa37b507ca2bb4056c8786f687c50ca58ef07a3d27f337f80b6a66073baff2ad\
+                 bf5ba03726fa2c84ab7b6be243fb56add0e313be4eddd6af55b42f85472074f440470f01a2fa1db1\
+                 c33e4b686c631e7bc09d92ce0c057ab372d5caeb549b6ddbc374017740debe825cbe35d51b269ddc\
+                 58fb1b2f389f2d179b97aaa5779374f5db737798905ba48002e3125a831c844ac1558ce197e5a2f6\
+                 6901a5933ff3da7b99faf3634e5ce532765b3477756080fe9952bdced8f1fe61073b99604526c18b\
+                 93a1dae6d078745db84ee658e83a02ec0b041027c25a5fa9f3d2e069fe1155579ef5fe9a6948cfbc\
+                 8444f99372fc615864d5bc0261fdc4e96f36827d5a2b3cd61f94dc213c7d83666f4bb464e3382645\
+                 4d75675e7a6d320d16ab240d0efc04744327ce830d4cf221352729786dd4a1ef180aa529526a5f04\
+                 4636582419f173e49e4f189106a64f181e03c7860a7cc26d9e84e401210cb564f81c26e5409f161d\
+                 1c5683820f3e307315d5a56dbe6a4bf41e60a6a7f66b28f6c353b174f6a84d0a3d040cd687f8e830\
+                 98e1d76e85d3193d65cd2114659d42a92499e250d38630a3166cc4f5f2aaa1207b9147c4ee54977f\
+                 b4211b8336348cb950e845f4b39aaabda3343a2871daf89229c3504f70a8fbe2de524ac352dc5b04\
+                 2b4d5171f51c021f626f640c8f08fab509244000877e1dd383a01e2639938011ab5c10a103dc8432\
+                 96dabbb9fe984fff7d269cf4f96a14907701c078e405340a468eb1f493c4dcbcbb851c09ecfe3b10\
+                 8d864e6125047fa7b1f71182524dd6f60f43542e09ec291fa03a200975a85cf38f7f114eed2408a6\
+                 e757f6bd6435d9afe8cbfa159941a7eb85b3c5b68b242c7ae0e206654c9ff6a0669754f34c022fb9\
+                 40bf7ba91b6a03ad20276162dbe1dac433b41b72f3d96cdddc975ba64ff23556f6dde4f8152bf1b6\
+                 15459d6bb245daeb14bd38201f6a89e996ad39e277bf59dab63e34f509f44a3f1fe187815c9a1ba4\
+                 849ca64859c91aeadb15842ddccdce7d1ded68b4e28b7988104dd1b0887425910d71b7e170d67275\
+                 f605da7e7b7fa270cca7a50e052fd771e241c50473d6f8e697f7669f16cc62697c921fea92d8c699\
+                 57c612ad9d3faab0c6260483a0f274a32b62723e85056f87b536830410464e4fccb47d6ad2e5270d\
+                 370efd1847dc07f3492d5a1244bf4256bb6f029a209bbdff10275e19a6bd15c6eb0f14ab81eb2d5d\
+                 56fbfa4ae2e36a0b1ce36c68300d5e8909e1a209358d155dc8d948dddeac9483bceced7a5e3da1b4\
+                 203b476e9c81c908a74bac120825efb711d83105f8aee9258fe2cf86138a4409e508aeb6d252be80\
+                 f240887407667fd62dcb029ee8c8e1ef5d613e19dfed38a06503fcacb2e590706fc024a9f3f859ed\
+                 af48b9fb3f65c70f0555f0651ea2b0fa28ec5739b94054a082d55e2ce0a3dded2906fc327f97145f\
+                 3987b39621c231df9b1769f0a28c7152881ead2f67bf7a2438e7858f6d2d3a1b72da0f65edc1e4ac\
+                 c12b9eec264f69f7b50b44fee7ad80d3d3029bdc7704c2836164d901392aadc5903c3dbb789ac14e\
+                 2a6a79dbee1796ce2bcd0f39d72a298696f22c5bc84ac4e719148f77e87a8d747ff08698544ba7e9\
+                 b0ad9c2b209e2df9ce00905a19a510086ae6d32bc2a8ece3f63ac2dfb19868f4e4c4d8bcb7d1ccbd\
+                 2a9a91d80314fb2e89f91693c36851ba27ea1fdc118b6aad0b8be4f02d7bf7c7051905461ad0c7cb\
+                 1cfd520e3203c4ad20248a2afb3ae7dca2f27992e0f233826acd487524c7b019c08c5ecc00bae8c4\
+                 2b0c43a3e9cce3a8c44a3017ec1b10c0813aff22bb7902c9c61e16a57bfccb98e4112a15fe562303\
+                 5f28d75104c302c1b8cab77a70cdca9c239e869cb42dad98bd2d656e608023a325abe007c6875329\
+                 824e571d5c8ea370078aac6ac758293e4c30729db81e0c8f962753b9007a4c486a7a31f658116080\
+                 3e95ae6e4f5228b0860489041490e51e88dedc874a49d18abcbda88bc3d80406e70d8701aa86b26e\
+                 594139c2f27eb838fd6d73ba0f58cce118c2f9f98539cbaeb82e8a5507ebe49a767fbf04096434ae\
+                 276e67bb48eee2c9e0bc06616acc8d705cae5ab408bf2046dbbf96d79b470b90ed1d7cfb1fd9d7b7\
+                 7148177971032af58b5bb772c823cf91f21b341495dba5d7bbb2408574aa59d3fe29e30019fa0076\
+                 9b7f1864781a037cae0d120261eac8f772d01f7e49ef75c8eabea1ae41dd8b5a89473d2559dd0fad\
+                 c9162561316b318eedec0acdcb64258288bea986bceaf944a27a2fa8ad311c72ac9d75d2800a0656\
+                 80cc2832d509087c5b156006762894be62164d72dfda3756833f33f5793ba545c9660df3485f90ac\
+                 a88fedc2bc1d931e03afd97118dbe0fc7af09ab2307f930e9737b6c4bc8a7d4ab0202c68277a5fc5\
+                 eb32c787e728ed6075c622f18dd83925ce04f4204993b3483ffd5046491a772ac4e1cc11c64a1921\
+                 0bd8d1820cb246ca40ad22a068086590beb06898dc9818c1539100e912fab4a364274d6212223388\
+                 934a2f564ed37a1353cb41954e1c6e1f59495a07b101016dadd680708d415ac504e3968f693fcad1\
+                 128ba263b69e1693c4144875232eb0e60815210aa2ce243b5bb988d7dfa6e04212d43b09bc8efe52\
+                 765bfe903b4a0c5f49ed9add4d6bbc5aebe0b286512054a4bd5655d07a988b62385152310c0a1ce2\
+                 881b0aa8fb93ce2565a519e2d056d7f44830baba1be2a22adf85064c57e30a1898e23d6ca37a59d9\
+                 096986bda40f192c72eb82332a8c149176ec49cd24d7443471b8793288ff90c0895e47fd53ad5600\
+                 df5d484a2c275fbb7f2b55d712cfe530ddc3ff3d2e02eb7f73de7a9f6968f0e85d62043a6eb9cd3e\
+                 7c8c5e61948df226433173708f0ae410abd13c4bab8d89c2f131097a84f9e8bacf5241b01f56153c\
+                 c76e29fb2d86f193e49a47df80630200030f87ce27f7a6eba73a6f5b454b1b4103493c5aeb8a6a8d\
+                 ff8dfac8b40f212fe13e01edc26bf19ae317b8c16641b371c1cdae9dd7fa2d7debcdce376c95d009\
+                 7068d0408461b786331ccf5d5dbb6306cfd25b6b86447a56881cf658cabe49e645fd4d0c60260000\
+                 00000000000143eb0463e272209bffaf9e7cbb1ae2fd6f960049f3ab0be423764c25e0785d71b3ce\
+                 e4ba5bde841d9f66f054289fad5109a5c2c4311d590ef67901a8b0de798834ce87ecb885c6aec7a8\
+                 fc90ac8cabb2ff43ba8206a6647e9b3aafcfa7d316c023b957ef7e641e2e5bea2710a2fae80d271b\
+                 4c34445717c98baddc7a54abeaac1a4ef58a6af74b345e445e23c948a4182e6d0eae3183d3afba58\
+                 422a069df4d3d06da04cca199c9eb2419c552ae429e58a245539540d80191dff862e62374bf181ba\
+                 81af14be1275231b9af1a5d2e70a62e21a63cb14dd7579e50e47f2423449267a693620d95ffa1b6c\
+                 01d6ccd82c90c02a58647b61f31835ac22254bdd40a069f0ba807fd70418d4b99bdf4526ae1059a2\
+                 7acc82c10238e9d8b51b0a67370bb6007512670a882cd4ecb1625ef21297ad9840baf7eeb1aff451\
+                 5228ad63cb06c5821741551cccfbd533d56e2007c7d6f5a867944d25654d007d3aadd8a037ad0b44\
+                 f3988c290b4df68c471ba154546b931d2cc65c38fd72bc98856a73fe04d6492496ea834f3b818131\
+                 49fd8d80c3bf75cec4957c78a9b1754b4d1f63786e75aecd7d3e327d4e4e59fa2bf75d95a1fe5329\
+                 4fb337e26178b784c88342f5eab473cc1b4bb77a91e3240eff123e1a0a5cafe2909ca3843de72023\
+                 bc739539bfe6ba05f6c078e4ed6f85e8cb1212a6f3d0c0d2e77db1e2a3c832fe20f5d681dd3cc3f6\
+                 72d76cf162890d2736023b09086400e15eb1356cddd39d78d59fa8b44247153d486e8b8ddf2a3e73\
+                 0603c14690cb871afd3e3d13ed4b36ce7a4b88b14f2be96caae4296f810ef269d8c02ed691cb5895\
+                 a06716e1601a9bf87ebcf8b5bda02343f2eac3dfa5fc6fc4c8c855fbf70af37da99231f160bb9971\
+                 7a32f4cd17a213aac1cfc39fa80badc7a9125477374c441468c594c1e751104e01f65a47e6039d80\
+                 4fa8550a8800a52f3b087e714acff868b62ceb123319afb65020309116dcea3742157dce1d0b85b8\
+                 f7c0a3ca0c54147c9a17bd15c06630f55666012d7b29ab5147c3b2c7aefb821562516ad902785983\
+                 e1330c489929cccf806810327538ff30fb3a690f9751200e67be85d6220e8b01dae3efc86b7b4340\
+                 68873583c4c0ca1744575be08dcef90211bd26b812858d077ca4dda520080b8cd001d9ef9d8d5085\
+                 e33911de7e3c4bb2580ee9ac5c3833a0c3ea0a1ec473f417fa4b85f6e66d0ff4182c528b711b505f\
+                 320a5d42cbc39b08808137cbb9290b7549ae61465c9dac0d0be6d6eefffffacc8e4d527d346209bd\
+                 14911453e397d9a5e414d32ce5be5895ca737712281d39f94a7e67c2243f95b07ea257d3df7fffa7\
+                 1e0bc169ea6b8e1981c2ac6b3ea14458dfc9166ce57db3fa1a99c6fd1c9973965637a9e7a31e4c6c\
+                 27486e3667bd69049ff3040aff40c560f3c15e3df870dfb0596e2623495e08dccc003113983bf8f2\
+                 dca02ea7af5b2de2f093aac789a5582026eda1d4038d32947016b21bea05d1276100c7a3262654bc\
+                 48b6187ee4d2bae5bb5383d671c59f41981c3d966cd9eb12ac107f3f14e8920f0f25113d92203cae\
+                 3734e9f70422e5e833b9011073bc691f79419b720470b9b895353a005c4356cb8c950fe5a83e2efb\
+                 461eb7d5887977638b40937b73f5e67861aaf0741506c24cf33c8ce68a63350c90e69e022e09efe5\
+                 d6cfe15e4bbe0674c1f4608e0396c281b3453efbba5c40d03962abd55dc9e63532255754a263db4e\
+                 7e1d4984496c7c0048d54442698585a276ddb1bbafa17390f143717fba16b5705b71127c6d615f9a\
+                 4302c0b67ae633d21c38a31c7e68a702a55163ed926093c0857e1902e336dffafc108711b1ac0cc4\
+                 50a774006680c2569a42e5d20f8172bde00bfdea57097c7a377442d47354bf44eeec6320013ef954\
+                 ca79bfd714f61aca47826990c91d607257d4f69986a0a6750b00c0dad72e665584059f589c428e09\
+                 c4f71cc8148ae8c739f89ea848aeda489c800316154cc338a0c6ec82a317cc09c880d6dbe093f579\
+                 aa56826249359edd65554ece318eee4211cf6ab34e2f4fd690352863abfab7d4db199bed131ba142\
+                 06b577b890178fbe7126ab9bf0af0fd959526c1ccb4808b37a9db4c2312c3b2f7292f35c4d7ad150\
+                 e997cb93b39176af7da6a4540e37efb0a4540f536ee3bb96b68ac57f43755b02f0e1e5ed4bcb5063\
+                 c97efead2457405b0b578663a12b8a5623168638a8a76c1eae249f6716aea4b1259e2e4023ba8d23\
+                 eb4ece7961400bf962dcbcb03858a92b56731f482d83baa715de969f82ae22bc7647fa17cae7ca91\
+                 13977b3f1af766eedb5e96e481ba319eb12044af64b971aeb054bde5e0440e6c9c5f5eb1aa5bdfe7\
+                 02fef1f983aaab12cb666a2ba87f9d7d7a00f1bed5f455d3748600ec7f28a781868e1896617cf0bb\
+                 db81a33dadca46df29554db003ded2c228c660e97d26546e58dba32fb48e1a8091c1442e6291c915\
+                 a412074af0b5bb28e9a00eda57bfa6c1974f7f41a8680a31ebfa34ae7f98bf39d657fd80776f03b5\
+                 bf1ecff9edaeb66e938987056d2a47c246d47d91b74616345c827792311be6524e285b1db60a7fc2\
+                 7bf3d5c5c31d34f072595b61f1c35fb26312ce337598376b81b93c3a1d60959224bb32807f9c37c3\
+                 227c23c0120e07e15f46d87ea017175f2f7105e08325054eb13efe3330fbef7eec8d0c9a598ce327\
+                 046372e37a6871abd2bb164441dad8e9d31fc3009a3cbc87b0a43abb1ddbfbe311028e747bc8caa9\
+                 ea8d45d37a6c2a0a9ba95190e0399b9449d06fa78c455c1c5ee47c0b7f3e01d1d4d1370a7377757e\
+                 3e02dd6e495996fbcefab719939490279f0847102c0a697b65bd420512fb9af201a796a0164b798d\
+                 ac45c8752078dc20b7123e033d01eb6f089d78627ac54271f1f7de98564d2860da314ce69b882e7f\
+                 03db8f1b3f630c808c2fa047eb3a1b39c48154bd54e6be5259364ad7cb82178fccd42fd8819370d1\
+                 c93d29eaad58d0398de41e55d3394fc444a9f8b6a923e70caea1a77fbe4e1560a7bf5d4b953ba617\
+                 5faa13eee9a92a9e6d7148c1198ffadaf72db1e64a066bc0d82e77a067b66eea0f4f4f9f75fcd27f\
+                 e5038942054b85325f2f6e003aa6b1c27580d95d030b63f706dfdd2b8e89c5ce80f2c54cfebe8433\
+                 469fd90ce9bff1462571af7c2eef3a266f5c8f875e3bc197c09d5476de7214a004673857d6080d57\
+                 e31b19954a3ca8f45601bfe30c54dd51cc22a5b17517868c95b868e96a9c14520dfbeb926030a9b2\
+                 13e9087973328b72d16035cb22e9835d74a9e8107f883d45f303b6e8d50721888c325b857f041c7f\
+                 2fe9260b64d04440bcbc2757504c1539c31632e65bb06f386aad9c65cd33c893c95d9470b9d7474d\
+                 5582a29a0146d35ce27b534f29eb161d73e3baa18638d7077be55251b3c321f0987ce59c53ac25ef\
+                 f5fb16df2009bd33a01cca1f16af5b78eb63ae751c89f42b7b411b3d4787b7c7090f8f069f90b1b4\
+                 92926f4813e2a9e1c0739c073bea3ec2983ef713a880e33f5fa0b2f203f03fc6d9670bd44f6a2716\
+                 8fcdb9c538b2d8800b2f5d8f50b1b282d139a24f49d17942d1305042e2fc26116d680816721cb2a6\
+                 ecf0c7e68f85464766e832a0ac65601da66f00ca270b8db5d7f1d789b518a5d2d27027be5d685b6d\
+                 f86c8e2ac7ec2f1b191c75bdfc0dc2b4b22c14bc3d828809adead46542c90cc3a0febd4391fd12cb\
+                 a8625de98d0b709b5d56efbd580a0442687d0e677b8515fe7a47e2c002d1ee16c157c0d3f09b9ed1\
+                 4b948413d1b88056874395c24c9694781c108ce8aa6b0ac7889c977f030270985fa68be1d2774f4c\
+                 7b9c06f1aab326301a56d424f5300c2c60c8220150f85192cde41282366066e87fe26d6ee5b7d284\
+                 537d62dc698b5913f57c72b95c6ec43291274acb2c8f3109b82fad4b8a135b12163102f317b409a0\
+                 4f57e93ebe311d89a31cb17d33817145548c92a6339c988d1eee7d9d57475709c29f829ebb8ad5fe\
+                 6113498b9ce6eb391aa806d45fdcfac594b93b12c1e1635a6469248af357891c6bb24fee7cb38b65\
+                 35af8462f90405e0f21ce4db4b20cb8c033c039c4e3561c3de87cc1687e0a6944ce9475179956392\
+                 cbf2aa5c0ca9d487fbbb77d9a0856e1cc5b131b37f2e337a97e18b761c1fa5cb1a40845b5e2a12a7\
+                 240ba050762e79aa754992218b092dfb22da3fdaf9573589e767f12fdbe567593c581e9482209ee8\
+                 c25258ee540b55e90c898e7c834676db7b9b8e11b74049a4447d4267adddcaa85926a9ff56c128b2\
+                 9b8956423fc994355e90fb48caa17d4156b80bfe668cda0a0ec108a487f4a7193e25cb78fea170dc\
+                 6956487744de0c263bd0c1847c5df09fad541b2be2d557896b566ae50186f922528705e5d8e7785f\
+                 8ef9568f5edbb36e2d46ffc89b1b83439ff07ba45c3d8f741d0000",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            tx.txid().to_string(),
+            "eda1d7c0f47fe209c3b5e98ec4bf48fc03f78ce8dcb9742683751fac42f7e4ed"
+        );
+        assert!(tx.input[0].has_issuance());
+        let sighash =
+            SighashCache::new(&tx).segwitv0_sighash(0, &script_code, value, EcdsaSighashType::All);
+        assert_eq!(sighash_for(&tx, &script_code, value)[..], sighash[..]);
 
-        // The last constraint satisfied must be the covenant pk
+        // Inputs without issuances contribute a single zero byte
         assert_eq!(
-            constraints.last().unwrap(),
-            &SatisfiedConstraint::PublicKey {
-                key_sig: interpreter::KeySigPair::Ecdsa(desc.pk, (sig, EcdsaSighashType::All))
-            }
+            hash_issuances(&[AssetIssuance::default(), AssetIssuance::default()]),
+            sha256d::Hash::hash(&[0, 0]),
         );
-        Ok(())
     }
 
     #[test]
@@ -299,6 +2185,13 @@ mod tests {
         )
         .unwrap();
 
+        // The covenant key also signs for the miniscript
+        _satisfy_and_interpret(
+            Descriptor::from_str(&format!("elcovwsh({},pk({}))", pks[0], pks[0])).unwrap(),
+            sks[0],
+        )
+        .unwrap();
+
         // Version tests
         // Satisfy with 2, err with 3
         _satisfy_and_interpret(
@@ -350,6 +2243,61 @@ mod tests {
         _satisfy_and_interpret(desc, sks[0]).unwrap_err();
     }
 
+    #[test]
+    fn sh_wrapped_cov_spend() {
+        let (pks, sks) = setup_keys(1);
+        let desc =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elcovwsh({},1)", pks[0])).unwrap();
+        let desc = desc.as_cov().unwrap();
+
+        let spend_tx = cov_spend_tx();
+        let script_code = desc.cov_script_code();
+        let cov_sat = cov_satisfier(&spend_tx, &script_code);
+        let mut sig_map = HashMap::new();
+        sig_map.insert(pks[0], sign_sighash(&cov_sat, &sks[..1])[0]);
+        let (wit, _) = desc.get_satisfaction((&cov_sat, &sig_map)).unwrap();
+
+        // Nest the p2wsh program in p2sh
+        let wsh_spk = desc.script_pubkey();
+        let spk = wsh_spk.to_p2sh();
+        let script_sig = script::Builder::new()
+            .push_slice(&wsh_spk[..])
+            .into_script();
+        let interpreter =
+            Interpreter::from_txdata(&spk, &script_sig, &wit, Sequence::ZERO, LockTime::ZERO)
+                .unwrap();
+        assert!(interpreter.is_segwit_v0());
+        assert_eq!(
+            interpreter.inferred_descriptor().unwrap().as_cov().unwrap(),
+            desc
+        );
+        let msg = secp256k1_zkp::Message::from_digest_slice(&cov_sat.segwit_sighash().unwrap()[..])
+            .unwrap();
+        let constraints = interpreter
+            .iter_assume_sigs()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            constraints.last().unwrap(),
+            &SatisfiedConstraint::CovenantSig {
+                key_sig: interpreter::KeySigPair::Ecdsa(pks[0], sig_map[&pks[0]]),
+                message: msg,
+            }
+        );
+
+        // The witness script must match the nested program
+        let other_wsh = Script::from(vec![0]).to_v0_p2wsh();
+        let script_sig = script::Builder::new()
+            .push_slice(&other_wsh[..])
+            .into_script();
+        let spk = other_wsh.to_p2sh();
+        match Interpreter::from_txdata(&spk, &script_sig, &wit, Sequence::ZERO, LockTime::ZERO) {
+            Err(interpreter::Error::IncorrectWScriptHash { .. }) => {}
+            Err(e) => panic!("Unexpected error {}", e),
+            Ok(_) => panic!("Mismatched witness script accepted"),
+        }
+    }
+
     // Fund output and spend tx are tests handy with code for
     // running with regtest mode and testing that the scripts
     // are accepted by elementsd
@@ -375,6 +2323,7 @@ mod tests {
         );
     }
     #[test]
+    #[allow(deprecated)]
     fn spend_tx() {
         let (pks, sks) = setup_keys(5);
         let desc =
@@ -454,7 +2403,7 @@ mod tests {
         let pk_sat = SimpleSat { sig, pk: pks[0] };
 
         // A pair of satisfiers is also a satisfier
-        let (wit, ss) = desc.get_satisfaction((cov_sat, pk_sat)).unwrap();
+        let (wit, ss) = desc.get_satisfaction((&cov_sat, &pk_sat)).unwrap();
         let interpreter = Interpreter::from_txdata(
             &desc.script_pubkey(),
             &ss,
@@ -466,12 +2415,61 @@ mod tests {
         // Check that everything is executed correctly with dummysigs
         let constraints: Result<Vec<_>, _> = interpreter.iter_assume_sigs().collect();
         constraints.expect("Covenant incorrect satisfaction");
+
+        // The same witness, split into its parts
+        let parts = desc
+            .satisfy_parts((&cov_sat, &pk_sat), false, CovSighashType::All)
+            .unwrap();
+        assert_eq!(parts.sigs, vec![sig.0.serialize_der().to_vec()]);
+        assert_eq!(parts.n_version, serialize(&spend_tx.version));
+        assert_eq!(parts.n_locktime, serialize(&spend_tx.lock_time));
+        assert_eq!(
+            parts.sighash_u32,
+            serialize(&EcdsaSighashType::All.as_u32())
+        );
+        assert_eq!(
+            desc.satisfy_inner((&cov_sat, &pk_sat)).unwrap(),
+            parts.ms_witness
+        );
+        // get_satisfaction also pushes the witness script
+        assert_eq!(Vec::from(parts.clone())[..], wit[..wit.len() - 1]);
+
+        // Tampering with a sighash item (nLockTime) must fail the CSFS check
+        let mut bad_parts = parts;
+        bad_parts.n_locktime[0] ^= 1;
+        let mut bad_wit = Vec::from(bad_parts);
+        bad_wit.push(wit[wit.len() - 1].clone());
+        let interpreter = Interpreter::from_txdata(
+            &desc.script_pubkey(),
+            &ss,
+            &bad_wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        match interpreter.iter_assume_sigs().last() {
+            Some(Err(interpreter::Error::CovSighashMismatch)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
         // Commented Demo test code:
         // 1) Send 0.002 btc to above address
         // 2) Create a tx by filling up txid
         // 3) Send the tx
         assert_eq!(witness_size(&wit), 385);
         assert_eq!(wit.len(), 13);
+        assert!(4 + serialize(&wit).len() <= desc.max_satisfaction_weight().unwrap());
+        // The witness is the only part of the input that grows
+        assert_eq!(
+            desc.max_weight_to_satisfy().unwrap(),
+            desc.max_satisfaction_weight().unwrap() - 4 - 1
+        );
+        assert!(serialize(&wit).len() - 1 <= desc.max_weight_to_satisfy().unwrap());
+        assert_eq!(
+            Descriptor::from(desc.clone())
+                .max_weight_to_satisfy()
+                .unwrap(),
+            desc.max_weight_to_satisfy().unwrap()
+        );
         // spend_tx.input[0].witness.script_witness = wit;
         // use elements::encode::serialize_hex;
         // println!("{}", serialize_hex(&spend_tx));
@@ -497,4 +2495,324 @@ mod tests {
             witness: TxInWitness::default(),
         }
     }
+
+    // A transaction spending a single covenant output to a single output
+    // of 99_000 units of `BTC_ASSET`
+    fn cov_spend_tx() -> Transaction {
+        let mut spend_tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![txin_from_txid_vout(
+                "141f79c7c254ee3a9a9bc76b4f60564385b784bdfc1882b25154617801fe2237",
+                1,
+            )],
+            output: vec![TxOut::default()],
+        };
+        spend_tx.output[0].value = confidential::Value::Explicit(99_000);
+        spend_tx.output[0].asset =
+            confidential::Asset::Explicit(AssetId::from_slice(&BTC_ASSET).unwrap());
+        spend_tx
+    }
+
+    // Satisfier for the first input of `spend_tx`, spending 100_000 units
+    // with `SIGHASH_ALL`
+    fn cov_satisfier<'tx>(
+        spend_tx: &'tx Transaction,
+        script_code: &'tx Script,
+    ) -> LegacyCovSatisfier<'tx, 'tx> {
+        LegacyCovSatisfier::new_segwitv0(
+            spend_tx,
+            0,
+            confidential::Value::Explicit(100_000),
+            script_code,
+            EcdsaSighashType::All,
+        )
+    }
+
+    // Sign the segwit v0 sighash of `cov_sat` with each of `sks`
+    fn sign_sighash(
+        cov_sat: &LegacyCovSatisfier,
+        sks: &[secp256k1_zkp::SecretKey],
+    ) -> Vec<ElementsSig> {
+        let sighash_u256 = cov_sat.segwit_sighash().unwrap();
+        let msg = secp256k1_zkp::Message::from_digest_slice(&sighash_u256[..]).unwrap();
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        sks.iter()
+            .map(|sk| (secp.sign_ecdsa(&msg, sk), EcdsaSighashType::All))
+            .collect()
+    }
+
+    fn tap_string_rtt(desc_str: &str) {
+        let desc = TapCSFSCov::<String, CovenantExt<CovExtArgs>>::from_str(desc_str).unwrap();
+        assert_eq!(format!("{:#}", desc), desc_str);
+    }
+
+    #[test]
+    fn parse_tap_cov() {
+        tap_string_rtt("eltapcov(A,pk(B))");
+        tap_string_rtt("eltapcov(A,or_i(pk(B),pk(C)))");
+        tap_string_rtt("eltapcov(A,multi_a(2,B,C,D))");
+        tap_string_rtt("eltapcov(A,and_v(v:pk(B),pk(C)))");
+        // Segwit only fragments are not allowed
+        TapCSFSCov::<String, CovenantExt<CovExtArgs>>::from_str("eltapcov(A,multi(2,B,C))")
+            .unwrap_err();
+        // Parsing runs the same checks as `new`: the satisfaction, the
+        // sighash items and the signature must fit in the stack
+        let keys: Vec<String> = (0..990).map(|i| format!("K{}", i)).collect();
+        let desc_str = format!("eltapcov(A,multi_a(1,{}))", keys.join(","));
+        match TapCSFSCov::<String, CovenantExt<CovExtArgs>>::from_str(&desc_str) {
+            Err(Error::ImpossibleSatisfaction) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn tap_script_encode_test() {
+        let (pks, _sks) = setup_keys(5);
+        let xpks: Vec<_> = pks
+            .iter()
+            .map(|pk| pk.inner.x_only_public_key().0)
+            .collect();
+
+        for desc_str in [
+            format!("eltapcov({},pk({}))", xpks[0], xpks[1]),
+            format!(
+                "eltapcov({},or_i(pk({}),pk({})))",
+                xpks[0], xpks[1], xpks[2]
+            ),
+            format!(
+                "eltapcov({},multi_a(2,{},{},{}))",
+                xpks[0], xpks[1], xpks[2], xpks[3]
+            ),
+            format!(
+                "eltapcov({},and_v(v:pk({}),pk({})))",
+                xpks[0], xpks[1], xpks[2]
+            ),
+        ] {
+            let desc =
+                TapCSFSCov::<bitcoin::key::XOnlyPublicKey, CovenantExt<CovExtArgs>>::from_str(
+                    &desc_str,
+                )
+                .unwrap();
+            let parsed =
+                TapCSFSCov::<_, CovenantExt<CovExtArgs>>::parse_insane(&desc.encode()).unwrap();
+            assert_eq!(parsed, desc);
+        }
+        // Legacy covenants are not taproot covenants and vice versa
+        let legacy = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elcovwsh({},pk({}))",
+            pks[0], pks[1]
+        ))
+        .unwrap();
+        TapCSFSCov::<_, CovenantExt<CovExtArgs>>::parse_insane(&legacy.as_cov().unwrap().encode())
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_tap_sanity_check_limits() {
+        let (pks, _sks) = setup_keys(1);
+        let xpk = pks[0].inner.x_only_public_key().0;
+        let cov_script = script::Builder::new().verify_tap_cov(&xpk).into_script();
+        assert_eq!(cov_script.len(), tap_cov::TAP_COV_SCRIPT_SIZE);
+        assert_eq!(script_internals::tap_sighash_tag_prefix().len(), 64);
+    }
+
+    fn _tap_satisfy_and_interpret(
+        desc_str: &str,
+        cov_sk: secp256k1_zkp::SecretKey,
+    ) -> Result<(), Error> {
+        use elements::hashes::Hash;
+        use elements::sighash::{Prevouts, SighashCache};
+        use elements::{BlockHash, SchnorrSig, SchnorrSighashType};
+
+        let desc =
+            TapCSFSCov::<bitcoin::key::XOnlyPublicKey, CovenantExt<CovExtArgs>>::from_str(desc_str)
+                .unwrap();
+        let genesis_hash = BlockHash::from_slice(&[0xab; 32]).unwrap();
+        let mut spend_tx = cov_spend_tx();
+        spend_tx.output[0].script_pubkey = desc.script_pubkey(); // send back to self
+        let spent_utxos = vec![TxOut {
+            script_pubkey: desc.script_pubkey(),
+            value: confidential::Value::Explicit(100_000),
+            asset: confidential::Asset::Explicit(AssetId::from_slice(&BTC_ASSET).unwrap()),
+            ..Default::default()
+        }];
+
+        // Sign the script spend with the covenant key
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let sighash = SighashCache::new(&spend_tx)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&spent_utxos),
+                desc.leaf_hash(),
+                SchnorrSighashType::All,
+                genesis_hash,
+            )
+            .unwrap();
+        let keypair = secp256k1_zkp::Keypair::from_secret_key(&secp, &cov_sk);
+        let sig = secp.sign_schnorr_no_aux_rand(
+            &secp256k1_zkp::Message::from_digest_slice(&sighash[..]).unwrap(),
+            &keypair,
+        );
+        let sig = SchnorrSig {
+            sig,
+            hash_ty: SchnorrSighashType::All,
+        };
+
+        // For satisfying the Pk part of the covenant
+        struct SimpleSat {
+            sig: SchnorrSig,
+            pk: bitcoin::key::XOnlyPublicKey,
+        }
+
+        impl Satisfier<bitcoin::key::XOnlyPublicKey> for SimpleSat {
+            fn lookup_tap_leaf_script_sig(
+                &self,
+                pk: &bitcoin::key::XOnlyPublicKey,
+                _: &elements::taproot::TapLeafHash,
+            ) -> Option<SchnorrSig> {
+                if *pk == self.pk {
+                    Some(self.sig)
+                } else {
+                    None
+                }
+            }
+        }
+
+        let cov_sat = TapCovSatisfier::new(&spend_tx, &spent_utxos, 0, genesis_hash).unwrap();
+        match TapCovSatisfier::new(&spend_tx, &spent_utxos, 1, genesis_hash) {
+            Err(Error::CovError(CovError::InputIndexOutOfBounds {
+                index: 1,
+                n_inputs: 1,
+            })) => {}
+            res => panic!("Unexpected result {:?}", res.map(|_| ())),
+        }
+        match TapCovSatisfier::new(&spend_tx, &[], 0, genesis_hash) {
+            Err(Error::CovError(CovError::SpentUtxosMismatch {
+                n_utxos: 0,
+                n_inputs: 1,
+            })) => {}
+            res => panic!("Unexpected result {:?}", res.map(|_| ())),
+        }
+        let pk_sat = SimpleSat { sig, pk: desc.pk };
+        let (wit, ss) = desc.get_satisfaction((cov_sat, pk_sat))?;
+        assert_eq!(wit[0].len(), 64);
+        assert_eq!(wit[14].len(), 32); // genesis hash
+                                       // The witness is the only part of the input that grows
+        assert!(serialize(&wit).len() - 1 <= desc.max_weight_to_satisfy().unwrap());
+        #[allow(deprecated)]
+        let max_satisfaction_weight = desc.max_satisfaction_weight().unwrap();
+        assert_eq!(
+            desc.max_weight_to_satisfy().unwrap(),
+            max_satisfaction_weight - 4 - 1
+        );
+
+        let interpreter = Interpreter::from_txdata(
+            &desc.script_pubkey(),
+            &ss,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert!(interpreter.is_taproot_v1_script_spend());
+        assert_eq!(interpreter.control_block(), Some(&desc.control_block()));
+        assert_eq!(
+            interpreter.leaf_version(),
+            Some(elements::taproot::LeafVersion::default())
+        );
+        assert_eq!(interpreter.merkle_path_len(), Some(0));
+        assert_eq!(interpreter.annex(), None);
+        let txenv = TxEnv::new(&spend_tx, &spent_utxos, 0).unwrap();
+        // Check all the signatures, including the one in CHECKSIGFROMSTACK
+        let constraints = interpreter
+            .iter(&secp, &txenv, genesis_hash)
+            .collect::<Result<Vec<_>, _>>()
+            .expect("If satisfy succeeds, interpret must succeed");
+        assert_eq!(
+            constraints.last().unwrap(),
+            &SatisfiedConstraint::CovenantSig {
+                key_sig: interpreter::KeySigPair::Schnorr(desc.pk, sig),
+                message: secp256k1_zkp::Message::from_digest_slice(&sighash[..]).unwrap(),
+            }
+        );
+
+        // Tampering with any sighash item must fail the covenant check
+        let mut bad_wit = wit.clone();
+        bad_wit[13][0] ^= 1; // version
+        let interpreter = Interpreter::from_txdata(
+            &desc.script_pubkey(),
+            &ss,
+            &bad_wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert!(interpreter
+            .iter_assume_sigs()
+            .collect::<Result<Vec<_>, _>>()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn tap_satisfy_and_interpret() {
+        let (pks, sks) = setup_keys(5);
+        let xpks: Vec<_> = pks
+            .iter()
+            .map(|pk| pk.inner.x_only_public_key().0)
+            .collect();
+        _tap_satisfy_and_interpret(&format!("eltapcov({},1)", xpks[0]), sks[0]).unwrap();
+        // Missing signature for the inner miniscript
+        _tap_satisfy_and_interpret(&format!("eltapcov({},pk({}))", xpks[0], xpks[1]), sks[0])
+            .unwrap_err();
+    }
+}
+
+#[cfg(miniscript_bench)]
+mod benches {
+    use std::str::FromStr;
+
+    use elements::secp256k1_zkp;
+    use test::{black_box, Bencher};
+
+    use super::{CompiledLegacyCSFSCov, LegacyCSFSCov};
+    use crate::descriptor::Descriptor;
+    use crate::extensions::{CovExtArgs, CovenantExt};
+
+    fn large_cov() -> LegacyCSFSCov<bitcoin::PublicKey, CovenantExt<CovExtArgs>> {
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let keys: Vec<_> = (1..=20u8)
+            .map(|i| {
+                let sk = secp256k1_zkp::SecretKey::from_slice(&[i; 32]).expect("secret key");
+                bitcoin::PublicKey::new(secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk))
+                    .to_string()
+            })
+            .collect();
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elcovwsh({},multi(10,{}))",
+            keys[0],
+            keys[1..].join(",")
+        ))
+        .expect("parsing");
+        desc.as_cov().expect("covenant").clone()
+    }
+
+    #[bench]
+    pub fn cov_script_pubkey_reencode(bh: &mut Bencher) {
+        let cov = large_cov();
+        bh.iter(|| {
+            black_box(cov.script_pubkey());
+            black_box(cov.inner_script());
+        });
+    }
+
+    #[bench]
+    pub fn cov_script_pubkey_compiled(bh: &mut Bencher) {
+        let compiled = CompiledLegacyCSFSCov::new(large_cov());
+        bh.iter(|| {
+            black_box(compiled.script_pubkey());
+            black_box(compiled.inner_script());
+        });
+    }
 }
@@ -14,13 +14,689 @@
 //
 //! Covenant Descriptor Satisfaction
 
+use std::cell::RefCell;
+use std::fmt;
+use std::iter::FromIterator;
+
 use elements::encode::Encodable;
 use elements::hashes::{sha256d, Hash};
 use elements::sighash::SighashCache;
-use elements::{self, confidential, EcdsaSighashType, OutPoint, Script, Sighash, Transaction};
+use elements::{
+    self, confidential, AssetIssuance, BlockHash, EcdsaSighashType, OutPoint, Script, Sighash,
+    Transaction, TxOut,
+};
 
+use super::tap_cov::check_tap_tx_env;
 use super::CovError;
-use crate::{MiniscriptKey, Satisfier, ToPublicKey};
+use crate::{Error, MiniscriptKey, Satisfier, ToPublicKey};
+
+/// The outputs committed to by the legacy covenant sighash.
+/// Mirrors the segwit v0 sighash types without ANYONECANPAY.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CovSighashType {
+    /// Commit to all the outputs(SIGHASH_ALL)
+    All,
+    /// Commit to none of the outputs(SIGHASH_NONE)
+    None,
+    /// Commit only to the output at the given index(SIGHASH_SINGLE).
+    /// This must be the index of the input being spent for the
+    /// sighash to match the transaction sighash.
+    Single(usize),
+}
+
+impl CovSighashType {
+    /// The corresponding ecdsa sighash type
+    pub fn ecdsa_sighash_type(&self) -> EcdsaSighashType {
+        match *self {
+            CovSighashType::All => EcdsaSighashType::All,
+            CovSighashType::None => EcdsaSighashType::None,
+            CovSighashType::Single(_) => EcdsaSighashType::Single,
+        }
+    }
+
+    /// The sighash type as serialized in the sighash(item 10). The
+    /// covenant script appends the first byte of this to the signature.
+    pub fn as_u32(&self) -> u32 {
+        self.ecdsa_sighash_type().as_u32()
+    }
+
+    /// The sighash type of the sighash_u32(item 10) looked up from `s`.
+    /// SIGHASH_SINGLE commits to the output at the index of the input
+    /// being spent, as looked up by [`Satisfier::lookup_curr_inp`].
+    pub(crate) fn from_satisfier<Pk, S>(s: &S) -> Result<Self, CovError>
+    where
+        Pk: MiniscriptKey + ToPublicKey,
+        S: Satisfier<Pk>,
+    {
+        let sighash_u32 = s
+            .lookup_sighashu32()
+            .ok_or(CovError::MissingSighashItem(CovSighashItem::SighashU32))?;
+        match EcdsaSighashType::from_standard(sighash_u32) {
+            Ok(EcdsaSighashType::All) => Ok(CovSighashType::All),
+            Ok(EcdsaSighashType::None) => Ok(CovSighashType::None),
+            Ok(EcdsaSighashType::Single) => s
+                .lookup_curr_inp()
+                .map(CovSighashType::Single)
+                .ok_or(CovError::MissingInputIndex),
+            // The covenant always commits to all the inputs
+            _ => Err(CovError::UnsupportedSighashType(sighash_u32)),
+        }
+    }
+
+    /// Compute hashOutputs(item 8) for this sighash type
+    pub(crate) fn hash_outputs(&self, outputs: &[TxOut]) -> Result<sha256d::Hash, CovError> {
+        let outputs = match *self {
+            CovSighashType::All => outputs,
+            CovSighashType::None => return Ok(sha256d::Hash::all_zeros()),
+            CovSighashType::Single(i) => match outputs.get(i) {
+                Some(out) => std::slice::from_ref(out),
+                None => {
+                    return Err(CovError::SingleOutputOutOfRange {
+                        index: i,
+                        n_outputs: outputs.len(),
+                    })
+                }
+            },
+        };
+        Ok(hash256_arr(outputs))
+    }
+}
+
+/// Double sha256 of the concatenated consensus encodings of `items`.
+///
+/// This is the Elements segwit v0 (BIP143) midstate primitive behind
+/// the aggregate sighash items: hashPrevouts over the outpoints,
+/// hashSequence over the nSequences and hashOutputs over the outputs.
+/// See [`Hash256Engine`] to add the items one at a time and
+/// [`hash_issuances`] for hashIssuances.
+pub fn hash256_arr<T: Encodable>(items: &[T]) -> sha256d::Hash {
+    let mut engine = Hash256Engine::new();
+    for item in items {
+        engine.input(item);
+    }
+    engine.finish()
+}
+
+/// Streaming version of [`hash256_arr`], for items that are not
+/// available as a slice, e.g. the outpoints of the inputs of a
+/// transaction.
+#[derive(Clone, Default)]
+pub struct Hash256Engine(<sha256d::Hash as Hash>::Engine);
+
+impl Hash256Engine {
+    /// Create an engine without any items
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the consensus encoding of `item`
+    pub fn input<T: Encodable + ?Sized>(&mut self, item: &T) {
+        item.consensus_encode(&mut self.0)
+            .expect("engines don't error");
+    }
+
+    /// The double sha256 of all the items added so far
+    pub fn finish(self) -> sha256d::Hash {
+        sha256d::Hash::from_engine(self.0)
+    }
+}
+
+/// Compute hashIssuances (item 3b) from the asset issuances of all the
+/// transaction inputs, in input order. As in Elements consensus, an
+/// input without an issuance contributes a single 0x00 byte.
+///
+/// [`Satisfier::lookup_hashissuances`] implementations can delegate to this.
+pub fn hash_issuances<'a, I>(issuances: I) -> sha256d::Hash
+where
+    I: IntoIterator<Item = &'a AssetIssuance>,
+{
+    let mut engine = Hash256Engine::new();
+    for issuance in issuances {
+        if issuance.is_null() {
+            engine.input(&0u8);
+        } else {
+            engine.input(issuance);
+        }
+    }
+    engine.finish()
+}
+
+/// The items of the legacy covenant sighash message in the
+/// order they are serialized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CovSighashItem {
+    /// nVersion of the transaction (item 1)
+    NVersion,
+    /// hashPrevouts (item 2)
+    HashPrevouts,
+    /// hashSequence (item 3)
+    HashSequence,
+    /// ELEMENTS EXTRA hashIssuances (item 3b)
+    HashIssuances,
+    /// outpoint of the input (item 4)
+    Outpoint,
+    /// scriptCode of the input (item 5)
+    ScriptCode,
+    /// value of the output spent by this input (item 6)
+    Value,
+    /// nSequence of the input (item 7)
+    NSequence,
+    /// the outputs committed to by hashOutputs (item 8)
+    Outputs,
+    /// nLocktime of the transaction (item 9)
+    NLocktime,
+    /// sighash type of the signature (item 10)
+    SighashU32,
+}
+
+impl CovSighashItem {
+    /// All the sighash items in serialization order
+    pub const ALL: [CovSighashItem; 11] = [
+        CovSighashItem::NVersion,
+        CovSighashItem::HashPrevouts,
+        CovSighashItem::HashSequence,
+        CovSighashItem::HashIssuances,
+        CovSighashItem::Outpoint,
+        CovSighashItem::ScriptCode,
+        CovSighashItem::Value,
+        CovSighashItem::NSequence,
+        CovSighashItem::Outputs,
+        CovSighashItem::NLocktime,
+        CovSighashItem::SighashU32,
+    ];
+}
+
+impl fmt::Display for CovSighashItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match *self {
+            CovSighashItem::NVersion => "nVersion",
+            CovSighashItem::HashPrevouts => "hashPrevouts",
+            CovSighashItem::HashSequence => "hashSequence",
+            CovSighashItem::HashIssuances => "hashIssuances",
+            CovSighashItem::Outpoint => "outpoint",
+            CovSighashItem::ScriptCode => "scriptCode",
+            CovSighashItem::Value => "value",
+            CovSighashItem::NSequence => "nSequence",
+            CovSighashItem::Outputs => "outputs",
+            CovSighashItem::NLocktime => "nLocktime",
+            CovSighashItem::SighashU32 => "sighash type",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A set of [`CovSighashItem`]s
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct CovSighashItems(u16);
+
+impl CovSighashItems {
+    /// Add an item to the set
+    pub fn insert(&mut self, item: CovSighashItem) {
+        self.0 |= 1 << item as u16;
+    }
+
+    /// Whether the set contains the item
+    pub fn contains(&self, item: CovSighashItem) -> bool {
+        self.0 & (1 << item as u16) != 0
+    }
+
+    /// Whether the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Iterate over the items of the set in serialization order
+    pub fn iter(&self) -> impl Iterator<Item = CovSighashItem> + '_ {
+        CovSighashItem::ALL
+            .iter()
+            .copied()
+            .filter(move |item| self.contains(*item))
+    }
+}
+
+impl FromIterator<CovSighashItem> for CovSighashItems {
+    fn from_iter<I: IntoIterator<Item = CovSighashItem>>(iter: I) -> Self {
+        let mut items = CovSighashItems::default();
+        for item in iter {
+            items.insert(item);
+        }
+        items
+    }
+}
+
+impl fmt::Display for CovSighashItems {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, item) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", item)?;
+        }
+        Ok(())
+    }
+}
+
+/// The witness of a legacy covenant spend split into its parts, as
+/// returned by [`super::LegacyCSFSCov::satisfy_parts`]. The sighash
+/// items are consensus serialized. Convert it into the final witness
+/// stack with [`Vec::from`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CovWitness {
+    /// DER encoded covenant signatures, without the sighash type. One
+    /// slot per covenant key in key order, empty for keys that do not sign.
+    pub sigs: Vec<Vec<u8>>,
+    /// nVersion of the transaction (item 1)
+    pub n_version: Vec<u8>,
+    /// hashPrevouts (item 2)
+    pub hash_prevouts: Vec<u8>,
+    /// hashSequence (item 3)
+    pub hash_sequence: Vec<u8>,
+    /// ELEMENTS EXTRA hashIssuances (item 3b)
+    pub hash_issuances: Vec<u8>,
+    /// outpoint of the input (item 4)
+    pub outpoint: Vec<u8>,
+    /// scriptCode of the input (item 5)
+    pub script_code: Vec<u8>,
+    /// value of the output spent by this input (item 6)
+    pub value: Vec<u8>,
+    /// nSequence of the input (item 7)
+    pub n_sequence: Vec<u8>,
+    /// hashOutputs (item 8)
+    pub hash_outputs: Vec<u8>,
+    /// nLocktime of the transaction (item 9)
+    pub n_locktime: Vec<u8>,
+    /// sighash type of the signatures (item 10)
+    pub sighash_u32: Vec<u8>,
+    /// The witness of the miniscript inside the covenant
+    pub ms_witness: Vec<Vec<u8>>,
+}
+
+impl From<CovWitness> for Vec<Vec<u8>> {
+    fn from(wit: CovWitness) -> Self {
+        // The first key's signature is closest to the sighash items
+        let mut stack: Vec<Vec<u8>> = wit.sigs.into_iter().rev().collect();
+        // Item 1 must be at the top of the stack
+        stack.extend([
+            wit.sighash_u32,
+            wit.n_locktime,
+            wit.hash_outputs,
+            wit.n_sequence,
+            wit.value,
+            wit.script_code,
+            wit.outpoint,
+            wit.hash_issuances,
+            wit.hash_sequence,
+            wit.hash_prevouts,
+            wit.n_version,
+        ]);
+        stack.extend(wit.ms_witness);
+        stack
+    }
+}
+
+/// Covenant sighash items collected one at a time, for spends where
+/// the items do not come from a single transaction. Use
+/// [`CovSatisfaction::validate`] to check that all the items are set
+/// before satisfying the covenant.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CovSatisfaction {
+    n_version: Option<u32>,
+    hash_prevouts: Option<sha256d::Hash>,
+    hash_sequence: Option<sha256d::Hash>,
+    hash_issuances: Option<sha256d::Hash>,
+    outpoint: Option<OutPoint>,
+    script_code: Option<Script>,
+    value: Option<confidential::Value>,
+    n_sequence: Option<u32>,
+    outputs: Option<Vec<TxOut>>,
+    n_locktime: Option<u32>,
+    sighash_u32: Option<u32>,
+}
+
+impl CovSatisfaction {
+    /// Create a new CovSatisfaction without any sighash items
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set nVersion of the transaction (item 1)
+    pub fn n_version(mut self, n_version: u32) -> Self {
+        self.n_version = Some(n_version);
+        self
+    }
+
+    /// Set hashPrevouts (item 2)
+    pub fn hash_prevouts(mut self, hash_prevouts: sha256d::Hash) -> Self {
+        self.hash_prevouts = Some(hash_prevouts);
+        self
+    }
+
+    /// Set hashSequence (item 3)
+    pub fn hash_sequence(mut self, hash_sequence: sha256d::Hash) -> Self {
+        self.hash_sequence = Some(hash_sequence);
+        self
+    }
+
+    /// Set hashIssuances (item 3b)
+    pub fn hash_issuances(mut self, hash_issuances: sha256d::Hash) -> Self {
+        self.hash_issuances = Some(hash_issuances);
+        self
+    }
+
+    /// Set hashIssuances (item 3b) from the asset issuances of all the
+    /// transaction inputs, see [`hash_issuances`]
+    pub fn issuances<'a, I>(self, issuances: I) -> Self
+    where
+        I: IntoIterator<Item = &'a AssetIssuance>,
+    {
+        self.hash_issuances(hash_issuances(issuances))
+    }
+
+    /// Set the outpoint of the input (item 4)
+    pub fn outpoint(mut self, outpoint: OutPoint) -> Self {
+        self.outpoint = Some(outpoint);
+        self
+    }
+
+    /// Set the scriptCode of the input (item 5). This is the
+    /// covenant script code, see [`super::LegacyCSFSCov::cov_script_code`]
+    pub fn script_code(mut self, script_code: Script) -> Self {
+        self.script_code = Some(script_code);
+        self
+    }
+
+    /// Set the value of the output spent by the input (item 6)
+    pub fn value(mut self, value: confidential::Value) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    /// Set nSequence of the input (item 7)
+    pub fn n_sequence(mut self, n_sequence: u32) -> Self {
+        self.n_sequence = Some(n_sequence);
+        self
+    }
+
+    /// Set the outputs of the transaction (item 8)
+    pub fn outputs(mut self, outputs: Vec<TxOut>) -> Self {
+        self.outputs = Some(outputs);
+        self
+    }
+
+    /// Set nLocktime of the transaction (item 9)
+    pub fn n_locktime(mut self, n_locktime: u32) -> Self {
+        self.n_locktime = Some(n_locktime);
+        self
+    }
+
+    /// Set the sighash type of the signature (item 10)
+    pub fn sighash_u32(mut self, sighash_u32: u32) -> Self {
+        self.sighash_u32 = Some(sighash_u32);
+        self
+    }
+
+    /// The sighash items that have not been set
+    pub fn missing_items(&self) -> CovSighashItems {
+        let set = [
+            self.n_version.is_some(),
+            self.hash_prevouts.is_some(),
+            self.hash_sequence.is_some(),
+            self.hash_issuances.is_some(),
+            self.outpoint.is_some(),
+            self.script_code.is_some(),
+            self.value.is_some(),
+            self.n_sequence.is_some(),
+            self.outputs.is_some(),
+            self.n_locktime.is_some(),
+            self.sighash_u32.is_some(),
+        ];
+        let mut missing = CovSighashItems::default();
+        for (item, set) in CovSighashItem::ALL.iter().zip(set.iter()) {
+            if !set {
+                missing.insert(*item);
+            }
+        }
+        missing
+    }
+
+    /// Check that all the sighash items are set. Returns all
+    /// the missing items in a single error otherwise.
+    pub fn validate(&self) -> Result<(), CovError> {
+        let missing = self.missing_items();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(CovError::MissingSighashItems(missing))
+        }
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for CovSatisfaction {
+    fn lookup_nversion(&self) -> Option<u32> {
+        self.n_version
+    }
+
+    fn lookup_hashprevouts(&self) -> Option<sha256d::Hash> {
+        self.hash_prevouts
+    }
+
+    fn lookup_hashsequence(&self) -> Option<sha256d::Hash> {
+        self.hash_sequence
+    }
+
+    fn lookup_hashissuances(&self) -> Option<sha256d::Hash> {
+        self.hash_issuances
+    }
+
+    fn lookup_outpoint(&self) -> Option<OutPoint> {
+        self.outpoint
+    }
+
+    fn lookup_scriptcode(&self) -> Option<&Script> {
+        self.script_code.as_ref()
+    }
+
+    fn lookup_value(&self) -> Option<confidential::Value> {
+        self.value
+    }
+
+    fn lookup_nsequence(&self) -> Option<u32> {
+        self.n_sequence
+    }
+
+    fn lookup_outputs(&self) -> Option<&[elements::TxOut]> {
+        self.outputs.as_deref()
+    }
+
+    fn lookup_nlocktime(&self) -> Option<u32> {
+        self.n_locktime
+    }
+
+    fn lookup_sighashu32(&self) -> Option<u32> {
+        self.sighash_u32
+    }
+}
+
+// A sighash item of a `LazyCovSatisfier`, computed on the first lookup
+enum LazyItem<'a, T> {
+    Pending(Box<dyn FnOnce() -> Option<T> + 'a>),
+    Done(Option<T>),
+}
+
+impl<'a, T: Copy> LazyItem<'a, T> {
+    fn get(cell: &RefCell<Self>) -> Option<T> {
+        let mut item = cell.borrow_mut();
+        if let LazyItem::Done(value) = *item {
+            return value;
+        }
+        let value = match std::mem::replace(&mut *item, LazyItem::Done(None)) {
+            LazyItem::Pending(f) => f(),
+            LazyItem::Done(value) => value,
+        };
+        *item = LazyItem::Done(value);
+        value
+    }
+}
+
+impl<'a, T> Default for LazyItem<'a, T> {
+    fn default() -> Self {
+        LazyItem::Done(None)
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for LazyItem<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LazyItem::Pending(_) => f.write_str("<pending>"),
+            LazyItem::Done(ref value) => fmt::Debug::fmt(value, f),
+        }
+    }
+}
+
+/// Covenant sighash items computed on demand. Each item is a closure
+/// that is only called the first time the item is looked up, and its
+/// result is kept for later lookups. Covenant satisfaction first looks
+/// up the sighash type (item 10), as it decides which outputs item 8
+/// commits to, and then items 1-9 in serialization order. It stops at
+/// the first missing item, so the closures of the later items are never
+/// called. This is useful when each item is expensive to get, e.g. from
+/// a remote signer.
+///
+/// Items without a closure are missing. Combine it with a satisfier for
+/// the signatures using a tuple, e.g. `(lazy_sat, sig_sat)`.
+#[derive(Debug, Default)]
+pub struct LazyCovSatisfier<'a> {
+    n_version: RefCell<LazyItem<'a, u32>>,
+    hash_prevouts: RefCell<LazyItem<'a, sha256d::Hash>>,
+    hash_sequence: RefCell<LazyItem<'a, sha256d::Hash>>,
+    hash_issuances: RefCell<LazyItem<'a, sha256d::Hash>>,
+    outpoint: RefCell<LazyItem<'a, OutPoint>>,
+    script_code: RefCell<LazyItem<'a, &'a Script>>,
+    value: RefCell<LazyItem<'a, confidential::Value>>,
+    n_sequence: RefCell<LazyItem<'a, u32>>,
+    outputs: RefCell<LazyItem<'a, &'a [TxOut]>>,
+    n_locktime: RefCell<LazyItem<'a, u32>>,
+    sighash_u32: RefCell<LazyItem<'a, u32>>,
+}
+
+impl<'a> LazyCovSatisfier<'a> {
+    /// Create a new LazyCovSatisfier without any sighash items
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the closure for nVersion of the transaction (item 1)
+    pub fn n_version<F: FnOnce() -> Option<u32> + 'a>(self, f: F) -> Self {
+        *self.n_version.borrow_mut() = LazyItem::Pending(Box::new(f));
+        self
+    }
+
+    /// Set the closure for hashPrevouts (item 2)
+    pub fn hash_prevouts<F: FnOnce() -> Option<sha256d::Hash> + 'a>(self, f: F) -> Self {
+        *self.hash_prevouts.borrow_mut() = LazyItem::Pending(Box::new(f));
+        self
+    }
+
+    /// Set the closure for hashSequence (item 3)
+    pub fn hash_sequence<F: FnOnce() -> Option<sha256d::Hash> + 'a>(self, f: F) -> Self {
+        *self.hash_sequence.borrow_mut() = LazyItem::Pending(Box::new(f));
+        self
+    }
+
+    /// Set the closure for hashIssuances (item 3b)
+    pub fn hash_issuances<F: FnOnce() -> Option<sha256d::Hash> + 'a>(self, f: F) -> Self {
+        *self.hash_issuances.borrow_mut() = LazyItem::Pending(Box::new(f));
+        self
+    }
+
+    /// Set the closure for the outpoint of the input (item 4)
+    pub fn outpoint<F: FnOnce() -> Option<OutPoint> + 'a>(self, f: F) -> Self {
+        *self.outpoint.borrow_mut() = LazyItem::Pending(Box::new(f));
+        self
+    }
+
+    /// Set the closure for the scriptCode of the input (item 5)
+    pub fn script_code<F: FnOnce() -> Option<&'a Script> + 'a>(self, f: F) -> Self {
+        *self.script_code.borrow_mut() = LazyItem::Pending(Box::new(f));
+        self
+    }
+
+    /// Set the closure for the value of the output spent by the input (item 6)
+    pub fn value<F: FnOnce() -> Option<confidential::Value> + 'a>(self, f: F) -> Self {
+        *self.value.borrow_mut() = LazyItem::Pending(Box::new(f));
+        self
+    }
+
+    /// Set the closure for nSequence of the input (item 7)
+    pub fn n_sequence<F: FnOnce() -> Option<u32> + 'a>(self, f: F) -> Self {
+        *self.n_sequence.borrow_mut() = LazyItem::Pending(Box::new(f));
+        self
+    }
+
+    /// Set the closure for the outputs of the transaction (item 8)
+    pub fn outputs<F: FnOnce() -> Option<&'a [TxOut]> + 'a>(self, f: F) -> Self {
+        *self.outputs.borrow_mut() = LazyItem::Pending(Box::new(f));
+        self
+    }
+
+    /// Set the closure for nLocktime of the transaction (item 9)
+    pub fn n_locktime<F: FnOnce() -> Option<u32> + 'a>(self, f: F) -> Self {
+        *self.n_locktime.borrow_mut() = LazyItem::Pending(Box::new(f));
+        self
+    }
+
+    /// Set the closure for the sighash type of the signature (item 10)
+    pub fn sighash_u32<F: FnOnce() -> Option<u32> + 'a>(self, f: F) -> Self {
+        *self.sighash_u32.borrow_mut() = LazyItem::Pending(Box::new(f));
+        self
+    }
+}
+
+impl<'a, Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for LazyCovSatisfier<'a> {
+    fn lookup_nversion(&self) -> Option<u32> {
+        LazyItem::get(&self.n_version)
+    }
+
+    fn lookup_hashprevouts(&self) -> Option<sha256d::Hash> {
+        LazyItem::get(&self.hash_prevouts)
+    }
+
+    fn lookup_hashsequence(&self) -> Option<sha256d::Hash> {
+        LazyItem::get(&self.hash_sequence)
+    }
+
+    fn lookup_hashissuances(&self) -> Option<sha256d::Hash> {
+        LazyItem::get(&self.hash_issuances)
+    }
+
+    fn lookup_outpoint(&self) -> Option<OutPoint> {
+        LazyItem::get(&self.outpoint)
+    }
+
+    fn lookup_scriptcode(&self) -> Option<&Script> {
+        LazyItem::get(&self.script_code)
+    }
+
+    fn lookup_value(&self) -> Option<confidential::Value> {
+        LazyItem::get(&self.value)
+    }
+
+    fn lookup_nsequence(&self) -> Option<u32> {
+        LazyItem::get(&self.n_sequence)
+    }
+
+    fn lookup_outputs(&self) -> Option<&[elements::TxOut]> {
+        LazyItem::get(&self.outputs)
+    }
+
+    fn lookup_nlocktime(&self) -> Option<u32> {
+        LazyItem::get(&self.n_locktime)
+    }
+
+    fn lookup_sighashu32(&self) -> Option<u32> {
+        LazyItem::get(&self.sighash_u32)
+    }
+}
 
 /// A satisfier for Covenant descriptors
 /// that can do transaction introspection
@@ -65,6 +741,34 @@ impl<'tx, 'ptx> LegacyCovSatisfier<'tx, 'ptx> {
         }
     }
 
+    /// Create a new Covsatisfier for v0 spends, taking the value from
+    /// the output spent by the input. All covenant sighash items are
+    /// then computed from the transaction, so that only the signatures
+    /// need to be supplied by another satisfier.
+    ///
+    /// Returns [`CovError::InputIndexOutOfBounds`] if `idx` is out of bounds
+    pub fn from_prevout(
+        tx: &'tx Transaction,
+        idx: u32,
+        prevout: &TxOut,
+        script_code: &'ptx Script,
+        hash_type: EcdsaSighashType,
+    ) -> Result<Self, CovError> {
+        if idx as usize >= tx.input.len() {
+            return Err(CovError::InputIndexOutOfBounds {
+                index: idx as usize,
+                n_inputs: tx.input.len(),
+            });
+        }
+        Ok(Self::new_segwitv0(
+            tx,
+            idx,
+            prevout.value,
+            script_code,
+            hash_type,
+        ))
+    }
+
     /// Easy way to get sighash since we already have
     /// all the required information.
     /// Note that this does not do any caching, so it
@@ -101,15 +805,9 @@ impl<'tx, 'ptx, Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for LegacyCovSati
     }
 
     fn lookup_hashissuances(&self) -> Option<sha256d::Hash> {
-        let mut enc = sha256d::Hash::engine();
-        for txin in &self.tx.input {
-            if txin.has_issuance() {
-                txin.asset_issuance.consensus_encode(&mut enc).unwrap();
-            } else {
-                0u8.consensus_encode(&mut enc).unwrap();
-            }
-        }
-        Some(sha256d::Hash::from_engine(enc))
+        Some(hash_issuances(
+            self.tx.input.iter().map(|txin| &txin.asset_issuance),
+        ))
     }
 
     fn lookup_outpoint(&self) -> Option<OutPoint> {
@@ -139,4 +837,63 @@ impl<'tx, 'ptx, Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for LegacyCovSati
     fn lookup_sighashu32(&self) -> Option<u32> {
         Some(self.hash_type.as_u32())
     }
+
+    fn lookup_curr_inp(&self) -> Option<usize> {
+        Some(self.idx as usize)
+    }
+}
+
+/// A satisfier for taproot Covenant descriptors.
+/// Unlike [`LegacyCovSatisfier`], the taproot sighash commits
+/// to all the spent utxos and the genesis hash of the chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TapCovSatisfier<'tx, 'ptx> {
+    /// The transaction being spent
+    tx: &'tx Transaction,
+    /// The utxos spent by all the inputs of the transaction
+    spent_utxos: &'ptx [TxOut],
+    /// The input index being spent
+    idx: u32,
+    /// The genesis hash of the chain
+    genesis_hash: BlockHash,
+}
+
+impl<'tx, 'ptx> TapCovSatisfier<'tx, 'ptx> {
+    /// Create a new TapCovSatisfier for taproot spends
+    ///
+    /// Returns [`CovError::InputIndexOutOfBounds`] if `idx` is out of bounds
+    /// and [`CovError::SpentUtxosMismatch`] if the number of spent utxos
+    /// does not match the number of inputs.
+    pub fn new(
+        tx: &'tx Transaction,
+        spent_utxos: &'ptx [TxOut],
+        idx: u32,
+        genesis_hash: BlockHash,
+    ) -> Result<Self, Error> {
+        check_tap_tx_env(tx, spent_utxos, idx as usize)?;
+        Ok(Self {
+            tx,
+            spent_utxos,
+            idx,
+            genesis_hash,
+        })
+    }
+}
+
+impl<'tx, 'ptx, Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for TapCovSatisfier<'tx, 'ptx> {
+    fn lookup_tx(&self) -> Option<&elements::Transaction> {
+        Some(self.tx)
+    }
+
+    fn lookup_spent_utxos(&self) -> Option<&[elements::TxOut]> {
+        Some(self.spent_utxos)
+    }
+
+    fn lookup_curr_inp(&self) -> Option<usize> {
+        Some(self.idx as usize)
+    }
+
+    fn lookup_genesis_hash(&self) -> Option<BlockHash> {
+        Some(self.genesis_hash)
+    }
 }
@@ -15,26 +15,156 @@
 //! Script Internals for Covenant Descriptor Support
 
 use bitcoin;
+use bitcoin::key::XOnlyPublicKey;
+use elements::hashes::{sha256, Hash};
 use elements::opcodes::all;
 use elements::script;
+
+use crate::util::varint_len;
+
+/// Tag used by Elements for taproot sighash messages
+pub(crate) const TAP_SIGHASH_TAG: &[u8] = b"TapSighash/elements";
+
+/// The fixed suffix of a taproot script spend sighash message:
+/// key_version (0x00) followed by the default codeseparator
+/// position 0xFFFFFFFF. Tap covenants never use OP_CODESEPARATOR.
+pub(crate) const TAP_COV_MSG_SUFFIX: [u8; 5] = [0x00, 0xff, 0xff, 0xff, 0xff];
+
+/// Sizes of the taproot sighash items as expected on the stack by
+/// [`CovOperations::verify_tap_cov`]. Item 1(genesis hash) is at the
+/// top of the stack and the last item(tapleaf hash) is the deepest one.
+pub(crate) const TAP_COV_ITEM_SIZES: [usize; 14] =
+    [32, 4, 4, 32, 32, 32, 32, 32, 32, 32, 32, 32, 4, 32];
+
+/// Index into [`TAP_COV_ITEM_SIZES`] of the first item after the constant
+/// spend_type byte in the sighash message, i.e. the spend type comes right
+/// before item 13(input index).
+pub(crate) const TAP_COV_SPEND_TYPE_POS: usize = 12;
+
+/// Number of sighash items the legacy covenants expect on the stack:
+/// the ten segwit v0 items and the elements specific item 3b(hashissuances).
+/// Item 1 is at the top of the stack and item 10 at depth
+/// `COV_SIGHASH_ITEM_COUNT - 1`.
+pub(crate) const COV_SIGHASH_ITEM_COUNT: usize = 11;
+
+/// Stack depth of the signature picked by [`CovOperations::verify_cov`]
+/// once the miniscript result is verified. It is right below the sighash
+/// items.
+pub(crate) const COV_CSFS_SIG_DEPTH: usize = COV_SIGHASH_ITEM_COUNT;
+
+/// Number of bytes of the 4 byte sighash type(item 10) that are
+/// appended to the transaction signature
+pub(crate) const COV_SIGHASH_TYPE_LEN: usize = 1;
+
+/// The sha256(tag) || sha256(tag) prefix for the taproot sighash
+/// tagged hash
+pub(crate) fn tap_sighash_tag_prefix() -> Vec<u8> {
+    let tag = sha256::Hash::hash(TAP_SIGHASH_TAG);
+    let mut prefix = Vec::with_capacity(64);
+    prefix.extend(tag.as_byte_array());
+    prefix.extend(tag.as_byte_array());
+    prefix
+}
+
 /// Additional operations required on script builder
 /// for Covenant operations support
+///
+/// These are the building blocks of the covenant descriptors in this
+/// crate and can be used to build other covenant scripts. The legacy
+/// covenant ops expect the segwit v0 sighash items on the stack with
+/// item 1(nVersion) at the top and the signature below item 10, as
+/// created by the covenant satisfaction.
+///
+/// # Examples
+///
+/// The script of a trivial covenant `elcovwsh(pk,1)`:
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use elements::opcodes::all::{OP_CHECKSIGFROMSTACK, OP_CODESEPARATOR};
+/// use elements::script::Builder;
+/// use elements_miniscript::descriptor::covenants::CovOperations;
+///
+/// let pk = bitcoin::PublicKey::from_str(
+///     "028c28a97bf8298bc0d23d8c749452a32e694b65e30a9472a3954ab30fe5324caa",
+/// )
+/// .unwrap();
+/// // The miniscript `1` followed by the covenant check
+/// let script = Builder::new().push_int(1).verify_cov(&pk).into_script();
+/// let bytes = script.as_bytes();
+/// assert_eq!(bytes.len(), 1 + 120);
+/// // The script code is everything after the OP_CODESEPARATOR
+/// assert_eq!(bytes[bytes.len() - 3], OP_CODESEPARATOR.into_u8());
+/// assert_eq!(bytes[bytes.len() - 1], OP_CHECKSIGFROMSTACK.into_u8());
+/// assert_eq!(
+///     Builder::new().post_codesep_script().into_script()[..],
+///     bytes[bytes.len() - 2..],
+/// );
+/// ```
 pub trait CovOperations: Sized {
     /// Assert that the size of top stack elem is `len`
+    ///
+    /// Emits `OP_SIZE <len> OP_EQUALVERIFY`.
     fn chk_size(self, len: usize) -> Self;
     /// Assert that the top item is a valid confidential Amount
     /// If it starts with 1, the len must be 9, otherwise the
     /// len must be 33
+    ///
+    /// Emits `OP_DUP 1 OP_LEFT 1 OP_EQUAL OP_IF OP_SIZE 9 OP_EQUALVERIFY
+    /// OP_ELSE OP_SIZE 33 OP_EQUALVERIFY OP_ENDIF`.
     fn chk_amt(self) -> Self;
     /// Assuming the 10 sighash components + 1 sig on the top of
     /// stack for segwit sighash as created by init_stack
     /// CAT all of them and check sig from stack
+    ///
+    /// In order, this emits:
+    /// 1. `OP_VERIFY` for the result of the preceding script,
+    /// 2. `11 OP_PICK 11 OP_PICK 1 OP_LEFT OP_CAT OP_TOALTSTACK` to save
+    ///    the signature with the one byte sighash type of item 10,
+    /// 3. a [`CovOperations::chk_size`] (or [`CovOperations::chk_amt`] for
+    ///    the value) and `OP_SWAP`/`OP_CAT`s concatenating items 1 to 10,
+    /// 4. `OP_SHA256 <key> OP_DUP OP_FROMALTSTACK OP_SWAP OP_CODESEPARATOR`,
+    /// 5. the [`CovOperations::post_codesep_script`].
     fn verify_cov(self, key: &bitcoin::PublicKey) -> Self;
 
+    /// Same as [`CovOperations::verify_cov`], but the reconstructed
+    /// sighash must be signed by `k` out of the `keys`. Assumes one
+    /// signature slot per key below the sighash items, with empty
+    /// slots for the keys that do not sign.
+    ///
+    /// The signature is not saved, instead `10 OP_PICK 1 OP_LEFT
+    /// OP_TOALTSTACK` saves the sighash type. The message hash is followed
+    /// by `0 OP_SWAP OP_CODESEPARATOR` and the
+    /// [`CovOperations::post_codesep_multi_script`].
+    fn verify_cov_multi(self, keys: &[bitcoin::PublicKey], k: usize) -> Self;
+
+    /// Assuming the 14 taproot sighash components + 1 schnorr sig on
+    /// the top of stack, reconstruct the BIP341(Elements) sighash
+    /// message for a SIGHASH_ALL script spend, CAT all of them and
+    /// check sig from stack.
+    ///
+    /// The constant parts of the message are pushed by the script and the
+    /// message is hashed with the `TapSighash/elements` tag. Ends with
+    /// [`CovOperations::post_codesep_script`], without an
+    /// `OP_CODESEPARATOR`.
+    fn verify_tap_cov(self, key: &XOnlyPublicKey) -> Self;
+
     /// Get the script code for the covenant script
     /// assuming the above construction of covenants
     /// which uses OP_CODESEP
+    ///
+    /// Emits `OP_CHECKSIGVERIFY OP_CHECKSIGFROMSTACK`.
     fn post_codesep_script(self) -> Self;
+
+    /// Get the script code for the multi key covenant script
+    /// created by [`CovOperations::verify_cov_multi`]
+    ///
+    /// For each key, a non-empty signature must pass both
+    /// `OP_CHECKSIGFROMSTACKVERIFY` on the message and `OP_CHECKSIGVERIFY`
+    /// on the transaction, and increments a counter. The counter is then
+    /// checked to equal `k`.
+    fn post_codesep_multi_script(self, keys: &[bitcoin::PublicKey], k: usize) -> Self;
 }
 
 impl CovOperations for script::Builder {
@@ -92,11 +222,11 @@ impl CovOperations for script::Builder {
         // We don't additionally need to check the depth because
         // cleanstack is a consensus rule in segwit.
         // Copy the ec_sig to the stack top
-        builder = builder.push_int(11).push_opcode(all::OP_PICK);
+        builder = builder.push_int(COV_CSFS_SIG_DEPTH as i64).push_opcode(all::OP_PICK);
         // convert sighash type into 1 byte(It is 4 byte in sighash calculation)
         // Since we copied the ecsig onto stack top, this will now be at pos 11
-        builder = builder.push_int(11).push_opcode(all::OP_PICK);
-        builder = builder.push_int(1).push_opcode(all::OP_LEFT);
+        builder = builder.push_int(COV_SIGHASH_ITEM_COUNT as i64).push_opcode(all::OP_PICK);
+        builder = builder.push_int(COV_SIGHASH_TYPE_LEN as i64).push_opcode(all::OP_LEFT);
         // create a bitcoinsig = [ecsig || sighashtype]cat the sig and hashtype
         builder = builder.push_opcode(all::OP_CAT);
         // Push the bitcoinsig to alt stack
@@ -138,6 +268,100 @@ impl CovOperations for script::Builder {
         builder.post_codesep_script()
     }
 
+    #[rustfmt::skip]
+    fn verify_cov_multi(self, keys: &[bitcoin::PublicKey], k: usize) -> Self {
+        use elements::opcodes::all::{OP_CAT, OP_SWAP};
+
+        // The script code is everything after the codeseparator
+        let script_code = script::Builder::new()
+            .post_codesep_multi_script(keys, k)
+            .into_script();
+        let script_code_len = varint_len(script_code.len()) + script_code.len();
+
+        let mut builder = self;
+        // stk = [sig_m .. sig_1 i10 i9 i8 i7 i6 i5 i4 i3b i3 i2 i1 B]
+        // alt_stk = []
+        builder = builder.push_verify();
+        // Save the 1 byte sighash type from item 10 for the CHECKSIGs
+        builder = builder.push_int(COV_SIGHASH_ITEM_COUNT as i64 - 1).push_opcode(all::OP_PICK);
+        builder = builder.push_int(COV_SIGHASH_TYPE_LEN as i64).push_opcode(all::OP_LEFT);
+        builder = builder.push_opcode(all::OP_TOALTSTACK);
+        // alt_stk = [hashtype]
+
+        // Same size checks as in the single key covenant
+        builder = builder.chk_size(4).push_opcode(OP_SWAP); // item 1: ver
+        builder = builder.chk_size(32).push_opcode(OP_CAT).push_opcode(OP_SWAP);//item 2: hashprevouts
+        builder = builder.chk_size(32).push_opcode(OP_CAT).push_opcode(OP_SWAP);//item 3: hashsequence
+        builder = builder.chk_size(32).push_opcode(OP_CAT).push_opcode(OP_SWAP);//item 3b: hashissuances
+        builder = builder.chk_size(36).push_opcode(OP_CAT).push_opcode(OP_SWAP);//item 4: outpoint
+        builder = builder.chk_size(script_code_len).push_opcode(OP_CAT).push_opcode(OP_SWAP); //item 5: script code
+        builder = builder.chk_amt().push_opcode(OP_CAT).push_opcode(OP_SWAP);   //item 6: check confAmt
+        builder = builder.chk_size(4).push_opcode(OP_CAT).push_opcode(OP_SWAP); //item 7: sequence
+        builder = builder.chk_size(32).push_opcode(OP_CAT).push_opcode(OP_SWAP);//item 8: hashoutputs
+        builder = builder.chk_size(4).push_opcode(OP_CAT).push_opcode(OP_SWAP); //item 9: nlocktime
+        builder = builder.chk_size(4).push_opcode(OP_CAT);                      //item 10: sighash type
+
+        builder = builder.push_opcode(all::OP_SHA256);
+        // Keep a count of the valid signatures below the sha2_msg
+        builder = builder.push_int(0).push_opcode(OP_SWAP);
+        // stk = [sig_m .. sig_1 0 sha2_msg]
+        // alt_stk = [hashtype]
+
+        // Everything after the codeseparator is the script code for
+        // the CHECKSIGs of all the keys
+        builder = builder.push_opcode(all::OP_CODESEPARATOR);
+        builder.post_codesep_multi_script(keys, k)
+    }
+
+    #[rustfmt::skip]
+    fn verify_tap_cov(self, key: &XOnlyPublicKey) -> Self {
+        use elements::opcodes::all::{OP_CAT, OP_SWAP};
+
+        let mut builder = self;
+        // Same construction as verify_cov, but the items are the ones from
+        // the Elements taproot sighash with SIGHASH_ALL on a script spend
+        // without annex. The constant parts of the message(hash type,
+        // spend type, key version and codesep position) are pushed by the
+        // script itself and the witness only contains the variable items.
+        // stk = [schnorrsig i14 i13 ... i2 i1 B]
+        // alt_stk = []
+        builder = builder.push_verify();
+        // Copy the schnorr sig to the top and append SIGHASH_ALL
+        builder = builder.push_int(14).push_opcode(all::OP_PICK);
+        builder = builder.push_int(1).push_opcode(OP_CAT);
+        builder = builder.push_opcode(all::OP_TOALTSTACK);
+        // alt_stk = [schnorrsig||0x01]
+        // item 1: genesis hash, committed twice, followed by hash_type
+        builder = builder.chk_size(TAP_COV_ITEM_SIZES[0]).push_opcode(all::OP_DUP).push_opcode(OP_CAT);
+        builder = builder.push_int(1).push_opcode(OP_CAT);
+        // items 2..12: version, locktime and the sha_* fields
+        for len in &TAP_COV_ITEM_SIZES[1..TAP_COV_SPEND_TYPE_POS] {
+            builder = builder.push_opcode(OP_SWAP).chk_size(*len).push_opcode(OP_CAT);
+        }
+        // spend_type: script path(ext_flag = 1), no annex
+        builder = builder.push_int(2).push_opcode(OP_CAT);
+        // item 13: input index, item 14: tapleaf hash
+        for len in &TAP_COV_ITEM_SIZES[TAP_COV_SPEND_TYPE_POS..] {
+            builder = builder.push_opcode(OP_SWAP).chk_size(*len).push_opcode(OP_CAT);
+        }
+        builder = builder.push_slice(&TAP_COV_MSG_SUFFIX).push_opcode(OP_CAT);
+        // stk = [schnorrsig msg]
+        // Compute the tagged hash for the message
+        builder = builder
+            .push_slice(&tap_sighash_tag_prefix())
+            .push_opcode(OP_SWAP)
+            .push_opcode(OP_CAT)
+            .push_opcode(all::OP_SHA256);
+        builder = builder.push_slice(&key.serialize()).push_opcode(all::OP_DUP);
+        builder = builder
+            .push_opcode(all::OP_FROMALTSTACK)
+            .push_opcode(OP_SWAP);
+        // stk = [schnorrsig sighash pk sig||0x01 pk]
+        // No codeseparator is required as the tapleaf hash is part
+        // of the message instead of the script code.
+        builder.post_codesep_script()
+    }
+
     /// The second parameter decides whether the script code should
     /// a hashlock verifying the entire script
     fn post_codesep_script(self) -> Self {
@@ -148,4 +372,54 @@ impl CovOperations for script::Builder {
             .push_opcode(all::OP_CHECKSIGVERIFY)
             .push_opcode(all::OP_CHECKSIGFROMSTACK)
     }
+
+    fn post_codesep_multi_script(self, keys: &[bitcoin::PublicKey], k: usize) -> Self {
+        use elements::opcodes::all::OP_SWAP;
+
+        let mut builder = self;
+        // stk = [sig_m .. sig_1 cnt sha2_msg]
+        for (i, key) in keys.iter().enumerate() {
+            // sig_(i + 1) is below cnt and sha2_msg. Empty signatures are
+            // skipped, all others must be valid for both the sha2_msg and
+            // the transaction.
+            let depth = i as i64 + 2;
+            builder = builder
+                .push_int(depth)
+                .push_opcode(all::OP_PICK)
+                .push_opcode(all::OP_SIZE)
+                .push_opcode(all::OP_0NOTEQUAL)
+                .push_opcode(all::OP_IF);
+            // stk = [sig_m .. sig_1 cnt sha2_msg sig]
+            builder = builder
+                .push_opcode(all::OP_OVER)
+                .push_key(key)
+                .push_opcode(all::OP_CHECKSIGFROMSTACKVERIFY);
+            // Append the hashtype to the signature for CHECKSIG
+            builder = builder
+                .push_int(depth)
+                .push_opcode(all::OP_PICK)
+                .push_opcode(all::OP_FROMALTSTACK)
+                .push_opcode(all::OP_DUP)
+                .push_opcode(all::OP_TOALTSTACK)
+                .push_opcode(all::OP_CAT)
+                .push_key(key)
+                .push_opcode(all::OP_CHECKSIGVERIFY);
+            // Increment the count
+            builder = builder
+                .push_opcode(OP_SWAP)
+                .push_int(1)
+                .push_opcode(all::OP_ADD)
+                .push_opcode(OP_SWAP);
+            builder = builder
+                .push_opcode(all::OP_ELSE)
+                .push_opcode(all::OP_DROP)
+                .push_opcode(all::OP_ENDIF);
+        }
+        // stk = [sig_m .. sig_1 cnt sha2_msg]
+        builder = builder.push_opcode(all::OP_DROP);
+        for _ in keys {
+            builder = builder.push_opcode(all::OP_NIP);
+        }
+        builder.push_int(k as i64).push_opcode(all::OP_EQUAL)
+    }
 }
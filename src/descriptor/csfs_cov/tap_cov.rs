@@ -0,0 +1,561 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Taproot Covenant Descriptor support
+//!
+//! A `eltapcov()` descriptor is the taproot version of the
+//! [`super::LegacyCSFSCov`] descriptor. The covenant check lives in
+//! a single tapleaf, committed under an unspendable(NUMS) internal key
+//! so that the covenant can't be bypassed with a key spend.
+//!
+//! ** WORKS only for SIGHASH_ALL script spends without annex
+//! The covenant reconstructs the Elements taproot sighash message and
+//! checks it with OP_CHECKSIGFROMSTACK. The message differs from the
+//! segwitv0 (BIP143) message built by `hash256_arr` in the following ways:
+//! - The message is hashed using the `TapSighash/elements` tagged hash
+//!   instead of double sha256. Since tapscript CHECKSIGFROMSTACK verifies
+//!   the 32 byte message directly, the script computes the tagged hash.
+//! - All the aggregate hashes(prevouts, sequences, issuances, outputs) are
+//!   single sha256 instead of sha256d.
+//! - The genesis hash is committed twice at the start of the message.
+//! - The sighash type is a single byte after the genesis hashes instead of
+//!   4 bytes at the end, and nLockTime directly follows nVersion.
+//! - ELEMENTS EXTRA: sha_outpoint_flags, sha_asset_amounts,
+//!   sha_issuance_rangeproofs and sha_output_witnesses are new. Together with
+//!   sha_scriptpubkeys they commit to all spent utxos.
+//! - The outpoint, scriptCode, value and nSequence of the current input are
+//!   replaced by the spend type and the input index.
+//! - The tapleaf hash, key version and codeseparator position are
+//!   appended at the end.
+//!
+//! The items committed by the witness(top of stack first) are:
+//! 1. genesis hash (32 bytes)
+//! 2. nVersion (4-byte little endian)
+//! 3. nLocktime (4-byte little endian)
+//! 4. sha_outpoint_flags (32-byte hash)
+//! 5. sha_prevouts (32-byte hash)
+//! 6. sha_asset_amounts (32-byte hash)
+//! 7. sha_scriptpubkeys (32-byte hash)
+//! 8. sha_sequences (32-byte hash)
+//! 9. sha_issuances (32-byte hash)
+//! 10. sha_issuance_rangeproofs (32-byte hash)
+//! 11. sha_outputs (32-byte hash)
+//! 12. sha_output_witnesses (32-byte hash)
+//! 13. input index (4-byte little endian)
+//! 14. tapleaf hash (32-byte hash)
+use std::fmt;
+
+use bitcoin::key::XOnlyPublicKey;
+use elements::sighash::{Prevouts, SighashCache};
+use elements::taproot::{
+    ControlBlock, LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo,
+    TAPROOT_CONTROL_BASE_SIZE,
+};
+use elements::{self, script, secp256k1_zkp, SchnorrSighashType, Script};
+
+use super::super::ELMTS_STR;
+use super::script_internals::{
+    tap_sighash_tag_prefix, TAP_COV_ITEM_SIZES, TAP_COV_MSG_SUFFIX, TAP_COV_SPEND_TYPE_POS,
+};
+use super::{CovError, CovOperations};
+use crate::descriptor::checksum::{self, verify_checksum};
+use crate::expression::{self, FromTree};
+use crate::extensions::ParseableExt;
+use crate::miniscript::lex::{lex, Token as Tk, TokenIter};
+use crate::miniscript::limits::MAX_STACK_SIZE;
+use crate::miniscript::{decode, types};
+use crate::util::varint_len;
+use crate::{
+    Error, ExtTranslator, Extension, ForEachKey, Miniscript, MiniscriptKey, Satisfier,
+    ScriptContext, Tap, ToPublicKey, TranslateExt, TranslatePk, Translator,
+};
+
+/// Length of the taproot sighash message committed by the covenant
+pub(crate) const TAP_COV_MSG_LEN: usize = 403;
+/// Spend type committed in the sighash: script path(ext_flag = 1) without annex
+pub(crate) const TAP_COV_SPEND_TYPE: u8 = 2;
+// Size of the covenant part of the script, see test_tap_sanity_check_limits
+pub(crate) const TAP_COV_SCRIPT_SIZE: usize = 204;
+// Size of the covenant witness: 64 byte signature and the 14 sighash items
+// along with their length prefixes
+const TAP_COV_WITNESS_SIZE: usize = 443;
+
+// The x-only key with unknown discrete logarithm from BIP341 used as the
+// internal key so that the covenant can only be spent via the script path
+pub(crate) const UNSPENDABLE_INTERNAL_KEY: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// The taproot covenant descriptor
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TapCSFSCov<Pk: MiniscriptKey, Ext: Extension> {
+    /// the pk constraining the Covenant
+    /// The key over which we want CHECKSIGFROMSTACK
+    pub(crate) pk: Pk,
+    /// the underlying Miniscript
+    /// Must be under tapscript context
+    pub(crate) ms: Miniscript<Pk, Tap, Ext>,
+}
+
+impl<Pk: MiniscriptKey, Ext: Extension> TapCSFSCov<Pk, Ext> {
+    /// Get the pk from covenant
+    pub fn pk(&self) -> &Pk {
+        &self.pk
+    }
+
+    /// Get a reference to Miniscript inside covenant
+    pub fn to_ms(&self) -> &Miniscript<Pk, Tap, Ext> {
+        &self.ms
+    }
+
+    /// Consume self and return inner miniscript
+    pub fn into_ms(self) -> Miniscript<Pk, Tap, Ext> {
+        self.ms
+    }
+
+    /// Create a new Self from components
+    pub fn new(pk: Pk, ms: Miniscript<Pk, Tap, Ext>) -> Result<Self, Error> {
+        Tap::check_global_validity(&ms)?;
+        // Tapscript has no opcode or script size limits, but the
+        // sighash items and the signature must fit in the stack
+        let ms_stack_elems = ms
+            .ext
+            .stack_elem_count_sat
+            .ok_or(Error::ImpossibleSatisfaction)?;
+        if ms_stack_elems + TAP_COV_ITEM_SIZES.len() + 1 > MAX_STACK_SIZE {
+            return Err(Error::ImpossibleSatisfaction);
+        }
+        Ok(Self { pk, ms })
+    }
+
+    /// Sanity checks for this covenant descriptor
+    pub fn sanity_check(&self) -> Result<(), Error> {
+        self.ms.sanity_check()?;
+        Ok(())
+    }
+
+    /// Computes an upper bound on the difference between a non-satisfied
+    /// `TxIn`'s `segwit_weight` and a satisfied `TxIn`'s `segwit_weight`.
+    /// This is the weight of the witness stack: its item count varint
+    /// (minus the one byte of an empty witness), the items with their
+    /// length prefixes, the tapleaf script and the control block. The
+    /// scriptSig and the rest of the `TxIn` are not included.
+    ///
+    /// The covenant part of the witness is the 64 byte schnorr signature
+    /// and the 14 sighash items, which all have a fixed size.
+    pub fn max_weight_to_satisfy(&self) -> Result<usize, Error> {
+        let (script_size, max_sat_elems, max_sat_size) = self.max_witness_sizes()?;
+        // stack size varint difference between non-satisfied (0) and satisfied
+        let stack_varint_diff = varint_len(max_sat_elems) - varint_len(0);
+
+        Ok(stack_varint_diff
+            + varint_len(script_size)
+            + script_size
+            + max_sat_size
+            + varint_len(TAPROOT_CONTROL_BASE_SIZE)
+            + TAPROOT_CONTROL_BASE_SIZE)
+    }
+
+    /// Computes an upper bound on the weight of a satisfying witness to the
+    /// transaction. Unlike [`Self::max_weight_to_satisfy`], this includes
+    /// 4 weight units for the empty scriptSig length byte and the full
+    /// item count varint of the witness stack.
+    #[deprecated(note = "use max_weight_to_satisfy instead")]
+    pub fn max_satisfaction_weight(&self) -> Result<usize, Error> {
+        let (script_size, max_sat_elems, max_sat_size) = self.max_witness_sizes()?;
+
+        Ok(4 +  // scriptSig length byte
+            varint_len(script_size) +
+            script_size +
+            varint_len(max_sat_elems) +
+            max_sat_size +
+            varint_len(TAPROOT_CONTROL_BASE_SIZE) +
+            TAPROOT_CONTROL_BASE_SIZE)
+    }
+
+    // The tapleaf script size, and the maximum number of witness elements
+    // (including the script and the control block) and their total
+    // serialized size (excluding the script and the control block)
+    fn max_witness_sizes(&self) -> Result<(usize, usize, usize), Error> {
+        let script_size = self.ms.script_size() + TAP_COV_SCRIPT_SIZE
+            - if self.ms.ext.has_free_verify { 1 } else { 0 };
+        // The extra +2 elements are control block and script itself
+        let max_sat_elems =
+            self.ms.max_satisfaction_witness_elements()? + TAP_COV_ITEM_SIZES.len() + 1 + 2;
+        let max_sat_size = self.ms.max_satisfaction_size()? + TAP_COV_WITNESS_SIZE;
+        Ok((script_size, max_sat_elems, max_sat_size))
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey, Ext: ParseableExt> TapCSFSCov<Pk, Ext> {
+    /// Encode the tapleaf script
+    pub fn encode(&self) -> Script {
+        let builder = self.ms.node.encode(script::Builder::new());
+        builder
+            .verify_tap_cov(&self.pk.to_x_only_pubkey())
+            .into_script()
+    }
+
+    /// The tapleaf hash of the covenant leaf. This is committed in the
+    /// sighash and is required for signing with the covenant pk.
+    pub fn leaf_hash(&self) -> TapLeafHash {
+        TapLeafHash::from_script(&self.encode(), LeafVersion::default())
+    }
+
+    /// Compute the [`TaprootSpendInfo`] for this covenant. The covenant leaf
+    /// is the only leaf of the tree and the internal key is unspendable.
+    pub fn spend_info(&self) -> TaprootSpendInfo {
+        let secp = secp256k1_zkp::Secp256k1::verification_only();
+        let internal_key = XOnlyPublicKey::from_slice(&UNSPENDABLE_INTERNAL_KEY)
+            .expect("NUMS point is a valid key");
+        TaprootBuilder::new()
+            .add_leaf(0, self.encode())
+            .expect("Single leaf at depth 0")
+            .finalize(&secp, internal_key)
+            .expect("Tree with a single leaf is complete")
+    }
+
+    /// The control block for spending the covenant leaf
+    pub fn control_block(&self) -> ControlBlock {
+        self.spend_info()
+            .control_block(&(self.encode(), LeafVersion::default()))
+            .expect("Covenant leaf is in the tree")
+    }
+
+    /// Obtains the blinded address for this descriptor.
+    pub fn address(
+        &self,
+        blinder: Option<secp256k1_zkp::PublicKey>,
+        params: &'static elements::AddressParams,
+    ) -> elements::Address {
+        elements::Address::p2tr_tweaked(self.spend_info().output_key(), blinder, params)
+    }
+
+    /// Obtains the script pubkey for this descriptor.
+    pub fn script_pubkey(&self) -> Script {
+        script::Builder::new()
+            .push_opcode(elements::opcodes::all::OP_PUSHNUM_1)
+            .push_slice(&self.spend_info().output_key().as_inner().serialize())
+            .into_script()
+    }
+
+    /// Computes the scriptSig that will be in place for an unsigned input
+    /// spending an output with this descriptor.
+    pub fn unsigned_script_sig(&self) -> Script {
+        Script::new()
+    }
+
+    /// Create a satisfaction for the Covenant Descriptor. The satisfier
+    /// must provide the transaction, the spent utxos, the input index and
+    /// the genesis hash, see [`super::TapCovSatisfier`].
+    pub fn satisfy<S: Satisfier<Pk>>(&self, s: S, allow_mall: bool) -> Result<Vec<Vec<u8>>, Error> {
+        let leaf_hash = self.leaf_hash();
+        let mut wit = {
+            let msg = tap_sighash_msg(&s, leaf_hash)?;
+            let sig = s
+                .lookup_tap_leaf_script_sig(&self.pk, &leaf_hash)
+                .ok_or(CovError::MissingCovSignature(0))?;
+            // Only SIGHASH_ALL is supported by the covenant script
+            if sig.hash_ty != SchnorrSighashType::All {
+                return Err(CovError::CovenantSighashTypeMismatch)?;
+            }
+
+            // Split the message into the items in the order they are
+            // expected by the script. Constant parts are skipped.
+            let mut items = Vec::with_capacity(TAP_COV_ITEM_SIZES.len());
+            // genesis hash(twice) and hash_type
+            items.push(msg[..32].to_vec());
+            let mut pos = 65;
+            for (i, len) in TAP_COV_ITEM_SIZES.iter().enumerate().skip(1) {
+                if i == TAP_COV_SPEND_TYPE_POS {
+                    // spend type
+                    pos += 1;
+                }
+                items.push(msg[pos..pos + len].to_vec());
+                pos += len;
+            }
+            debug_assert_eq!(msg[pos..], TAP_COV_MSG_SUFFIX);
+
+            let mut wit = vec![sig.sig.as_ref().to_vec()]; // The covenant sig
+            wit.extend(items.into_iter().rev());
+            wit
+        };
+
+        let ms_wit = if !allow_mall {
+            self.ms.satisfy(s)?
+        } else {
+            self.ms.satisfy_malleable(s)?
+        };
+        wit.extend(ms_wit);
+        Ok(wit)
+    }
+
+    /// Returns satisfying non-malleable witness and scriptSig to spend an
+    /// output controlled by the given descriptor if it possible to
+    /// construct one using the satisfier S.
+    pub fn get_satisfaction<S>(&self, satisfier: S) -> Result<(Vec<Vec<u8>>, Script), Error>
+    where
+        S: Satisfier<Pk>,
+    {
+        let mut witness = self.satisfy(satisfier, /*allow_mall*/ false)?;
+        witness.push(self.encode().into_bytes());
+        witness.push(self.control_block().serialize());
+        Ok((witness, Script::new()))
+    }
+
+    /// Returns a possilbly mallable satisfying non-malleable witness and scriptSig to spend an
+    /// output controlled by the given descriptor if it possible to
+    /// construct one using the satisfier S.
+    pub fn get_satisfaction_mall<S>(&self, satisfier: S) -> Result<(Vec<Vec<u8>>, Script), Error>
+    where
+        S: Satisfier<Pk>,
+    {
+        let mut witness = self.satisfy(satisfier, /*allow_mall*/ true)?;
+        witness.push(self.encode().into_bytes());
+        witness.push(self.control_block().serialize());
+        Ok((witness, Script::new()))
+    }
+}
+
+// Compute the taproot sighash message(before tagged hashing) for the
+// covenant leaf using the transaction data from the satisfier
+fn tap_sighash_msg<Pk: ToPublicKey, S: Satisfier<Pk>>(
+    s: &S,
+    leaf_hash: TapLeafHash,
+) -> Result<Vec<u8>, Error> {
+    let tx = s.lookup_tx().ok_or(CovError::MissingTxEnv)?;
+    let spent_utxos = s.lookup_spent_utxos().ok_or(CovError::MissingTxEnv)?;
+    let idx = s.lookup_curr_inp().ok_or(CovError::MissingTxEnv)?;
+    let genesis_hash = s
+        .lookup_genesis_hash()
+        .ok_or(CovError::MissingGenesisHash)?;
+    check_tap_tx_env(tx, spent_utxos, idx)?;
+
+    let mut msg = Vec::with_capacity(TAP_COV_MSG_LEN);
+    SighashCache::new(tx)
+        .taproot_encode_signing_data_to(
+            &mut msg,
+            idx,
+            &Prevouts::All(spent_utxos),
+            None,
+            Some((leaf_hash, 0xFFFFFFFF)),
+            SchnorrSighashType::All,
+            genesis_hash,
+        )
+        // The input index and the spent utxos are checked above, so this
+        // can only be an internal error
+        .map_err(|e| Error::Unexpected(e.to_string()))?;
+    debug_assert_eq!(msg.len(), TAP_COV_MSG_LEN);
+    Ok(msg)
+}
+
+// Check that the input index and the spent utxos are consistent with the
+// transaction
+pub(crate) fn check_tap_tx_env(
+    tx: &elements::Transaction,
+    spent_utxos: &[elements::TxOut],
+    idx: usize,
+) -> Result<(), CovError> {
+    if idx >= tx.input.len() {
+        Err(CovError::InputIndexOutOfBounds {
+            index: idx,
+            n_inputs: tx.input.len(),
+        })
+    } else if spent_utxos.len() != tx.input.len() {
+        Err(CovError::SpentUtxosMismatch {
+            n_utxos: spent_utxos.len(),
+            n_inputs: tx.input.len(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+impl<Ext: ParseableExt> TapCSFSCov<XOnlyPublicKey, Ext> {
+    // Check a single sighash item of given length
+    fn check_tap_cov_item(tokens: &mut TokenIter<'_>, len: usize) -> Result<(), Error> {
+        match_token!(tokens,
+            Tk::Cat, Tk::Verify, Tk::Equal, Tk::Num(n), Tk::Size, Tk::Swap => {
+                if n as usize == len {
+                    Ok(())
+                } else {
+                    Err(Error::CovError(CovError::BadCovDescriptor))
+                }
+            },
+        )
+    }
+
+    /// Check if the given script is a taproot covenant descriptor
+    /// Consumes the iterator so that only remaining miniscript
+    /// needs to be parsed from the iterator
+    fn check_tap_cov_script(tokens: &mut TokenIter<'_>) -> Result<XOnlyPublicKey, Error> {
+        let pk = match_token!(tokens,
+            Tk::CheckSigFromStack, Tk::Verify, Tk::CheckSig, Tk::Swap, Tk::FromAltStack,
+            Tk::Dup, Tk::Bytes32(pk), Tk::Sha256, Tk::Cat, Tk::Swap, Tk::Push(prefix),
+            Tk::Cat, Tk::Push(suffix) => {
+                if prefix != tap_sighash_tag_prefix() || suffix != TAP_COV_MSG_SUFFIX {
+                    return Err(Error::CovError(CovError::BadCovDescriptor));
+                }
+                XOnlyPublicKey::from_slice(pk)?
+            },
+        );
+        // item 14 and 13
+        for len in TAP_COV_ITEM_SIZES[TAP_COV_SPEND_TYPE_POS..].iter().rev() {
+            Self::check_tap_cov_item(tokens, *len)?;
+        }
+        // spend type
+        match_token!(tokens, Tk::Cat, Tk::Num(2) => {},);
+        // item 12 to item 2
+        for len in TAP_COV_ITEM_SIZES[1..TAP_COV_SPEND_TYPE_POS].iter().rev() {
+            Self::check_tap_cov_item(tokens, *len)?;
+        }
+        match_token!(tokens,
+            Tk::Cat, Tk::Num(1), Tk::Cat, Tk::Dup, Tk::Verify, Tk::Equal, Tk::Num(32), Tk::Size, // item 1
+            Tk::ToAltStack, Tk::Cat, Tk::Num(1), Tk::Pick, Tk::Num(14), Tk::Verify => {
+                Ok(pk)
+            },
+        )
+    }
+
+    /// Parse a taproot covenant descriptor from its tapleaf script.
+    // This parsing is parse_insane
+    pub fn parse_insane(script: &script::Script) -> Result<Self, Error> {
+        let (pk, ms) = Self::parse_cov_components(script)?;
+        Self::new(pk, ms)
+    }
+
+    // Utility function to parse the components of cov
+    // descriptor. This allows us to parse Miniscript with
+    // it's context so that it can be used with NoChecks
+    // context while using the interpreter
+    pub(crate) fn parse_cov_components(
+        script: &script::Script,
+    ) -> Result<(XOnlyPublicKey, Miniscript<XOnlyPublicKey, Tap, Ext>), Error> {
+        let tokens = lex(script)?;
+        let mut iter = TokenIter::new(tokens);
+
+        let pk = Self::check_tap_cov_script(&mut iter)?;
+        let ms = decode::parse(&mut iter)?;
+        Tap::check_global_validity(&ms)?;
+        if ms.ty.corr.base != types::Base::B {
+            return Err(Error::NonTopLevel(format!("{:?}", ms)));
+        };
+        if let Some(leading) = iter.next() {
+            Err(Error::Trailing(leading.to_string()))
+        } else {
+            Ok((pk, ms))
+        }
+    }
+
+    /// Parse a descriptor with additional local sanity checks.
+    /// See [`Miniscript::sanity_check`] for all the checks. Use
+    /// [`Self::parse_insane`] to allow parsing insane scripts
+    pub fn parse(script: &script::Script) -> Result<Self, Error> {
+        let cov = Self::parse_insane(script)?;
+        cov.ms.sanity_check()?;
+        Ok(cov)
+    }
+}
+
+impl_from_tree!(
+    TapCSFSCov<Pk, Ext>,
+    => Ext; Extension,
+    fn from_tree(top: &expression::Tree<'_>) -> Result<Self, Error> {
+        if top.name == "eltapcov" && top.args.len() == 2 {
+            let pk = expression::terminal(&top.args[0], |pk| Pk::from_str(pk))?;
+            let top = &top.args[1];
+            let sub = Miniscript::from_tree(top)?;
+            Tap::top_level_checks(&sub)?;
+            Self::new(pk, sub)
+        } else {
+            Err(Error::Unexpected(format!(
+                "{}({} args) while parsing eltapcov descriptor",
+                top.name,
+                top.args.len(),
+            )))
+        }
+    }
+);
+
+impl<Pk, Ext> fmt::Debug for TapCSFSCov<Pk, Ext>
+where
+    Pk: MiniscriptKey,
+    Ext: Extension,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}tapcov({},{})", ELMTS_STR, self.pk, self.ms)
+    }
+}
+
+impl<Pk, Ext> fmt::Display for TapCSFSCov<Pk, Ext>
+where
+    Pk: MiniscriptKey,
+    Ext: Extension,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use fmt::Write;
+        let mut wrapped_f = checksum::Formatter::new(f);
+        write!(wrapped_f, "{}tapcov({},{})", ELMTS_STR, self.pk, self.ms)?;
+        wrapped_f.write_checksum_if_not_alt()
+    }
+}
+
+impl_from_str!(
+    TapCSFSCov<Pk, Ext>,
+    => Ext; Extension,
+    type Err = Error;,
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let desc_str = verify_checksum(s)?;
+        let top = expression::Tree::from_str(desc_str)?;
+        TapCSFSCov::<Pk, Ext>::from_tree(&top)
+    }
+);
+
+impl<Pk: MiniscriptKey, Ext: Extension> ForEachKey<Pk> for TapCSFSCov<Pk, Ext> {
+    fn for_each_key<'a, F: FnMut(&'a Pk) -> bool>(&'a self, mut pred: F) -> bool
+    where
+        Pk: 'a,
+    {
+        pred(&self.pk) && self.ms.for_each_key(pred)
+    }
+}
+
+impl<P, Q, Ext> TranslatePk<P, Q> for TapCSFSCov<P, Ext>
+where
+    P: MiniscriptKey,
+    Q: MiniscriptKey,
+    Ext: Extension,
+{
+    type Output = TapCSFSCov<Q, Ext>;
+
+    fn translate_pk<T, E>(&self, t: &mut T) -> Result<Self::Output, E>
+    where
+        T: Translator<P, Q, E>,
+    {
+        Ok(TapCSFSCov {
+            pk: t.pk(&self.pk)?,
+            ms: self.ms.translate_pk(t)?,
+        })
+    }
+}
+
+impl<Pk, Ext, ExtQ> TranslateExt<Ext, ExtQ> for TapCSFSCov<Pk, Ext>
+where
+    Pk: MiniscriptKey,
+    Ext: Extension,
+    ExtQ: Extension,
+    Ext: TranslateExt<Ext, ExtQ, Output = ExtQ>,
+{
+    type Output = TapCSFSCov<Pk, ExtQ>;
+
+    fn translate_ext<T, E>(&self, translator: &mut T) -> Result<Self::Output, E>
+    where
+        T: ExtTranslator<Ext, ExtQ, E>,
+    {
+        Ok(TapCSFSCov {
+            pk: self.pk.clone(),
+            ms: self.ms.translate_ext(translator)?,
+        })
+    }
+}
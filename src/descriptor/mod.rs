@@ -26,6 +26,7 @@ use elements::{secp256k1_zkp as secp256k1, secp256k1_zkp, Script, TxIn};
 use {bitcoin, elements};
 
 use self::checksum::verify_checksum;
+use crate::confidential::slip77;
 use crate::extensions::{CovExtArgs, ExtParam, ParseableExt};
 use crate::miniscript::{Legacy, Miniscript, Segwitv0};
 use crate::{
@@ -36,20 +37,34 @@ use crate::{
 
 mod bare;
 mod csfs_cov;
+mod pretaproot;
 mod segwitv0;
 mod sh;
 mod sortedmulti;
 mod tr;
 
 // Descriptor Exports
-pub use self::bare::{Bare, Pkh};
+pub use self::bare::{Bare, Pkh, PkhHash};
+pub use self::pretaproot::{PreTaprootDescriptor, PreTaprootType};
 pub use self::segwitv0::{Wpkh, Wsh, WshInner};
 pub use self::sh::{Sh, ShInner};
 pub use self::sortedmulti::SortedMultiVec;
 
 pub mod checksum;
 mod key;
-pub use self::csfs_cov::{CovError, CovOperations, LegacyCSFSCov, LegacyCovSatisfier};
+pub use self::csfs_cov::{
+    hash256_arr, hash_issuances, CompiledLegacyCSFSCov, CovError, CovOperations, CovSatisfaction,
+    CovSighashItem, CovSighashItems, CovSighashType, CovWitness, CovWrapperError, Hash256Engine,
+    LazyCovSatisfier, LegacyCSFSCov, LegacyCovSatisfier, TapCSFSCov, TapCovSatisfier,
+};
+pub(crate) use self::csfs_cov::{
+    TAP_COV_ITEM_SIZES, TAP_COV_MSG_SUFFIX, TAP_COV_SPEND_TYPE, TAP_COV_SPEND_TYPE_POS,
+    UNSPENDABLE_INTERNAL_KEY,
+};
+/// Script building blocks for implementing covenant descriptors
+pub mod covenants {
+    pub use super::csfs_cov::CovOperations;
+}
 pub(crate) use self::key::maybe_fmt_master_id;
 pub use self::key::{
     ConversionError, DefiniteDescriptorKey, DerivPaths, DescriptorKeyParseError,
@@ -296,7 +311,7 @@ impl DescriptorType {
             Bare | Sh | Pkh | ShSortedMulti => None,
             LegacyPegin => Some(WitnessVersion::V1),
             Pegin => None, // Can have any witness version
-            Cov => None,   // Can have any witness version
+            Cov => Some(WitnessVersion::V0),
         }
     }
 }
@@ -392,7 +407,7 @@ impl<Pk: MiniscriptKey, Ext: Extension> Descriptor<Pk, Ext> {
     }
 
     /// Create a new wsh sorted multi descriptor
-    /// Errors when miniscript exceeds resource limits under p2sh context
+    /// Errors when miniscript exceeds resource limits under segwit context
     pub fn new_wsh_sortedmulti(k: usize, pks: Vec<Pk>) -> Result<Self, Error> {
         Ok(Descriptor::Wsh(Wsh::new_sortedmulti(k, pks)?))
     }
@@ -434,6 +449,42 @@ impl<Pk: MiniscriptKey, Ext: Extension> Descriptor<Pk, Ext> {
         }
     }
 
+    /// Whether the descriptor is a [`LegacyCSFSCov`] covenant
+    pub fn is_covenant(&self) -> bool {
+        matches!(*self, Descriptor::LegacyCSFSCov(_))
+    }
+
+    /// Tries to convert descriptor as a covenant descriptor
+    pub fn as_cov(&self) -> Result<&LegacyCSFSCov<Pk, Ext>, Error> {
+        if let Descriptor::LegacyCSFSCov(cov) = self {
+            Ok(cov)
+        } else {
+            Err(Error::CovError(CovError::BadCovDescriptor))
+        }
+    }
+
+    /// Mutable version of [`Descriptor::as_cov`]
+    pub fn as_cov_mut(&mut self) -> Result<&mut LegacyCSFSCov<Pk, Ext>, Error> {
+        if let Descriptor::LegacyCSFSCov(cov) = self {
+            Ok(cov)
+        } else {
+            Err(Error::CovError(CovError::BadCovDescriptor))
+        }
+    }
+
+    /// The covenant key of a single key covenant, which signs every spend
+    /// of it. Signers can use this to sign the covenant sighash first.
+    ///
+    /// `None` for multi key covenants, where the signing keys depend on the
+    /// spend, and for other descriptors. Use [`Descriptor::as_cov`] for all
+    /// the covenant keys and the threshold.
+    pub fn covenant_pk(&self) -> Option<&Pk> {
+        self.as_cov()
+            .ok()
+            .filter(|cov| cov.pks().len() == 1)
+            .map(LegacyCSFSCov::pk)
+    }
+
     /// Checks whether the descriptor is safe.
     ///
     /// Checks whether all the spend paths in the descriptor are possible on the
@@ -503,7 +554,7 @@ impl<Pk: MiniscriptKey, Ext: Extension> Descriptor<Pk, Ext> {
             Descriptor::Sh(ref sh) => sh.max_weight_to_satisfy()?,
             Descriptor::Tr(ref tr) => tr.max_weight_to_satisfy()?,
             Descriptor::TrExt(ref tr) => tr.max_weight_to_satisfy()?,
-            Descriptor::LegacyCSFSCov(ref csfs) => csfs.max_satisfaction_weight()?,
+            Descriptor::LegacyCSFSCov(ref csfs) => csfs.max_weight_to_satisfy()?,
         };
         Ok(weight)
     }
@@ -544,15 +595,6 @@ impl<Pk: MiniscriptKey, Arg: ExtParam> Descriptor<Pk, CovenantExt<Arg>> {
         let cov = LegacyCSFSCov::new(pk, ms)?;
         Ok(Descriptor::LegacyCSFSCov(cov))
     }
-
-    /// Tries to convert descriptor as a covenant descriptor
-    pub fn as_cov(&self) -> Result<&LegacyCSFSCov<Pk, CovenantExt<Arg>>, Error> {
-        if let Descriptor::LegacyCSFSCov(cov) = self {
-            Ok(cov)
-        } else {
-            Err(Error::CovError(CovError::BadCovDescriptor))
-        }
-    }
 }
 
 impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<Pk, Ext> {
@@ -583,6 +625,49 @@ impl<Pk: MiniscriptKey + ToPublicKey, Ext: Extension + ParseableExt> Descriptor<
         }
     }
 
+    /// Checks that `addr` is the address of this descriptor blinded with
+    /// `blinder`, i.e. the address [`Descriptor::blinded_address`] returns.
+    /// When it is, the plaintext scriptPubKey of the address is
+    /// [`Descriptor::script_pubkey`].
+    ///
+    /// Returns `Ok(false)` for unconfidential addresses, addresses with a
+    /// different blinding key, payload or network.
+    ///
+    /// # Errors
+    /// For raw/bare descriptors that don't have an address.
+    pub fn verify_blinded_address(
+        &self,
+        addr: &elements::Address,
+        blinder: secp256k1_zkp::PublicKey,
+        params: &'static elements::AddressParams,
+    ) -> Result<bool, Error>
+    where
+        Pk: ToPublicKey,
+    {
+        Ok(self.blinded_address(blinder, params)? == *addr)
+    }
+
+    /// Obtains the blinded address for this descriptor, deriving the blinding
+    /// key from a SLIP-77 master blinding key and the script pubkey.
+    ///
+    /// # Errors
+    /// For raw/bare descriptors that don't have an address.
+    pub fn slip77_address<C: secp256k1_zkp::Signing>(
+        &self,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+        master: &slip77::MasterBlindingKey,
+        params: &'static elements::AddressParams,
+    ) -> Result<elements::Address, Error>
+    where
+        Pk: ToPublicKey,
+    {
+        if let Descriptor::Bare(_) = *self {
+            return Err(Error::BareDescriptorAddr);
+        }
+        let blinder = master.blinding_key(secp, &self.script_pubkey());
+        self.blinded_address(blinder, params)
+    }
+
     /// Obtains an address for this descriptor. For blinding see [`Descriptor::blinded_address`]
     pub fn address(
         &self,
@@ -828,19 +913,7 @@ impl<Ext: Extension + ParseableExt> Descriptor<DescriptorPublicKey, Ext> {
         &self,
         index: u32,
     ) -> Result<Descriptor<DefiniteDescriptorKey, Ext>, ConversionError> {
-        struct Derivator(u32);
-
-        impl Translator<DescriptorPublicKey, DefiniteDescriptorKey, ConversionError> for Derivator {
-            fn pk(
-                &mut self,
-                pk: &DescriptorPublicKey,
-            ) -> Result<DefiniteDescriptorKey, ConversionError> {
-                pk.clone().at_derivation_index(self.0)
-            }
-
-            translate_hash_clone!(DescriptorPublicKey, DescriptorPublicKey, ConversionError);
-        }
-        self.translate_pk(&mut Derivator(index))
+        self.translate_pk(&mut IndexDerivator(index))
     }
 
     #[deprecated(note = "use at_derivation_index instead")]
@@ -1157,25 +1230,37 @@ impl<Ext: Extension> Descriptor<DefiniteDescriptorKey, Ext> {
         &self,
         secp: &secp256k1::Secp256k1<C>,
     ) -> Result<Descriptor<bitcoin::PublicKey, Ext>, ConversionError> {
-        struct Derivator<'a, C: secp256k1::Verification>(&'a secp256k1::Secp256k1<C>);
+        let derived = self.translate_pk(&mut KeyDerivator(secp))?;
+        Ok(derived)
+    }
+}
 
-        impl<'a, C: secp256k1::Verification>
-            Translator<DefiniteDescriptorKey, bitcoin::PublicKey, ConversionError>
-            for Derivator<'a, C>
-        {
-            fn pk(
-                &mut self,
-                pk: &DefiniteDescriptorKey,
-            ) -> Result<bitcoin::PublicKey, ConversionError> {
-                pk.derive_public_key(self.0)
-            }
+// Replaces the wildcards of the descriptor keys with a fixed index, shared
+// by the `at_derivation_index` methods of the descriptors
+pub(crate) struct IndexDerivator(pub(crate) u32);
 
-            translate_hash_clone!(DefiniteDescriptorKey, bitcoin::PublicKey, ConversionError);
-        }
+impl Translator<DescriptorPublicKey, DefiniteDescriptorKey, ConversionError> for IndexDerivator {
+    fn pk(&mut self, pk: &DescriptorPublicKey) -> Result<DefiniteDescriptorKey, ConversionError> {
+        pk.clone().at_derivation_index(self.0)
+    }
 
-        let derived = self.translate_pk(&mut Derivator(secp))?;
-        Ok(derived)
+    translate_hash_clone!(DescriptorPublicKey, DescriptorPublicKey, ConversionError);
+}
+
+// Derives the public keys of definite descriptor keys, shared by the
+// `derived_descriptor` methods of the descriptors
+pub(crate) struct KeyDerivator<'a, C: secp256k1::Verification>(
+    pub(crate) &'a secp256k1::Secp256k1<C>,
+);
+
+impl<'a, C: secp256k1::Verification>
+    Translator<DefiniteDescriptorKey, bitcoin::PublicKey, ConversionError> for KeyDerivator<'a, C>
+{
+    fn pk(&mut self, pk: &DefiniteDescriptorKey) -> Result<bitcoin::PublicKey, ConversionError> {
+        pk.derive_public_key(self.0)
     }
+
+    translate_hash_clone!(DefiniteDescriptorKey, bitcoin::PublicKey, ConversionError);
 }
 
 impl_from_tree!(
@@ -1285,7 +1370,7 @@ mod tests {
     use crate::miniscript::satisfy::ElementsSig;
     #[cfg(feature = "compiler")]
     use crate::policy;
-    use crate::{hex_script, Descriptor, Error, Miniscript, NoExt, Satisfier};
+    use crate::{hex_script, Descriptor, Error, Miniscript, NoExt, Satisfier, SigType};
 
     type StdDescriptor = Descriptor<PublicKey, CovenantExt<CovExtArgs>>;
     const TEST_PK: &str =
@@ -1425,6 +1510,17 @@ mod tests {
                 .to_string(),
             "2dmYXpSu8YP6aLcJYhHfB1C19mdzSx2GPB9"
         );
+        // The script pubkey does not depend on the address params
+        for params in [
+            &elements::AddressParams::ELEMENTS,
+            &elements::AddressParams::LIQUID,
+            &elements::AddressParams::LIQUID_TESTNET,
+        ] {
+            assert_eq!(
+                pkh.address(params).unwrap().script_pubkey(),
+                pkh.script_pubkey()
+            );
+        }
 
         let wpkh = StdDescriptor::from_str(
             "elwpkh(\
@@ -1552,6 +1648,92 @@ mod tests {
         );
     }
 
+    #[test]
+    fn slip77_address() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let mbk = slip77::MasterBlindingKey::from_seed(b"abcd");
+        let params = &elements::AddressParams::LIQUID;
+
+        for desc in &[
+            "elpkh(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8)",
+            "elwpkh(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8)",
+            "elsh(wpkh(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8))",
+            "eltr(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8)",
+        ] {
+            let desc = StdDescriptor::from_str(desc).unwrap();
+            let addr = desc.slip77_address(&secp, &mbk, params).unwrap();
+            let blinder = mbk.blinding_key(&secp, &desc.script_pubkey());
+            assert_eq!(addr.blinding_pubkey, Some(blinder));
+            assert_eq!(addr, desc.blinded_address(blinder, params).unwrap());
+
+            let ct_desc = crate::confidential::Descriptor {
+                key: crate::confidential::Key::Slip77(mbk),
+                descriptor: desc.clone(),
+            };
+            assert_eq!(addr, ct_desc.address(&secp, params).unwrap());
+        }
+
+        let bare = StdDescriptor::from_str(
+            "elpk(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8)",
+        )
+        .unwrap();
+        match bare.slip77_address(&secp, &mbk, params) {
+            Err(Error::BareDescriptorAddr) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn verify_blinded_address() {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let mbk = slip77::MasterBlindingKey::from_seed(b"abcd");
+        let params = &elements::AddressParams::LIQUID;
+        let desc = StdDescriptor::from_str(
+            "elwpkh(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8)",
+        )
+        .unwrap();
+        let blinder = mbk.blinding_key(&secp, &desc.script_pubkey());
+        let other_blinder = slip77::MasterBlindingKey::from_seed(b"efgh")
+            .blinding_key(&secp, &desc.script_pubkey());
+
+        let addr = desc.blinded_address(blinder, params).unwrap();
+        assert!(desc.verify_blinded_address(&addr, blinder, params).unwrap());
+        assert_eq!(addr.script_pubkey(), desc.script_pubkey());
+        // Wrong blinding key
+        assert!(!desc
+            .verify_blinded_address(&addr, other_blinder, params)
+            .unwrap());
+        let other_addr = desc.blinded_address(other_blinder, params).unwrap();
+        assert!(!desc
+            .verify_blinded_address(&other_addr, blinder, params)
+            .unwrap());
+        // Unconfidential address or another network
+        let explicit = desc.address(params).unwrap();
+        assert!(!desc
+            .verify_blinded_address(&explicit, blinder, params)
+            .unwrap());
+        assert!(!desc
+            .verify_blinded_address(&addr, blinder, &elements::AddressParams::ELEMENTS)
+            .unwrap());
+        // Another descriptor
+        let other_desc = StdDescriptor::from_str(
+            "elpkh(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8)",
+        )
+        .unwrap();
+        assert!(!other_desc
+            .verify_blinded_address(&addr, blinder, params)
+            .unwrap());
+
+        let bare = StdDescriptor::from_str(
+            "elpk(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8)",
+        )
+        .unwrap();
+        match bare.verify_blinded_address(&addr, blinder, params) {
+            Err(Error::BareDescriptorAddr) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
     #[test]
     fn satisfy() {
         let secp = secp256k1_zkp::Secp256k1::new();
@@ -1683,6 +1865,26 @@ mod tests {
         assert_eq!(shwsh.unsigned_script_sig(), expected_ssig);
     }
 
+    #[test]
+    fn covenant_accessors() {
+        let mut cov = Descriptor::<String>::from_str("elcovwsh(A,older(10))").unwrap();
+        assert!(cov.is_covenant());
+        assert_eq!(cov.as_cov().unwrap().pk(), "A");
+        assert_eq!(cov.covenant_pk().unwrap(), "A");
+        let other = Descriptor::<String>::from_str("elcovwsh(B,older(10))").unwrap();
+        *cov.as_cov_mut().unwrap() = other.as_cov().unwrap().clone();
+        assert_eq!(cov, other);
+
+        let mut wsh = Descriptor::<String>::from_str("elwsh(older(10))").unwrap();
+        assert!(!wsh.is_covenant());
+        assert!(wsh.as_cov().is_err());
+        assert!(wsh.as_cov_mut().is_err());
+        assert!(wsh.covenant_pk().is_none());
+
+        let multi = Descriptor::<String>::from_str("elcovwsh(multi(1,C,D),older(10))").unwrap();
+        assert!(multi.covenant_pk().is_none());
+    }
+
     #[test]
     fn after_is_cltv() {
         let descriptor = Descriptor::<bitcoin::PublicKey>::from_str("elwsh(after(1000))").unwrap();
@@ -2036,6 +2238,70 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sortedmulti_constructors() {
+        let secp_ctx = secp256k1_zkp::Secp256k1::verification_only();
+        let keys = [
+            "xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB/1/0/*",
+            "xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH/0/0/*",
+        ];
+        let pks: Vec<_> = keys
+            .iter()
+            .map(|k| DescriptorPublicKey::from_str(k).unwrap())
+            .collect();
+        let address = |desc: &Descriptor<DescriptorPublicKey>| {
+            desc.at_derivation_index(5)
+                .unwrap()
+                .derived_descriptor(&secp_ctx)
+                .unwrap()
+                .address(&elements::AddressParams::ELEMENTS)
+                .unwrap()
+                .to_string()
+        };
+
+        // Same descriptors and addresses as in test_sortedmulti
+        let sh_wsh =
+            Descriptor::<DescriptorPublicKey>::new_sh_wsh_sortedmulti(1, pks.clone()).unwrap();
+        assert_eq!(
+            sh_wsh,
+            Descriptor::from_str(&format!(
+                "elsh(wsh(sortedmulti(1,{},{})))",
+                keys[0], keys[1]
+            ))
+            .unwrap()
+        );
+        assert_eq!(address(&sh_wsh), "XBkDY63XnRTz6BbwzJi3ifGhBwLTomEzkq");
+        // The wsh reference uses the xpubs without derivation
+        let xpubs: Vec<_> = keys
+            .iter()
+            .map(|k| DescriptorPublicKey::from_str(k.split('/').next().unwrap()).unwrap())
+            .collect();
+        let wsh = Descriptor::<DescriptorPublicKey>::new_wsh_sortedmulti(1, xpubs.clone()).unwrap();
+        assert_eq!(
+            wsh,
+            Descriptor::from_str(&format!("elwsh(sortedmulti(1,{},{}))", xpubs[0], xpubs[1]))
+                .unwrap()
+        );
+        assert_eq!(
+            Descriptor::Wsh(Wsh::new_sortedmulti(1, xpubs).unwrap()),
+            wsh
+        );
+        assert_eq!(
+            address(&wsh),
+            "ert1qpq2cfgz5lktxzr5zqv7nrzz46hsvq3492ump9pz8rzcl8wqtwqcs2yqnuv"
+        );
+
+        // Threshold and key count bounds
+        for k in [0, 3].iter() {
+            Descriptor::<DescriptorPublicKey>::new_wsh_sortedmulti(*k, pks.clone()).unwrap_err();
+            Descriptor::<DescriptorPublicKey>::new_sh_wsh_sortedmulti(*k, pks.clone()).unwrap_err();
+        }
+        let many_pks: Vec<_> = (0..21).map(|_| pks[0].clone()).collect();
+        Descriptor::<DescriptorPublicKey>::new_wsh_sortedmulti(1, many_pks.clone()).unwrap_err();
+        Descriptor::<DescriptorPublicKey>::new_sh_wsh_sortedmulti(1, many_pks[..20].to_vec())
+            .unwrap();
+    }
+
     #[test]
     fn test_parse_descriptor() {
         let secp = &secp256k1_zkp::Secp256k1::signing_only();
@@ -2284,4 +2550,109 @@ pk(03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8))";
         Descriptor::<DescriptorPublicKey>::from_str("elwsh(andor(pk(tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/0'/<0;1>/*),older(10000),pk(tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/8/<0;1;2;3;4>/*)))").unwrap_err();
         Descriptor::<DescriptorPublicKey>::from_str("elwsh(andor(pk(tpubDEN9WSToTyy9ZQfaYqSKfmVqmq1VVLNtYfj3Vkqh67et57eJ5sTKZQBkHqSwPUsoSskJeaYnPttHe2VrkCsKA27kUaN9SDc5zhqeLzKa1rr/0'/<0;1;2;3>/*),older(10000),pk(tpubD8LYfn6njiA2inCoxwM7EuN3cuLVcaHAwLYeups13dpevd3nHLRdK9NdQksWXrhLQVxcUZRpnp5CkJ1FhE61WRAsHxDNAkvGkoQkAeWDYjV/8/<0;1;2>/*)))").unwrap_err();
     }
+
+    #[test]
+    fn sanity_check_standard() {
+        use std::sync::Arc;
+
+        use crate::miniscript::context::ScriptContextError;
+        use crate::{Legacy, ScriptContext, Segwitv0, Terminal};
+
+        // thresh(1,c:pk_h(K0),a:c:pk_h(K1),...) with `n` subscripts
+        fn pkh_thresh<Ctx: ScriptContext>(n: usize) -> Miniscript<String, Ctx> {
+            let subs = (0..n)
+                .map(|i| {
+                    let pkh = Miniscript::from_ast(Terminal::PkH(format!("K{}", i))).unwrap();
+                    let c = Miniscript::from_ast(Terminal::Check(Arc::new(pkh))).unwrap();
+                    if i == 0 {
+                        Arc::new(c)
+                    } else {
+                        Arc::new(Miniscript::from_ast(Terminal::Alt(Arc::new(c))).unwrap())
+                    }
+                })
+                .collect();
+            Miniscript::from_ast(Terminal::Thresh(1, subs)).unwrap()
+        }
+
+        let wsh = Wsh::new(pkh_thresh::<Segwitv0>(10)).unwrap();
+        wsh.sanity_check_standard().unwrap();
+        Sh::new_wsh(pkh_thresh::<Segwitv0>(10))
+            .unwrap()
+            .sanity_check_standard()
+            .unwrap();
+        Sh::new(pkh_thresh::<Legacy>(10))
+            .unwrap()
+            .sanity_check_standard()
+            .unwrap();
+
+        // Each dissatisfied pk_h takes two witness elements
+        let wsh = Wsh::new(pkh_thresh::<Segwitv0>(60)).unwrap();
+        match wsh.sanity_check_standard() {
+            Err(Error::ContextError(ScriptContextError::MaxWitnessItemssExceeded {
+                actual: 120,
+                limit: 100,
+            })) => {}
+            e => panic!("unexpected result {:?}", e),
+        }
+        let sh = Sh::new_wsh(pkh_thresh::<Segwitv0>(60)).unwrap();
+        match sh.sanity_check_standard() {
+            Err(Error::ContextError(ScriptContextError::MaxWitnessItemssExceeded { .. })) => {}
+            e => panic!("unexpected result {:?}", e),
+        }
+
+        // Each c:pk_h fragment takes more than 26 bytes of redeem script
+        let sh = Sh::new(pkh_thresh::<Legacy>(20)).unwrap();
+        match sh.sanity_check_standard() {
+            Err(Error::ContextError(ScriptContextError::MaxRedeemScriptSizeExceeded)) => {}
+            e => panic!("unexpected result {:?}", e),
+        }
+    }
+
+    #[test]
+    fn wpkh_sighash_script_code() {
+        use elements::sighash::SighashCache;
+        use elements::{confidential, EcdsaSighashType, LockTime};
+
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let pk = bitcoin::PublicKey::new(secp256k1_zkp::PublicKey::from_secret_key(&secp, &sk));
+        let hash = pk.to_pubkeyhash(SigType::Ecdsa);
+        // bip143: `0x1976a914{20-byte-pubkey-hash}88ac`, without the length prefix
+        let expected = hex_script(&format!("76a914{}88ac", hash));
+
+        let tx = elements::Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![elements_txin(Script::new(), vec![])],
+            output: vec![elements::TxOut::default()],
+        };
+        let value = confidential::Value::Explicit(100_000);
+
+        for desc in [format!("elwpkh({})", pk), format!("elsh(wpkh({}))", pk)] {
+            let desc = Descriptor::<PublicKey>::from_str(&desc).unwrap();
+            let script_code = desc.script_code().unwrap();
+            assert_eq!(script_code, expected);
+
+            let sighash = SighashCache::new(&tx).segwitv0_sighash(
+                0,
+                &script_code,
+                value,
+                EcdsaSighashType::All,
+            );
+            assert_eq!(
+                sighash.to_string(),
+                "127fbc180fa64e5c8426c95b3526943c5f62720613ea5f0a38f4a4bf282252e1"
+            );
+
+            // A signature over this sighash satisfies the descriptor
+            let msg = secp256k1_zkp::Message::from_digest_slice(&sighash[..]).unwrap();
+            let sig = secp.sign_ecdsa(&msg, &sk);
+            let mut sigs = HashMap::new();
+            sigs.insert(pk, (sig, EcdsaSighashType::All));
+            let (witness, _) = desc.get_satisfaction(&sigs).unwrap();
+            let der = &witness[0][..witness[0].len() - 1];
+            let sig = secp256k1_zkp::ecdsa::Signature::from_der(der).unwrap();
+            secp.verify_ecdsa(&msg, &sig, &pk.inner).unwrap();
+        }
+    }
 }
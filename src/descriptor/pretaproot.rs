@@ -1,11 +1,12 @@
+use std::convert::TryFrom;
 use std::fmt;
-use std::str::{self, FromStr};
 
-use elements::{self, Script};
+use elements::{self, secp256k1_zkp, Script};
 
 use super::checksum::verify_checksum;
-use super::{Bare, ElementsTrait, Pkh, Sh, Wpkh, Wsh};
-use crate::{expression, DescriptorTrait, Error, MiniscriptKey, Satisfier, ToPublicKey};
+use super::{Bare, Descriptor, Pkh, Sh, ShInner, Wpkh, Wsh, WshInner};
+use crate::expression;
+use crate::{Error, Extension, MiniscriptKey, Satisfier, ToPublicKey};
 
 /// Script descriptor
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -22,49 +23,201 @@ pub enum PreTaprootDescriptor<Pk: MiniscriptKey> {
     Wsh(Wsh<Pk>),
 }
 
-impl<Pk: MiniscriptKey> ElementsTrait<Pk> for PreTaprootDescriptor<Pk> {
-    fn blind_addr(
-        &self,
-        blinder: Option<elements::secp256k1_zkp::PublicKey>,
-        params: &'static elements::AddressParams,
-    ) -> Result<elements::Address, Error>
-    where
-        Pk: ToPublicKey,
-    {
+/// Type of a [`PreTaprootDescriptor`]. Unlike the descriptor variants,
+/// this distinguishes the descriptors nested inside `sh`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum PreTaprootType {
+    /// Bare descriptor(Contains the native P2pk)
+    Bare,
+    /// Pure Sh Descriptor. Does not contain nested Wsh/Wpkh
+    Sh,
+    /// Pkh Descriptor
+    Pkh,
+    /// Wpkh Descriptor
+    Wpkh,
+    /// Wsh
+    Wsh,
+    /// Sh Wrapped Wsh
+    ShWsh,
+    /// Sh wrapped Wpkh
+    ShWpkh,
+    /// Sh Sorted Multi
+    ShSortedMulti,
+    /// Wsh Sorted Multi
+    WshSortedMulti,
+    /// Sh Wsh Sorted Multi
+    ShWshSortedMulti,
+}
+
+impl fmt::Display for PreTaprootType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PreTaprootType::Bare => write!(f, "bare"),
+            PreTaprootType::Sh => write!(f, "sh"),
+            PreTaprootType::Pkh => write!(f, "pkh"),
+            PreTaprootType::Wpkh => write!(f, "wpkh"),
+            PreTaprootType::Wsh => write!(f, "wsh"),
+            PreTaprootType::ShWsh => write!(f, "shwsh"),
+            PreTaprootType::ShWpkh => write!(f, "shwpkh"),
+            PreTaprootType::ShSortedMulti => write!(f, "shsortedmulti"),
+            PreTaprootType::WshSortedMulti => write!(f, "wshsortedmulti"),
+            PreTaprootType::ShWshSortedMulti => write!(f, "shwshsortedmulti"),
+        }
+    }
+}
+
+impl<Pk: MiniscriptKey> PreTaprootDescriptor<Pk> {
+    /// Get the [`PreTaprootType`] of the descriptor
+    pub fn desc_type(&self) -> PreTaprootType {
+        match *self {
+            PreTaprootDescriptor::Bare(ref _bare) => PreTaprootType::Bare,
+            PreTaprootDescriptor::Pkh(ref _pkh) => PreTaprootType::Pkh,
+            PreTaprootDescriptor::Wpkh(ref _wpkh) => PreTaprootType::Wpkh,
+            PreTaprootDescriptor::Sh(ref sh) => match sh.as_inner() {
+                ShInner::Wsh(ref wsh) => match wsh.as_inner() {
+                    WshInner::SortedMulti(ref _smv) => PreTaprootType::ShWshSortedMulti,
+                    WshInner::Ms(ref _ms) => PreTaprootType::ShWsh,
+                },
+                ShInner::Wpkh(ref _wpkh) => PreTaprootType::ShWpkh,
+                ShInner::SortedMulti(ref _smv) => PreTaprootType::ShSortedMulti,
+                ShInner::Ms(ref _ms) => PreTaprootType::Sh,
+            },
+            PreTaprootDescriptor::Wsh(ref wsh) => match wsh.as_inner() {
+                WshInner::SortedMulti(ref _smv) => PreTaprootType::WshSortedMulti,
+                WshInner::Ms(ref _ms) => PreTaprootType::Wsh,
+            },
+        }
+    }
+
+    /// Converts this pre-taproot descriptor into the general [`Descriptor`]
+    pub fn into_descriptor(self) -> Descriptor<Pk> {
         match self {
-            PreTaprootDescriptor::Bare(bare) => bare.blind_addr(blinder, params),
-            PreTaprootDescriptor::Pkh(pkh) => pkh.blind_addr(blinder, params),
-            PreTaprootDescriptor::Wpkh(wpkh) => wpkh.blind_addr(blinder, params),
-            PreTaprootDescriptor::Sh(sh) => sh.blind_addr(blinder, params),
-            PreTaprootDescriptor::Wsh(wsh) => wsh.blind_addr(blinder, params),
+            PreTaprootDescriptor::Bare(bare) => Descriptor::Bare(bare),
+            PreTaprootDescriptor::Pkh(pkh) => Descriptor::Pkh(pkh),
+            PreTaprootDescriptor::Wpkh(wpkh) => Descriptor::Wpkh(wpkh),
+            PreTaprootDescriptor::Sh(sh) => Descriptor::Sh(sh),
+            PreTaprootDescriptor::Wsh(wsh) => Descriptor::Wsh(wsh),
+        }
+    }
+
+    /// Same as [`PreTaprootDescriptor::into_descriptor`], but clones the
+    /// inner descriptor instead of consuming `self`
+    pub fn as_descriptor(&self) -> Descriptor<Pk> {
+        self.clone().into_descriptor()
+    }
+
+    /// Computes an upper bound on the difference between a non-satisfied
+    /// `TxIn`'s `segwit_weight` and a satisfied `TxIn`'s `segwit_weight`,
+    /// in weight units. See [`Descriptor::max_weight_to_satisfy`] for what
+    /// is included.
+    ///
+    /// # Errors
+    /// When the descriptor is impossible to safisfy (ex: sh(OP_FALSE)).
+    pub fn max_weight_to_satisfy(&self) -> Result<usize, Error> {
+        let weight = match *self {
+            PreTaprootDescriptor::Bare(ref bare) => bare.max_weight_to_satisfy()?,
+            PreTaprootDescriptor::Pkh(ref pkh) => pkh.max_weight_to_satisfy(),
+            PreTaprootDescriptor::Wpkh(ref wpkh) => wpkh.max_weight_to_satisfy(),
+            PreTaprootDescriptor::Wsh(ref wsh) => wsh.max_weight_to_satisfy()?,
+            PreTaprootDescriptor::Sh(ref sh) => sh.max_weight_to_satisfy()?,
+        };
+        Ok(weight)
+    }
+}
+
+impl<Pk: MiniscriptKey, T: Extension> TryFrom<Descriptor<Pk, T>> for PreTaprootDescriptor<Pk> {
+    type Error = Error;
+
+    /// Fails for taproot and covenant descriptors
+    fn try_from(desc: Descriptor<Pk, T>) -> Result<Self, Self::Error> {
+        match desc {
+            Descriptor::Bare(bare) => Ok(PreTaprootDescriptor::Bare(bare)),
+            Descriptor::Pkh(pkh) => Ok(PreTaprootDescriptor::Pkh(pkh)),
+            Descriptor::Wpkh(wpkh) => Ok(PreTaprootDescriptor::Wpkh(wpkh)),
+            Descriptor::Sh(sh) => Ok(PreTaprootDescriptor::Sh(sh)),
+            Descriptor::Wsh(wsh) => Ok(PreTaprootDescriptor::Wsh(wsh)),
+            Descriptor::Tr(..) | Descriptor::TrExt(..) | Descriptor::LegacyCSFSCov(..) => Err(
+                Error::Unexpected(format!("{} is not a pre-taproot descriptor", desc)),
+            ),
+        }
+    }
+}
+
+impl<Pk: MiniscriptKey, T: Extension> PartialEq<PreTaprootDescriptor<Pk>> for Descriptor<Pk, T> {
+    /// Taproot and covenant descriptors are never equal to a
+    /// [`PreTaprootDescriptor`]
+    fn eq(&self, other: &PreTaprootDescriptor<Pk>) -> bool {
+        match (self, other) {
+            (Descriptor::Bare(a), PreTaprootDescriptor::Bare(b)) => a == b,
+            (Descriptor::Pkh(a), PreTaprootDescriptor::Pkh(b)) => a == b,
+            (Descriptor::Wpkh(a), PreTaprootDescriptor::Wpkh(b)) => a == b,
+            (Descriptor::Sh(a), PreTaprootDescriptor::Sh(b)) => a == b,
+            (Descriptor::Wsh(a), PreTaprootDescriptor::Wsh(b)) => a == b,
+            _ => false,
         }
     }
 }
 
-impl<Pk: MiniscriptKey> DescriptorTrait<Pk> for PreTaprootDescriptor<Pk> {
-    /// Whether the descriptor is safe
-    /// Checks whether all the spend paths in the descriptor are possible
-    /// on the bitcoin network under the current standardness and consensus rules
-    /// Also checks whether the descriptor requires signauture on all spend paths
-    /// And whether the script is malleable.
-    /// In general, all the guarantees of miniscript hold only for safe scripts.
-    /// All the analysis guarantees of miniscript only hold safe scripts.
-    /// The signer may not be able to find satisfactions even if one exists
-    fn sanity_check(&self) -> Result<(), Error> {
+impl<Pk: MiniscriptKey, T: Extension> PartialEq<Descriptor<Pk, T>> for PreTaprootDescriptor<Pk> {
+    fn eq(&self, other: &Descriptor<Pk, T>) -> bool {
+        other == self
+    }
+}
+
+impl<Pk: MiniscriptKey> PreTaprootDescriptor<Pk> {
+    /// Whether the descriptor is safe. See [`Descriptor::sanity_check`].
+    pub fn sanity_check(&self) -> Result<(), Error> {
         match *self {
             PreTaprootDescriptor::Bare(ref bare) => bare.sanity_check(),
-            PreTaprootDescriptor::Pkh(ref pkh) => pkh.sanity_check(),
+            PreTaprootDescriptor::Pkh(_) => Ok(()),
             PreTaprootDescriptor::Wpkh(ref wpkh) => wpkh.sanity_check(),
             PreTaprootDescriptor::Wsh(ref wsh) => wsh.sanity_check(),
             PreTaprootDescriptor::Sh(ref sh) => sh.sanity_check(),
         }
     }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey> PreTaprootDescriptor<Pk> {
+    /// Obtains the blinded address for this descriptor
+    ///
+    /// # Errors
+    /// For raw/bare descriptors that don't have an address.
+    pub fn blinded_address(
+        &self,
+        blinder: secp256k1_zkp::PublicKey,
+        params: &'static elements::AddressParams,
+    ) -> Result<elements::Address, Error> {
+        self.address_inner(Some(blinder), params)
+    }
+
+    /// Obtains an address for this descriptor. For blinding see
+    /// [`PreTaprootDescriptor::blinded_address`]
+    ///
+    /// # Errors
+    /// For raw/bare descriptors that don't have an address.
+    pub fn address(
+        &self,
+        params: &'static elements::AddressParams,
+    ) -> Result<elements::Address, Error> {
+        self.address_inner(None, params)
+    }
+
+    fn address_inner(
+        &self,
+        blinder: Option<secp256k1_zkp::PublicKey>,
+        params: &'static elements::AddressParams,
+    ) -> Result<elements::Address, Error> {
+        match *self {
+            PreTaprootDescriptor::Bare(_) => Err(Error::BareDescriptorAddr),
+            PreTaprootDescriptor::Pkh(ref pkh) => Ok(pkh.address(blinder, params)),
+            PreTaprootDescriptor::Wpkh(ref wpkh) => Ok(wpkh.address(blinder, params)),
+            PreTaprootDescriptor::Wsh(ref wsh) => Ok(wsh.address(blinder, params)),
+            PreTaprootDescriptor::Sh(ref sh) => Ok(sh.address(blinder, params)),
+        }
+    }
 
     /// Computes the scriptpubkey of the descriptor
-    fn script_pubkey(&self) -> Script
-    where
-        Pk: ToPublicKey,
-    {
+    pub fn script_pubkey(&self) -> Script {
         match *self {
             PreTaprootDescriptor::Bare(ref bare) => bare.script_pubkey(),
             PreTaprootDescriptor::Pkh(ref pkh) => pkh.script_pubkey(),
@@ -75,51 +228,46 @@ impl<Pk: MiniscriptKey> DescriptorTrait<Pk> for PreTaprootDescriptor<Pk> {
     }
 
     /// Computes the scriptSig that will be in place for an unsigned
-    /// input spending an output with this descriptor. For pre-segwit
-    /// descriptors, which use the scriptSig for signatures, this
-    /// returns the empty script.
-    ///
-    /// This is used in Segwit transactions to produce an unsigned
-    /// transaction whose txid will not change during signing (since
-    /// only the witness data will change).
-    fn unsigned_script_sig(&self) -> Script
-    where
-        Pk: ToPublicKey,
-    {
+    /// input spending an output with this descriptor. See
+    /// [`Descriptor::unsigned_script_sig`].
+    pub fn unsigned_script_sig(&self) -> Script {
         match *self {
-            PreTaprootDescriptor::Bare(ref bare) => bare.unsigned_script_sig(),
-            PreTaprootDescriptor::Pkh(ref pkh) => pkh.unsigned_script_sig(),
-            PreTaprootDescriptor::Wpkh(ref wpkh) => wpkh.unsigned_script_sig(),
-            PreTaprootDescriptor::Wsh(ref wsh) => wsh.unsigned_script_sig(),
             PreTaprootDescriptor::Sh(ref sh) => sh.unsigned_script_sig(),
+            _ => Script::new(),
         }
     }
 
-    /// Computes the "witness script" of the descriptor, i.e. the underlying
-    /// script before any hashing is done. For `Bare`, `Pkh` and `Wpkh` this
-    /// is the scriptPubkey; for `ShWpkh` and `Sh` this is the redeemScript;
-    /// for the others it is the witness script.
-    /// Errors:
-    /// - When the descriptor is Tr
-    fn explicit_script(&self) -> Result<Script, Error>
-    where
-        Pk: ToPublicKey,
-    {
+    /// Computes the underlying script before any hashing is done. Unlike
+    /// [`Descriptor::explicit_script`], this cannot fail because all
+    /// pre-taproot descriptors have an explicit script.
+    pub fn explicit_script(&self) -> Script {
+        match *self {
+            PreTaprootDescriptor::Bare(ref bare) => bare.script_pubkey(),
+            PreTaprootDescriptor::Pkh(ref pkh) => pkh.script_pubkey(),
+            PreTaprootDescriptor::Wpkh(ref wpkh) => wpkh.script_pubkey(),
+            PreTaprootDescriptor::Wsh(ref wsh) => wsh.inner_script(),
+            PreTaprootDescriptor::Sh(ref sh) => sh.inner_script(),
+        }
+    }
+
+    /// Computes the `scriptCode` of a transaction output. Unlike
+    /// [`Descriptor::script_code`], this cannot fail because all
+    /// pre-taproot descriptors have a script code.
+    pub fn script_code(&self) -> Script {
         match *self {
-            PreTaprootDescriptor::Bare(ref bare) => bare.explicit_script(),
-            PreTaprootDescriptor::Pkh(ref pkh) => pkh.explicit_script(),
-            PreTaprootDescriptor::Wpkh(ref wpkh) => wpkh.explicit_script(),
-            PreTaprootDescriptor::Wsh(ref wsh) => wsh.explicit_script(),
-            PreTaprootDescriptor::Sh(ref sh) => sh.explicit_script(),
+            PreTaprootDescriptor::Bare(ref bare) => bare.ecdsa_sighash_script_code(),
+            PreTaprootDescriptor::Pkh(ref pkh) => pkh.ecdsa_sighash_script_code(),
+            PreTaprootDescriptor::Wpkh(ref wpkh) => wpkh.ecdsa_sighash_script_code(),
+            PreTaprootDescriptor::Wsh(ref wsh) => wsh.ecdsa_sighash_script_code(),
+            PreTaprootDescriptor::Sh(ref sh) => sh.ecdsa_sighash_script_code(),
         }
     }
 
     /// Returns satisfying non-malleable witness and scriptSig to spend an
     /// output controlled by the given descriptor if it possible to
     /// construct one using the satisfier S.
-    fn get_satisfaction<S>(&self, satisfier: S) -> Result<(Vec<Vec<u8>>, Script), Error>
+    pub fn get_satisfaction<S>(&self, satisfier: S) -> Result<(Vec<Vec<u8>>, Script), Error>
     where
-        Pk: ToPublicKey,
         S: Satisfier<Pk>,
     {
         match *self {
@@ -134,9 +282,8 @@ impl<Pk: MiniscriptKey> DescriptorTrait<Pk> for PreTaprootDescriptor<Pk> {
     /// Returns a possilbly mallable satisfying non-malleable witness and scriptSig to spend an
     /// output controlled by the given descriptor if it possible to
     /// construct one using the satisfier S.
-    fn get_satisfaction_mall<S>(&self, satisfier: S) -> Result<(Vec<Vec<u8>>, Script), Error>
+    pub fn get_satisfaction_mall<S>(&self, satisfier: S) -> Result<(Vec<Vec<u8>>, Script), Error>
     where
-        Pk: ToPublicKey,
         S: Satisfier<Pk>,
     {
         match *self {
@@ -147,96 +294,46 @@ impl<Pk: MiniscriptKey> DescriptorTrait<Pk> for PreTaprootDescriptor<Pk> {
             PreTaprootDescriptor::Sh(ref sh) => sh.get_satisfaction_mall(satisfier),
         }
     }
-
-    /// Computes an upper bound on the weight of a satisfying witness to the
-    /// transaction. Assumes all signatures are 73 bytes, including push opcode
-    /// and sighash suffix. Includes the weight of the VarInts encoding the
-    /// scriptSig and witness stack length.
-    fn max_satisfaction_weight(&self) -> Result<usize, Error> {
-        match *self {
-            PreTaprootDescriptor::Bare(ref bare) => bare.max_satisfaction_weight(),
-            PreTaprootDescriptor::Pkh(ref pkh) => pkh.max_satisfaction_weight(),
-            PreTaprootDescriptor::Wpkh(ref wpkh) => wpkh.max_satisfaction_weight(),
-            PreTaprootDescriptor::Wsh(ref wsh) => wsh.max_satisfaction_weight(),
-            PreTaprootDescriptor::Sh(ref sh) => sh.max_satisfaction_weight(),
-        }
-    }
-
-    /// Get the `scriptCode` of a transaction output.
-    ///
-    /// The `scriptCode` is the Script of the previous transaction output being serialized in the
-    /// sighash when evaluating a `CHECKSIG` & co. OP code.
-    /// Returns Error for Tr descriptors
-    fn script_code(&self) -> Result<Script, Error>
-    where
-        Pk: ToPublicKey,
-    {
-        match *self {
-            PreTaprootDescriptor::Bare(ref bare) => bare.script_code(),
-            PreTaprootDescriptor::Pkh(ref pkh) => pkh.script_code(),
-            PreTaprootDescriptor::Wpkh(ref wpkh) => wpkh.script_code(),
-            PreTaprootDescriptor::Wsh(ref wsh) => wsh.script_code(),
-            PreTaprootDescriptor::Sh(ref sh) => sh.script_code(),
-        }
-    }
-
-    fn address(&self, params: &'static elements::AddressParams) -> Result<elements::Address, Error>
-    where
-        Pk: ToPublicKey,
-    {
-        match *self {
-            PreTaprootDescriptor::Bare(ref bare) => bare.address(params),
-            PreTaprootDescriptor::Pkh(ref pkh) => pkh.address(params),
-            PreTaprootDescriptor::Wpkh(ref wpkh) => wpkh.address(params),
-            PreTaprootDescriptor::Wsh(ref wsh) => wsh.address(params),
-            PreTaprootDescriptor::Sh(ref sh) => sh.address(params),
-        }
-    }
 }
 
-impl<Pk> expression::FromTree for PreTaprootDescriptor<Pk>
-where
-    Pk: MiniscriptKey + str::FromStr,
-    Pk::Hash: str::FromStr,
-    <Pk as FromStr>::Err: ToString,
-    <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
-{
+impl_from_tree!(
+    PreTaprootDescriptor<Pk>,
     /// Parse an expression tree into a descriptor
-    fn from_tree(top: &expression::Tree<'_>) -> Result<PreTaprootDescriptor<Pk>, Error> {
+    fn from_tree(top: &expression::Tree) -> Result<PreTaprootDescriptor<Pk>, Error> {
         Ok(match (top.name, top.args.len() as u32) {
-            ("pkh", 1) => PreTaprootDescriptor::Pkh(Pkh::from_tree(top)?),
-            ("wpkh", 1) => PreTaprootDescriptor::Wpkh(Wpkh::from_tree(top)?),
-            ("sh", 1) => PreTaprootDescriptor::Sh(Sh::from_tree(top)?),
-            ("wsh", 1) => PreTaprootDescriptor::Wsh(Wsh::from_tree(top)?),
+            ("elpkh", 1) => PreTaprootDescriptor::Pkh(Pkh::from_tree(top)?),
+            ("elwpkh", 1) => PreTaprootDescriptor::Wpkh(Wpkh::from_tree(top)?),
+            ("elsh", 1) => PreTaprootDescriptor::Sh(Sh::from_tree(top)?),
+            ("elwsh", 1) => PreTaprootDescriptor::Wsh(Wsh::from_tree(top)?),
+            ("elcovwsh", _) | ("eltr", _) => {
+                return Err(Error::Unexpected(format!(
+                    "{} is not a pre-taproot descriptor",
+                    top.name
+                )))
+            }
             _ => PreTaprootDescriptor::Bare(Bare::from_tree(top)?),
         })
     }
-}
-
-impl<Pk> FromStr for PreTaprootDescriptor<Pk>
-where
-    Pk: MiniscriptKey + str::FromStr,
-    Pk::Hash: str::FromStr,
-    <Pk as FromStr>::Err: ToString,
-    <<Pk as MiniscriptKey>::Hash as FromStr>::Err: ToString,
-{
-    type Err = Error;
+);
 
+impl_from_str!(
+    PreTaprootDescriptor<Pk>,
+    type Err = Error;,
     fn from_str(s: &str) -> Result<PreTaprootDescriptor<Pk>, Error> {
         let desc_str = verify_checksum(s)?;
         let top = expression::Tree::from_str(desc_str)?;
         expression::FromTree::from_tree(&top)
     }
-}
+);
 
 impl<Pk: MiniscriptKey> fmt::Debug for PreTaprootDescriptor<Pk> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
-            PreTaprootDescriptor::Bare(ref sub) => write!(f, "{:?}", sub),
-            PreTaprootDescriptor::Pkh(ref pkh) => write!(f, "{:?}", pkh),
-            PreTaprootDescriptor::Wpkh(ref wpkh) => write!(f, "{:?}", wpkh),
-            PreTaprootDescriptor::Sh(ref sub) => write!(f, "{:?}", sub),
-            PreTaprootDescriptor::Wsh(ref sub) => write!(f, "{:?}", sub),
+            PreTaprootDescriptor::Bare(ref sub) => fmt::Debug::fmt(sub, f),
+            PreTaprootDescriptor::Pkh(ref pkh) => fmt::Debug::fmt(pkh, f),
+            PreTaprootDescriptor::Wpkh(ref wpkh) => fmt::Debug::fmt(wpkh, f),
+            PreTaprootDescriptor::Sh(ref sub) => fmt::Debug::fmt(sub, f),
+            PreTaprootDescriptor::Wsh(ref sub) => fmt::Debug::fmt(sub, f),
         }
     }
 }
@@ -244,58 +341,182 @@ impl<Pk: MiniscriptKey> fmt::Debug for PreTaprootDescriptor<Pk> {
 impl<Pk: MiniscriptKey> fmt::Display for PreTaprootDescriptor<Pk> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
-            PreTaprootDescriptor::Bare(ref sub) => write!(f, "{}", sub),
-            PreTaprootDescriptor::Pkh(ref pkh) => write!(f, "{}", pkh),
-            PreTaprootDescriptor::Wpkh(ref wpkh) => write!(f, "{}", wpkh),
-            PreTaprootDescriptor::Sh(ref sub) => write!(f, "{}", sub),
-            PreTaprootDescriptor::Wsh(ref sub) => write!(f, "{}", sub),
+            PreTaprootDescriptor::Bare(ref sub) => fmt::Display::fmt(sub, f),
+            PreTaprootDescriptor::Pkh(ref pkh) => fmt::Display::fmt(pkh, f),
+            PreTaprootDescriptor::Wpkh(ref wpkh) => fmt::Display::fmt(wpkh, f),
+            PreTaprootDescriptor::Sh(ref sub) => fmt::Display::fmt(sub, f),
+            PreTaprootDescriptor::Wsh(ref sub) => fmt::Display::fmt(sub, f),
         }
     }
 }
 
 serde_string_impl_pk!(PreTaprootDescriptor, "a pre-taproot script descriptor");
 
-// Have the trait in a separate module to avoid conflicts
-pub(crate) mod traits {
-    use elements::Script;
-
-    use super::PreTaprootDescriptor;
-    use crate::descriptor::{Pkh, Sh, Wpkh, Wsh};
-    use crate::{DescriptorTrait, MiniscriptKey, ToPublicKey};
-
-    /// A general trait for Pre taproot bitcoin descriptor.
-    /// Similar to [`DescriptorTrait`], but `explicit_script` and `script_code` methods cannot fail
-    pub trait PreTaprootDescriptorTrait<Pk: MiniscriptKey>: DescriptorTrait<Pk> {
-        /// Same as [`DescriptorTrait::explicit_script`], but a non failing version.
-        /// All PreTaproot descriptors have a unique explicit script
-        fn explicit_script(&self) -> Script
-        where
-            Pk: ToPublicKey,
-        {
-            // This expect can technically be avoided if we implement this for types, but
-            // having this expect saves lots of LoC because of default implementation
-            <Self as DescriptorTrait<Pk>>::explicit_script(self)
-                .expect("Pre taproot descriptor have explicit script")
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::str::FromStr;
+
+    use super::{PreTaprootDescriptor, PreTaprootType};
+    use crate::{Descriptor, NoExt};
+
+    #[test]
+    fn try_from_descriptor() {
+        let pk = "020000000000000000000000000000000000000000000000000000000000000002";
+        for s in [
+            format!("elpk({})", pk),
+            format!("elpkh({})", pk),
+            format!("elwpkh({})", pk),
+            format!("elsh(wpkh({}))", pk),
+            format!("elwsh(pk({}))", pk),
+        ] {
+            let desc = Descriptor::<bitcoin::PublicKey>::from_str(&s).unwrap();
+            let pre_tr = PreTaprootDescriptor::try_from(desc.clone()).unwrap();
+            assert_eq!(
+                pre_tr.max_weight_to_satisfy().unwrap(),
+                desc.max_weight_to_satisfy().unwrap()
+            );
+            // The downcast gives the non-failing script methods
+            assert_eq!(pre_tr.explicit_script(), desc.explicit_script().unwrap());
+            assert_eq!(pre_tr.script_code(), desc.script_code().unwrap());
+            assert_eq!(pre_tr.as_descriptor(), desc);
+            assert_eq!(pre_tr.into_descriptor(), desc);
         }
 
-        /// Same as [`DescriptorTrait::script_code`], but a non failing version.
-        /// All PreTaproot descriptors have a script code
-        fn script_code(&self) -> Script
-        where
-            Pk: ToPublicKey,
-        {
-            <Self as DescriptorTrait<Pk>>::script_code(self)
-                .expect("Pre taproot descriptor have non-failing script code")
+        let desc = Descriptor::<bitcoin::PublicKey>::from_str(&format!("eltr({})", pk)).unwrap();
+        PreTaprootDescriptor::try_from(desc).unwrap_err();
+        let desc =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elcovwsh({},1)", pk)).unwrap();
+        PreTaprootDescriptor::try_from(desc).unwrap_err();
+    }
+
+    #[test]
+    fn max_weight_to_satisfy() {
+        let pk = "020000000000000000000000000000000000000000000000000000000000000002";
+        for s in [
+            format!("elsh(wsh(pk({})))", pk),
+            format!("elsh(sortedmulti(1,{}))", pk),
+            format!("elwsh(sortedmulti(1,{}))", pk),
+        ] {
+            let desc = Descriptor::<bitcoin::PublicKey>::from_str(&s).unwrap();
+            let pre_tr = PreTaprootDescriptor::try_from(desc.clone()).unwrap();
+            assert_eq!(
+                pre_tr.max_weight_to_satisfy().unwrap(),
+                desc.max_weight_to_satisfy().unwrap()
+            );
         }
+        // Impossible to satisfy
+        let pre_tr = PreTaprootDescriptor::<bitcoin::PublicKey>::from_str("elwsh(0)").unwrap();
+        pre_tr.max_weight_to_satisfy().unwrap_err();
     }
 
-    impl<Pk: MiniscriptKey> PreTaprootDescriptorTrait<Pk> for Pkh<Pk> {}
+    #[test]
+    fn from_str_and_scripts() {
+        let pk = "020000000000000000000000000000000000000000000000000000000000000002";
+        for s in [
+            format!("elpk({})", pk),
+            format!("elpkh({})", pk),
+            format!("elwpkh({})", pk),
+            format!("elsh(wpkh({}))", pk),
+            format!("elsh(wsh(pk({})))", pk),
+            format!("elwsh(sortedmulti(1,{}))", pk),
+        ] {
+            let pre_tr = PreTaprootDescriptor::<bitcoin::PublicKey>::from_str(&s).unwrap();
+            let desc = Descriptor::<bitcoin::PublicKey>::from_str(&s).unwrap();
+            assert_eq!(pre_tr.to_string(), desc.to_string());
+            assert_eq!(pre_tr, desc);
+            assert_eq!(pre_tr.script_pubkey(), desc.script_pubkey());
+            assert_eq!(pre_tr.unsigned_script_sig(), desc.unsigned_script_sig());
+            assert_eq!(pre_tr.explicit_script(), desc.explicit_script().unwrap());
+            assert_eq!(pre_tr.script_code(), desc.script_code().unwrap());
+            let params = &elements::AddressParams::ELEMENTS;
+            assert_eq!(pre_tr.address(params).ok(), desc.address(params).ok());
+        }
+
+        for s in [format!("eltr({})", pk), format!("elcovwsh({},1)", pk)] {
+            PreTaprootDescriptor::<bitcoin::PublicKey>::from_str(&s).unwrap_err();
+        }
+    }
 
-    impl<Pk: MiniscriptKey> PreTaprootDescriptorTrait<Pk> for Sh<Pk> {}
+    #[test]
+    fn eq_descriptor() {
+        let pk = "020000000000000000000000000000000000000000000000000000000000000002";
+        let pk2 = "020000000000000000000000000000000000000000000000000000000000000003";
+        let desc =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elwsh(pk({}))", pk)).unwrap();
+        let pre_tr = PreTaprootDescriptor::try_from(desc.clone()).unwrap();
+        assert!(desc == pre_tr);
+        assert!(pre_tr == desc);
+        // The comparison does not depend on the descriptor's extension
+        let no_ext =
+            Descriptor::<bitcoin::PublicKey, NoExt>::from_str(&format!("elwsh(pk({}))", pk))
+                .unwrap();
+        assert!(no_ext == pre_tr);
+        assert!(pre_tr == no_ext);
 
-    impl<Pk: MiniscriptKey> PreTaprootDescriptorTrait<Pk> for Wpkh<Pk> {}
+        // Same variant, different descriptor
+        let other =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elwsh(pk({}))", pk2)).unwrap();
+        assert!(other != pre_tr);
+        assert!(pre_tr != other);
+        // Different variant with the same script
+        let other =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elsh(pk({}))", pk)).unwrap();
+        assert!(other != pre_tr);
 
-    impl<Pk: MiniscriptKey> PreTaprootDescriptorTrait<Pk> for Wsh<Pk> {}
+        // Taproot and covenant descriptors are never equal
+        for s in [
+            format!("eltr({})", pk),
+            format!("elcovwsh({},pk({}))", pk, pk),
+        ] {
+            let desc = Descriptor::<bitcoin::PublicKey>::from_str(&s).unwrap();
+            assert!(desc != pre_tr);
+            assert!(pre_tr != desc);
+        }
+    }
 
-    impl<Pk: MiniscriptKey> PreTaprootDescriptorTrait<Pk> for PreTaprootDescriptor<Pk> {}
+    #[test]
+    fn desc_type() {
+        let pk = "020000000000000000000000000000000000000000000000000000000000000002";
+        for (s, ty, name) in [
+            (format!("elpk({})", pk), PreTaprootType::Bare, "bare"),
+            (format!("elpkh({})", pk), PreTaprootType::Pkh, "pkh"),
+            (format!("elwpkh({})", pk), PreTaprootType::Wpkh, "wpkh"),
+            (format!("elsh(pk({}))", pk), PreTaprootType::Sh, "sh"),
+            (
+                format!("elsh(wpkh({}))", pk),
+                PreTaprootType::ShWpkh,
+                "shwpkh",
+            ),
+            (
+                format!("elsh(wsh(pk({})))", pk),
+                PreTaprootType::ShWsh,
+                "shwsh",
+            ),
+            (
+                format!("elsh(sortedmulti(1,{}))", pk),
+                PreTaprootType::ShSortedMulti,
+                "shsortedmulti",
+            ),
+            (
+                format!("elsh(wsh(sortedmulti(1,{})))", pk),
+                PreTaprootType::ShWshSortedMulti,
+                "shwshsortedmulti",
+            ),
+            (format!("elwsh(pk({}))", pk), PreTaprootType::Wsh, "wsh"),
+            (
+                format!("elwsh(sortedmulti(1,{}))", pk),
+                PreTaprootType::WshSortedMulti,
+                "wshsortedmulti",
+            ),
+        ] {
+            let desc = Descriptor::<bitcoin::PublicKey>::from_str(&s).unwrap();
+            let pre_tr = PreTaprootDescriptor::try_from(desc.clone()).unwrap();
+            assert_eq!(pre_tr.desc_type(), ty);
+            let parsed = PreTaprootDescriptor::<bitcoin::PublicKey>::from_str(&s).unwrap();
+            assert_eq!(parsed.desc_type(), ty);
+            assert_eq!(pre_tr.desc_type().to_string(), name);
+            assert_eq!(desc.desc_type().to_string(), name);
+        }
+    }
 }
@@ -17,11 +17,12 @@ use super::{SortedMultiVec, ELMTS_STR};
 use crate::descriptor::checksum;
 use crate::expression::{self, FromTree};
 use crate::miniscript::context::{ScriptContext, ScriptContextError};
+use crate::miniscript::limits::{MAX_STANDARD_P2WSH_SCRIPT_SIZE, MAX_STANDARD_P2WSH_STACK_ITEMS};
 use crate::policy::{semantic, Liftable};
 use crate::util::varint_len;
 use crate::{
     elementssig_to_rawsig, Error, ForEachKey, Miniscript, MiniscriptKey, Satisfier, Segwitv0,
-    ToPublicKey, TranslatePk, Translator,
+    SigType, ToPublicKey, TranslatePk, Translator,
 };
 /// A Segwitv0 wsh descriptor
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
@@ -76,6 +77,35 @@ impl<Pk: MiniscriptKey> Wsh<Pk> {
         Ok(())
     }
 
+    /// Checks whether the descriptor is safe and standard under the
+    /// Elements relay policy. On top of [`Wsh::sanity_check`], this checks
+    /// the witness script against `MAX_STANDARD_P2WSH_SCRIPT_SIZE` and the
+    /// satisfaction against `MAX_STANDARD_P2WSH_STACK_ITEMS`. The returned
+    /// [`Error::ContextError`] names the limit that was exceeded.
+    pub fn sanity_check_standard(&self) -> Result<(), Error> {
+        // Both counts include the witness script itself
+        let (script_size, max_sat_elems) = match self.inner {
+            WshInner::SortedMulti(ref smv) => {
+                (smv.script_size(), smv.max_satisfaction_witness_elements())
+            }
+            WshInner::Ms(ref ms) => (ms.script_size(), ms.max_satisfaction_witness_elements()?),
+        };
+        if script_size > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+            return Err(Error::ContextError(
+                ScriptContextError::MaxWitnessScriptSizeExceeded,
+            ));
+        }
+        if max_sat_elems - 1 > MAX_STANDARD_P2WSH_STACK_ITEMS {
+            return Err(Error::ContextError(
+                ScriptContextError::MaxWitnessItemssExceeded {
+                    actual: max_sat_elems - 1,
+                    limit: MAX_STANDARD_P2WSH_STACK_ITEMS,
+                },
+            ));
+        }
+        self.sanity_check()
+    }
+
     /// Computes an upper bound on the difference between a non-satisfied
     /// `TxIn`'s `segwit_weight` and a satisfied `TxIn`'s `segwit_weight`
     ///
@@ -378,6 +408,13 @@ impl<Pk: MiniscriptKey> Wpkh<Pk> {
         }
     }
 
+    /// Checks whether the descriptor is standard under the Elements relay
+    /// policy. A p2wpkh output has a fixed size, so this is the same as
+    /// [`Wpkh::sanity_check`].
+    pub fn sanity_check_standard(&self) -> Result<(), Error> {
+        self.sanity_check()
+    }
+
     /// Computes an upper bound on the difference between a non-satisfied
     /// `TxIn`'s `segwit_weight` and a satisfied `TxIn`'s `segwit_weight`
     ///
@@ -397,6 +434,7 @@ impl<Pk: MiniscriptKey> Wpkh<Pk> {
     /// Assumes all ec-signatures are 73 bytes, including push opcode and
     /// sighash suffix. Includes the weight of the VarInts encoding the
     /// scriptSig and witness stack length.
+    #[deprecated(note = "use max_weight_to_satisfy instead")]
     pub fn max_satisfaction_weight(&self) -> usize {
         4 + 1 + 73 + Segwitv0::pk_len(&self.pk)
     }
@@ -448,14 +486,8 @@ impl<Pk: MiniscriptKey + ToPublicKey> Wpkh<Pk> {
         // the previous txo's scriptPubKey.
         // The item 5:
         //     - For P2WPKH witness program, the scriptCode is `0x1976a914{20-byte-pubkey-hash}88ac`.
-        let addr = elements::Address::p2pkh(
-            &self.pk.to_public_key(),
-            None,
-            &elements::AddressParams::ELEMENTS,
-        );
-        // Need this expect in future
-        // .expect("wpkh descriptors have compressed keys");
-        addr.script_pubkey()
+        let hash = self.pk.to_pubkeyhash(SigType::Ecdsa);
+        Script::new_p2pkh(&hash.into())
     }
 
     /// Returns satisfying non-malleable witness and scriptSig with minimum
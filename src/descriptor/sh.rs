@@ -16,7 +16,8 @@ use super::checksum::verify_checksum;
 use super::{SortedMultiVec, Wpkh, Wsh, ELMTS_STR};
 use crate::descriptor::checksum;
 use crate::expression::{self, FromTree};
-use crate::miniscript::context::ScriptContext;
+use crate::miniscript::context::{ScriptContext, ScriptContextError};
+use crate::miniscript::limits::{MAX_SCRIPTSIG_SIZE, MAX_SCRIPT_ELEMENT_SIZE};
 use crate::policy::{semantic, Liftable};
 use crate::util::{varint_len, witness_to_scriptsig};
 use crate::{
@@ -180,6 +181,32 @@ impl<Pk: MiniscriptKey> Sh<Pk> {
         Ok(())
     }
 
+    /// Checks whether the descriptor is safe and standard under the
+    /// Elements relay policy. Nested segwit descriptors are checked with
+    /// [`Wsh::sanity_check_standard`]. For legacy p2sh, this checks the
+    /// redeem script against `MAX_SCRIPT_ELEMENT_SIZE` and the satisfying
+    /// scriptSig against `MAX_SCRIPTSIG_SIZE`. The returned
+    /// [`Error::ContextError`] names the limit that was exceeded.
+    pub fn sanity_check_standard(&self) -> Result<(), Error> {
+        let (script_size, max_sat_size) = match self.inner {
+            ShInner::Wsh(ref wsh) => return wsh.sanity_check_standard(),
+            ShInner::Wpkh(ref wpkh) => return wpkh.sanity_check_standard(),
+            ShInner::SortedMulti(ref smv) => (smv.script_size(), smv.max_satisfaction_size()),
+            ShInner::Ms(ref ms) => (ms.script_size(), ms.max_satisfaction_size()?),
+        };
+        if script_size > MAX_SCRIPT_ELEMENT_SIZE {
+            return Err(Error::ContextError(
+                ScriptContextError::MaxRedeemScriptSizeExceeded,
+            ));
+        }
+        if push_opcode_size(script_size) + script_size + max_sat_size > MAX_SCRIPTSIG_SIZE {
+            return Err(Error::ContextError(
+                ScriptContextError::MaxScriptSigSizeExceeded,
+            ));
+        }
+        self.sanity_check()
+    }
+
     /// Create a new p2sh wrapped wsh sortedmulti descriptor from threshold
     /// `k` and Vec of `pks`
     pub fn new_wsh_sortedmulti(k: usize, pks: Vec<Pk>) -> Result<Self, Error> {
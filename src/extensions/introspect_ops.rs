@@ -1,5 +1,11 @@
-//! Miniscript Arithmetic expressions:
-//! Note that this fragment is only supported for Tapscript context
+//! Miniscript Introspection expressions:
+//! Fragments that inspect the assets, values and script pubkeys of the
+//! transaction inputs and outputs with the Elements `OP_INSPECT*` opcodes.
+//! For example, requiring an explicit amount of an explicit asset at
+//! output `i` is
+//! `and_v(v:asset_eq(out_asset(i),<asset>),value_eq(out_value(i),<value>))`.
+//! Note that this fragment is only supported for Tapscript context, the
+//! introspection opcodes are not available in segwit v0 scripts.
 use std::convert::TryFrom;
 use std::fmt;
 use std::str::FromStr;
@@ -1274,6 +1280,9 @@ mod tests {
         _test_parse("curr_idx_eq(1)");
         _test_parse("curr_idx_eq(0)");
 
+        // explicit asset and amount at an output
+        _test_parse("and_v(v:asset_eq(out_asset(0),ExpAst),value_eq(out_value(0),ExpVal))");
+
         // test some misc combinations with other miniscript fragments
         _test_parse(
             "and_v(v:pk(K),and_v(v:is_exp_value(out_value(1)),is_exp_asset(out_asset(1))))",
@@ -1,15 +1,20 @@
 // Written in 2019 by Sanket Kanjular and Andrew Poelstra
 // SPDX-License-Identifier: CC0-1.0
 
-use std::{error, fmt};
+use core::fmt;
+use std::error;
 
-use elements::hashes::hash160;
+use elements::hashes::{hash160, sha256};
 use elements::hex::ToHex;
+use elements::taproot::{
+    TAPROOT_CONTROL_BASE_SIZE, TAPROOT_CONTROL_MAX_NODE_COUNT, TAPROOT_CONTROL_NODE_SIZE,
+};
 use elements::{secp256k1_zkp, taproot};
 use {bitcoin, elements};
 
 use super::BitcoinKey;
 use crate::extensions::EvalError;
+use crate::SigType;
 
 /// Detailed Error type for Interpreter
 #[derive(Debug)]
@@ -36,17 +41,47 @@ pub enum Error {
     /// The preimage to the hash function must be exactly 32 bytes.
     HashPreimageLengthMismatch,
     /// Incorrect scriptPubKey (pay-to-pubkeyhash) for the provided public key
-    IncorrectPubkeyHash,
+    IncorrectPubkeyHash {
+        /// The hash committed to by the scriptPubKey
+        expected: hash160::Hash,
+        /// The hash of the provided public key
+        computed: hash160::Hash,
+    },
     /// Incorrect scriptPubKey for the provided redeem script
-    IncorrectScriptHash,
-    /// Incorrect scriptPubKey (pay-to-witness-pubkeyhash) for the provided public key
-    IncorrectWPubkeyHash,
-    /// Incorrect scriptPubKey for the provided witness script
-    IncorrectWScriptHash,
+    IncorrectScriptHash {
+        /// The hash committed to by the scriptPubKey
+        expected: hash160::Hash,
+        /// The hash of the provided redeem script
+        computed: hash160::Hash,
+    },
+    /// Incorrect scriptPubKey (pay-to-witness-pubkeyhash) or p2sh redeem
+    /// script for the provided public key
+    IncorrectWPubkeyHash {
+        /// The hash committed to by the scriptPubKey or redeem script
+        expected: hash160::Hash,
+        /// The hash of the provided public key
+        computed: hash160::Hash,
+    },
+    /// Incorrect scriptPubKey or p2sh redeem script for the provided
+    /// witness script
+    IncorrectWScriptHash {
+        /// The hash committed to by the scriptPubKey or redeem script
+        expected: sha256::Hash,
+        /// The hash of the provided witness script
+        computed: sha256::Hash,
+    },
     /// MultiSig missing at least `1` witness elements out of `k + 1` required
     InsufficientSignaturesMultiSig,
     /// Invalid Sighash type
     InvalidSchnorrSighashType(Vec<u8>),
+    /// A key in script is not encoded as the script context requires,
+    /// such as a 33 byte full key in a tapscript
+    InvalidKeyLengthForContext {
+        /// The key length required by the script context
+        expected: usize,
+        /// The length of the key in script
+        got: usize,
+    },
     /// ecdsa Signature failed to verify
     InvalidEcdsaSignature(bitcoin::PublicKey),
     /// Signature failed to verify
@@ -62,6 +97,9 @@ pub enum Error {
     /// this error. This is network standardness assumption and miniscript only
     /// supports standard scripts
     MultiSigEvaluationError,
+    /// The spend is a taproot spend, but no taproot spend info was
+    /// supplied to check the output key against
+    NeedsSpendInfo,
     ///Witness must be empty for pre-segwit transactions
     NonEmptyWitness,
     ///ScriptSig must be empty for pure segwit transactions
@@ -76,6 +114,21 @@ pub enum Error {
     /// The Public Key hash check for the given pubkey. This occurs in `PkH`
     /// node when the given key does not match to Hash in script.
     PkHashVerifyFail(hash160::Hash),
+    /// The key revealed for a `PkH` node matches the hash in script, but
+    /// is not encoded as the key type required by the script context.
+    /// Tapscript requires 32 byte x-only keys, all other contexts require
+    /// 33 or 65 byte full keys. Keys of the required length that are not
+    /// valid are reported as [`Error::PubkeyParseError`] or
+    /// [`Error::XOnlyPublicKeyParseError`].
+    PkHashKeyTypeMismatch {
+        /// The key hash in script
+        keyhash: hash160::Hash,
+        /// The signature type of the script context. Determines the
+        /// expected key type.
+        sig_type: SigType,
+        /// The length of the revealed key
+        key_len: usize,
+    },
     /// Parse Error while parsing a `stack::Element::Push` as a Pubkey. Both
     /// 33 byte and 65 bytes are supported.
     PubkeyParseError,
@@ -91,11 +144,33 @@ pub enum Error {
     SchnorrSig(elements::SchnorrSigError),
     /// Errors in signature hash calculations
     SighashError(elements::sighash::Error),
+    /// The initial stack of the spend has more items than allowed
+    StackSizeLimitExceeded {
+        /// Number of items on the stack
+        actual: usize,
+        /// The maximum number of stack items
+        limit: usize,
+    },
     /// Taproot Annex Unsupported
     TapAnnexUnsupported,
+    /// The length of the taproot control block is not 33 bytes plus a
+    /// multiple of 32 byte merkle path nodes
+    TapControlBlockBadMerklePathLength(usize),
+    /// The merkle path of the taproot control block has more than 128 nodes
+    TapControlBlockTooLong(usize),
+    /// The taproot control block is shorter than the 33 bytes of leaf
+    /// version, parity and internal key
+    TapControlBlockTooShort(usize),
+    /// The taproot output key is not the tweak of the given internal key
+    /// and merkle root
+    TapKeyTweakMismatch,
+    /// The control block commits to a leaf version other than tapscript
+    TapLeafVersionUnsupported(taproot::LeafVersion),
     /// An uncompressed public key was encountered in a context where it is
     /// disallowed (e.g. in a Segwit script or p2wpkh output)
     UncompressedPubkey,
+    /// The scriptPubKey starts with `OP_RETURN` and can never be spent
+    UnspendableOutput,
     /// Got `stack::Element::Satisfied` or `stack::Element::Dissatisfied` when the
     /// interpreter was expecting `stack::Element::Push`
     UnexpectedStackBoolean,
@@ -110,6 +185,9 @@ pub enum Error {
     VerifyFailed,
     /// Incorrect Covenant Witness
     IncorrectCovenantWitness,
+    /// The covenant signature does not verify against the sighash message
+    /// reassembled from the sighash items on the witness stack
+    CovSighashMismatch,
     /// Covenant witness size mismatch
     /// eg: supplied a witness at
     /// nVersion with 5 bytes instead of 4
@@ -147,18 +225,37 @@ impl fmt::Display for Error {
             Error::ExpectedPush => f.write_str("expected push in script"),
             Error::CouldNotEvaluate => f.write_str("Interpreter Error: Could not evaluate"),
             Error::HashPreimageLengthMismatch => f.write_str("Hash preimage should be 32 bytes"),
-            Error::IncorrectPubkeyHash => f.write_str("public key did not match scriptpubkey"),
-            Error::IncorrectScriptHash => f.write_str("redeem script did not match scriptpubkey"),
-            Error::IncorrectWPubkeyHash => {
-                f.write_str("public key did not match scriptpubkey (segwit v0)")
-            }
-            Error::IncorrectWScriptHash => f.write_str("witness script did not match scriptpubkey"),
+            Error::IncorrectPubkeyHash { expected, computed } => write!(
+                f,
+                "public key did not match scriptpubkey: expected {}, computed {}",
+                expected, computed
+            ),
+            Error::IncorrectScriptHash { expected, computed } => write!(
+                f,
+                "redeem script did not match scriptpubkey: expected {}, computed {}",
+                expected, computed
+            ),
+            Error::IncorrectWPubkeyHash { expected, computed } => write!(
+                f,
+                "public key did not match scriptpubkey (segwit v0): expected {}, computed {}",
+                expected, computed
+            ),
+            Error::IncorrectWScriptHash { expected, computed } => write!(
+                f,
+                "witness script did not match scriptpubkey: expected {}, computed {}",
+                expected, computed
+            ),
             Error::InsufficientSignaturesMultiSig => f.write_str("Insufficient signatures for CMS"),
             Error::InvalidSchnorrSighashType(ref sig) => write!(
                 f,
                 "Invalid sighash type for schnorr signature '{}'",
                 sig.to_hex()
             ),
+            Error::InvalidKeyLengthForContext { expected, got } => write!(
+                f,
+                "{} byte key in script, the script context requires {} byte keys",
+                got, expected
+            ),
             Error::InvalidEcdsaSignature(pk) => write!(f, "bad ecdsa signature with pk {}", pk),
             Error::InvalidSchnorrSignature(pk) => write!(f, "bad schnorr signature with pk {}", pk),
             Error::NonStandardSighash(ref sig) => write!(
@@ -166,6 +263,7 @@ impl fmt::Display for Error {
                 "Non standard sighash type for signature '{}'",
                 sig.to_hex()
             ),
+            Error::NeedsSpendInfo => f.write_str("taproot spend requires the taproot spend info"),
             Error::NonEmptyWitness => f.write_str("legacy spend had nonempty witness"),
             Error::NonEmptyScriptSig => f.write_str("segwit spend had nonempty scriptsig"),
             Error::Miniscript(ref e) => write!(f, "parse error: {}", e),
@@ -175,6 +273,21 @@ impl fmt::Display for Error {
             }
             Error::PkEvaluationError(ref key) => write!(f, "Incorrect Signature for pk {}", key),
             Error::PkHashVerifyFail(ref hash) => write!(f, "Pubkey Hash check failed {}", hash),
+            Error::PkHashKeyTypeMismatch {
+                keyhash,
+                sig_type,
+                key_len,
+            } => {
+                let expected = match sig_type {
+                    SigType::Ecdsa => "a 33 or 65 byte full key",
+                    SigType::Schnorr => "a 32 byte x-only key",
+                };
+                write!(
+                    f,
+                    "Key for pubkey hash {} has {} bytes, expected {}",
+                    keyhash, key_len, expected
+                )
+            }
             Error::PubkeyParseError => f.write_str("could not parse pubkey"),
             Error::XOnlyPublicKeyParseError => f.write_str("could not parse x-only pubkey"),
             Error::RelativeLocktimeNotMet(n) => {
@@ -184,10 +297,38 @@ impl fmt::Display for Error {
             Error::Secp(ref e) => fmt::Display::fmt(e, f),
             Error::SchnorrSig(ref s) => write!(f, "Schnorr sig error: {}", s),
             Error::SighashError(ref e) => fmt::Display::fmt(e, f),
+            Error::StackSizeLimitExceeded { actual, limit } => write!(
+                f,
+                "stack has {} items, more than the limit of {}",
+                actual, limit
+            ),
             Error::TapAnnexUnsupported => f.write_str("Encountered annex element"),
+            Error::TapControlBlockBadMerklePathLength(len) => write!(
+                f,
+                "taproot control block of {} bytes does not have a whole number of {} byte \
+                merkle path nodes",
+                len, TAPROOT_CONTROL_NODE_SIZE
+            ),
+            Error::TapControlBlockTooLong(len) => write!(
+                f,
+                "taproot control block of {} bytes has more than {} merkle path nodes",
+                len, TAPROOT_CONTROL_MAX_NODE_COUNT
+            ),
+            Error::TapControlBlockTooShort(len) => write!(
+                f,
+                "taproot control block of {} bytes is shorter than {} bytes",
+                len, TAPROOT_CONTROL_BASE_SIZE
+            ),
+            Error::TapKeyTweakMismatch => {
+                f.write_str("taproot output key does not match the tweaked internal key")
+            }
+            Error::TapLeafVersionUnsupported(v) => {
+                write!(f, "unsupported taproot leaf version {:#04x}", v.as_u8())
+            }
             Error::UncompressedPubkey => {
                 f.write_str("uncompressed pubkey in non-legacy descriptor")
             }
+            Error::UnspendableOutput => f.write_str("spent output is provably unspendable"),
             Error::UnexpectedStackBoolean => {
                 f.write_str("Expected Stack Push operation, found stack bool")
             }
@@ -200,6 +341,10 @@ impl fmt::Display for Error {
                 "Covenant witness incorrect, the initial stack supplied for \
                 covenant global context is incorrect",
             ),
+            Error::CovSighashMismatch => f.write_str(
+                "Covenant signature does not verify against the sighash \
+                message reconstructed from the witness",
+            ),
             Error::CovWitnessSizeErr {
                 pos,
                 expected,
@@ -226,27 +371,37 @@ impl error::Error for Error {
             | CouldNotEvaluate
             | ExpectedPush
             | HashPreimageLengthMismatch
-            | IncorrectPubkeyHash
-            | IncorrectScriptHash
-            | IncorrectWPubkeyHash
-            | IncorrectWScriptHash
+            | IncorrectPubkeyHash { .. }
+            | IncorrectScriptHash { .. }
+            | IncorrectWPubkeyHash { .. }
+            | IncorrectWScriptHash { .. }
             | InsufficientSignaturesMultiSig
+            | InvalidKeyLengthForContext { .. }
             | InvalidEcdsaSignature(_)
             | InvalidSchnorrSignature(_)
             | InvalidSchnorrSighashType(_)
             | NonStandardSighash(_)
             | MissingExtraZeroMultiSig
             | MultiSigEvaluationError
+            | NeedsSpendInfo
             | NonEmptyWitness
             | NonEmptyScriptSig
             | PubkeyParseError
             | XOnlyPublicKeyParseError
             | PkEvaluationError(_)
             | PkHashVerifyFail(_)
+            | PkHashKeyTypeMismatch { .. }
             | RelativeLocktimeNotMet(_)
             | ScriptSatisfactionError
+            | StackSizeLimitExceeded { .. }
             | TapAnnexUnsupported
+            | TapControlBlockBadMerklePathLength(_)
+            | TapControlBlockTooLong(_)
+            | TapControlBlockTooShort(_)
+            | TapKeyTweakMismatch
+            | TapLeafVersionUnsupported(_)
             | UncompressedPubkey
+            | UnspendableOutput
             | UnexpectedStackBoolean
             | UnexpectedStackEnd
             | UnexpectedStackElementPush
@@ -258,6 +413,7 @@ impl error::Error for Error {
             SchnorrSig(e) => Some(e),
             SighashError(e) => Some(e),
             IncorrectCovenantWitness => None,
+            CovSighashMismatch => None,
             CovWitnessSizeErr { .. } => None,
             ArithError(..) => None,
         }
@@ -1,15 +1,21 @@
 // Written in 2019 by Sanket Kanjular and Andrew Poelstra
 // SPDX-License-Identifier: CC0-1.0
 
+use core::fmt;
+use core::str::FromStr;
+
 use bitcoin;
 use bitcoin::taproot::TAPROOT_ANNEX_PREFIX;
 use elements::hashes::{hash160, sha256, Hash};
+use elements::opcodes::all::{OP_PUSHNUM_1, OP_PUSHNUM_2};
 use elements::schnorr::TweakedPublicKey;
-use elements::taproot::ControlBlock;
+use elements::taproot::{ControlBlock, LeafVersion, TaprootError, TAPROOT_CONTROL_BASE_SIZE};
 use elements::{self, script};
 
-use super::{stack, BitcoinKey, Error, Stack};
-use crate::descriptor::{CovOperations, LegacyCSFSCov};
+use super::{stack, BitcoinKey, Error, InterpreterOptions, Stack};
+use crate::descriptor::{
+    CovOperations, DescriptorType, LegacyCSFSCov, TapCSFSCov, UNSPENDABLE_INTERNAL_KEY,
+};
 use crate::extensions::ParseableExt;
 use crate::miniscript::context::{NoChecks, ScriptContext, SigType};
 use crate::util::is_v1_p2tr;
@@ -43,6 +49,34 @@ fn pk_from_stack_elem(
     pk_from_slice(slice, require_compressed)
 }
 
+// The hashes committed to by a scriptPubKey or redeem script, which has
+// already been checked to be of the right type
+fn hash160_from_slice(slice: &[u8]) -> hash160::Hash {
+    hash160::Hash::from_slice(slice).expect("20 byte hash")
+}
+
+fn sha256_from_slice(slice: &[u8]) -> sha256::Hash {
+    sha256::Hash::from_slice(slice).expect("32 byte hash")
+}
+
+// Parse a taproot control block, telling apart the ways its length can
+// be wrong
+fn control_block_from_slice(sl: &[u8]) -> Result<ControlBlock, Error> {
+    match ControlBlock::from_slice(sl) {
+        Ok(ctrl_blk) => Ok(ctrl_blk),
+        Err(TaprootError::InvalidControlBlockSize(len)) if len < TAPROOT_CONTROL_BASE_SIZE => {
+            Err(Error::TapControlBlockTooShort(len))
+        }
+        Err(TaprootError::InvalidControlBlockSize(len)) => {
+            Err(Error::TapControlBlockBadMerklePathLength(len))
+        }
+        Err(TaprootError::InvalidMerkleTreeDepth(_)) => {
+            Err(Error::TapControlBlockTooLong(sl.len()))
+        }
+        Err(e) => Err(Error::ControlBlockParse(e)),
+    }
+}
+
 // Parse the script with appropriate context to check for context errors like
 // correct usage of x-only keys or multi_a
 fn script_from_stack_elem<Ctx: ScriptContext, Ext: ParseableExt>(
@@ -63,25 +97,92 @@ fn script_from_stack_elem<Ctx: ScriptContext, Ext: ParseableExt>(
     }
 }
 
+// Tapscript keys are 32 byte x-only keys. Report full keys pushed for
+// a signature check with their length, instead of as a failure to parse
+// the tapscript as miniscript.
+fn check_tap_key_lengths(elem: &stack::Element<'_>) -> Result<(), Error> {
+    use crate::miniscript::lex::{lex, Token as Tk};
+
+    let sl = match *elem {
+        stack::Element::Push(sl) => sl,
+        _ => return Ok(()),
+    };
+    let script = elements::Script::from(sl.to_owned());
+    // Scripts that do not lex are reported by the miniscript parser
+    let tokens = match lex(&script) {
+        Ok(tokens) => tokens,
+        Err(_) => return Ok(()),
+    };
+    for pair in tokens.windows(2) {
+        match (&pair[0], &pair[1]) {
+            (Tk::Bytes33(pk), Tk::CheckSig)
+            | (Tk::Bytes33(pk), Tk::CheckSigAdd)
+            | (Tk::Bytes65(pk), Tk::CheckSig)
+            | (Tk::Bytes65(pk), Tk::CheckSigAdd) => {
+                return Err(Error::InvalidKeyLengthForContext {
+                    expected: 32,
+                    got: pk.len(),
+                })
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 // Try to parse covenant components from witness script
-// stack element
+// stack element. Returns the threshold, the covenant keys, the
+// miniscript and the script code signed by the covenant keys
 fn cov_components_from_stackelem<Ext>(
     elem: &stack::Element<'_>,
 ) -> Option<(
-    super::BitcoinKey,
+    usize,
+    Vec<super::BitcoinKey>,
     Miniscript<super::BitcoinKey, NoChecks, Ext>,
+    elements::Script,
 )>
 where
     Ext: ParseableExt,
 {
-    let (pk, ms) = match *elem {
+    let (k, pks, ms) = match *elem {
         stack::Element::Push(sl) => LegacyCSFSCov::<bitcoin::PublicKey, Ext>::parse_cov_components(
             &elements::Script::from(sl.to_owned()),
         )
         .ok()?,
         _ => return None,
     };
-    Some((super::BitcoinKey::Fullkey(pk), ms.to_no_checks_ms()))
+    let script_code = if k == 1 && pks.len() == 1 {
+        script::Builder::new().post_codesep_script().into_script()
+    } else {
+        script::Builder::new()
+            .post_codesep_multi_script(&pks, k)
+            .into_script()
+    };
+    let pks = pks.into_iter().map(super::BitcoinKey::Fullkey).collect();
+    Some((k, pks, ms.to_no_checks_ms(), script_code))
+}
+
+// Try to parse taproot covenant components from tapleaf script
+// stack element
+fn tap_cov_components_from_stackelem<Ext>(
+    elem: &stack::Element<'_>,
+) -> Option<(
+    super::BitcoinKey,
+    Miniscript<super::BitcoinKey, NoChecks, Ext>,
+)>
+where
+    Ext: ParseableExt,
+{
+    let (pk, ms) = match *elem {
+        stack::Element::Push(sl) => {
+            TapCSFSCov::<bitcoin::key::XOnlyPublicKey, Ext>::parse_cov_components(
+                &elements::Script::from(sl.to_owned()),
+            )
+            .ok()?
+        }
+        _ => return None,
+    };
+    Some((super::BitcoinKey::XOnlyPublicKey(pk), ms.to_no_checks_ms()))
 }
 
 /// Helper type to indicate the origin of the bare pubkey that the interpereter uses
@@ -104,6 +205,84 @@ pub enum ScriptType {
     Tr, // Script Spend
 }
 
+impl PubkeyType {
+    /// The descriptor style label of the spend type
+    pub fn descriptor_type_label(&self) -> &'static str {
+        match *self {
+            PubkeyType::Pk => "pk",
+            PubkeyType::Pkh => "pkh",
+            PubkeyType::Wpkh => "wpkh",
+            PubkeyType::ShWpkh => "sh(wpkh)",
+            PubkeyType::Tr => "tr",
+        }
+    }
+}
+
+impl fmt::Display for PubkeyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.descriptor_type_label())
+    }
+}
+
+impl FromStr for PubkeyType {
+    type Err = crate::Error;
+
+    /// Parses the labels written by `Display`. `sh-wpkh` is accepted
+    /// as well as `sh(wpkh)`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pk" => Ok(PubkeyType::Pk),
+            "pkh" => Ok(PubkeyType::Pkh),
+            "wpkh" => Ok(PubkeyType::Wpkh),
+            "sh(wpkh)" | "sh-wpkh" => Ok(PubkeyType::ShWpkh),
+            "tr" => Ok(PubkeyType::Tr),
+            _ => Err(crate::Error::Unexpected(format!(
+                "unknown public key spend type {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl ScriptType {
+    /// The descriptor style label of the spend type
+    pub fn descriptor_type_label(&self) -> &'static str {
+        match *self {
+            ScriptType::Bare => "bare",
+            ScriptType::Sh => "sh",
+            ScriptType::Wsh => "wsh",
+            ScriptType::ShWsh => "sh(wsh)",
+            ScriptType::Tr => "tr",
+        }
+    }
+}
+
+impl fmt::Display for ScriptType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.descriptor_type_label())
+    }
+}
+
+impl FromStr for ScriptType {
+    type Err = crate::Error;
+
+    /// Parses the labels written by `Display`. `sh-wsh` is accepted
+    /// as well as `sh(wsh)`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bare" => Ok(ScriptType::Bare),
+            "sh" => Ok(ScriptType::Sh),
+            "wsh" => Ok(ScriptType::Wsh),
+            "sh(wsh)" | "sh-wsh" => Ok(ScriptType::ShWsh),
+            "tr" => Ok(ScriptType::Tr),
+            _ => Err(crate::Error::Unexpected(format!(
+                "unknown script spend type {}",
+                s
+            ))),
+        }
+    }
+}
+
 /// Structure representing a script under evaluation as a Miniscript
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Inner<Ext: Extension> {
@@ -114,17 +293,78 @@ pub enum Inner<Ext: Extension> {
     PublicKey(super::BitcoinKey, PubkeyType),
     /// The script being evaluated is an actual script
     Script(Miniscript<super::BitcoinKey, NoChecks, Ext>, ScriptType),
-    /// The Covenant Miniscript
-    /// Only Witnessv0 scripts are supported for now
+    /// The Covenant Miniscript with its threshold and covenant keys.
+    /// Only Wsh and Tr(script spend) scripts are supported, taproot
+    /// covenants always have a single key
     CovScript(
-        super::BitcoinKey,
+        usize,
+        Vec<super::BitcoinKey>,
         Miniscript<super::BitcoinKey, NoChecks, Ext>,
-        // Add scriptType when we support additional things here
-        // ScriptType,
+        ScriptType,
     ),
+    /// A witness program of a version without consensus rules yet (v2 and
+    /// above), which is anyone-can-spend under the current rules. Only
+    /// returned with [`InterpreterOptions::future_witness`].
+    FutureWitness {
+        /// The witness version
+        version: u8,
+        /// The witness program
+        program: Vec<u8>,
+    },
     // todo: add extensions support as explicit enum
 }
 
+impl<Ext: Extension> Inner<Ext> {
+    /// The descriptor style label of the spend type. Covenants are
+    /// labelled `covwsh` or `tapcov`.
+    pub fn descriptor_type_label(&self) -> &'static str {
+        match *self {
+            Inner::PublicKey(_, ty) => ty.descriptor_type_label(),
+            Inner::Script(_, ty) => ty.descriptor_type_label(),
+            Inner::CovScript(_, _, _, ScriptType::Tr) => "tapcov",
+            Inner::CovScript(..) => "covwsh",
+            Inner::FutureWitness { .. } => "future_witness",
+        }
+    }
+
+    /// The [`DescriptorType`] of the spend. Sorted multi descriptors
+    /// can not be told apart from their miniscript `multi` equivalent and
+    /// are reported as such. Taproot covenants are reported as
+    /// [`DescriptorType::Tr`], and future witness programs, which have no
+    /// descriptor, as [`DescriptorType::Bare`].
+    pub fn descriptor_type(&self) -> DescriptorType {
+        match *self {
+            Inner::PublicKey(_, PubkeyType::Pk) => DescriptorType::Bare,
+            Inner::PublicKey(_, PubkeyType::Pkh) => DescriptorType::Pkh,
+            Inner::PublicKey(_, PubkeyType::Wpkh) => DescriptorType::Wpkh,
+            Inner::PublicKey(_, PubkeyType::ShWpkh) => DescriptorType::ShWpkh,
+            Inner::PublicKey(_, PubkeyType::Tr) => DescriptorType::Tr,
+            Inner::Script(_, ScriptType::Bare) => DescriptorType::Bare,
+            Inner::Script(_, ScriptType::Sh) => DescriptorType::Sh,
+            Inner::Script(_, ScriptType::Wsh) => DescriptorType::Wsh,
+            Inner::Script(_, ScriptType::ShWsh) => DescriptorType::ShWsh,
+            Inner::Script(_, ScriptType::Tr) => DescriptorType::Tr,
+            Inner::CovScript(_, _, _, ScriptType::Tr) => DescriptorType::Tr,
+            Inner::CovScript(..) => DescriptorType::Cov,
+            Inner::FutureWitness { .. } => DescriptorType::Bare,
+        }
+    }
+}
+
+/// Taproot specific data parsed from the witness which is not part of
+/// the [`Inner`] script under evaluation
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TapSpendData<'txin> {
+    /// The annex, if present and allowed
+    pub annex: Option<&'txin [u8]>,
+    /// The control block for script spends, `None` for key spends
+    pub control_block: Option<ControlBlock>,
+    /// Whether the control block of a script spend reveals the BIP341
+    /// unspendable internal key, i.e. the output has no key path spend.
+    /// Always `false` for key spends.
+    pub unspendable_internal_key: bool,
+}
+
 // The `Script` returned by this method is always generated/cloned ... when
 // rust-bitcoin is updated to use a copy-on-write internal representation we
 // should revisit this and return references to the actual txdata wherever
@@ -132,22 +372,102 @@ pub enum Inner<Ext: Extension> {
 /// Parses an `Inner` and appropriate `Stack` from completed transaction data,
 /// as well as the script that should be used as a scriptCode in a sighash
 /// Tr outputs don't have script code and return None.
-#[allow(clippy::collapsible_else_if)]
+///
+/// Taproot spends also return the [`TapSpendData`] parsed from the witness.
+///
+/// Taproot spends carrying an annex are rejected with [`Error::TapAnnexUnsupported`];
+/// use [`InterpreterOptions::annex`] to accept them.
 pub fn from_txdata<'txin, Ext: ParseableExt>(
     spk: &elements::Script,
     script_sig: &'txin elements::Script,
     witness: &'txin [Vec<u8>],
-) -> Result<(Inner<Ext>, Stack<'txin>, Option<elements::Script>), Error> {
-    let mut ssig_stack: Stack = script_sig
-        .instructions_minimal()
-        .map(stack::Element::from_instruction)
-        .collect::<Result<Vec<stack::Element<'_>>, Error>>()?
-        .into();
-    let mut wit_stack: Stack<'_> = witness
-        .iter()
-        .map(stack::Element::from)
-        .collect::<Vec<stack::Element<'_>>>()
-        .into();
+) -> Result<
+    (
+        Inner<Ext>,
+        Stack<'txin>,
+        Option<elements::Script>,
+        Option<TapSpendData<'txin>>,
+    ),
+    Error,
+> {
+    from_txdata_with_options(spk, script_sig, witness, &InterpreterOptions::default())
+}
+
+/// Same as [`from_txdata`], but parses the spend with `options`. The spend
+/// info of the options is not checked here, as it needs the parsed spend.
+pub fn from_txdata_with_options<'txin, Ext: ParseableExt>(
+    spk: &elements::Script,
+    script_sig: &'txin elements::Script,
+    witness: &'txin [Vec<u8>],
+    options: &InterpreterOptions<'_>,
+) -> Result<
+    (
+        Inner<Ext>,
+        Stack<'txin>,
+        Option<elements::Script>,
+        Option<TapSpendData<'txin>>,
+    ),
+    Error,
+> {
+    let res = parse_txdata(spk, script_sig, witness, options)?;
+    // The initial stack of the spend, i.e. the stack the miniscript is
+    // executed on, may have at most `max_stack_items` items
+    if res.1.len() > options.max_stack_items {
+        return Err(Error::StackSizeLimitExceeded {
+            actual: res.1.len(),
+            limit: options.max_stack_items,
+        });
+    }
+    Ok(res)
+}
+
+/// Same as [`from_txdata`], but takes the scriptSig and witness straight from
+/// the spending input. Inputs without a witness (legacy spends, or pegins and
+/// coinbases with an empty script witness) are parsed with an empty witness
+/// stack.
+pub fn from_txin<'txin, Ext: ParseableExt>(
+    spk: &elements::Script,
+    txin: &'txin elements::TxIn,
+) -> Result<
+    (
+        Inner<Ext>,
+        Stack<'txin>,
+        Option<elements::Script>,
+        Option<TapSpendData<'txin>>,
+    ),
+    Error,
+> {
+    from_txdata(spk, &txin.script_sig, &txin.witness.script_witness)
+}
+
+#[allow(clippy::collapsible_else_if)]
+// Without a verifier in `options`, taproot commitments are checked with a new
+// secp256k1 context.
+fn parse_txdata<'txin, Ext: ParseableExt>(
+    spk: &elements::Script,
+    script_sig: &'txin elements::Script,
+    witness: &'txin [Vec<u8>],
+    options: &InterpreterOptions<'_>,
+) -> Result<
+    (
+        Inner<Ext>,
+        Stack<'txin>,
+        Option<elements::Script>,
+        Option<TapSpendData<'txin>>,
+    ),
+    Error,
+> {
+    // OP_RETURN outputs can never be spent, so don't try to parse
+    // them as bare scripts
+    if spk.is_op_return() {
+        return Err(Error::UnspendableOutput);
+    }
+    let mut ssig_stack = if options.allow_non_minimal {
+        Stack::from_script_sig_non_minimal(script_sig)?
+    } else {
+        Stack::from_script_sig(script_sig)?
+    };
+    let mut wit_stack = Stack::from_witness(witness);
 
     // ** pay to pubkey **
     if spk.is_p2pk() {
@@ -161,6 +481,7 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
                 ),
                 ssig_stack,
                 Some(spk.clone()),
+                None,
             ))
         }
     // ** pay to pubkeyhash **
@@ -171,15 +492,19 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
             match ssig_stack.pop() {
                 Some(elem) => {
                     let pk = pk_from_stack_elem(&elem, false)?;
-                    if *spk == elements::Script::new_p2pkh(&pk.to_pubkeyhash(SigType::Ecdsa).into())
-                    {
+                    let hash160 = pk.to_pubkeyhash(SigType::Ecdsa);
+                    if *spk == elements::Script::new_p2pkh(&hash160.into()) {
                         Ok((
                             Inner::PublicKey(pk.into(), PubkeyType::Pkh),
                             ssig_stack,
                             Some(spk.clone()),
+                            None,
                         ))
                     } else {
-                        Err(Error::IncorrectPubkeyHash)
+                        Err(Error::IncorrectPubkeyHash {
+                            expected: hash160_from_slice(&spk[3..23]),
+                            computed: hash160,
+                        })
                     }
                 }
                 None => Err(Error::UnexpectedStackEnd),
@@ -199,9 +524,13 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
                             Inner::PublicKey(pk.into(), PubkeyType::Wpkh),
                             wit_stack,
                             Some(elements::Script::new_p2pkh(&hash160.into())), // bip143, why..
+                            None,
                         ))
                     } else {
-                        Err(Error::IncorrectWPubkeyHash)
+                        Err(Error::IncorrectWPubkeyHash {
+                            expected: hash160_from_slice(&spk[2..]),
+                            computed: hash160,
+                        })
                     }
                 }
                 None => Err(Error::UnexpectedStackEnd),
@@ -214,10 +543,13 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
         } else {
             match wit_stack.pop() {
                 Some(elem) => {
-                    if let Some((pk, ms)) = cov_components_from_stackelem(&elem) {
-                        let script_code =
-                            script::Builder::new().post_codesep_script().into_script();
-                        return Ok((Inner::CovScript(pk, ms), wit_stack, Some(script_code)));
+                    if let Some((k, pks, ms, script_code)) = cov_components_from_stackelem(&elem) {
+                        return Ok((
+                            Inner::CovScript(k, pks, ms, ScriptType::Wsh),
+                            wit_stack,
+                            Some(script_code),
+                            None,
+                        ));
                     }
                     let miniscript = script_from_stack_elem::<Segwitv0, Ext>(&elem)?;
                     let script = miniscript.encode();
@@ -229,9 +561,13 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
                             Inner::Script(miniscript, ScriptType::Wsh),
                             wit_stack,
                             Some(script),
+                            None,
                         ))
                     } else {
-                        Err(Error::IncorrectWScriptHash)
+                        Err(Error::IncorrectWScriptHash {
+                            expected: sha256_from_slice(&spk[2..]),
+                            computed: scripthash,
+                        })
                     }
                 }
                 None => Err(Error::UnexpectedStackEnd),
@@ -250,41 +586,73 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
                 .map(|x| !x.is_empty() && x[0] == TAPROOT_ANNEX_PREFIX)
                 .unwrap_or(false);
             let has_annex = has_annex && (wit_stack.len() >= 2);
-            if has_annex {
-                // Annex is non-standard, bitcoin consensus rules ignore it.
-                // Our sighash structure and signature verification
-                // does not support annex, return error
-                return Err(Error::TapAnnexUnsupported);
-            }
+            let annex = if has_annex {
+                // Annex is non-standard, consensus rules ignore it but it is
+                // committed to in the sighash. Only strip it if the caller
+                // asked for it, so that sighash semantics don't silently change.
+                if !options.allow_annex {
+                    return Err(Error::TapAnnexUnsupported);
+                }
+                wit_stack.pop();
+                witness.last().map(|annex| &annex[..])
+            } else {
+                None
+            };
             match wit_stack.len() {
                 0 => Err(Error::UnexpectedStackEnd),
                 1 => Ok((
                     Inner::PublicKey(output_key.into(), PubkeyType::Tr),
                     wit_stack,
                     None, // Tr key spend script code None
+                    Some(TapSpendData {
+                        annex,
+                        control_block: None,
+                        unspendable_internal_key: false,
+                    }),
                 )),
                 _ => {
                     // Script spend
                     let ctrl_blk = wit_stack.pop().ok_or(Error::UnexpectedStackEnd)?;
                     let ctrl_blk = ctrl_blk.as_push()?;
                     let tap_script = wit_stack.pop().ok_or(Error::UnexpectedStackEnd)?;
-                    let ctrl_blk =
-                        ControlBlock::from_slice(ctrl_blk).map_err(Error::ControlBlockParse)?;
-                    let tap_script = script_from_stack_elem::<Tap, Ext>(&tap_script)?;
-                    let ms = tap_script.to_no_checks_ms();
-                    // Creating new contexts is cheap
-                    let secp = bitcoin::secp256k1::Secp256k1::verification_only();
-                    let tap_script = tap_script.encode();
+                    let ctrl_blk = control_block_from_slice(ctrl_blk)?;
+                    // Only tapscript leaves can be interpreted as miniscript
+                    if ctrl_blk.leaf_version != LeafVersion::default() {
+                        return Err(Error::TapLeafVersionUnsupported(ctrl_blk.leaf_version));
+                    }
+                    let unspendable_internal_key =
+                        ctrl_blk.internal_key.serialize() == UNSPENDABLE_INTERNAL_KEY;
+                    let (inner, tap_script) = if let Some((pk, ms)) =
+                        tap_cov_components_from_stackelem(&tap_script)
+                    {
+                        let tap_script = elements::Script::from(tap_script.as_push()?.to_owned());
+                        (
+                            Inner::CovScript(1, vec![pk], ms, ScriptType::Tr),
+                            tap_script,
+                        )
+                    } else {
+                        check_tap_key_lengths(&tap_script)?;
+                        let tap_script = script_from_stack_elem::<Tap, Ext>(&tap_script)?;
+                        let ms = tap_script.to_no_checks_ms();
+                        (Inner::Script(ms, ScriptType::Tr), tap_script.encode())
+                    };
                     // Should not really need to call dangerous assumed tweaked here.
                     // Should be fixed after RC
                     // This is fixed in rust-bitcoin. Should also be fixed in rust-elements
-                    if ctrl_blk.verify_taproot_commitment(
-                        &secp,
-                        &TweakedPublicKey::new(output_key),
-                        &tap_script,
-                    ) {
+                    let output_key = TweakedPublicKey::new(output_key);
+                    let committed = match options.verifier {
+                        Some(ref verifier) => {
+                            verifier.verify_taproot_commitment(&ctrl_blk, &output_key, &tap_script)
+                        }
+                        None => {
+                            // Creating new contexts is cheap
+                            let secp = bitcoin::secp256k1::Secp256k1::verification_only();
+                            ctrl_blk.verify_taproot_commitment(&secp, &output_key, &tap_script)
+                        }
+                    };
+                    if committed {
                         Ok((
-                            Inner::Script(ms, ScriptType::Tr),
+                            inner,
                             wit_stack,
                             // Tapscript is returned as a "scriptcode". This is a hack, but avoids adding yet
                             // another enum just for taproot, and this function is not a publicly exposed API,
@@ -292,7 +660,15 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
                             //
                             // In particular, this return value will be put into the `script_code` member of
                             // the `Interpreter` script; the iterpreter logic does the right thing with it.
+                            //
+                            // This is the full script even for covenants: tapscript sighashes commit to the
+                            // leaf hash and the codeseparator position, not to a post-codeseparator script.
                             Some(tap_script),
+                            Some(TapSpendData {
+                                annex,
+                                control_block: Some(ctrl_blk),
+                                unspendable_internal_key,
+                            }),
                         ))
                     } else {
                         Err(Error::ControlBlockVerificationError)
@@ -307,7 +683,10 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
                 if let stack::Element::Push(slice) = elem {
                     let scripthash = hash160::Hash::hash(slice);
                     if *spk != elements::Script::new_p2sh(&scripthash.into()) {
-                        return Err(Error::IncorrectScriptHash);
+                        return Err(Error::IncorrectScriptHash {
+                            expected: hash160_from_slice(&spk[2..22]),
+                            computed: scripthash,
+                        });
                     }
                     // ** p2sh-wrapped wpkh **
                     if slice.len() == 22 && slice[0] == 0 && slice[1] == 20 {
@@ -324,9 +703,13 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
                                             Inner::PublicKey(pk.into(), PubkeyType::ShWpkh),
                                             wit_stack,
                                             Some(elements::Script::new_p2pkh(&hash160.into())), // bip143, why..
+                                            None,
                                         ))
                                     } else {
-                                        Err(Error::IncorrectWScriptHash)
+                                        Err(Error::IncorrectWPubkeyHash {
+                                            expected: hash160_from_slice(&slice[2..]),
+                                            computed: hash160,
+                                        })
                                     }
                                 }
                             }
@@ -338,6 +721,26 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
                             Some(elem) => {
                                 if !ssig_stack.is_empty() {
                                     Err(Error::NonEmptyScriptSig)
+                                } else if let Some((k, pks, ms, script_code)) =
+                                    cov_components_from_stackelem(&elem)
+                                {
+                                    let script = elem.as_push().expect("Covenants are pushes");
+                                    let scripthash = sha256::Hash::hash(script);
+                                    if slice
+                                        == &elements::Script::new_v0_wsh(&scripthash.into())[..]
+                                    {
+                                        Ok((
+                                            Inner::CovScript(k, pks, ms, ScriptType::ShWsh),
+                                            wit_stack,
+                                            Some(script_code),
+                                            None,
+                                        ))
+                                    } else {
+                                        Err(Error::IncorrectWScriptHash {
+                                            expected: sha256_from_slice(&slice[2..]),
+                                            computed: scripthash,
+                                        })
+                                    }
                                 } else {
                                     // parse wsh with Segwitv0 context
                                     let miniscript =
@@ -352,9 +755,13 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
                                             Inner::Script(miniscript, ScriptType::ShWsh),
                                             wit_stack,
                                             Some(script),
+                                            None,
                                         ))
                                     } else {
-                                        Err(Error::IncorrectWScriptHash)
+                                        Err(Error::IncorrectWScriptHash {
+                                            expected: sha256_from_slice(&slice[2..]),
+                                            computed: scripthash,
+                                        })
                                     }
                                 }
                             }
@@ -364,6 +771,9 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
                 }
                 // normal p2sh parsed in Legacy context
                 let miniscript = script_from_stack_elem::<Legacy, Ext>(&elem)?;
+                // Miniscript has no OP_CODESEPARATOR fragment, so redeem scripts
+                // containing one fail to parse above and the whole re-encoded
+                // script is always the legacy script code.
                 let script = miniscript.encode();
                 let miniscript = miniscript.to_no_checks_ms();
                 if wit_stack.is_empty() {
@@ -373,9 +783,13 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
                             Inner::Script(miniscript, ScriptType::Sh),
                             ssig_stack,
                             Some(script),
+                            None,
                         ))
                     } else {
-                        Err(Error::IncorrectScriptHash)
+                        Err(Error::IncorrectScriptHash {
+                            expected: hash160_from_slice(&spk[2..22]),
+                            computed: scripthash,
+                        })
                     }
                 } else {
                     Err(Error::NonEmptyWitness)
@@ -383,6 +797,24 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
             }
             None => Err(Error::UnexpectedStackEnd),
         }
+    // ** future witness versions **
+    } else if options.allow_future_witness
+        && spk.is_witness_program()
+        && spk[0] >= OP_PUSHNUM_2.into_u8()
+    {
+        if !ssig_stack.is_empty() {
+            Err(Error::NonEmptyScriptSig)
+        } else {
+            Ok((
+                Inner::FutureWitness {
+                    version: spk[0] - OP_PUSHNUM_1.into_u8() + 1,
+                    program: spk[2..].to_vec(),
+                },
+                wit_stack,
+                None,
+                None,
+            ))
+        }
     // ** bare script **
     } else if wit_stack.is_empty() {
         // Bare script parsed in BareCtx
@@ -395,6 +827,7 @@ pub fn from_txdata<'txin, Ext: ParseableExt>(
             Inner::Script(miniscript, ScriptType::Bare),
             ssig_stack,
             Some(spk.clone()),
+            None,
         ))
     } else {
         Err(Error::NonEmptyWitness)
@@ -459,10 +892,12 @@ mod tests {
 
     use elements::hashes::{hash160, sha256, Hash};
     use elements::hex::FromHex;
-    use elements::{self, script, Script};
+    use elements::{self, script, secp256k1_zkp, LockTime, Script, Sequence};
 
     use super::*;
+    use crate::interpreter::Interpreter;
     use crate::miniscript::analyzable::ExtParams;
+    use crate::miniscript::limits::MAX_STACK_SIZE;
     use crate::NoExt;
 
     struct KeyTestData {
@@ -541,6 +976,50 @@ mod tests {
         }
     }
 
+    #[test]
+    fn unspendable_outputs() {
+        let blank_script = elements::Script::new();
+        let op_return = elements::script::Builder::new()
+            .push_opcode(elements::opcodes::all::OP_RETURN)
+            .push_slice(b"hello elements")
+            .into_script();
+        match from_txdata::<NoExt>(&op_return, &blank_script, &[]) {
+            Err(Error::UnspendableOutput) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+        // Even with a witness, the output is reported as unspendable
+        match from_txdata::<NoExt>(&op_return, &blank_script, &[vec![1]]) {
+            Err(Error::UnspendableOutput) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn stack_display() {
+        let hash = [0xab; 20];
+        let sig = [0x30; 72];
+        let wit = vec![vec![], vec![1], hash.to_vec(), sig.to_vec()];
+        assert_eq!(
+            Stack::from_witness(&wit).to_string(),
+            format!(
+                "[Dissatisfied, Satisfied, Push({}), Push({}..; 72 bytes)]",
+                "ab".repeat(20),
+                "30".repeat(32)
+            )
+        );
+
+        let script_sig = elements::script::Builder::new()
+            .push_int(0)
+            .push_int(1)
+            .push_slice(&hash)
+            .into_script();
+        assert_eq!(
+            Stack::from_script_sig(&script_sig).unwrap().to_string(),
+            format!("[Dissatisfied, Satisfied, Push({})]", "ab".repeat(20))
+        );
+        assert_eq!(Stack::default().to_string(), "[]");
+    }
+
     #[test]
     fn pubkey_pk() {
         let fixed = fixed_test_data();
@@ -549,7 +1028,7 @@ mod tests {
         let blank_script = elements::Script::new();
 
         // Compressed pk, empty scriptsig
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&comp.pk_spk, &blank_script, &[]).expect("parse txdata");
         assert_eq!(
             inner,
@@ -559,7 +1038,7 @@ mod tests {
         assert_eq!(script_code, Some(comp.pk_spk.clone()));
 
         // Uncompressed pk, empty scriptsig
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&uncomp.pk_spk, &blank_script, &[]).expect("parse txdata");
         assert_eq!(
             inner,
@@ -569,7 +1048,7 @@ mod tests {
         assert_eq!(script_code, Some(uncomp.pk_spk.clone()));
 
         // Compressed pk, correct scriptsig
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&comp.pk_spk, &comp.pk_sig, &[]).expect("parse txdata");
         assert_eq!(
             inner,
@@ -579,7 +1058,7 @@ mod tests {
         assert_eq!(script_code, Some(comp.pk_spk.clone()));
 
         // Uncompressed pk, correct scriptsig
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&uncomp.pk_spk, &uncomp.pk_sig, &[]).expect("parse txdata");
         assert_eq!(
             inner,
@@ -621,10 +1100,17 @@ mod tests {
 
         // pkh, wrong pubkey
         let err = from_txdata::<NoExt>(&comp.pkh_spk, &uncomp.pkh_sig_justkey, &[]).unwrap_err();
-        assert_eq!(err.to_string(), "public key did not match scriptpubkey");
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "public key did not match scriptpubkey: expected {}, computed {}",
+                fixed.pk_comp.to_pubkeyhash(SigType::Ecdsa),
+                fixed.pk_uncomp.to_pubkeyhash(SigType::Ecdsa),
+            )
+        );
 
         // pkh, right pubkey, no signature
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&comp.pkh_spk, &comp.pkh_sig_justkey, &[]).expect("parse txdata");
         assert_eq!(
             inner,
@@ -633,7 +1119,7 @@ mod tests {
         assert_eq!(stack, Stack::from(vec![]));
         assert_eq!(script_code, Some(comp.pkh_spk.clone()));
 
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&uncomp.pkh_spk, &uncomp.pkh_sig_justkey, &[])
                 .expect("parse txdata");
         assert_eq!(
@@ -644,7 +1130,7 @@ mod tests {
         assert_eq!(script_code, Some(uncomp.pkh_spk.clone()));
 
         // pkh, right pubkey, signature
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&comp.pkh_spk, &comp.pkh_sig_justkey, &[]).expect("parse txdata");
         assert_eq!(
             inner,
@@ -653,7 +1139,7 @@ mod tests {
         assert_eq!(stack, Stack::from(vec![]));
         assert_eq!(script_code, Some(comp.pkh_spk.clone()));
 
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&uncomp.pkh_spk, &uncomp.pkh_sig_justkey, &[])
                 .expect("parse txdata");
         assert_eq!(
@@ -692,11 +1178,15 @@ mod tests {
             .unwrap_err();
         assert_eq!(
             err.to_string(),
-            "public key did not match scriptpubkey (segwit v0)"
+            format!(
+                "public key did not match scriptpubkey (segwit v0): expected {}, computed {}",
+                fixed.pk_uncomp.to_pubkeyhash(SigType::Ecdsa),
+                fixed.pk_comp.to_pubkeyhash(SigType::Ecdsa),
+            )
         );
 
         // wpkh, right pubkey, no signature
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&comp.wpkh_spk, &blank_script, &comp.wpkh_stack_justkey)
                 .expect("parse txdata");
         assert_eq!(
@@ -707,7 +1197,7 @@ mod tests {
         assert_eq!(script_code, Some(comp.pkh_spk.clone()));
 
         // wpkh, right pubkey, signature
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&comp.wpkh_spk, &blank_script, &comp.wpkh_stack)
                 .expect("parse txdata");
         assert_eq!(
@@ -770,7 +1260,18 @@ mod tests {
             &comp.sh_wpkh_stack_justkey,
         )
         .unwrap_err();
-        assert_eq!(err.to_string(), "redeem script did not match scriptpubkey",);
+        let redeem_hash = |pk: bitcoin::PublicKey| {
+            let wpkh = elements::Script::new_v0_wpkh(&pk.to_pubkeyhash(SigType::Ecdsa).into());
+            hash160::Hash::hash(&wpkh[..])
+        };
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "redeem script did not match scriptpubkey: expected {}, computed {}",
+                redeem_hash(fixed.pk_uncomp),
+                redeem_hash(fixed.pk_comp),
+            )
+        );
 
         // sh_wpkh, wrong redeem script for witness script
         let err = from_txdata::<NoExt>(
@@ -779,10 +1280,17 @@ mod tests {
             &comp.sh_wpkh_stack_justkey,
         )
         .unwrap_err();
-        assert_eq!(err.to_string(), "witness script did not match scriptpubkey",);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "public key did not match scriptpubkey (segwit v0): expected {}, computed {}",
+                fixed.pk_uncomp.to_pubkeyhash(SigType::Ecdsa),
+                fixed.pk_comp.to_pubkeyhash(SigType::Ecdsa),
+            )
+        );
 
         // sh_wpkh, right pubkey, no signature
-        let (inner, stack, script_code) = from_txdata::<NoExt>(
+        let (inner, stack, script_code, _) = from_txdata::<NoExt>(
             &comp.sh_wpkh_spk,
             &comp.sh_wpkh_sig,
             &comp.sh_wpkh_stack_justkey,
@@ -796,7 +1304,7 @@ mod tests {
         assert_eq!(script_code, Some(comp.pkh_spk.clone()));
 
         // sh_wpkh, right pubkey, signature
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&comp.sh_wpkh_spk, &comp.sh_wpkh_sig, &comp.sh_wpkh_stack)
                 .expect("parse txdata");
         assert_eq!(
@@ -810,6 +1318,224 @@ mod tests {
         assert_eq!(script_code, Some(comp.pkh_spk.clone()));
     }
 
+    #[test]
+    fn script_sig_non_minimal() {
+        let fixed = fixed_test_data();
+        let comp = KeyTestData::from_key(fixed.pk_comp);
+
+        // The 22 byte wpkh program pushed with OP_PUSHDATA1
+        let redeem_script = comp.sh_wpkh_sig.instructions().next().unwrap().unwrap();
+        let redeem_script = match redeem_script {
+            script::Instruction::PushBytes(b) => b,
+            _ => unreachable!(),
+        };
+        let mut non_minimal = vec![0x4c, redeem_script.len() as u8];
+        non_minimal.extend(redeem_script);
+        let non_minimal = elements::Script::from(non_minimal);
+
+        let err =
+            from_txdata::<NoExt>(&comp.sh_wpkh_spk, &non_minimal, &comp.sh_wpkh_stack).unwrap_err();
+        assert_eq!(err.to_string(), "expected push in script");
+
+        let (inner, stack, script_code, _) = from_txdata_with_options::<NoExt>(
+            &comp.sh_wpkh_spk,
+            &non_minimal,
+            &comp.sh_wpkh_stack,
+            &InterpreterOptions::new().non_minimal(),
+        )
+        .expect("parse txdata");
+        assert_eq!(
+            inner,
+            Inner::PublicKey(fixed.pk_comp.into(), PubkeyType::ShWpkh)
+        );
+        assert_eq!(
+            stack,
+            Stack::from(vec![comp.wpkh_stack[comp.wpkh_stack.len() - 2][..].into()])
+        );
+        assert_eq!(script_code, Some(comp.pkh_spk.clone()));
+    }
+
+    #[test]
+    fn pubkey_tr_annex() {
+        let fixed = fixed_test_data();
+        let (xpk, _parity) = fixed.pk_comp.inner.x_only_public_key();
+        let spk = script::Builder::new()
+            .push_opcode(elements::opcodes::all::OP_PUSHNUM_1)
+            .push_slice(&xpk.serialize())
+            .into_script();
+        let blank_script = elements::Script::new();
+        let dummy_sig = vec![0xab; 64];
+        let annex = vec![TAPROOT_ANNEX_PREFIX, 0x01, 0x02];
+
+        // Annex is rejected by default
+        let witness = vec![dummy_sig.clone(), annex.clone()];
+        let err = from_txdata::<NoExt>(&spk, &blank_script, &witness).unwrap_err();
+        assert_eq!(err.to_string(), "Encountered annex element");
+
+        // Annex is stripped and returned when allowed
+        let annex_options = InterpreterOptions::new().annex();
+        let (inner, stack, script_code, tap_data) =
+            from_txdata_with_options::<NoExt>(&spk, &blank_script, &witness, &annex_options)
+                .expect("parse txdata");
+        assert_eq!(inner, Inner::PublicKey(xpk.into(), PubkeyType::Tr));
+        assert_eq!(stack, Stack::from(vec![dummy_sig[..].into()]));
+        assert_eq!(script_code, None);
+        let tap_data = tap_data.expect("taproot spend");
+        assert_eq!(tap_data.annex, Some(&annex[..]));
+        assert_eq!(tap_data.control_block, None);
+
+        // A lone element with the annex prefix is not an annex
+        let witness = vec![annex.clone()];
+        let (_inner, stack, _script_code, tap_data) =
+            from_txdata_with_options::<NoExt>(&spk, &blank_script, &witness, &annex_options)
+                .expect("parse txdata");
+        assert_eq!(stack, Stack::from(vec![annex[..].into()]));
+        assert_eq!(tap_data.expect("taproot spend").annex, None);
+
+        // Options combine, the annex is not part of the initial stack
+        let witness = vec![dummy_sig.clone(), annex.clone()];
+        let options = InterpreterOptions::new().annex().max_stack_items(1);
+        from_txdata_with_options::<NoExt>(&spk, &blank_script, &witness, &options)
+            .expect("parse txdata");
+        let options = InterpreterOptions::new().annex().max_stack_items(0);
+        match from_txdata_with_options::<NoExt>(&spk, &blank_script, &witness, &options) {
+            Err(Error::StackSizeLimitExceeded {
+                actual: 1,
+                limit: 0,
+            }) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn script_tr_leaf_version() {
+        let fixed = fixed_test_data();
+        let desc = crate::Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "eltr({},pk({}))",
+            fixed.pk_comp, fixed.pk_comp
+        ))
+        .unwrap();
+        let tr = match desc {
+            crate::Descriptor::Tr(tr) => tr,
+            _ => unreachable!(),
+        };
+        let spk = tr.script_pubkey();
+        let leaf = tr.iter_scripts().next().unwrap().1.encode();
+        let ctrl_blk = tr
+            .spend_info()
+            .control_block(&(leaf.clone(), LeafVersion::default()))
+            .unwrap()
+            .serialize();
+        let blank_script = elements::Script::new();
+        let dummy_sig = vec![0xab; 64];
+
+        let witness = vec![dummy_sig.clone(), leaf.to_bytes(), ctrl_blk.clone()];
+        from_txdata::<NoExt>(&spk, &blank_script, &witness).expect("parse txdata");
+
+        // Same control block, but committing to a non-tapscript leaf version
+        let mut bad_ctrl_blk = ctrl_blk;
+        bad_ctrl_blk[0] = 0xbe | (bad_ctrl_blk[0] & 1);
+        let witness = vec![dummy_sig, leaf.to_bytes(), bad_ctrl_blk];
+        match from_txdata::<NoExt>(&spk, &blank_script, &witness) {
+            Err(Error::TapLeafVersionUnsupported(v)) => assert_eq!(v.as_u8(), 0xbe),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn script_tr_control_block_length() {
+        let fixed = fixed_test_data();
+        let desc = crate::Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "eltr({},pk({}))",
+            fixed.pk_comp, fixed.pk_comp
+        ))
+        .unwrap();
+        let tr = match desc {
+            crate::Descriptor::Tr(tr) => tr,
+            _ => unreachable!(),
+        };
+        let spk = tr.script_pubkey();
+        let leaf = tr.iter_scripts().next().unwrap().1.encode();
+        let ctrl_blk = tr
+            .spend_info()
+            .control_block(&(leaf.clone(), LeafVersion::default()))
+            .unwrap()
+            .serialize();
+        let blank_script = elements::Script::new();
+        let witness = |ctrl_blk: Vec<u8>| vec![vec![0xab; 64], leaf.to_bytes(), ctrl_blk];
+
+        // Truncated inside the internal key
+        match from_txdata::<NoExt>(&spk, &blank_script, &witness(ctrl_blk[..32].to_vec())) {
+            Err(Error::TapControlBlockTooShort(32)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // Truncated inside a merkle path node
+        let mut bad_ctrl_blk = ctrl_blk.clone();
+        bad_ctrl_blk.extend([0; 16]);
+        match from_txdata::<NoExt>(&spk, &blank_script, &witness(bad_ctrl_blk)) {
+            Err(Error::TapControlBlockBadMerklePathLength(49)) => {}
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // One merkle path node over the limit
+        let mut long_ctrl_blk = ctrl_blk;
+        long_ctrl_blk.extend(vec![0; 129 * 32]);
+        let err = from_txdata::<NoExt>(&spk, &blank_script, &witness(long_ctrl_blk)).unwrap_err();
+        match err {
+            Error::TapControlBlockTooLong(4161) => {}
+            ref e => panic!("unexpected error {:?}", e),
+        }
+        assert_eq!(
+            err.to_string(),
+            "taproot control block of 4161 bytes has more than 128 merkle path nodes"
+        );
+    }
+
+    #[test]
+    fn script_tr_full_key() {
+        let fixed = fixed_test_data();
+        let desc = crate::Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "eltr({},pk({}))",
+            fixed.pk_comp, fixed.pk_comp
+        ))
+        .unwrap();
+        let tr = match desc {
+            crate::Descriptor::Tr(tr) => tr,
+            _ => unreachable!(),
+        };
+        let spk = tr.script_pubkey();
+        let leaf = tr.iter_scripts().next().unwrap().1.encode();
+        let ctrl_blk = tr
+            .spend_info()
+            .control_block(&(leaf, LeafVersion::default()))
+            .unwrap()
+            .serialize();
+        let blank_script = elements::Script::new();
+
+        // pk() with a full key
+        let full_key_leaf = script::Builder::new()
+            .push_key(&fixed.pk_comp)
+            .push_opcode(elements::opcodes::all::OP_CHECKSIG)
+            .into_script();
+        // multi_a() with a full key in the second position
+        let multi_a_leaf = script::Builder::new()
+            .push_slice(&fixed.pk_comp.inner.x_only_public_key().0.serialize())
+            .push_opcode(elements::opcodes::all::OP_CHECKSIG)
+            .push_key(&fixed.pk_uncomp)
+            .push_opcode(elements::opcodes::all::OP_CHECKSIGADD)
+            .push_int(1)
+            .push_opcode(elements::opcodes::all::OP_NUMEQUAL)
+            .into_script();
+        for (leaf, len) in [(full_key_leaf, 33), (multi_a_leaf, 65)].iter() {
+            let witness = vec![vec![0xab; 64], leaf.to_bytes(), ctrl_blk.clone()];
+            match from_txdata::<NoExt>(&spk, &blank_script, &witness) {
+                Err(Error::InvalidKeyLengthForContext { expected: 32, got }) if got == *len => {}
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+    }
+
     fn ms_inner_script(ms: &str) -> (Miniscript<BitcoinKey, NoChecks, NoExt>, elements::Script) {
         let ms = Miniscript::<bitcoin::PublicKey, Segwitv0>::from_str_ext(ms, &ExtParams::insane())
             .unwrap();
@@ -828,7 +1554,7 @@ mod tests {
         let blank_script = elements::Script::new();
 
         // bare script has no validity requirements beyond being a sane script
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&spk, &blank_script, &[]).expect("parse txdata");
         assert_eq!(inner, Inner::Script(miniscript, ScriptType::Bare));
         assert_eq!(stack, Stack::from(vec![]));
@@ -842,6 +1568,194 @@ mod tests {
         assert_eq!(&err.to_string(), "legacy spend had nonempty witness");
     }
 
+    #[test]
+    fn txin_spends() {
+        let fixed = fixed_test_data();
+        let comp = KeyTestData::from_key(fixed.pk_comp);
+
+        let mut txin = elements::TxIn {
+            script_sig: comp.pkh_sig.clone(),
+            ..Default::default()
+        };
+
+        // Legacy spend, empty script witness
+        let (inner, stack, script_code, _) =
+            from_txin::<NoExt>(&comp.pkh_spk, &txin).expect("parse txin");
+        assert_eq!(
+            inner,
+            Inner::PublicKey(fixed.pk_comp.into(), PubkeyType::Pkh)
+        );
+        assert_eq!(stack, Stack::from(vec![comp.wpkh_stack[0][..].into()]));
+        assert_eq!(script_code, Some(comp.pkh_spk.clone()));
+
+        // Segwit spend, the script witness is used rather than the pegin witness
+        txin.script_sig = elements::Script::new();
+        txin.witness.script_witness = comp.wpkh_stack.clone();
+        txin.witness.pegin_witness = vec![vec![0; 32]];
+        let (inner, stack, script_code, _) =
+            from_txin::<NoExt>(&comp.wpkh_spk, &txin).expect("parse txin");
+        assert_eq!(
+            inner,
+            Inner::PublicKey(fixed.pk_comp.into(), PubkeyType::Wpkh)
+        );
+        assert_eq!(stack, Stack::from(vec![comp.wpkh_stack[0][..].into()]));
+        assert_eq!(
+            script_code,
+            Some(elements::Script::new_p2pkh(
+                &fixed.pk_comp.to_pubkeyhash(SigType::Ecdsa).into()
+            ))
+        );
+
+        // Segwit spend with an empty script witness
+        txin.witness.script_witness = vec![];
+        let err = from_txin::<NoExt>(&comp.wpkh_spk, &txin).unwrap_err();
+        assert_eq!(&err.to_string(), "unexpected end of stack");
+    }
+
+    #[test]
+    fn descriptor_type_labels() {
+        let fixed = fixed_test_data();
+        let comp = KeyTestData::from_key(fixed.pk_comp);
+
+        let (inner, ..) = from_txdata::<NoExt>(&comp.pkh_spk, &comp.pkh_sig, &[]).unwrap();
+        assert_eq!(inner.descriptor_type_label(), "pkh");
+        assert_eq!(inner.descriptor_type(), DescriptorType::Pkh);
+        let (inner, ..) =
+            from_txdata::<NoExt>(&comp.sh_wpkh_spk, &comp.sh_wpkh_sig, &comp.sh_wpkh_stack)
+                .unwrap();
+        assert_eq!(inner.descriptor_type_label(), "sh(wpkh)");
+        assert_eq!(inner.descriptor_type(), DescriptorType::ShWpkh);
+
+        let labels: Vec<_> = [
+            PubkeyType::Pk,
+            PubkeyType::Pkh,
+            PubkeyType::Wpkh,
+            PubkeyType::ShWpkh,
+            PubkeyType::Tr,
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+        assert_eq!(labels, ["pk", "pkh", "wpkh", "sh(wpkh)", "tr"]);
+        let labels: Vec<_> = [
+            ScriptType::Bare,
+            ScriptType::Sh,
+            ScriptType::Wsh,
+            ScriptType::ShWsh,
+            ScriptType::Tr,
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+        assert_eq!(labels, ["bare", "sh", "wsh", "sh(wsh)", "tr"]);
+
+        // Labels parse back, including the dashed forms
+        for label in &["pk", "pkh", "wpkh", "sh(wpkh)", "tr"] {
+            assert_eq!(PubkeyType::from_str(label).unwrap().to_string(), *label);
+        }
+        for label in &["bare", "sh", "wsh", "sh(wsh)", "tr"] {
+            assert_eq!(ScriptType::from_str(label).unwrap().to_string(), *label);
+        }
+        assert_eq!(PubkeyType::from_str("sh-wpkh").unwrap(), PubkeyType::ShWpkh);
+        assert_eq!(ScriptType::from_str("sh-wsh").unwrap(), ScriptType::ShWsh);
+        assert_eq!(
+            PubkeyType::from_str("wsh").unwrap_err().to_string(),
+            "unexpected «unknown public key spend type wsh»"
+        );
+        assert_eq!(
+            ScriptType::from_str("pkh").unwrap_err().to_string(),
+            "unexpected «unknown script spend type pkh»"
+        );
+
+        let (ms, _) = ms_inner_script("1");
+        let desc_types: Vec<_> = [
+            ScriptType::Bare,
+            ScriptType::Sh,
+            ScriptType::Wsh,
+            ScriptType::ShWsh,
+            ScriptType::Tr,
+        ]
+        .iter()
+        .map(|ty| Inner::Script(ms.clone(), *ty).descriptor_type())
+        .collect();
+        assert_eq!(
+            desc_types,
+            [
+                DescriptorType::Bare,
+                DescriptorType::Sh,
+                DescriptorType::Wsh,
+                DescriptorType::ShWsh,
+                DescriptorType::Tr,
+            ]
+        );
+        let cov = Inner::CovScript(1, vec![fixed.pk_comp.into()], ms.clone(), ScriptType::Wsh);
+        assert_eq!(cov.descriptor_type_label(), "covwsh");
+        assert_eq!(cov.descriptor_type(), DescriptorType::Cov);
+        let cov = Inner::CovScript(1, vec![fixed.pk_comp.into()], ms, ScriptType::Tr);
+        assert_eq!(cov.descriptor_type_label(), "tapcov");
+        assert_eq!(cov.descriptor_type(), DescriptorType::Tr);
+    }
+
+    #[test]
+    fn uncompressed_legacy_scripts() {
+        let fixed = fixed_test_data();
+        let blank_script = elements::Script::new();
+        // Any valid signature, the signatures are not checked
+        let secp = secp256k1_zkp::Secp256k1::signing_only();
+        let sk = secp256k1_zkp::SecretKey::from_slice(&[1; 32]).unwrap();
+        let msg = secp256k1_zkp::Message::from_digest_slice(&[2; 32]).unwrap();
+        let mut sig = secp.sign_ecdsa(&msg, &sk).serialize_der().to_vec();
+        sig.push(0x01);
+
+        // sh(pk(<uncompressed>))
+        let ms = Miniscript::<bitcoin::PublicKey, Legacy>::from_str_ext(
+            &format!("pk({})", fixed.pk_uncomp),
+            &ExtParams::allow_all(),
+        )
+        .unwrap();
+        let redeem_script = ms.encode();
+        let spk = Script::new_p2sh(&hash160::Hash::hash(&redeem_script[..]).into());
+        let script_sig = script::Builder::new()
+            .push_slice(&sig)
+            .push_slice(&redeem_script[..])
+            .into_script();
+        let (inner, stack, script_code, _) =
+            from_txdata::<NoExt>(&spk, &script_sig, &[]).expect("parse txdata");
+        assert_eq!(inner, Inner::Script(ms.to_no_checks_ms(), ScriptType::Sh));
+        assert_eq!(stack, Stack::from(vec![sig[..].into()]));
+        assert_eq!(script_code, Some(redeem_script));
+
+        // The spend is interpreted and the descriptor inferred
+        let interpreter = Interpreter::<NoExt>::from_txdata_ext(
+            &spk,
+            &script_sig,
+            &[],
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        let constraints: Result<Vec<_>, _> = interpreter.iter_assume_sigs().collect();
+        assert_eq!(constraints.unwrap().len(), 1);
+        let desc = interpreter.inferred_descriptor().unwrap();
+        assert_eq!(
+            format!("{:#}", desc),
+            format!("elsh(pk({}))", fixed.pk_uncomp)
+        );
+
+        // bare and_v(v:pk(<uncompressed>),pk(<compressed>))
+        let ms = Miniscript::<bitcoin::PublicKey, BareCtx>::from_str_ext(
+            &format!("and_v(v:pk({}),pk({}))", fixed.pk_uncomp, fixed.pk_comp),
+            &ExtParams::allow_all(),
+        )
+        .unwrap();
+        let spk = ms.encode();
+        let (inner, stack, script_code, _) =
+            from_txdata::<NoExt>(&spk, &blank_script, &[]).expect("parse txdata");
+        assert_eq!(inner, Inner::Script(ms.to_no_checks_ms(), ScriptType::Bare));
+        assert_eq!(stack, Stack::from(vec![]));
+        assert_eq!(script_code, Some(spk));
+    }
+
     #[test]
     fn script_sh() {
         let preimage = b"12345678----____12345678----____";
@@ -865,7 +1779,7 @@ mod tests {
         assert_eq!(&err.to_string(), "expected push in script");
 
         // with correct scriptsig
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&spk, &script_sig, &[]).expect("parse txdata");
         assert_eq!(inner, Inner::Script(miniscript, ScriptType::Sh));
         assert_eq!(stack, Stack::from(vec![]));
@@ -876,6 +1790,24 @@ mod tests {
         assert_eq!(&err.to_string(), "legacy spend had nonempty witness");
     }
 
+    #[test]
+    fn script_sh_codeseparator() {
+        let preimage = b"12345678----____12345678----____";
+        let hash = hash160::Hash::hash(&preimage[..]);
+        let (_, redeem_script) = ms_inner_script(&format!("hash160({})", hash));
+
+        let mut with_sep = vec![elements::opcodes::all::OP_CODESEPARATOR.into_u8()];
+        with_sep.extend(redeem_script.as_bytes());
+        let rs_hash = hash160::Hash::hash(&with_sep[..]).into();
+        let spk = Script::new_p2sh(&rs_hash);
+        let script_sig = script::Builder::new().push_slice(&with_sep).into_script();
+
+        // no script code is returned for a redeem script that miniscript
+        // cannot represent
+        let err = from_txdata::<NoExt>(&spk, &script_sig, &[]).unwrap_err();
+        assert_eq!(&err.to_string()[0..12], "parse error:");
+    }
+
     #[test]
     fn script_wsh() {
         let preimage = b"12345678----____12345678----____";
@@ -898,7 +1830,7 @@ mod tests {
         assert_eq!(&err.to_string()[0..12], "parse error:");
 
         // with correct witness
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&spk, &blank_script, &wit_stack).expect("parse txdata");
         assert_eq!(inner, Inner::Script(miniscript, ScriptType::Wsh));
         assert_eq!(stack, Stack::from(vec![]));
@@ -912,6 +1844,108 @@ mod tests {
         assert_eq!(&err.to_string(), "segwit spend had nonempty scriptsig");
     }
 
+    #[test]
+    fn script_stack_size_limit() {
+        let preimage = b"12345678----____12345678----____";
+        let hash = hash160::Hash::hash(&preimage[..]);
+        let (_, witness_script) = ms_inner_script(&format!("hash160({})", hash));
+        let spk = Script::new_v0_wsh(&sha256::Hash::hash(&witness_script[..]).into());
+        let blank_script = elements::Script::new();
+
+        // The witness script is not part of the initial stack
+        let mut wit_stack = vec![vec![]; MAX_STACK_SIZE];
+        wit_stack.push(witness_script.to_bytes());
+        let (_, stack, _, _) =
+            from_txdata::<NoExt>(&spk, &blank_script, &wit_stack).expect("parse txdata");
+        assert_eq!(stack.len(), MAX_STACK_SIZE);
+
+        wit_stack.insert(0, vec![]);
+        let err = from_txdata::<NoExt>(&spk, &blank_script, &wit_stack).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "stack has 1001 items, more than the limit of 1000"
+        );
+
+        // A lower limit for untrusted witnesses
+        let max_stack_items = |limit| InterpreterOptions::new().max_stack_items(limit);
+        let wit_stack = vec![vec![], vec![], witness_script.to_bytes()];
+        from_txdata_with_options::<NoExt>(&spk, &blank_script, &wit_stack, &max_stack_items(2))
+            .expect("parse txdata");
+        let err =
+            from_txdata_with_options::<NoExt>(&spk, &blank_script, &wit_stack, &max_stack_items(1))
+                .unwrap_err();
+        match err {
+            Error::StackSizeLimitExceeded {
+                actual: 2,
+                limit: 1,
+            } => {}
+            e => panic!("Unexpected error {:?}", e),
+        }
+
+        // The scriptSig of legacy spends is limited the same way
+        let spk = Script::new_p2sh(&hash160::Hash::hash(&witness_script[..]).into());
+        let script_sig = script::Builder::new()
+            .push_slice(preimage)
+            .push_slice(&witness_script[..])
+            .into_script();
+        from_txdata_with_options::<NoExt>(&spk, &script_sig, &[], &max_stack_items(1))
+            .expect("parse txdata");
+        from_txdata_with_options::<NoExt>(&spk, &script_sig, &[], &max_stack_items(0)).unwrap_err();
+    }
+
+    #[test]
+    fn script_future_witness() {
+        let program = [0xab; 32];
+        let spk = script::Builder::new()
+            .push_opcode(OP_PUSHNUM_2)
+            .push_slice(&program)
+            .into_script();
+        let blank_script = elements::Script::new();
+        let wit_stack = vec![vec![1], vec![2, 3]];
+
+        // Not recognized by default
+        from_txdata::<NoExt>(&spk, &blank_script, &wit_stack).unwrap_err();
+        let future_witness = InterpreterOptions::new().future_witness();
+
+        let (inner, stack, script_code, tap_data) =
+            from_txdata_with_options::<NoExt>(&spk, &blank_script, &wit_stack, &future_witness)
+                .expect("parse txdata");
+        assert_eq!(
+            inner,
+            Inner::FutureWitness {
+                version: 2,
+                program: program.to_vec(),
+            }
+        );
+        assert_eq!(stack.len(), 2);
+        assert_eq!(script_code, None);
+        assert!(tap_data.is_none());
+
+        // Witness spends must have an empty scriptSig
+        let script_sig = script::Builder::new().push_int(1).into_script();
+        match from_txdata_with_options::<NoExt>(&spk, &script_sig, &wit_stack, &future_witness) {
+            Err(Error::NonEmptyScriptSig) => {}
+            e => panic!("Unexpected result {:?}", e),
+        }
+
+        // Known witness versions are parsed as usual
+        let v0 = Script::new_v0_wsh(&sha256::Hash::hash(&program).into());
+        from_txdata_with_options::<NoExt>(&v0, &blank_script, &wit_stack, &future_witness)
+            .unwrap_err();
+
+        let interpreter = Interpreter::from_txdata_with_options(
+            &spk,
+            &blank_script,
+            &wit_stack,
+            Sequence::ZERO,
+            LockTime::ZERO,
+            &future_witness,
+        )
+        .expect("parse txdata");
+        assert_eq!(interpreter.future_witness(), Some((2, &program[..])));
+        assert_eq!(interpreter.iter_assume_sigs().count(), 0);
+    }
+
     #[test]
     fn script_sh_wsh() {
         let preimage = b"12345678----____12345678----____";
@@ -947,10 +1981,16 @@ mod tests {
 
         // with incorrect scriptsig
         let err = from_txdata::<NoExt>(&spk, &redeem_script, &wit_stack).unwrap_err();
-        assert_eq!(&err.to_string(), "redeem script did not match scriptpubkey");
+        match err {
+            Error::IncorrectScriptHash { expected, computed } => {
+                assert_eq!(expected, hash160::Hash::hash(&redeem_script[..]));
+                assert_eq!(computed, hash160::Hash::hash(&redeem_script[2..]));
+            }
+            e => panic!("expected IncorrectScriptHash, got {:?}", e),
+        }
 
         // with correct witness
-        let (inner, stack, script_code) =
+        let (inner, stack, script_code, _) =
             from_txdata::<NoExt>(&spk, &script_sig, &wit_stack).expect("parse txdata");
         assert_eq!(inner, Inner::Script(miniscript, ScriptType::ShWsh));
         assert_eq!(stack, Stack::from(vec![]));
@@ -8,17 +8,27 @@
 //! assuming that the spent coin was descriptor controlled.
 //!
 
-use std::borrow::Borrow;
-use std::fmt;
-use std::str::FromStr;
+use core::borrow::Borrow;
+use core::fmt;
+use core::str::FromStr;
 
 use bitcoin;
+
 use elements::hashes::{hash160, ripemd160, sha256, Hash, HashEngine};
+use elements::hex::ToHex;
+use elements::schnorr::TweakedPublicKey;
+use elements::taproot::{ControlBlock, LeafVersion, TapNodeHash, TaprootSpendInfo};
 use elements::{self, secp256k1_zkp, sighash, EcdsaSighashType, LockTime, Sequence, Sighash};
 
+use crate::descriptor::{
+    DescriptorType, TAP_COV_ITEM_SIZES, TAP_COV_MSG_SUFFIX, TAP_COV_SPEND_TYPE,
+    TAP_COV_SPEND_TYPE_POS,
+};
 use crate::extensions::{CovExtArgs, ParseableExt, TxEnv};
 use crate::miniscript::context::{NoChecks, SigType};
+use crate::miniscript::limits::MAX_STACK_SIZE;
 use crate::miniscript::ScriptContext;
+use crate::policy::{Liftable, Semantic};
 use crate::{hash256, util, Descriptor, ElementsSig, Miniscript, Terminal, ToPublicKey};
 
 mod error;
@@ -27,20 +37,224 @@ mod stack;
 
 pub use self::error::Error;
 use self::error::PkEvalErrInner;
+pub use self::inner::{PubkeyType, ScriptType};
 pub use self::stack::{Element, Stack};
 use crate::{elementssig_from_rawsig, CovenantExt, Extension, MiniscriptKey};
 
+/// Codeseparator position committed to by tapscript sighashes when no
+/// OP_CODESEPARATOR was executed. Unlike segwitv0, taproot sighashes commit
+/// to this position instead of a post-codeseparator script code. Neither
+/// miniscript nor the taproot covenant wrapper use OP_CODESEPARATOR, so
+/// every tapscript spend understood by the interpreter uses this value.
+const TAP_NO_CODESEP_POS: u32 = 0xFFFF_FFFF;
+
 /// An iterable Miniscript-structured representation of the spending of a coin
 pub struct Interpreter<'txin, Ext: Extension> {
     inner: inner::Inner<Ext>,
     stack: Stack<'txin>,
     /// For non-Taproot spends, the scriptCode; for Taproot script-spends, this
-    /// is the leaf script; for key-spends it is `None`.
+    /// is the full leaf script, needed for the leaf hash; for key-spends it is `None`.
     script_code: Option<elements::Script>,
+    /// Taproot spend data(annex and control block) parsed from the witness.
+    /// The annex is committed to in BIP341 sighashes.
+    tap_data: Option<inner::TapSpendData<'txin>>,
     age: Sequence,
     lock_time: LockTime,
 }
 
+/// The signature and taproot commitment checks done by the interpreter.
+///
+/// This is implemented for any secp256k1 context that can verify. Implement
+/// it to plug in e.g. a hardware accelerator or a cache of signatures that
+/// were already validated, and pass it to [`Interpreter::iter`] and
+/// [`InterpreterOptions::verifier`].
+pub trait Verifier {
+    /// Whether `sig` is a valid ECDSA signature of `msg` by `pk`
+    fn verify_ecdsa(
+        &self,
+        msg: &secp256k1_zkp::Message,
+        sig: &secp256k1_zkp::ecdsa::Signature,
+        pk: &secp256k1_zkp::PublicKey,
+    ) -> bool;
+
+    /// Whether `sig` is a valid BIP340 signature of `msg` by `pk`
+    fn verify_schnorr(
+        &self,
+        msg: &secp256k1_zkp::Message,
+        sig: &secp256k1_zkp::schnorr::Signature,
+        pk: &secp256k1_zkp::XOnlyPublicKey,
+    ) -> bool;
+
+    /// Whether the control block of a taproot script spend commits to
+    /// `script` under `output_key`, see
+    /// [`ControlBlock::verify_taproot_commitment`]
+    fn verify_taproot_commitment(
+        &self,
+        ctrl_blk: &ControlBlock,
+        output_key: &TweakedPublicKey,
+        script: &elements::Script,
+    ) -> bool;
+}
+
+impl<C: secp256k1_zkp::Verification> Verifier for secp256k1_zkp::Secp256k1<C> {
+    fn verify_ecdsa(
+        &self,
+        msg: &secp256k1_zkp::Message,
+        sig: &secp256k1_zkp::ecdsa::Signature,
+        pk: &secp256k1_zkp::PublicKey,
+    ) -> bool {
+        secp256k1_zkp::Secp256k1::verify_ecdsa(self, msg, sig, pk).is_ok()
+    }
+
+    fn verify_schnorr(
+        &self,
+        msg: &secp256k1_zkp::Message,
+        sig: &secp256k1_zkp::schnorr::Signature,
+        pk: &secp256k1_zkp::XOnlyPublicKey,
+    ) -> bool {
+        secp256k1_zkp::Secp256k1::verify_schnorr(self, sig, msg, pk).is_ok()
+    }
+
+    fn verify_taproot_commitment(
+        &self,
+        ctrl_blk: &ControlBlock,
+        output_key: &TweakedPublicKey,
+        script: &elements::Script,
+    ) -> bool {
+        ctrl_blk.verify_taproot_commitment(self, output_key, script)
+    }
+}
+
+impl<V: Verifier + ?Sized> Verifier for &V {
+    fn verify_ecdsa(
+        &self,
+        msg: &secp256k1_zkp::Message,
+        sig: &secp256k1_zkp::ecdsa::Signature,
+        pk: &secp256k1_zkp::PublicKey,
+    ) -> bool {
+        (**self).verify_ecdsa(msg, sig, pk)
+    }
+
+    fn verify_schnorr(
+        &self,
+        msg: &secp256k1_zkp::Message,
+        sig: &secp256k1_zkp::schnorr::Signature,
+        pk: &secp256k1_zkp::XOnlyPublicKey,
+    ) -> bool {
+        (**self).verify_schnorr(msg, sig, pk)
+    }
+
+    fn verify_taproot_commitment(
+        &self,
+        ctrl_blk: &ControlBlock,
+        output_key: &TweakedPublicKey,
+        script: &elements::Script,
+    ) -> bool {
+        (**self).verify_taproot_commitment(ctrl_blk, output_key, script)
+    }
+}
+
+/// Options for parsing a spend with [`Interpreter::from_txdata_with_options`].
+///
+/// The default options are the ones used by [`Interpreter::from_txdata`]:
+/// taproot spends with an annex, scriptSigs with non-minimal pushes and
+/// spends of unknown witness versions are rejected, the initial stack may
+/// have at most 1000 items and taproot commitments are checked with a new
+/// secp256k1 context. The builder methods can be combined.
+pub struct InterpreterOptions<'a> {
+    allow_annex: bool,
+    allow_non_minimal: bool,
+    allow_future_witness: bool,
+    max_stack_items: usize,
+    verifier: Option<Box<dyn Verifier + 'a>>,
+    // `Some(None)` rejects taproot spends as no spend info is known
+    spend_info: Option<Option<&'a TaprootSpendInfo>>,
+}
+
+impl<'a> Default for InterpreterOptions<'a> {
+    fn default() -> Self {
+        InterpreterOptions {
+            allow_annex: false,
+            allow_non_minimal: false,
+            allow_future_witness: false,
+            max_stack_items: MAX_STACK_SIZE,
+            verifier: None,
+            spend_info: None,
+        }
+    }
+}
+
+impl<'a> InterpreterOptions<'a> {
+    /// Create the default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builder that accepts taproot spends with an annex.
+    ///
+    /// The annex is stripped from the witness and committed to when computing
+    /// BIP341 sighashes in [`Interpreter::verify_sig`].
+    pub fn annex(mut self) -> Self {
+        self.allow_annex = true;
+        self
+    }
+
+    /// Builder that accepts scriptSigs with pushes that are not minimally
+    /// encoded.
+    ///
+    /// This is meant for analyzing historical spends only. Non-minimal pushes
+    /// are non-standard, and anyone can re-encode the pushes of a legacy or
+    /// p2sh-wrapped spend to change its txid without invalidating it. A spend
+    /// parsed with this option may therefore not be the one that gets mined,
+    /// and must not be used to decide whether to accept a transaction.
+    pub fn non_minimal(mut self) -> Self {
+        self.allow_non_minimal = true;
+        self
+    }
+
+    /// Builder that treats spends of witness programs with versions 2 and
+    /// above as anyone-can-spend, the way consensus currently does, instead
+    /// of failing to parse them.
+    ///
+    /// Iterating over such a spend yields no constraints. Use
+    /// [`Interpreter::future_witness`] to tell them apart from other spends.
+    pub fn future_witness(mut self) -> Self {
+        self.allow_future_witness = true;
+        self
+    }
+
+    /// Builder that allows at most `max_stack_items` items on the initial
+    /// stack of the spend instead of the consensus limit of 1000. Use a
+    /// lower limit when interpreting untrusted witnesses.
+    pub fn max_stack_items(mut self, max_stack_items: usize) -> Self {
+        self.max_stack_items = max_stack_items;
+        self
+    }
+
+    /// Builder that checks the control block of taproot script spends, and
+    /// the spend info set with [`Self::spend_info`], with `verifier` instead
+    /// of a new secp256k1 context.
+    pub fn verifier<V: Verifier + ?Sized>(mut self, verifier: &'a V) -> Self {
+        self.verifier = Some(Box::new(verifier));
+        self
+    }
+
+    /// Builder that checks the output key of taproot spends against the
+    /// caller supplied `spend_info`.
+    ///
+    /// The output key of a key spend is checked to be the tweak of the
+    /// internal key and merkle root of `spend_info`, and the control block of
+    /// a script spend is checked to commit to the internal key and output key
+    /// of `spend_info`. Taproot spends are rejected with
+    /// [`Error::NeedsSpendInfo`] if `spend_info` is `None`, and with
+    /// [`Error::TapKeyTweakMismatch`] if the keys do not match. Other spends
+    /// are not checked.
+    pub fn spend_info(mut self, spend_info: Option<&'a TaprootSpendInfo>) -> Self {
+        self.spend_info = Some(spend_info);
+        self
+    }
+}
+
 // A type representing functions for checking signatures that accept both
 // Ecdsa and Schnorr signatures
 
@@ -144,6 +358,12 @@ impl<'txin> Interpreter<'txin, CovenantExt<CovExtArgs>> {
     /// function; otherwise, it should be a closure containing a sighash and
     /// secp context, which can actually verify a given signature.
     /// For downstream cursom implementations of [`Extension`], use [`Interpreter::from_txdata_ext`]
+    ///
+    /// The witness of an [`elements::TxIn`] can be passed without copying as
+    /// `&txin.witness.script_witness`, or use [`Interpreter::from_txin`].
+    ///
+    /// Spends with more than 1000 items on the initial stack are rejected
+    /// with [`Error::StackSizeLimitExceeded`].
     pub fn from_txdata(
         spk: &elements::Script,
         script_sig: &'txin elements::Script,
@@ -153,6 +373,34 @@ impl<'txin> Interpreter<'txin, CovenantExt<CovExtArgs>> {
     ) -> Result<Self, Error> {
         Interpreter::from_txdata_ext(spk, script_sig, witness, age, lock_time)
     }
+
+    /// Constructs an interpreter from a spending transaction input and the
+    /// scriptPubKey of the output it spends.
+    ///
+    /// The relative lock time is taken from the input's sequence number.
+    /// For downstream custom implementations of [`Extension`], use [`Interpreter::from_txin_ext`]
+    pub fn from_txin(
+        spk: &elements::Script,
+        txin: &'txin elements::TxIn,
+        lock_time: LockTime, // CLTV, absolute lock time.
+    ) -> Result<Self, Error> {
+        Interpreter::from_txin_ext(spk, txin, lock_time)
+    }
+
+    /// Same as [`Interpreter::from_txdata`], but parses the spend with
+    /// `options`, see [`InterpreterOptions`].
+    /// For downstream custom implementations of [`Extension`], use
+    /// [`Interpreter::from_txdata_ext_with_options`]
+    pub fn from_txdata_with_options(
+        spk: &elements::Script,
+        script_sig: &'txin elements::Script,
+        witness: &'txin [Vec<u8>],
+        age: Sequence,       // CSV, relative lock time.
+        lock_time: LockTime, // CLTV, absolute lock time.
+        options: &InterpreterOptions<'_>,
+    ) -> Result<Self, Error> {
+        Interpreter::from_txdata_ext_with_options(spk, script_sig, witness, age, lock_time, options)
+    }
 }
 
 impl<'txin, Ext> Interpreter<'txin, Ext>
@@ -172,16 +420,104 @@ where
         age: Sequence,       // CSV, relative lock time.
         lock_time: LockTime, // CLTV, absolute lock time.
     ) -> Result<Self, Error> {
-        let (inner, stack, script_code) = inner::from_txdata(spk, script_sig, witness)?;
+        Self::from_txdata_ext_with_options(
+            spk,
+            script_sig,
+            witness,
+            age,
+            lock_time,
+            &InterpreterOptions::default(),
+        )
+    }
+
+    /// Constructs an interpreter from a spending transaction input and the
+    /// scriptPubKey of the output it spends.
+    ///
+    /// The scriptSig and witness are taken from the input, so that the caller
+    /// does not have to split them out, and the relative lock time is taken
+    /// from the input's sequence number. Inputs with an empty script witness
+    /// are treated as legacy spends.
+    pub fn from_txin_ext(
+        spk: &elements::Script,
+        txin: &'txin elements::TxIn,
+        lock_time: LockTime, // CLTV, absolute lock time.
+    ) -> Result<Self, Error> {
+        let (inner, stack, script_code, tap_data) = inner::from_txin(spk, txin)?;
         Ok(Interpreter {
             inner,
             stack,
             script_code,
-            age,
+            tap_data,
+            age: txin.sequence,
             lock_time,
         })
     }
 
+    /// Same as [`Interpreter::from_txdata_ext`], but parses the spend with
+    /// `options`, see [`InterpreterOptions`].
+    pub fn from_txdata_ext_with_options(
+        spk: &elements::Script,
+        script_sig: &'txin elements::Script,
+        witness: &'txin [Vec<u8>],
+        age: Sequence,       // CSV, relative lock time.
+        lock_time: LockTime, // CLTV, absolute lock time.
+        options: &InterpreterOptions<'_>,
+    ) -> Result<Self, Error> {
+        let (inner, stack, script_code, tap_data) =
+            inner::from_txdata_with_options(spk, script_sig, witness, options)?;
+        let interpreter = Interpreter {
+            inner,
+            stack,
+            script_code,
+            tap_data,
+            age,
+            lock_time,
+        };
+        if let Some(spend_info) = options.spend_info {
+            interpreter.check_spend_info(spend_info, options.verifier.as_deref())?;
+        }
+        Ok(interpreter)
+    }
+
+    // Check the output key of a taproot spend against `spend_info`
+    fn check_spend_info(
+        &self,
+        spend_info: Option<&TaprootSpendInfo>,
+        verifier: Option<&dyn Verifier>,
+    ) -> Result<(), Error> {
+        if self.is_taproot_v1_key_spend() {
+            let spend_info = spend_info.ok_or(Error::NeedsSpendInfo)?;
+            let secp = secp256k1_zkp::Secp256k1::verification_only();
+            self.verify_tap_key_tweak(&secp, spend_info.internal_key(), spend_info.merkle_root())
+        } else if self.is_taproot_v1_script_spend() {
+            let spend_info = spend_info.ok_or(Error::NeedsSpendInfo)?;
+            let ctrl_blk = self
+                .control_block()
+                .expect("Script spends have a control block");
+            let tap_script = self
+                .script_code
+                .as_ref()
+                .expect("Script spends have a tapscript");
+            let output_key = spend_info.output_key();
+            let committed = match verifier {
+                Some(verifier) => {
+                    verifier.verify_taproot_commitment(ctrl_blk, &output_key, tap_script)
+                }
+                None => {
+                    let secp = secp256k1_zkp::Secp256k1::verification_only();
+                    ctrl_blk.verify_taproot_commitment(&secp, &output_key, tap_script)
+                }
+            };
+            if ctrl_blk.internal_key == spend_info.internal_key() && committed {
+                Ok(())
+            } else {
+                Err(Error::TapKeyTweakMismatch)
+            }
+        } else {
+            Ok(())
+        }
+    }
+
     /// Same as [`Interpreter::iter`], but allows for a custom verification function.
     /// See [Self::iter_assume_sigs] for a simpler API without information about Prevouts
     /// but skips the signature verification
@@ -189,6 +525,15 @@ where
         &'iter self,
         verify_sig: Box<dyn FnMut(&KeySigPair) -> bool + 'iter>,
         txenv: Option<&'txin TxEnv<'txin, 'txin>>,
+    ) -> Iter<'txin, 'iter, Ext> {
+        self.iter_with_verifier(verify_sig, txenv, None)
+    }
+
+    fn iter_with_verifier<'iter>(
+        &'iter self,
+        verify_sig: Box<dyn FnMut(&KeySigPair) -> bool + 'iter>,
+        txenv: Option<&'txin TxEnv<'txin, 'txin>>,
+        verifier: Option<Box<dyn Verifier + 'iter>>,
     ) -> Iter<'txin, 'iter, Ext> {
         Iter {
             verify_sig,
@@ -203,26 +548,33 @@ where
                     n_evaluated: 0,
                     n_satisfied: 0,
                 }],
-                inner::Inner::CovScript(ref _pk, ref ms) => vec![NodeEvaluationState {
+                inner::Inner::CovScript(_, _, ref ms, _) => vec![NodeEvaluationState {
                     node: ms,
                     n_evaluated: 0,
                     n_satisfied: 0,
                 }],
                 inner::Inner::PublicKey(ref _pk, _) => vec![],
+                inner::Inner::FutureWitness { .. } => vec![],
             },
             // Cloning the references to elements of stack should be fine as it allows
             // call interpreter.iter() without mutating interpreter
-            stack: self.stack.clone(),
+            stack: match self.inner {
+                // Anyone can spend, nothing to check
+                inner::Inner::FutureWitness { .. } => Stack::from(vec![stack::Element::Satisfied]),
+                _ => self.stack.clone(),
+            },
             age: self.age,
             lock_time: self.lock_time,
-            cov: if let inner::Inner::CovScript(ref pk, ref _ms) = self.inner {
-                Some(pk)
+            cov: if let inner::Inner::CovScript(k, ref pks, ref _ms, _) = self.inner {
+                Some((k, pks))
             } else {
                 None
             },
+            cov_sigs: vec![],
             has_errored: false,
             txenv,
             sig_type: self.sig_type(),
+            verifier,
         }
     }
 
@@ -234,10 +586,13 @@ where
     /// - the input index is out of range
     /// - Insufficient sighash information is present
     /// - sighash single without corresponding output
+    ///
+    /// The signature itself is checked with `verifier`, usually a secp256k1
+    /// context.
     // TODO: Create a good first isse to change this to error
-    pub fn verify_sig<C: secp256k1_zkp::Verification, T: Borrow<elements::TxOut>>(
+    pub fn verify_sig<V: Verifier + ?Sized, T: Borrow<elements::TxOut>>(
         &self,
-        secp: &secp256k1_zkp::Secp256k1<C>,
+        verifier: &V,
         tx: &elements::Transaction,
         input_idx: usize,
         prevouts: &sighash::Prevouts<'_, T>,
@@ -277,13 +632,18 @@ where
                 };
                 let msg =
                     secp256k1_zkp::Message::from_digest_slice(sighash.as_ref()).expect("32 byte");
-                secp.verify_ecdsa(&msg, &ecdsa_sig.0, &key.inner).is_ok()
+                verifier.verify_ecdsa(&msg, &ecdsa_sig.0, &key.inner)
             }
             KeySigPair::Schnorr(xpk, schnorr_sig) => {
+                let annex = self
+                    .annex()
+                    .map(|annex| sighash::Annex::new(annex).expect("Annex prefix checked"));
                 let sighash_msg = if self.is_taproot_v1_key_spend() {
-                    cache.taproot_key_spend_signature_hash(
+                    cache.taproot_sighash(
                         input_idx,
                         prevouts,
+                        annex,
+                        None,
                         schnorr_sig.hash_ty,
                         genesis_hash,
                     )
@@ -294,10 +654,11 @@ where
                     );
                     let leaf_hash =
                         elements::sighash::ScriptPath::with_defaults(tap_script).leaf_hash();
-                    cache.taproot_script_spend_signature_hash(
+                    cache.taproot_sighash(
                         input_idx,
                         prevouts,
-                        leaf_hash,
+                        annex,
+                        Some((leaf_hash, TAP_NO_CODESEP_POS)),
                         schnorr_sig.hash_ty,
                         genesis_hash,
                     )
@@ -308,8 +669,7 @@ where
                 let msg = sighash_msg.map(|hash| {
                     secp256k1_zkp::Message::from_digest_slice(hash.as_ref()).expect("32 byte")
                 });
-                let success =
-                    msg.map(|msg| secp.verify_schnorr(&schnorr_sig.sig, &msg, xpk).is_ok());
+                let success = msg.map(|msg| verifier.verify_schnorr(&msg, &schnorr_sig.sig, xpk));
                 success.unwrap_or(false) // unwrap_or_default checks for errors, while success would have checksig results
             }
         }
@@ -332,16 +692,19 @@ where
     /// - For legacy outputs, no information about prevouts is required
     /// - For segwitv0 outputs, prevout at corresponding index with correct amount must be provided
     /// - For taproot outputs, information about all prevouts must be supplied and genesis_hash must be supplied
-    pub fn iter<'iter, C: secp256k1_zkp::Verification>(
+    ///
+    /// Signatures, including the signatures of covenants, are checked with
+    /// `verifier`, usually a secp256k1 context.
+    pub fn iter<'iter, V: Verifier + ?Sized>(
         &'iter self,
-        secp: &'iter secp256k1_zkp::Secp256k1<C>,
+        verifier: &'iter V,
         txenv: &'txin TxEnv, // actually a 'prevouts, but 'prevouts: 'iter
         genesis_hash: elements::BlockHash, // required for sighash computation in BIP341
     ) -> Iter<'txin, 'iter, Ext> {
-        self.iter_custom(
+        self.iter_with_verifier(
             Box::new(move |sig| {
                 self.verify_sig(
-                    secp,
+                    verifier,
                     txenv.tx(),
                     txenv.idx(),
                     &sighash::Prevouts::All(txenv.spent_utxos()),
@@ -350,6 +713,7 @@ where
                 )
             }),
             Some(txenv),
+            Some(Box::new(verifier)),
         )
     }
 
@@ -358,6 +722,44 @@ where
         self.iter_custom(Box::new(|_| true), None)
     }
 
+    /// All the combinations of conditions that satisfy the spent script,
+    /// see [`crate::policy::semantic::Policy::spend_paths`]. The covenant
+    /// key of a covenant spend is the first condition of every path.
+    ///
+    /// The paths only depend on the script, not on the witness. When there
+    /// is more than one, a third party holding the conditions of another
+    /// path could have replaced the witness. Use
+    /// [`Interpreter::iter_assume_sigs`] for the path the witness took.
+    ///
+    /// # Errors
+    /// If the miniscript can not be lifted, e.g. because it uses
+    /// extension fragments or mixes height and time locks.
+    pub fn spend_paths(&self) -> Result<std::vec::IntoIter<Vec<Semantic<BitcoinKey>>>, Error> {
+        let paths = match self.inner {
+            inner::Inner::PublicKey(ref pk, _) => vec![vec![Semantic::Key(*pk)]],
+            inner::Inner::Script(ref ms, _) => ms.lift()?.spend_paths(),
+            inner::Inner::CovScript(k, ref pks, ref ms, _) => ms
+                .lift()?
+                .spend_paths()
+                .into_iter()
+                .map(|path| {
+                    let mut cov_path = if pks.len() == 1 && k == 1 {
+                        vec![Semantic::Key(pks[0])]
+                    } else {
+                        vec![Semantic::Threshold(
+                            k,
+                            pks.iter().map(|pk| Semantic::Key(*pk)).collect(),
+                        )]
+                    };
+                    cov_path.extend(path);
+                    cov_path
+                })
+                .collect(),
+            inner::Inner::FutureWitness { .. } => vec![vec![]],
+        };
+        Ok(paths.into_iter())
+    }
+
     /// Outputs a "descriptor" string which reproduces the spent coins
     ///
     /// This may not represent the original descriptor used to produce the transaction,
@@ -388,15 +790,135 @@ where
             inner::Inner::Script(ref ms, inner::ScriptType::Sh) => format!("elsh({})", ms),
             inner::Inner::Script(ref ms, inner::ScriptType::Wsh) => format!("elwsh({})", ms),
             inner::Inner::Script(ref ms, inner::ScriptType::ShWsh) => format!("elsh(wsh({}))", ms),
-            inner::Inner::CovScript(ref pk, ref ms) => {
-                // always wsh for now
-                format!("elcovwsh({},{})", pk, ms)
+            inner::Inner::CovScript(_, ref pks, ref ms, inner::ScriptType::Tr) => {
+                format!("eltapcov({},{})", pks[0], ms)
+            }
+            inner::Inner::CovScript(k, ref pks, ref ms, _) => {
+                // always wsh otherwise. Covenant descriptors have no sh
+                // wrapped form, so sh(wsh) covenants show the inner covenant
+                if pks.len() == 1 && k == 1 {
+                    format!("elcovwsh({},{})", pks[0], ms)
+                } else {
+                    let pks: Vec<_> = pks.iter().map(|pk| pk.to_string()).collect();
+                    format!("elcovwsh(multi({},{}),{})", k, pks.join(","), ms)
+                }
             }
             inner::Inner::Script(ref ms, inner::ScriptType::Tr) => {
                 // Hidden paths are still under discussion, once the spec is finalized, we can support
                 // rawnode and raw leaf.
                 format!("eltr(hidden_paths_not_yet_supported,{})", ms)
             }
+            inner::Inner::FutureWitness {
+                version,
+                ref program,
+            } => format!(
+                "future_witness_not_supported({},{})",
+                version,
+                program.to_hex()
+            ),
+        }
+    }
+
+    /// A descriptor style label of the spend type, such as `wpkh`,
+    /// `sh(wsh)` or `tr`. Covenant spends are labelled `covwsh` or
+    /// `tapcov`. Unlike [`Self::inferred_descriptor_string`], this does
+    /// not include the keys or the script.
+    pub fn descriptor_type_label(&self) -> &'static str {
+        self.inner.descriptor_type_label()
+    }
+
+    /// The [`DescriptorType`] of the spend, e.g. to look up the maximum
+    /// satisfaction weight of the descriptor. Sorted multi descriptors are
+    /// reported as the matching non-sorted type.
+    ///
+    /// Taproot covenants are reported as [`DescriptorType::Tr`], as an
+    /// `eltapcov` covenant is a leaf of a taproot descriptor and not a
+    /// descriptor of its own. [`DescriptorType::Cov`] is only used for
+    /// `elcovwsh` covenants. Use [`Self::descriptor_type_label`] to tell
+    /// taproot covenant spends apart.
+    pub fn descriptor_type(&self) -> DescriptorType {
+        self.inner.descriptor_type()
+    }
+
+    /// The witness version and program of a spend parsed as anyone-can-spend
+    /// with [`InterpreterOptions::future_witness`].
+    /// Returns `None` for all other spends.
+    pub fn future_witness(&self) -> Option<(u8, &[u8])> {
+        match self.inner {
+            inner::Inner::FutureWitness {
+                version,
+                ref program,
+            } => Some((version, program)),
+            _ => None,
+        }
+    }
+
+    /// The taproot annex stripped from the witness, if any.
+    /// Only spends parsed with [`InterpreterOptions::annex`] can carry an
+    /// annex.
+    pub fn annex(&self) -> Option<&'txin [u8]> {
+        self.tap_data.as_ref().and_then(|data| data.annex)
+    }
+
+    /// The control block of a taproot script spend.
+    /// Returns `None` for all other spends.
+    pub fn control_block(&self) -> Option<&ControlBlock> {
+        self.tap_data
+            .as_ref()
+            .and_then(|data| data.control_block.as_ref())
+    }
+
+    /// The leaf version of a taproot script spend as committed in the
+    /// control block. Returns `None` for all other spends.
+    pub fn leaf_version(&self) -> Option<LeafVersion> {
+        self.control_block().map(|cb| cb.leaf_version)
+    }
+
+    /// The length of the merkle path in the control block of a taproot
+    /// script spend, i.e. the depth of the leaf in the taproot tree.
+    /// Returns `None` for all other spends.
+    pub fn merkle_path_len(&self) -> Option<usize> {
+        self.control_block()
+            .map(|cb| cb.merkle_branch.as_inner().len())
+    }
+
+    /// Whether this is a taproot script spend that reveals the BIP341
+    /// unspendable(NUMS) internal key, as used by script path only
+    /// descriptors such as [`crate::descriptor::TapCSFSCov`]. Outputs with
+    /// such a key can not be spent via the key path, so there is no need to
+    /// try key path satisfactions for them.
+    ///
+    /// The output key alone does not reveal the internal key, so this is
+    /// always `false` for key spends.
+    pub fn has_unspendable_internal_key(&self) -> bool {
+        self.tap_data
+            .as_ref()
+            .map_or(false, |data| data.unspendable_internal_key)
+    }
+
+    /// Checks that the output key of a taproot key spend is the tweak of
+    /// `internal_key` with `merkle_root`. The witness of a key spend does
+    /// not reveal the internal key, so it must be supplied by the caller,
+    /// e.g. from the descriptor or PSET being spent.
+    ///
+    /// Returns [`Error::TapKeyTweakMismatch`] if the keys do not match and
+    /// [`Error::CouldNotEvaluate`] if this is not a taproot key spend.
+    pub fn verify_tap_key_tweak<C: secp256k1_zkp::Verification>(
+        &self,
+        secp: &secp256k1_zkp::Secp256k1<C>,
+        internal_key: secp256k1_zkp::XOnlyPublicKey,
+        merkle_root: Option<TapNodeHash>,
+    ) -> Result<(), Error> {
+        match self.inner {
+            inner::Inner::PublicKey(BitcoinKey::XOnlyPublicKey(pk), inner::PubkeyType::Tr) => {
+                let spend_info = TaprootSpendInfo::new_key_spend(secp, internal_key, merkle_root);
+                if spend_info.output_key().into_inner() == pk {
+                    Ok(())
+                } else {
+                    Err(Error::TapKeyTweakMismatch)
+                }
+            }
+            _ => Err(Error::CouldNotEvaluate),
         }
     }
 
@@ -414,6 +936,7 @@ where
             inner::Inner::Script(_, inner::ScriptType::ShWsh) => false, // lol "sorta"
             inner::Inner::CovScript(..) => false,
             inner::Inner::Script(_, inner::ScriptType::Tr) => false,
+            inner::Inner::FutureWitness { .. } => false,
         }
     }
 
@@ -430,7 +953,9 @@ where
             inner::Inner::Script(_, inner::ScriptType::Wsh) => true,
             inner::Inner::Script(_, inner::ScriptType::ShWsh) => true, // lol "sorta"
             inner::Inner::Script(_, inner::ScriptType::Tr) => false,
-            inner::Inner::CovScript(_, _) => true,
+            inner::Inner::CovScript(_, _, _, inner::ScriptType::Tr) => false,
+            inner::Inner::CovScript(..) => true,
+            inner::Inner::FutureWitness { .. } => false,
         }
     }
 
@@ -447,7 +972,8 @@ where
             inner::Inner::Script(_, inner::ScriptType::Wsh) => false,
             inner::Inner::Script(_, inner::ScriptType::ShWsh) => false,
             inner::Inner::Script(_, inner::ScriptType::Tr) => false,
-            inner::Inner::CovScript(_, _) => false,
+            inner::Inner::CovScript(..) => false,
+            inner::Inner::FutureWitness { .. } => false,
         }
     }
 
@@ -464,7 +990,9 @@ where
             inner::Inner::Script(_, inner::ScriptType::Wsh) => false,
             inner::Inner::Script(_, inner::ScriptType::ShWsh) => false,
             inner::Inner::Script(_, inner::ScriptType::Tr) => true,
-            inner::Inner::CovScript(_, _) => false,
+            inner::Inner::CovScript(_, _, _, inner::ScriptType::Tr) => true,
+            inner::Inner::CovScript(..) => false,
+            inner::Inner::FutureWitness { .. } => false,
         }
     }
 
@@ -473,6 +1001,7 @@ where
         match self.inner {
             inner::Inner::PublicKey(_, inner::PubkeyType::Tr) => SigType::Schnorr,
             inner::Inner::Script(_, inner::ScriptType::Tr) => SigType::Schnorr,
+            inner::Inner::CovScript(_, _, _, inner::ScriptType::Tr) => SigType::Schnorr,
             inner::Inner::PublicKey(_, inner::PubkeyType::Pk)
             | inner::Inner::PublicKey(_, inner::PubkeyType::Pkh)
             | inner::Inner::PublicKey(_, inner::PubkeyType::Wpkh)
@@ -481,7 +1010,31 @@ where
             | inner::Inner::Script(_, inner::ScriptType::Sh)
             | inner::Inner::Script(_, inner::ScriptType::Wsh)
             | inner::Inner::Script(_, inner::ScriptType::ShWsh)
-            | inner::Inner::CovScript(_, _) => SigType::Ecdsa,
+            | inner::Inner::CovScript(..)
+            | inner::Inner::FutureWitness { .. } => SigType::Ecdsa,
+        }
+    }
+
+    /// The hash160 of the public key of a `Pkh`, `Wpkh` or `ShWpkh` spend,
+    /// as matched against the scriptPubkey. This is read from the parsed
+    /// scriptCode, so it does not hash the key again. Returns `None` for
+    /// all other spends.
+    pub fn pubkey_hash(&self) -> Option<hash160::Hash> {
+        match self.inner {
+            inner::Inner::PublicKey(_, inner::PubkeyType::Pkh)
+            | inner::Inner::PublicKey(_, inner::PubkeyType::Wpkh)
+            | inner::Inner::PublicKey(_, inner::PubkeyType::ShWpkh) => {
+                // The scriptCode of these spends is the p2pkh script of the key
+                let script_code = self
+                    .script_code
+                    .as_ref()
+                    .expect("Pkh spends have a scriptCode");
+                Some(hash160::Hash::from_slice(&script_code[3..23]).expect("20 byte hash"))
+            }
+            inner::Inner::PublicKey(..)
+            | inner::Inner::Script(..)
+            | inner::Inner::CovScript(..)
+            | inner::Inner::FutureWitness { .. } => None,
         }
     }
 
@@ -560,6 +1113,16 @@ pub enum SatisfiedConstraint<Ext: Extension> {
         pref: Vec<u8>,
     },
 
+    /// The covenant signature. It is checked against the transaction
+    /// sighash and, with CHECKSIGFROMSTACK, against the sighash message
+    /// built from the covenant witness.
+    CovenantSig {
+        /// The covenant public key and signature
+        key_sig: KeySigPair,
+        /// The sighash verified by CHECKSIGFROMSTACK
+        message: secp256k1_zkp::Message,
+    },
+
     /// Extension Interpreter
     Ext {
         /// Extension
@@ -607,9 +1170,14 @@ where
     txenv: Option<&'txin TxEnv<'txin, 'txin>>,
     age: Sequence,
     lock_time: LockTime,
-    cov: Option<&'intp BitcoinKey>,
+    // The threshold and keys of a covenant that is yet to be checked
+    cov: Option<(usize, &'intp [BitcoinKey])>,
+    // Checked covenant signatures that are yet to be returned
+    cov_sigs: Vec<SatisfiedConstraint<Ext>>,
     has_errored: bool,
     sig_type: SigType,
+    // Checks covenant signatures, a new secp256k1 context if `None`
+    verifier: Option<Box<dyn Verifier + 'intp>>,
 }
 
 ///Iterator for Iter
@@ -653,6 +1221,75 @@ where
         })
     }
 
+    /// Helper function to verify the taproot covenant. At this point the
+    /// stack must contain the covenant signature followed by the 14 sighash
+    /// items, with the first item(genesis hash) at the top.
+    fn verify_tap_cov(
+        &mut self,
+        pk: &BitcoinKey,
+        x_only_pk: bitcoin::key::XOnlyPublicKey,
+    ) -> Result<SatisfiedConstraint<Ext>, Error> {
+        let n_items = TAP_COV_ITEM_SIZES.len();
+        if self.stack.len() != n_items + 1 {
+            return Err(Error::UnexpectedStackEnd);
+        }
+        for i in 0..n_items + 1 {
+            self.stack[i].try_push()?;
+        }
+        // Reconstruct the sighash message the same way as the script does
+        let mut sighash_msg = Vec::new();
+        for (i, len) in TAP_COV_ITEM_SIZES.iter().enumerate() {
+            let item = self.stack[n_items - i]
+                .as_push()
+                .expect("Push checked above");
+            if item.len() != *len {
+                return Err(Error::IncorrectCovenantWitness);
+            }
+            if i == TAP_COV_SPEND_TYPE_POS {
+                sighash_msg.push(TAP_COV_SPEND_TYPE);
+            }
+            sighash_msg.extend(item);
+            if i == 0 {
+                // genesis hash is committed twice followed by hash_type
+                sighash_msg.extend(item);
+                sighash_msg.push(sighash::SchnorrSighashType::All as u8);
+            }
+        }
+        sighash_msg.extend(&TAP_COV_MSG_SUFFIX);
+
+        let mut ser_sig = Vec::from(self.stack[0].as_push().expect("Push checked above"));
+        ser_sig.push(sighash::SchnorrSighashType::All as u8);
+        let key_sig = verify_sersig(&mut self.verify_sig, pk, &ser_sig)?;
+        let schnorr_sig = match key_sig {
+            KeySigPair::Schnorr(_, sig) => sig.sig,
+            KeySigPair::Ecdsa(_, _) => {
+                unreachable!("Internal error: Taproot cov check in ecdsa sigs")
+            }
+        };
+        // Do the checkSigFromStackCheck over the tagged hash
+        let sighash = elements::taproot::TapSighashHash::hash(&sighash_msg);
+        let msg = secp256k1_zkp::Message::from_digest_slice(&sighash[..]).unwrap();
+        let valid = match self.verifier {
+            Some(ref verifier) => verifier.verify_schnorr(&msg, &schnorr_sig, &x_only_pk),
+            None => {
+                let secp = secp256k1_zkp::Secp256k1::verification_only();
+                secp.verify_schnorr(&schnorr_sig, &msg, &x_only_pk).is_ok()
+            }
+        };
+        if !valid {
+            return Err(Error::CovSighashMismatch);
+        }
+        //Signature check successful, set cov to None to
+        //terminate the next() function in the subsequent call
+        self.cov = None;
+        self.stack.0.clear();
+        self.stack.push(stack::Element::Satisfied);
+        Ok(SatisfiedConstraint::CovenantSig {
+            key_sig,
+            message: msg,
+        })
+    }
+
     /// Helper function to step the iterator
     fn iter_next(&mut self) -> Option<Result<SatisfiedConstraint<Ext>, Error>> {
         while let Some(node_state) = self.state.pop() {
@@ -1081,49 +1718,66 @@ where
 
         //state empty implies that either the execution has terminated or we have a
         //Pk based descriptor or a Covenant descriptor
-        if let Some(pk) = self.cov {
+        // Return the remaining signatures of a checked multi key covenant
+        if let Some(cov_sig) = self.cov_sigs.pop() {
+            return Some(Ok(cov_sig));
+        }
+        if let Some((k, pks)) = self.cov {
             // First verify the top of Miniscript.
-            // At this point, the stack must contain 13 elements
-            // pop the satisfied top and verify the covenant code.
+            // At this point, the stack must contain one signature slot per
+            // covenant key and the 11 sighash items. Pop the satisfied top
+            // and verify the covenant code.
             if self.stack.pop() != Some(stack::Element::Satisfied) {
                 return Some(Err(Error::IncorrectCovenantWitness));
             }
-            if self.stack.len() != 12 {
+            if let BitcoinKey::XOnlyPublicKey(x_only_pk) = pks[0] {
+                return Some(self.verify_tap_cov(&pks[0], x_only_pk));
+            }
+            let n_sigs = pks.len();
+            if self.stack.len() != n_sigs + 11 {
                 return Some(Err(Error::UnexpectedStackEnd));
             }
-            // safe to unwrap 12 times
-            for i in 0..12 {
+            // Multi key covenants have empty signature slots for the keys
+            // that did not sign
+            let is_multi = n_sigs != 1 || k != 1;
+            for i in 0..n_sigs + 11 {
+                if is_multi && i < n_sigs && self.stack[i] == stack::Element::Dissatisfied {
+                    continue;
+                }
                 if let Err(e) = self.stack[i].try_push() {
                     return Some(Err(e));
                 }
             }
-            let mut ser_sig = Vec::new();
-            // 1.29 errors
-            {
-                let sighash_bytes = self.stack[1].as_push().expect("Push checked above");
-                let sighash_u32 = util::slice_to_u32_le(sighash_bytes);
-                let sighash_ty = EcdsaSighashType::from_u32(sighash_u32);
-                let sig_vec = self.stack[0].as_push().expect("Size checked above");
-                ser_sig.extend(sig_vec);
+            let sighash_bytes = self.stack[n_sigs].as_push().expect("Push checked above");
+            let sighash_ty = EcdsaSighashType::from_u32(util::slice_to_u32_le(sighash_bytes));
+            // Do the checkSigFromStackCheck over the same message for all keys
+            let sighash_msg: Vec<u8> = self.stack.0[n_sigs..]
+                .iter()
+                .rev()
+                .flat_map(|x| Vec::from(x.as_push().expect("Push checked above")))
+                .collect();
+            let mut eng = Sighash::engine();
+            eng.input(&sighash_msg);
+            let sighash_u256 = Sighash::from_engine(eng);
+            let msg =
+                elements::secp256k1_zkp::Message::from_digest_slice(&sighash_u256[..]).unwrap();
+
+            // The signature of the first key is closest to the sighash items
+            let mut cov_sigs = Vec::with_capacity(k);
+            for (i, pk) in pks.iter().enumerate() {
+                let sig_vec = match self.stack[n_sigs - 1 - i] {
+                    stack::Element::Push(sig_vec) => sig_vec,
+                    // Empty signature slots are checked above
+                    _ => continue,
+                };
+                let mut ser_sig = Vec::from(sig_vec);
                 ser_sig.push(sighash_ty as u8);
-            }
-
-            if let Ok(sig) = verify_sersig(&mut self.verify_sig, pk, &ser_sig) {
-                //Signature check successful, set cov to None to
-                //terminate the next() function in the subsequent call
-                self.cov = None;
-                // Do the checkSigFromStackCheck
-                let sighash_msg: Vec<u8> = self.stack.0[1..]
-                    .iter()
-                    .rev()
-                    .flat_map(|x| Vec::from(x.as_push().expect("Push checked above")))
-                    .collect();
-                let mut eng = Sighash::engine();
-                eng.input(&sighash_msg);
-                let sighash_u256 = Sighash::from_engine(eng);
-                let msg =
-                    elements::secp256k1_zkp::Message::from_digest_slice(&sighash_u256[..]).unwrap();
-
+                let sig = match verify_sersig(&mut self.verify_sig, pk, &ser_sig) {
+                    Ok(sig) => sig,
+                    Err(_) => {
+                        return Some(Err(Error::PkEvaluationError(PkEvalErrInner::from(*pk))))
+                    }
+                };
                 // Legacy Cov scripts only operate on Ecdsa key sig pairs
                 let (ec_pk, ecdsa_sig) = match sig {
                     KeySigPair::Ecdsa(pk, sig) => (pk, sig.0),
@@ -1131,17 +1785,34 @@ where
                         unreachable!("Internal error: Legacy cov check in schnorr sigs")
                     }
                 };
-                // Creating a context is no-longer expensive
-                let secp = secp256k1_zkp::Secp256k1::verification_only();
-                if secp.verify_ecdsa(&msg, &ecdsa_sig, &ec_pk.inner).is_err() {
-                    return Some(Err(Error::PkEvaluationError(PkEvalErrInner::from(*pk))));
+                let valid = match self.verifier {
+                    Some(ref verifier) => verifier.verify_ecdsa(&msg, &ecdsa_sig, &ec_pk.inner),
+                    None => {
+                        // Creating a context is no-longer expensive
+                        let secp = secp256k1_zkp::Secp256k1::verification_only();
+                        secp.verify_ecdsa(&msg, &ecdsa_sig, &ec_pk.inner).is_ok()
+                    }
+                };
+                if !valid {
+                    return Some(Err(Error::CovSighashMismatch));
                 }
-                self.stack.0.clear();
-                self.stack.push(stack::Element::Satisfied);
-                return Some(Ok(SatisfiedConstraint::PublicKey { key_sig: sig }));
-            } else {
-                return Some(Err(Error::PkEvaluationError(PkEvalErrInner::from(*pk))));
+                cov_sigs.push(SatisfiedConstraint::CovenantSig {
+                    key_sig: sig,
+                    message: msg,
+                });
+            }
+            // The covenant script requires exactly k signatures
+            if cov_sigs.len() != k {
+                return Some(Err(Error::IncorrectCovenantWitness));
             }
+            //Signature checks successful, set cov to None to
+            //terminate the next() function in the subsequent calls
+            self.cov = None;
+            self.stack.0.clear();
+            self.stack.push(stack::Element::Satisfied);
+            cov_sigs.reverse();
+            self.cov_sigs = cov_sigs;
+            return self.cov_sigs.pop().map(Ok);
         }
         if let Some(pk) = self.public_key {
             if let Some(stack::Element::Push(sig)) = self.stack.pop() {
@@ -1208,7 +1879,7 @@ mod tests {
     use super::*;
     use crate::miniscript::analyzable::ExtParams;
     use crate::miniscript::context::NoChecks;
-    use crate::{ElementsSig, Miniscript, NoExt, ToPublicKey};
+    use crate::{ElementsSig, Legacy, Miniscript, NoExt, ToPublicKey};
 
     fn setup_keys_sigs(
         n: usize,
@@ -1305,9 +1976,11 @@ mod tests {
                 age: Sequence::from_height(1002),
                 lock_time: LockTime::from_height(1002).unwrap(),
                 cov: None,
+                cov_sigs: vec![],
                 has_errored: false,
                 txenv: None,
                 sig_type: SigType::Ecdsa,
+                verifier: None,
             }
         }
 
@@ -1753,6 +2426,400 @@ mod tests {
 
     // By design there is no support for parse a miniscript with BitcoinKey
     // because it does not implement FromStr
+    #[test]
+    fn pkh_key_type_mismatch() {
+        let (pks, _der_sigs, _ecdsa_sigs, _sighash, _secp, _xpks, _schnorr_sigs, ser_schnorr_sigs) =
+            setup_keys_sigs(2);
+        // Evaluates a raw pkh revealing `pk_bytes` in a tapscript context
+        let eval = |pk_bytes: &[u8]| {
+            let keyhash = hash160::Hash::hash(pk_bytes);
+            let pkh = no_checks_ms(&format!("c:expr_raw_pkh({})", keyhash));
+            let stack = Stack::from(vec![
+                stack::Element::Push(&ser_schnorr_sigs[1]),
+                stack::Element::Push(pk_bytes),
+            ]);
+            let iter: Iter<'_, '_, NoExt> = Iter {
+                verify_sig: Box::new(|_| true),
+                stack,
+                public_key: None,
+                state: vec![NodeEvaluationState {
+                    node: &pkh,
+                    n_evaluated: 0,
+                    n_satisfied: 0,
+                }],
+                age: Sequence::ZERO,
+                lock_time: LockTime::ZERO,
+                cov: None,
+                cov_sigs: vec![],
+                has_errored: false,
+                txenv: None,
+                sig_type: SigType::Schnorr,
+                verifier: None,
+            };
+            iter.collect::<Result<Vec<SatisfiedConstraint<NoExt>>, Error>>()
+                .unwrap_err()
+        };
+
+        // A raw pkh committing to a full key
+        let keyhash = pks[1].to_pubkeyhash(SigType::Ecdsa);
+        let err = eval(&pks[1].to_bytes());
+        match err {
+            Error::PkHashKeyTypeMismatch {
+                keyhash: hash,
+                sig_type: SigType::Schnorr,
+                key_len: 33,
+            } if hash == keyhash => {}
+            ref e => panic!("Unexpected error {}", e),
+        }
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Key for pubkey hash {} has 33 bytes, expected a 32 byte x-only key",
+                keyhash
+            )
+        );
+
+        // A 32 byte key that is not on the curve
+        match eval(&[0xff; 32]) {
+            Error::XOnlyPublicKeyParseError => {}
+            ref e => panic!("Unexpected error {}", e),
+        }
+    }
+
+    #[test]
+    fn sh_multi_spend() {
+        let (pks, der_sigs, ecdsa_sigs, sighash, secp, _xpks, _schnorr_sigs, _ser_schnorr_sigs) =
+            setup_keys_sigs(3);
+        let ms = Miniscript::<bitcoin::PublicKey, Legacy>::from_str_ext(
+            &format!("multi(2,{},{},{})", pks[0], pks[1], pks[2]),
+            &ExtParams::allow_all(),
+        )
+        .unwrap();
+        let redeem_script = ms.encode();
+        let spk = elements::Script::new_p2sh(&elements::ScriptHash::hash(&redeem_script[..]));
+        // Extra zero for the CHECKMULTISIG bug, the signatures in key order,
+        // then the redeem script
+        let script_sig = elements::script::Builder::new()
+            .push_int(0)
+            .push_slice(&der_sigs[0])
+            .push_slice(&der_sigs[2])
+            .push_slice(&redeem_script[..])
+            .into_script();
+
+        let interpreter = Interpreter::<NoExt>::from_txdata_ext(
+            &spk,
+            &script_sig,
+            &[],
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert_eq!(interpreter.script_code, Some(redeem_script.clone()));
+        assert_eq!(
+            interpreter.stack,
+            Stack::from(vec![
+                stack::Element::Dissatisfied,
+                stack::Element::Push(&der_sigs[0]),
+                stack::Element::Push(&der_sigs[2]),
+            ])
+        );
+        assert_eq!(
+            interpreter.inferred_descriptor_string(),
+            format!("elsh({})", ms)
+        );
+
+        let secp_ref = &secp;
+        let vfyfn = move |pksig: &KeySigPair| match pksig {
+            KeySigPair::Ecdsa(pk, ecdsa_sig) => secp_ref
+                .verify_ecdsa(&sighash, &ecdsa_sig.0, &pk.inner)
+                .is_ok(),
+            KeySigPair::Schnorr(..) => false,
+        };
+        let constraints: Result<Vec<SatisfiedConstraint<NoExt>>, Error> =
+            interpreter.iter_custom(Box::new(vfyfn), None).collect();
+        assert_eq!(
+            constraints.unwrap(),
+            vec![
+                SatisfiedConstraint::PublicKey {
+                    key_sig: KeySigPair::Ecdsa(pks[2], ecdsa_sigs[2])
+                },
+                SatisfiedConstraint::PublicKey {
+                    key_sig: KeySigPair::Ecdsa(pks[0], ecdsa_sigs[0])
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn sortedmulti_spend() {
+        let (pks, der_sigs, ecdsa_sigs, sighash, secp, _xpks, _schnorr_sigs, _ser_schnorr_sigs) =
+            setup_keys_sigs(3);
+        // Indices of the keys in bip67 order
+        let mut order = vec![0, 1, 2];
+        order.sort_by_key(|&i| pks[i].to_bytes());
+        let desc = crate::Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elwsh(sortedmulti(2,{},{},{}))",
+            pks[0], pks[1], pks[2]
+        ))
+        .unwrap();
+        let witness_script = desc.explicit_script().unwrap();
+        let spk = desc.script_pubkey();
+        let empty = elements::Script::new();
+
+        let secp_ref = &secp;
+        let vfyfn = move |pksig: &KeySigPair| match pksig {
+            KeySigPair::Ecdsa(pk, ecdsa_sig) => secp_ref
+                .verify_ecdsa(&sighash, &ecdsa_sig.0, &pk.inner)
+                .is_ok(),
+            KeySigPair::Schnorr(..) => false,
+        };
+
+        // Signatures following the sorted key order
+        let witness = vec![
+            vec![],
+            der_sigs[order[0]].clone(),
+            der_sigs[order[2]].clone(),
+            witness_script.to_bytes(),
+        ];
+        let interpreter = Interpreter::<NoExt>::from_txdata_ext(
+            &spk,
+            &empty,
+            &witness,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert_eq!(
+            interpreter.inferred_descriptor_string(),
+            format!(
+                "elwsh(multi(2,{},{},{}))",
+                pks[order[0]], pks[order[1]], pks[order[2]]
+            )
+        );
+        let constraints: Result<Vec<SatisfiedConstraint<NoExt>>, Error> =
+            interpreter.iter_custom(Box::new(vfyfn), None).collect();
+        assert_eq!(
+            constraints.unwrap(),
+            vec![
+                SatisfiedConstraint::PublicKey {
+                    key_sig: KeySigPair::Ecdsa(pks[order[2]], ecdsa_sigs[order[2]])
+                },
+                SatisfiedConstraint::PublicKey {
+                    key_sig: KeySigPair::Ecdsa(pks[order[0]], ecdsa_sigs[order[0]])
+                },
+            ]
+        );
+
+        // The same signatures in the opposite order must not satisfy it
+        let witness = vec![
+            vec![],
+            der_sigs[order[2]].clone(),
+            der_sigs[order[0]].clone(),
+            witness_script.to_bytes(),
+        ];
+        let interpreter = Interpreter::<NoExt>::from_txdata_ext(
+            &spk,
+            &empty,
+            &witness,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        let constraints: Result<Vec<SatisfiedConstraint<NoExt>>, Error> =
+            interpreter.iter_custom(Box::new(vfyfn), None).collect();
+        match constraints {
+            Err(Error::MultiSigEvaluationError) => {}
+            x => panic!("unexpected result {:?}", x),
+        }
+
+        // Same for the legacy p2sh form
+        let desc = crate::Descriptor::<bitcoin::PublicKey>::from_str(&format!(
+            "elsh(sortedmulti(2,{},{},{}))",
+            pks[0], pks[1], pks[2]
+        ))
+        .unwrap();
+        let redeem_script = desc.explicit_script().unwrap();
+        let spk = desc.script_pubkey();
+        for (first, second, ok) in [(order[0], order[1], true), (order[1], order[0], false)] {
+            let script_sig = elements::script::Builder::new()
+                .push_int(0)
+                .push_slice(&der_sigs[first])
+                .push_slice(&der_sigs[second])
+                .push_slice(&redeem_script[..])
+                .into_script();
+            let interpreter = Interpreter::<NoExt>::from_txdata_ext(
+                &spk,
+                &script_sig,
+                &[],
+                Sequence::ZERO,
+                LockTime::ZERO,
+            )
+            .unwrap();
+            let constraints: Result<Vec<SatisfiedConstraint<NoExt>>, Error> =
+                interpreter.iter_custom(Box::new(vfyfn), None).collect();
+            assert_eq!(constraints.is_ok(), ok);
+        }
+    }
+
+    #[test]
+    fn pubkey_hash() {
+        let (pks, der_sigs, _ecdsa_sigs, _sighash, _secp, _xpks, _schnorr_sigs, _ser_schnorr_sigs) =
+            setup_keys_sigs(1);
+        let pk_bytes = pks[0].to_bytes();
+        let keyhash = pks[0].to_pubkeyhash(SigType::Ecdsa);
+        let wpkh_spk = elements::Script::new_v0_wpkh(&keyhash.into());
+
+        // pkh
+        let spk = elements::Script::new_p2pkh(&keyhash.into());
+        let script_sig = elements::script::Builder::new()
+            .push_slice(&der_sigs[0])
+            .push_slice(&pk_bytes)
+            .into_script();
+        let interpreter = Interpreter::<NoExt>::from_txdata_ext(
+            &spk,
+            &script_sig,
+            &[],
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert_eq!(interpreter.pubkey_hash(), Some(keyhash));
+
+        // wpkh
+        let wit = vec![der_sigs[0].clone(), pk_bytes.clone()];
+        let empty = elements::Script::new();
+        let interpreter = Interpreter::<NoExt>::from_txdata_ext(
+            &wpkh_spk,
+            &empty,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert_eq!(interpreter.pubkey_hash(), Some(keyhash));
+
+        // sh-wpkh
+        let spk = elements::Script::new_p2sh(&elements::ScriptHash::hash(&wpkh_spk[..]));
+        let script_sig = elements::script::Builder::new()
+            .push_slice(&wpkh_spk[..])
+            .into_script();
+        let interpreter = Interpreter::<NoExt>::from_txdata_ext(
+            &spk,
+            &script_sig,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert_eq!(interpreter.pubkey_hash(), Some(keyhash));
+
+        // Only key hash spends have a key hash
+        let spk = elements::script::Builder::new()
+            .push_slice(&pk_bytes)
+            .push_opcode(elements::opcodes::all::OP_CHECKSIG)
+            .into_script();
+        let script_sig = elements::script::Builder::new()
+            .push_slice(&der_sigs[0])
+            .into_script();
+        let interpreter = Interpreter::<NoExt>::from_txdata_ext(
+            &spk,
+            &script_sig,
+            &[],
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert_eq!(
+            interpreter.inferred_descriptor_string(),
+            format!("elpk({})", pks[0])
+        );
+        assert_eq!(interpreter.pubkey_hash(), None);
+    }
+
+    #[test]
+    fn tap_multi_a_spend() {
+        let (_, _, _, sighash, secp, xpks, schnorr_sigs, ser_schnorr_sigs) = setup_keys_sigs(4);
+        let desc = Descriptor::<bitcoin::key::XOnlyPublicKey>::from_str(&format!(
+            "eltr({},multi_a(2,{},{},{}))",
+            xpks[0], xpks[1], xpks[2], xpks[3]
+        ))
+        .unwrap();
+        let (leaf_script, control_block) = match desc {
+            Descriptor::Tr(ref tr) => {
+                let (_, ms) = tr.iter_scripts().next().unwrap();
+                let leaf_script = ms.encode();
+                let control_block = tr
+                    .spend_info()
+                    .control_block(&(leaf_script.clone(), LeafVersion::default()))
+                    .unwrap();
+                (leaf_script, control_block)
+            }
+            _ => unreachable!(),
+        };
+        let spk = desc.script_pubkey();
+        let script_sig = elements::Script::new();
+        let secp_ref = &secp;
+        let constraints = |sigs: Vec<Vec<u8>>| {
+            // The signature for the first key is at the top of the stack
+            let mut wit: Vec<Vec<u8>> = sigs.into_iter().rev().collect();
+            wit.push(leaf_script.to_bytes());
+            wit.push(control_block.serialize());
+            let interpreter =
+                Interpreter::from_txdata(&spk, &script_sig, &wit, Sequence::ZERO, LockTime::ZERO)
+                    .unwrap();
+            let vfyfn = move |pksig: &KeySigPair| match pksig {
+                KeySigPair::Schnorr(xpk, schnorr_sig) => secp_ref
+                    .verify_schnorr(&schnorr_sig.sig, &sighash, xpk)
+                    .is_ok(),
+                KeySigPair::Ecdsa(..) => false,
+            };
+            interpreter
+                .iter_custom(Box::new(vfyfn), None)
+                .collect::<Result<Vec<SatisfiedConstraint<_>>, Error>>()
+        };
+
+        // 2-of-3 with an empty signature placeholder for the second key
+        let sats = constraints(vec![
+            ser_schnorr_sigs[1].clone(),
+            vec![],
+            ser_schnorr_sigs[3].clone(),
+        ])
+        .unwrap();
+        assert_eq!(
+            sats,
+            vec![
+                SatisfiedConstraint::PublicKey {
+                    key_sig: KeySigPair::Schnorr(xpks[1], schnorr_sigs[1])
+                },
+                SatisfiedConstraint::PublicKey {
+                    key_sig: KeySigPair::Schnorr(xpks[3], schnorr_sigs[3])
+                },
+            ]
+        );
+
+        // Too few or too many signatures do not satisfy the threshold
+        constraints(vec![ser_schnorr_sigs[1].clone(), vec![], vec![]]).unwrap_err();
+        constraints(vec![
+            ser_schnorr_sigs[1].clone(),
+            ser_schnorr_sigs[2].clone(),
+            ser_schnorr_sigs[3].clone(),
+        ])
+        .unwrap_err();
+        // A signature for the wrong key is not accepted
+        constraints(vec![
+            ser_schnorr_sigs[2].clone(),
+            vec![],
+            ser_schnorr_sigs[3].clone(),
+        ])
+        .unwrap_err();
+        // Every key needs a stack element, even when empty
+        constraints(vec![
+            ser_schnorr_sigs[1].clone(),
+            ser_schnorr_sigs[2].clone(),
+        ])
+        .unwrap_err();
+    }
+
     fn no_checks_ms(ms: &str) -> Miniscript<BitcoinKey, NoChecks> {
         // Parsing should allow raw hashes in the interpreter
         let elem: Miniscript<bitcoin::PublicKey, NoChecks> =
@@ -1760,6 +2827,390 @@ mod tests {
         elem.to_no_checks_ms()
     }
 
+    #[test]
+    fn tap_cov_script_code() {
+        use crate::descriptor::{TapCSFSCov, TAP_COV_MSG_SUFFIX};
+
+        let (_, _, _, _, _, x_only_pks, _, _) = setup_keys_sigs(2);
+        let desc = TapCSFSCov::<bitcoin::key::XOnlyPublicKey, CovenantExt<CovExtArgs>>::from_str(
+            &format!("eltapcov({},pk({}))", x_only_pks[0], x_only_pks[1]),
+        )
+        .unwrap();
+        let tap_script = desc.encode();
+        // Only the script is parsed here, the rest of the stack is not checked
+        let wit = vec![
+            vec![1],
+            tap_script.to_bytes(),
+            desc.control_block().serialize(),
+        ];
+        let script_sig = elements::Script::new();
+        let interpreter = Interpreter::from_txdata(
+            &desc.script_pubkey(),
+            &script_sig,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert!(interpreter.is_taproot_v1_script_spend());
+        // The covenant is committed under the unspendable internal key
+        assert!(interpreter.has_unspendable_internal_key());
+        // The full leaf script is kept, there is no post-codeseparator
+        // script code in taproot
+        assert_eq!(interpreter.script_code, Some(tap_script.clone()));
+        assert!(!tap_script.instructions().any(|ins| ins
+            == Ok(elements::script::Instruction::Op(
+                elements::opcodes::all::OP_CODESEPARATOR
+            ))));
+        // The covenant message commits to the same codeseparator position
+        assert_eq!(TAP_COV_MSG_SUFFIX[1..], TAP_NO_CODESEP_POS.to_le_bytes());
+    }
+
+    #[test]
+    fn spend_paths() {
+        let (pks, der_sigs, _, _, _, _, _, _) = setup_keys_sigs(3);
+        let key = |i: usize| Semantic::Key(BitcoinKey::Fullkey(pks[i]));
+        let older = Semantic::Older(Sequence::from_height(10));
+        let empty = elements::Script::new();
+        let paths_for = |desc: &str| {
+            let desc = Descriptor::<bitcoin::PublicKey>::from_str(desc).unwrap();
+            let script = desc.explicit_script().unwrap();
+            // Only the witness script is parsed
+            let wit = vec![vec![], script.into_bytes()];
+            Interpreter::from_txdata(
+                &desc.script_pubkey(),
+                &empty,
+                &wit,
+                Sequence::ZERO,
+                LockTime::ZERO,
+            )
+            .unwrap()
+            .spend_paths()
+            .unwrap()
+            .collect::<Vec<_>>()
+        };
+
+        let paths = paths_for(&format!(
+            "elwsh(or_d(pk({}),and_v(v:pk({}),older(10))))",
+            pks[0], pks[1]
+        ));
+        assert_eq!(paths, vec![vec![key(0)], vec![key(1), older.clone()]]);
+
+        let paths = paths_for(&format!("elwsh(multi(2,{},{},{}))", pks[0], pks[1], pks[2]));
+        assert_eq!(
+            paths,
+            vec![
+                vec![key(0), key(1)],
+                vec![key(0), key(2)],
+                vec![key(1), key(2)]
+            ]
+        );
+
+        // The covenant key is part of every path
+        let paths = paths_for(&format!(
+            "elcovwsh({},or_i(pk({}),older(10)))",
+            pks[0], pks[1]
+        ));
+        assert_eq!(paths, vec![vec![key(0), key(1)], vec![key(0), older]]);
+
+        // Key spends have a single path
+        let keyhash = pks[0].to_pubkeyhash(SigType::Ecdsa);
+        let wit = vec![der_sigs[0].clone(), pks[0].to_bytes()];
+        let interpreter = Interpreter::from_txdata(
+            &elements::Script::new_v0_wpkh(&keyhash.into()),
+            &empty,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert_eq!(
+            interpreter.spend_paths().unwrap().collect::<Vec<_>>(),
+            vec![vec![key(0)]]
+        );
+    }
+
+    #[test]
+    fn tap_key_tweak() {
+        let (_, _, _, _, secp, x_only_pks, schnorr_sigs, _) = setup_keys_sigs(3);
+        let desc = Descriptor::<bitcoin::key::XOnlyPublicKey>::from_str(&format!(
+            "eltr({},pk({}))",
+            x_only_pks[0], x_only_pks[1]
+        ))
+        .unwrap();
+        let merkle_root = match desc {
+            Descriptor::Tr(ref tr) => tr.spend_info().merkle_root(),
+            _ => unreachable!(),
+        };
+        assert!(merkle_root.is_some());
+
+        let wit = vec![schnorr_sigs[0].to_vec()];
+        let script_sig = elements::Script::new();
+        let interpreter = Interpreter::from_txdata(
+            &desc.script_pubkey(),
+            &script_sig,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert!(interpreter.is_taproot_v1_key_spend());
+        assert!(!interpreter.has_unspendable_internal_key());
+        interpreter
+            .verify_tap_key_tweak(&secp, x_only_pks[0], merkle_root)
+            .unwrap();
+        match interpreter.verify_tap_key_tweak(&secp, x_only_pks[0], None) {
+            Err(Error::TapKeyTweakMismatch) => {}
+            x => panic!("expected TapKeyTweakMismatch, got {:?}", x),
+        }
+        match interpreter.verify_tap_key_tweak(&secp, x_only_pks[2], merkle_root) {
+            Err(Error::TapKeyTweakMismatch) => {}
+            x => panic!("expected TapKeyTweakMismatch, got {:?}", x),
+        }
+
+        // Script spends don't have a key spend output key to check
+        let (leaf_script, control_block) = match desc {
+            Descriptor::Tr(ref tr) => {
+                let (_, ms) = tr.iter_scripts().next().unwrap();
+                let leaf_script = ms.encode();
+                let control_block = tr
+                    .spend_info()
+                    .control_block(&(leaf_script.clone(), LeafVersion::default()))
+                    .unwrap();
+                (leaf_script, control_block)
+            }
+            _ => unreachable!(),
+        };
+        let wit = vec![
+            schnorr_sigs[1].to_vec(),
+            leaf_script.to_bytes(),
+            control_block.serialize(),
+        ];
+        let interpreter = Interpreter::from_txdata(
+            &desc.script_pubkey(),
+            &script_sig,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+        )
+        .unwrap();
+        assert!(interpreter.is_taproot_v1_script_spend());
+        assert!(!interpreter.has_unspendable_internal_key());
+        match interpreter.verify_tap_key_tweak(&secp, x_only_pks[0], merkle_root) {
+            Err(Error::CouldNotEvaluate) => {}
+            x => panic!("expected CouldNotEvaluate, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn txdata_with_spend_info() {
+        let (pks, der_sigs, _, _, _, x_only_pks, schnorr_sigs, _) = setup_keys_sigs(3);
+        let tr = |key: &bitcoin::key::XOnlyPublicKey| {
+            match Descriptor::<bitcoin::key::XOnlyPublicKey>::from_str(&format!(
+                "eltr({},pk({}))",
+                key, x_only_pks[1]
+            ))
+            .unwrap()
+            {
+                Descriptor::Tr(tr) => tr,
+                _ => unreachable!(),
+            }
+        };
+        let desc = tr(&x_only_pks[0]);
+        let spend_info = desc.spend_info();
+        let other_spend_info = tr(&x_only_pks[2]).spend_info();
+        let spk = desc.script_pubkey();
+        let script_sig = elements::Script::new();
+
+        let (_, ms) = desc.iter_scripts().next().unwrap();
+        let leaf_script = ms.encode();
+        let control_block = spend_info
+            .control_block(&(leaf_script.clone(), LeafVersion::default()))
+            .unwrap();
+        let key_wit = vec![schnorr_sigs[0].to_vec()];
+        let script_wit = vec![
+            schnorr_sigs[1].to_vec(),
+            leaf_script.to_bytes(),
+            control_block.serialize(),
+        ];
+        for wit in &[key_wit, script_wit] {
+            let from_txdata = |spend_info| {
+                Interpreter::from_txdata_with_options(
+                    &spk,
+                    &script_sig,
+                    wit,
+                    Sequence::ZERO,
+                    LockTime::ZERO,
+                    &InterpreterOptions::new().spend_info(spend_info),
+                )
+            };
+            from_txdata(Some(&spend_info)).unwrap();
+            match from_txdata(None) {
+                Err(Error::NeedsSpendInfo) => {}
+                Err(e) => panic!("expected NeedsSpendInfo, got {:?}", e),
+                Ok(_) => panic!("expected NeedsSpendInfo"),
+            }
+            match from_txdata(Some(&other_spend_info)) {
+                Err(Error::TapKeyTweakMismatch) => {}
+                Err(e) => panic!("expected TapKeyTweakMismatch, got {:?}", e),
+                Ok(_) => panic!("expected TapKeyTweakMismatch"),
+            }
+        }
+
+        // The spend info is not needed for other spends
+        let wpkh =
+            Descriptor::<bitcoin::PublicKey>::from_str(&format!("elwpkh({})", pks[0])).unwrap();
+        let wit = vec![der_sigs[0].clone(), pks[0].to_bytes()];
+        Interpreter::from_txdata_with_options(
+            &wpkh.script_pubkey(),
+            &script_sig,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+            &InterpreterOptions::new().spend_info(None),
+        )
+        .unwrap();
+    }
+
+    // Accepts the signatures in `cache` without checking them, and counts
+    // the taproot commitments checked
+    struct CacheVerifier<'a> {
+        secp: &'a Secp256k1<secp256k1_zkp::All>,
+        cache: Vec<secp256k1_zkp::schnorr::Signature>,
+        commitments: std::cell::Cell<usize>,
+    }
+
+    impl<'a> Verifier for CacheVerifier<'a> {
+        fn verify_ecdsa(
+            &self,
+            _: &secp256k1_zkp::Message,
+            _: &secp256k1_zkp::ecdsa::Signature,
+            _: &secp256k1_zkp::PublicKey,
+        ) -> bool {
+            false
+        }
+
+        fn verify_schnorr(
+            &self,
+            _: &secp256k1_zkp::Message,
+            sig: &secp256k1_zkp::schnorr::Signature,
+            _: &secp256k1_zkp::XOnlyPublicKey,
+        ) -> bool {
+            self.cache.contains(sig)
+        }
+
+        fn verify_taproot_commitment(
+            &self,
+            ctrl_blk: &ControlBlock,
+            output_key: &TweakedPublicKey,
+            script: &elements::Script,
+        ) -> bool {
+            self.commitments.set(self.commitments.get() + 1);
+            ctrl_blk.verify_taproot_commitment(self.secp, output_key, script)
+        }
+    }
+
+    #[test]
+    fn custom_verifier() {
+        let (_, _, _, _, secp, x_only_pks, schnorr_sigs, _) = setup_keys_sigs(3);
+        let desc = match Descriptor::<bitcoin::key::XOnlyPublicKey>::from_str(&format!(
+            "eltr({},pk({}))",
+            x_only_pks[0], x_only_pks[1]
+        ))
+        .unwrap()
+        {
+            Descriptor::Tr(tr) => tr,
+            _ => unreachable!(),
+        };
+        let (_, ms) = desc.iter_scripts().next().unwrap();
+        let leaf_script = ms.encode();
+        let control_block = desc
+            .spend_info()
+            .control_block(&(leaf_script.clone(), LeafVersion::default()))
+            .unwrap();
+        let spk = desc.script_pubkey();
+        let script_sig = elements::Script::new();
+        let wit = vec![
+            schnorr_sigs[1].to_vec(),
+            leaf_script.to_bytes(),
+            control_block.serialize(),
+        ];
+
+        let verifier = CacheVerifier {
+            secp: &secp,
+            cache: vec![schnorr_sigs[1].sig],
+            commitments: std::cell::Cell::new(0),
+        };
+        let interpreter = Interpreter::from_txdata_with_options(
+            &spk,
+            &script_sig,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+            &InterpreterOptions::new().verifier(&verifier),
+        )
+        .unwrap();
+        assert_eq!(verifier.commitments.get(), 1);
+
+        // The spend info commitment is checked with the same verifier
+        let spend_info = desc.spend_info();
+        Interpreter::from_txdata_with_options(
+            &spk,
+            &script_sig,
+            &wit,
+            Sequence::ZERO,
+            LockTime::ZERO,
+            &InterpreterOptions::new()
+                .verifier(&verifier)
+                .spend_info(Some(&spend_info)),
+        )
+        .unwrap();
+        assert_eq!(verifier.commitments.get(), 3);
+
+        // The cached signature is accepted without computing the sighash
+        // of a real transaction
+        let tx = elements::Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![elements::TxIn::default()],
+            output: vec![],
+        };
+        let prevouts = [elements::TxOut::default()];
+        let key_sig = KeySigPair::Schnorr(x_only_pks[1], schnorr_sigs[1]);
+        let genesis_hash = elements::BlockHash::all_zeros();
+        let verify = |verifier: &dyn Verifier| {
+            interpreter.verify_sig(
+                verifier,
+                &tx,
+                0,
+                &sighash::Prevouts::All(&prevouts),
+                genesis_hash,
+                &key_sig,
+            )
+        };
+        assert!(verify(&verifier));
+        assert!(!verify(&secp));
+
+        // The iterator takes the verifier by type or as a trait object
+        let txenv = TxEnv::new(&tx, &prevouts, 0).unwrap();
+        let constraints: Vec<_> = interpreter
+            .iter(&verifier, &txenv, genesis_hash)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(constraints.len(), 1);
+        let dyn_verifier: &dyn Verifier = &verifier;
+        let dyn_constraints: Vec<_> = interpreter
+            .iter(dyn_verifier, &txenv, genesis_hash)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(dyn_constraints, constraints);
+        let verifier = CacheVerifier {
+            cache: vec![],
+            ..verifier
+        };
+        assert!(!verify(&verifier));
+    }
+
     fn x_only_no_checks_ms(ms: &str) -> Miniscript<BitcoinKey, NoChecks> {
         let elem: Miniscript<bitcoin::key::XOnlyPublicKey, NoChecks> =
             Miniscript::from_str_ext(ms, &ExtParams::allow_all()).unwrap();
@@ -3,7 +3,8 @@
 
 //! Interpreter stack
 
-use std::ops::Index;
+use core::fmt;
+use core::ops::Index;
 
 use elements::hashes::{hash160, ripemd160, sha256, Hash};
 use elements::{self, opcodes, script, LockTime, Sequence};
@@ -89,6 +90,28 @@ impl<'txin> Element<'txin> {
     }
 }
 
+/// Pushes longer than this are truncated when displayed
+const MAX_DISPLAY_PUSH_LEN: usize = 32;
+
+impl<'txin> fmt::Display for Element<'txin> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Element::Satisfied => f.write_str("Satisfied"),
+            Element::Dissatisfied => f.write_str("Dissatisfied"),
+            Element::Push(v) if v.len() > MAX_DISPLAY_PUSH_LEN => {
+                f.write_str("Push(")?;
+                elements::hex::format_hex(&v[..MAX_DISPLAY_PUSH_LEN], f)?;
+                write!(f, "..; {} bytes)", v.len())
+            }
+            Element::Push(v) => {
+                f.write_str("Push(")?;
+                elements::hex::format_hex(v, f)?;
+                f.write_str(")")
+            }
+        }
+    }
+}
+
 /// Stack Data structure representing the stack input to Miniscript. This Stack
 /// is created from the combination of ScriptSig and Witness stack.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Default)]
@@ -100,6 +123,20 @@ impl<'txin> From<Vec<Element<'txin>>> for Stack<'txin> {
     }
 }
 
+/// Displays the elements from the bottom to the top of the stack
+impl<'txin> fmt::Display for Stack<'txin> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        for (i, elem) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", elem)?;
+        }
+        f.write_str("]")
+    }
+}
+
 impl<'txin> Index<usize> for Stack<'txin> {
     type Output = Element<'txin>;
 
@@ -109,6 +146,37 @@ impl<'txin> Index<usize> for Stack<'txin> {
 }
 
 impl<'txin> Stack<'txin> {
+    /// Builds the stack pushed by a scriptSig. This is the stack the
+    /// interpreter starts from for legacy spends, and is useful to inspect
+    /// a spend that the interpreter fails to parse.
+    pub fn from_script_sig(script_sig: &'txin elements::Script) -> Result<Self, Error> {
+        Ok(script_sig
+            .instructions_minimal()
+            .map(Element::from_instruction)
+            .collect::<Result<Vec<Element<'_>>, Error>>()?
+            .into())
+    }
+
+    /// Same as [`Stack::from_script_sig`], but also accepts pushes that are
+    /// not minimally encoded. See [`super::InterpreterOptions::non_minimal`]
+    /// for why this should only be used to analyze existing spends.
+    pub fn from_script_sig_non_minimal(script_sig: &'txin elements::Script) -> Result<Self, Error> {
+        Ok(script_sig
+            .instructions()
+            .map(Element::from_instruction)
+            .collect::<Result<Vec<Element<'_>>, Error>>()?
+            .into())
+    }
+
+    /// Builds the stack from a segwit witness. See [`Stack::from_script_sig`].
+    pub fn from_witness(witness: &'txin [Vec<u8>]) -> Self {
+        witness
+            .iter()
+            .map(Element::from)
+            .collect::<Vec<Element<'_>>>()
+            .into()
+    }
+
     /// Whether the stack is empty
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
@@ -189,8 +257,6 @@ impl<'txin> Stack<'txin> {
         // Parse a bitcoin key from witness data slice depending on hash context
         // when we encounter a pkh(hash)
         // Depending on the tag of hash, we parse the as full key or x-only-key
-        // TODO: All keys parse errors are currently captured in a single BadPubErr
-        // We don't really store information about which key error.
         fn bitcoin_key_from_slice(sl: &[u8], sig_type: SigType) -> Option<BitcoinKey> {
             let key: BitcoinKey = match sig_type {
                 SigType::Schnorr => bitcoin::key::XOnlyPublicKey::from_slice(sl).ok()?.into(),
@@ -230,7 +296,18 @@ impl<'txin> Stack<'txin> {
                         Some(Err(Error::UnexpectedStackEnd))
                     }
                 }
-                None => Some(Err(Error::PubkeyParseError)),
+                // Keys of the right length are not valid curve points
+                None => match (sig_type, pk.len()) {
+                    (SigType::Schnorr, 32) => Some(Err(Error::XOnlyPublicKeyParseError)),
+                    (SigType::Ecdsa, 33) | (SigType::Ecdsa, 65) => {
+                        Some(Err(Error::PubkeyParseError))
+                    }
+                    _ => Some(Err(Error::PkHashKeyTypeMismatch {
+                        keyhash: pkh,
+                        sig_type,
+                        key_len: pk.len(),
+                    })),
+                },
             }
         } else {
             Some(Err(Error::UnexpectedStackEnd))
@@ -142,7 +142,7 @@ mod util;
 
 use std::{cmp, error, fmt, str};
 
-use elements::hashes::sha256;
+use elements::hashes::{hash160, sha256};
 use elements::secp256k1_zkp::Secp256k1;
 use elements::{locktime, opcodes, script, secp256k1_zkp};
 
@@ -324,6 +324,8 @@ pub enum Error {
     MissingHash(sha256::Hash),
     /// Could not satisfy a script (fragment) because of a missing signature
     MissingSig(bitcoin::PublicKey),
+    /// Could not satisfy a pubkey hash because its public key is not known
+    MissingPubkey(hash160::Hash),
     /// Could not satisfy, relative locktime not met
     RelativeLocktimeNotMet(u32),
     /// Could not satisfy, absolute locktime not met
@@ -494,6 +496,7 @@ impl fmt::Display for Error {
             Error::Trailing(ref s) => write!(f, "trailing tokens: {}", s),
             Error::MissingHash(ref h) => write!(f, "missing preimage of hash {}", h),
             Error::MissingSig(ref pk) => write!(f, "missing signature for key {:?}", pk),
+            Error::MissingPubkey(ref h) => write!(f, "missing public key for key hash {}", h),
             Error::RelativeLocktimeNotMet(n) => {
                 write!(f, "required relative locktime CSV of {} blocks, not met", n)
             }
@@ -571,6 +574,7 @@ impl error::Error for Error {
             | Trailing(_)
             | MissingHash(_)
             | MissingSig(_)
+            | MissingPubkey(_)
             | RelativeLocktimeNotMet(_)
             | AbsoluteLocktimeNotMet(_)
             | CouldNotSatisfy
@@ -814,6 +814,18 @@ impl ScriptContext for BareCtx {
         ms.ext.max_sat_size.map(|x| x.1)
     }
 
+    fn check_witness<Pk: MiniscriptKey, Ext: Extension>(
+        witness: &[Vec<u8>],
+    ) -> Result<(), ScriptContextError> {
+        // Standard bare scripts can never reach this limit, but insane
+        // miniscripts can still be satisfied. The whole satisfaction must
+        // fit in the scriptSig as there is no witness to spill into.
+        if witness_to_scriptsig(witness).len() > MAX_SCRIPTSIG_SIZE {
+            return Err(ScriptContextError::MaxScriptSigSizeExceeded);
+        }
+        Ok(())
+    }
+
     fn pk_len<Pk: MiniscriptKey>(pk: &Pk) -> usize {
         if pk.is_uncompressed() {
             66
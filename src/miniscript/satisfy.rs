@@ -206,6 +206,12 @@ pub trait Satisfier<Pk: MiniscriptKey + ToPublicKey> {
         None
     }
 
+    /// Lookup the genesis hash of the chain. Required for taproot covenants
+    /// as it is committed in the taproot sighash message
+    fn lookup_genesis_hash(&self) -> Option<elements::BlockHash> {
+        None
+    }
+
     /// Lookup (msg, sig) for CSFS fragment
     fn lookup_csfs_sig(&self, _pk: &XOnlyPublicKey, _msg: &CsfsMsg) -> Option<schnorr::Signature> {
         None
@@ -447,6 +453,10 @@ impl<'a, Pk: MiniscriptKey + ToPublicKey, S: Satisfier<Pk>> Satisfier<Pk> for &'
         (**self).lookup_curr_inp()
     }
 
+    fn lookup_genesis_hash(&self) -> Option<elements::BlockHash> {
+        (**self).lookup_genesis_hash()
+    }
+
     fn lookup_csfs_sig(&self, pk: &XOnlyPublicKey, msg: &CsfsMsg) -> Option<schnorr::Signature> {
         (**self).lookup_csfs_sig(pk, msg)
     }
@@ -581,6 +591,10 @@ impl<'a, Pk: MiniscriptKey + ToPublicKey, S: Satisfier<Pk>> Satisfier<Pk> for &'
         (**self).lookup_curr_inp()
     }
 
+    fn lookup_genesis_hash(&self) -> Option<elements::BlockHash> {
+        (**self).lookup_genesis_hash()
+    }
+
     fn lookup_csfs_sig(&self, pk: &XOnlyPublicKey, msg: &CsfsMsg) -> Option<schnorr::Signature> {
         (**self).lookup_csfs_sig(pk, msg)
     }
@@ -886,6 +900,16 @@ macro_rules! impl_tuple_satisfier {
                 None
             }
 
+            fn lookup_genesis_hash(&self) -> Option<elements::BlockHash> {
+                let &($(ref $ty,)*) = self;
+                $(
+                    if let Some(result) = $ty.lookup_genesis_hash() {
+                        return Some(result);
+                    }
+                )*
+                None
+            }
+
             fn lookup_tx(&self) -> Option<&elements::Transaction> {
                 let &($(ref $ty,)*) = self;
                 $(
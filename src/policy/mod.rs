@@ -516,6 +516,43 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(feature = "compiler")]
+    fn semantic_taproot_compile() {
+        let unspendable_key = "UNSPENDABLE".to_string();
+
+        // The key that satisfies the policy alone becomes the internal key
+        let policy = SemanticPol::from_str("or(pk(A),and(pk(B),older(10)))").unwrap();
+        let descriptor = policy.compile_tr(Some(unspendable_key.clone())).unwrap();
+        let ms_compilation: Miniscript<String, Tap> = ms_str!("and_v(v:pk(B),older(10))");
+        let expected_descriptor = Descriptor::new_tr(
+            "A".to_string(),
+            Some(TapTree::Leaf(Arc::new(ms_compilation))),
+        )
+        .unwrap();
+        assert_eq!(descriptor, expected_descriptor);
+        assert!(descriptor.to_string().starts_with("eltr(A,"));
+
+        // Branches are equally likely, the same as a concrete policy with
+        // equal odds
+        let policy =
+            SemanticPol::from_str("or(and(pk(A),pk(B)),or(pk(C),and(pk(D),pk(E))))").unwrap();
+        let concrete: ConcretePol = policy_str!("or(and(pk(A),pk(B)),or(pk(C),and(pk(D),pk(E))))");
+        assert_eq!(
+            policy.compile_tr(Some(unspendable_key.clone())).unwrap(),
+            concrete.compile_tr(Some(unspendable_key.clone())).unwrap()
+        );
+
+        // Without a key that satisfies the policy alone
+        let policy = SemanticPol::from_str("and(pk(A),pk(B))").unwrap();
+        let descriptor = policy.compile_tr(Some(unspendable_key.clone())).unwrap();
+        match descriptor {
+            Descriptor::Tr(ref tr) => assert_eq!(tr.internal_key(), &unspendable_key),
+            _ => unreachable!(),
+        }
+        assert!(policy.compile_tr(None).is_err());
+    }
+
     #[test]
     #[cfg(feature = "compiler")]
     fn experimental_taproot_compile() {
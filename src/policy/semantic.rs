@@ -10,8 +10,12 @@ use bitcoin_miniscript::expression::check_valid_chars;
 use elements::{LockTime, Sequence};
 
 use super::concrete::PolicyError;
+#[cfg(feature = "compiler")]
+use super::Concrete;
 use super::ENTAILMENT_MAX_TERMINALS;
 use crate::{errstr, expression, AbsLockTime, Error, MiniscriptKey, Translator};
+#[cfg(feature = "compiler")]
+use crate::{Descriptor, NoExt};
 
 /// Abstract policy which corresponds to the semantics of a Miniscript
 /// and which allows complex forms of analysis, e.g. filtering and
@@ -594,6 +598,68 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
             }
         }
     }
+
+    /// The combinations of conditions that satisfy the policy, i.e. the
+    /// policy in disjunctive normal form. Every item is a list of `Key`,
+    /// timelock and hashlock conditions that are sufficient together.
+    ///
+    /// A threshold contributes every combination of exactly `k` of its
+    /// subpolicies, so paths that satisfy more branches than required
+    /// are not listed. The number of paths is exponential in the size of
+    /// the policy, so this is meant for analysis of small policies.
+    pub fn spend_paths(&self) -> Vec<Vec<Policy<Pk>>> {
+        match *self {
+            Policy::Unsatisfiable => vec![],
+            Policy::Trivial => vec![vec![]],
+            Policy::Key(..)
+            | Policy::After(..)
+            | Policy::Older(..)
+            | Policy::Sha256(..)
+            | Policy::Hash256(..)
+            | Policy::Ripemd160(..)
+            | Policy::Hash160(..) => vec![vec![self.clone()]],
+            Policy::Threshold(k, ref subs) => {
+                let sub_paths: Vec<_> = subs.iter().map(Policy::spend_paths).collect();
+                let mut ret = vec![];
+                for combination in k_combinations(subs.len(), k) {
+                    let mut paths = vec![vec![]];
+                    for i in combination {
+                        paths = paths
+                            .iter()
+                            .flat_map(|path: &Vec<Policy<Pk>>| {
+                                sub_paths[i].iter().map(move |sub_path| {
+                                    path.iter().chain(sub_path).cloned().collect()
+                                })
+                            })
+                            .collect();
+                    }
+                    ret.extend(paths);
+                }
+                ret
+            }
+        }
+    }
+}
+
+// All the sorted `k` element subsets of `0..n`, in lexicographic order
+fn k_combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k > n {
+        return vec![];
+    }
+    let mut ret = vec![];
+    let mut indices: Vec<usize> = (0..k).collect();
+    loop {
+        ret.push(indices.clone());
+        // Find the rightmost index that can still be incremented
+        let i = match (0..k).rev().find(|&i| indices[i] < n - k + i) {
+            Some(i) => i,
+            None => return ret,
+        };
+        indices[i] += 1;
+        for j in i + 1..k {
+            indices[j] = indices[j - 1] + 1;
+        }
+    }
 }
 
 impl<Pk: MiniscriptKey> Policy<Pk> {
@@ -613,6 +679,45 @@ impl<Pk: MiniscriptKey> Policy<Pk> {
     }
 }
 
+#[cfg(feature = "compiler")]
+impl<Pk: MiniscriptKey> Policy<Pk> {
+    /// Compile the policy into an `eltr` [`Descriptor`], see
+    /// [`Concrete::compile_tr`].
+    ///
+    /// A semantic policy has no odds, so every branch of a disjunction is
+    /// assumed to be equally likely when balancing the taproot tree. A key
+    /// that satisfies the policy on its own is promoted to the internal key,
+    /// otherwise `unspendable_key` is used. Compile a [`Concrete`] policy
+    /// instead to weight the branches.
+    pub fn compile_tr(&self, unspendable_key: Option<Pk>) -> Result<Descriptor<Pk, NoExt>, Error> {
+        self.to_concrete().compile_tr(unspendable_key)
+    }
+
+    // Convert to the concrete policy with equal odds for all branches
+    fn to_concrete(&self) -> Concrete<Pk> {
+        match *self {
+            Policy::Unsatisfiable => Concrete::Unsatisfiable,
+            Policy::Trivial => Concrete::Trivial,
+            Policy::Key(ref pk) => Concrete::Key(pk.clone()),
+            Policy::After(t) => Concrete::After(t),
+            Policy::Older(t) => Concrete::Older(t),
+            Policy::Sha256(ref h) => Concrete::Sha256(h.clone()),
+            Policy::Hash256(ref h) => Concrete::Hash256(h.clone()),
+            Policy::Ripemd160(ref h) => Concrete::Ripemd160(h.clone()),
+            Policy::Hash160(ref h) => Concrete::Hash160(h.clone()),
+            Policy::Threshold(k, ref subs) => {
+                let subs: Vec<_> = subs.iter().map(Policy::to_concrete).collect();
+                // Concrete `and` and `or` are binary
+                match (k, subs.len()) {
+                    (2, 2) => Concrete::And(subs),
+                    (1, 2) => Concrete::Or(subs.into_iter().map(|sub| (1, sub)).collect()),
+                    _ => Concrete::Threshold(k, subs),
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -647,6 +752,41 @@ mod tests {
         .is_ok());
     }
 
+    #[test]
+    fn spend_paths() {
+        let paths = |s: &str| {
+            StringPolicy::from_str(s)
+                .unwrap()
+                .spend_paths()
+                .iter()
+                .map(|path| path.iter().map(|p| p.to_string()).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(paths("pk(A)"), vec![vec!["pk(A)"]]);
+        assert_eq!(paths("UNSATISFIABLE"), Vec::<Vec<String>>::new());
+        assert_eq!(paths("TRIVIAL"), vec![Vec::<String>::new()]);
+        assert_eq!(
+            paths("or(pk(A),and(pk(B),older(10)))"),
+            vec![vec!["pk(A)"], vec!["pk(B)", "older(10)"]]
+        );
+        assert_eq!(
+            paths("thresh(2,pk(A),or(pk(B),sha256(H)),after(10))"),
+            vec![
+                vec!["pk(A)", "pk(B)"],
+                vec!["pk(A)", "sha256(H)"],
+                vec!["pk(A)", "after(10)"],
+                vec!["pk(B)", "after(10)"],
+                vec!["sha256(H)", "after(10)"],
+            ]
+        );
+        // Thresholds larger than the number of subpolicies have no paths
+        assert!(
+            StringPolicy::Threshold(3, vec![Policy::Trivial, Policy::Trivial])
+                .spend_paths()
+                .is_empty()
+        );
+    }
+
     #[test]
     fn semantic_analysis() {
         let policy = StringPolicy::from_str("pk()").unwrap();
@@ -1839,6 +1839,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_update_input_cov_witness_utxo() {
+        let desc = "elcovwsh(020e0338c96a8870479f2396c373cc7696ba124e8635d41b0ea581112b67817261,pk(0250863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352))";
+        let desc = Descriptor::<DefiniteDescriptorKey>::from_str(desc).unwrap();
+        assert_eq!(
+            desc.desc_type().segwit_version(),
+            Some(bitcoin::WitnessVersion::V0)
+        );
+
+        let asset = AssetId::from_slice(&[1; 32]).unwrap();
+        let tx = elements::Transaction {
+            version: 1,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                is_pegin: false,
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                asset_issuance: AssetIssuance::default(),
+                witness: TxInWitness::default(),
+            }],
+            output: vec![],
+        };
+        let mut psbt = Psbt::from_tx(tx);
+        psbt.inputs_mut()[0].witness_utxo = Some(TxOut {
+            value: confidential::Value::Explicit(1_000),
+            script_pubkey: desc.script_pubkey(),
+            asset: confidential::Asset::Explicit(asset),
+            nonce: confidential::Nonce::Null,
+            witness: elements::TxOutWitness::default(),
+        });
+        // Covenants are p2wsh outputs, so the witness_utxo is enough
+        assert_eq!(psbt.update_input_with_descriptor(0, &desc), Ok(()));
+    }
+
     #[test]
     fn test_update_output_checks() {
         let desc = "eltr([73c5da0a/86'/0'/0']xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ/0/0)";